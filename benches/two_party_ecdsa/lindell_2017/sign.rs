@@ -4,6 +4,7 @@ mod bench {
     use criterion::{criterion_group, Criterion};
     use curv::BigInt;
     use multi_party_ecdsa::protocols::two_party_ecdsa::lindell_2017::*;
+    use multi_party_ecdsa::utilities::digest::MessageDigest;
 
     pub fn bench_full_sign_party_one_two(c: &mut Criterion) {
         c.bench_function("sign", move |b| {
@@ -37,7 +38,7 @@ mod bench {
                     )
                     .expect("failed to verify commitments and DLog proof");
                 let party2_private = party_two::Party2Private::set_private_key(&ec_key_pair_party2);
-                let message = BigInt::from(1234);
+                let message = MessageDigest::from_bigint(&BigInt::from(1234)).unwrap();
                 let partial_sig = party_two::PartialSig::compute(
                     &keypair.ek,
                     &keypair.encrypted_share,
@@ -61,7 +62,8 @@ mod bench {
                     &party1_private,
                     &party_two_private_share_gen.public_share,
                 );
-                party_one::verify(&signature, &pubkey, &message).expect("Invalid signature")
+                party_one::verify(&signature, &pubkey, &message.to_bigint())
+                    .expect("Invalid signature")
             })
         });
     }
@@ -4,12 +4,10 @@ use anyhow::{anyhow, Context, Result};
 use futures::{SinkExt, StreamExt, TryStreamExt};
 use structopt::StructOpt;
 
-use curv::arithmetic::Converter;
-use curv::BigInt;
-
 use multi_party_ecdsa::protocols::multi_party_ecdsa::gg_2020::state_machine::sign::{
     OfflineStage, SignManual,
 };
+use multi_party_ecdsa::utilities::digest::MessageDigest;
 use round_based::async_runtime::AsyncProtocol;
 use round_based::Msg;
 
@@ -62,10 +60,8 @@ async fn main() -> Result<()> {
     tokio::pin!(incoming);
     tokio::pin!(outgoing);
 
-    let (signing, partial_signature) = SignManual::new(
-        BigInt::from_bytes(args.data_to_sign.as_bytes()),
-        completed_offline_stage,
-    )?;
+    let digest = MessageDigest::sha256(args.data_to_sign.as_bytes());
+    let (signing, partial_signature) = SignManual::new(digest, completed_offline_stage)?;
 
     outgoing
         .send(Msg {
@@ -75,11 +71,12 @@ async fn main() -> Result<()> {
         })
         .await?;
 
-    let partial_signatures: Vec<_> = incoming
-        .take(number_of_parties - 1)
-        .map_ok(|msg| msg.body)
-        .try_collect()
-        .await?;
+    let partial_signatures: Vec<Msg<_>> =
+        incoming.take(number_of_parties - 1).try_collect().await?;
+    let partial_signatures: Vec<_> = partial_signatures
+        .into_iter()
+        .map(|msg| (msg.sender, msg.body))
+        .collect();
     let signature = signing
         .complete(&partial_signatures)
         .context("online stage failed")?;
@@ -37,6 +37,7 @@ use zk_paillier::zkproofs::NiCorrectKeyProof;
 
 use serde::{Deserialize, Serialize};
 
+use crate::utilities::signature::SignatureEncoding;
 use crate::Error::{self, InvalidCom, InvalidKey, InvalidSS, InvalidSig};
 
 const SECURITY: usize = 256;
@@ -144,6 +145,25 @@ pub struct SignatureRecid {
     pub recid: u8,
 }
 
+impl SignatureEncoding for SignatureRecid {
+    fn r(&self) -> BigInt {
+        self.r.to_bigint()
+    }
+    fn s(&self) -> BigInt {
+        self.s.to_bigint()
+    }
+    fn recid(&self) -> u8 {
+        self.recid
+    }
+    fn from_parts(r: BigInt, s: BigInt, recid: u8) -> Self {
+        Self {
+            r: Scalar::from(&r),
+            s: Scalar::from(&s),
+            recid,
+        }
+    }
+}
+
 impl Keys {
     pub fn create(index: u16) -> Self {
         let u = Scalar::<Secp256k1>::random();
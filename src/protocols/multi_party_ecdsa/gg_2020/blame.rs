@@ -14,7 +14,7 @@
 
     @license GPL-3.0+ <https://github.com/KZen-networks/multi-party-ecdsa/blob/master/LICENSE>
 */
-use crate::protocols::multi_party_ecdsa::gg_2020::ErrorType;
+use crate::protocols::multi_party_ecdsa::gg_2020::{ErrorKind, ErrorType};
 use crate::utilities::mta::{MessageA, MessageB};
 use curv::cryptographic_primitives::proofs::sigma_ec_ddh::ECDDHProof;
 use curv::cryptographic_primitives::proofs::sigma_ec_ddh::ECDDHStatement;
@@ -217,7 +217,7 @@ impl GlobalStatePhase5 {
         bad_signers_vec.sort_unstable();
         bad_signers_vec.dedup();
         let err_type = ErrorType {
-            error_type: "phase6_blame".to_string(),
+            kind: ErrorKind::BadDeltaShare,
             bad_actors: bad_signers_vec,
         };
         Err(err_type)
@@ -413,7 +413,7 @@ impl GlobalStatePhase6 {
         bad_signers_vec.sort_unstable();
         bad_signers_vec.dedup();
         let err_type = ErrorType {
-            error_type: "phase6_blame".to_string(),
+            kind: ErrorKind::BadSigmaShare,
             bad_actors: bad_signers_vec,
         };
         Err(err_type)
@@ -447,7 +447,7 @@ impl GlobalStatePhase7 {
         }
 
         let err_type = ErrorType {
-            error_type: "phase7_blame".to_string(),
+            kind: ErrorKind::BadPartialSignature,
             bad_actors: bad_signers_vec,
         };
         Err(err_type)
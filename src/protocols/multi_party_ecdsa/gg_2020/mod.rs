@@ -20,8 +20,80 @@ pub mod state_machine;
 #[cfg(test)]
 mod test;
 
-#[derive(Clone, Debug)]
+use thiserror::Error;
+
+/// Failed check of the protocol along with parties blamed for the failure
+#[derive(Clone, Debug, Error)]
+#[error("{kind}, bad actors: {bad_actors:?}")]
 pub struct ErrorType {
-    error_type: String,
+    kind: ErrorKind,
     bad_actors: Vec<usize>,
 }
+
+impl ErrorType {
+    /// Kind of the check that failed
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Parties whose messages didn't pass the check
+    ///
+    /// Parties are identified by their position in the list of protocol participants (starting
+    /// from 0), e.g. for signing it's position in the list of signers. List might be empty if no
+    /// party could be blamed.
+    pub fn bad_actors(&self) -> &[usize] {
+        &self.bad_actors
+    }
+}
+
+/// Kind of the failed check
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Error)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Decommitment doesn't match commitment
+    #[error("bad commitment")]
+    BadCommitment,
+    /// Paillier key or ZK setup `(N_tilde, h1, h2)` isn't proven to be well formed, or broadcast
+    /// message carrying them is otherwise malformed
+    #[error("invalid key")]
+    InvalidKey,
+    /// Secret share doesn't match commitments published by the dealer
+    #[error("bad vss share")]
+    BadVssShare,
+    /// Proof of knowledge of discrete log is invalid
+    #[error("bad dlog proof")]
+    BadDLogProof,
+    /// MtA range proof or proof of knowledge of MtA input is invalid
+    #[error("bad range proof")]
+    BadRangeProof,
+    /// Pedersen commitment proof is invalid
+    #[error("bad pedersen proof")]
+    BadPedersenProof,
+    /// PDL with slack proof is invalid
+    #[error("bad pdl proof")]
+    BadPdlProof,
+    /// Homomorphic ElGamal proof of consistency of `S_i` is invalid
+    #[error("bad homo elgamal proof")]
+    BadHomoElGamalProof,
+    /// Sum of `R_dash_i` is not equal to generator
+    #[error("sum of R_dash doesn't match generator")]
+    BadRDashSum,
+    /// Party's `delta_i` doesn't match its revealed MtA values (phase 5 blame)
+    #[error("bad delta share")]
+    BadDeltaShare,
+    /// Party's `S_i` doesn't match its revealed MtA values (phase 6 blame)
+    #[error("bad sigma share")]
+    BadSigmaShare,
+    /// Partial signature doesn't match party's `R_dash_i` and `S_i` (phase 7 blame)
+    #[error("bad partial signature")]
+    BadPartialSignature,
+    /// Party sent message not intended for its role
+    #[error("unexpected message")]
+    UnexpectedMessage,
+    /// Protocol parameters are invalid
+    #[error("invalid parameters")]
+    InvalidParameters,
+    /// Public key or public shares don't match each other
+    #[error("public key mismatch")]
+    PublicKeyMismatch,
+}
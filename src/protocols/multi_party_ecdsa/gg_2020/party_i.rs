@@ -32,22 +32,31 @@ use sha2::Sha256;
 
 use crate::Error::{self, InvalidSig, Phase5BadSum, Phase6Error};
 use paillier::{
-    Decrypt, DecryptionKey, EncryptionKey, KeyGeneration, Paillier, RawCiphertext, RawPlaintext,
+    Decrypt, DecryptionKey, EncryptionKey, KeyGeneration, Keypair, Paillier, PrimeSampable,
+    RawCiphertext, RawPlaintext,
 };
 
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
 use zk_paillier::zkproofs::NiCorrectKeyProof;
 use zk_paillier::zkproofs::{CompositeDLogProof, DLogStatement};
 
-use crate::protocols::multi_party_ecdsa::gg_2020::ErrorType;
+use crate::protocols::multi_party_ecdsa::gg_2020::{ErrorKind, ErrorType};
+use crate::utilities::backup::VerifiableBackup;
+use crate::utilities::rng;
+use crate::utilities::signature::SignatureEncoding;
+use crate::utilities::zk_no_small_factor::NoSmallFactorProof;
+use crate::utilities::zk_paillier_blum_modulus::PaillierBlumModulusProof;
 use crate::utilities::zk_pdl_with_slack::{PDLwSlackProof, PDLwSlackStatement, PDLwSlackWitness};
 use curv::cryptographic_primitives::proofs::sigma_valid_pedersen::PedersenProof;
 
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 
 const SECURITY: usize = 256;
 const PAILLIER_MIN_BIT_LENGTH: usize = 2047;
 const PAILLIER_MAX_BIT_LENGTH: usize = 2048;
+const PAILLIER_KEY_SIZE: usize = 2048;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Parameters {
@@ -63,6 +72,7 @@ pub struct Keys<E: Curve = Secp256k1> {
     pub ek: EncryptionKey,
     pub party_index: usize,
     pub N_tilde: BigInt,
+    pub dk_tilde: DecryptionKey,
     pub h1: BigInt,
     pub h2: BigInt,
     pub xhi: BigInt,
@@ -84,12 +94,36 @@ pub struct KeyGenBroadcastMessage1 {
     pub correct_key_proof: NiCorrectKeyProof,
     pub composite_dlog_proof_base_h1: CompositeDLogProof,
     pub composite_dlog_proof_base_h2: CompositeDLogProof,
+    pub modulus_proof: PaillierBlumModulusProof,
+    pub n_tilde_modulus_proof: PaillierBlumModulusProof,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RefreshBroadcastMessage1 {
+    pub e: EncryptionKey,
+    pub dlog_statement: DLogStatement,
+    pub correct_key_proof: NiCorrectKeyProof,
+    pub composite_dlog_proof_base_h1: CompositeDLogProof,
+    pub composite_dlog_proof_base_h2: CompositeDLogProof,
+    pub modulus_proof: PaillierBlumModulusProof,
+    pub n_tilde_modulus_proof: PaillierBlumModulusProof,
+    pub no_small_factor_proofs: Vec<NoSmallFactorProofs>,
+    pub vss_scheme: VerifiableSS<Secp256k1>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct KeyGenDecommitMessage1 {
     pub blind_factor: BigInt,
     pub y_i: Point<Secp256k1>,
+    /// `no_small_factor_proofs[j]` is generated with respect to `dlog_statement` of `j`-th party
+    pub no_small_factor_proofs: Vec<NoSmallFactorProofs>,
+}
+
+/// Proofs that party's Paillier modulus and `N_tilde` have no small factors
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NoSmallFactorProofs {
+    pub paillier_n: NoSmallFactorProof,
+    pub n_tilde: NoSmallFactorProof,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -98,6 +132,14 @@ pub struct SharedKeys {
     pub x_i: Scalar<Secp256k1>,
 }
 
+impl SharedKeys {
+    /// Encrypts `x_i` under `recovery_key` along with a proof that other parties can check
+    /// against our public key share `x_i * G`
+    pub fn to_verifiable_backup(&self, recovery_key: &Point<Secp256k1>) -> VerifiableBackup {
+        VerifiableBackup::new(&self.x_i, recovery_key)
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SignKeys {
     pub w_i: Scalar<Secp256k1>,
@@ -134,15 +176,91 @@ pub struct SignatureRecid {
     pub recid: u8,
 }
 
+impl SignatureEncoding for SignatureRecid {
+    fn r(&self) -> BigInt {
+        self.r.to_bigint()
+    }
+    fn s(&self) -> BigInt {
+        self.s.to_bigint()
+    }
+    fn recid(&self) -> u8 {
+        self.recid
+    }
+    fn from_parts(r: BigInt, s: BigInt, recid: u8) -> Self {
+        Self {
+            r: Scalar::from(&r),
+            s: Scalar::from(&s),
+            recid,
+        }
+    }
+}
+
 pub fn generate_h1_h2_N_tilde() -> (BigInt, BigInt, BigInt, BigInt, BigInt) {
+    let (dk_tilde, h1, h2, xhi, xhi_inv) = generate_zk_setup();
+    (&dk_tilde.p * &dk_tilde.q, h1, h2, xhi, xhi_inv)
+}
+
+/// Same as [generate_h1_h2_N_tilde], but takes `h1` and the trapdoor from `rng`
+///
+/// Factors of `N_tilde` are still sampled by `paillier` crate from operating system.
+pub fn generate_h1_h2_N_tilde_with_rng<R: RngCore + CryptoRng + ?Sized>(
+    rng: &mut R,
+) -> (BigInt, BigInt, BigInt, BigInt, BigInt) {
+    let (dk_tilde, h1, h2, xhi, xhi_inv) = generate_zk_setup_with_rng(rng);
+    (&dk_tilde.p * &dk_tilde.q, h1, h2, xhi, xhi_inv)
+}
+
+/// Generates Paillier keypair with primes sampled by `paillier` crate
+///
+/// Primes are resampled until both are Blum primes (`p = 3 mod 4`), so the modulus can be proven
+/// well-formed with [PaillierBlumModulusProof].
+pub(crate) fn paillier_blum_keypair() -> (EncryptionKey, DecryptionKey) {
+    let four = BigInt::from(4);
+    let three = BigInt::from(3);
+    let sample = || loop {
+        let p = BigInt::sample_prime(PAILLIER_KEY_SIZE / 2);
+        if p.mod_floor(&four) == three {
+            return p;
+        }
+    };
+    let p = sample();
+    let q = loop {
+        let q = sample();
+        if q != p {
+            break q;
+        }
+    };
+    Keypair { p, q }.keys()
+}
+
+/// Same as [generate_h1_h2_N_tilde], but returns factorization of `N_tilde` instead of `N_tilde`
+pub(crate) fn generate_zk_setup() -> (DecryptionKey, BigInt, BigInt, BigInt, BigInt) {
     // note, should be safe primes:
-    // let (ek_tilde, dk_tilde) = Paillier::keypair_safe_primes().keys();;
-    let (ek_tilde, dk_tilde) = Paillier::keypair().keys();
+    // let (ek_tilde, dk_tilde) = Paillier::keypair_safe_primes().keys();
+    let (ek_tilde, dk_tilde) = paillier_blum_keypair();
+    zk_setup_from(ek_tilde, dk_tilde, &mut OsRng)
+}
+
+/// Same as [generate_zk_setup], but takes `h1` and the trapdoor from `rng`
+pub(crate) fn generate_zk_setup_with_rng<R: RngCore + CryptoRng + ?Sized>(
+    rng: &mut R,
+) -> (DecryptionKey, BigInt, BigInt, BigInt, BigInt) {
+    // note, should be safe primes:
+    // let (ek_tilde, dk_tilde) = Paillier::keypair_safe_primes().keys();
+    let (ek_tilde, dk_tilde) = paillier_blum_keypair();
+    zk_setup_from(ek_tilde, dk_tilde, rng)
+}
+
+fn zk_setup_from<R: RngCore + CryptoRng + ?Sized>(
+    ek_tilde: EncryptionKey,
+    dk_tilde: DecryptionKey,
+    rng: &mut R,
+) -> (DecryptionKey, BigInt, BigInt, BigInt, BigInt) {
     let one = BigInt::one();
     let phi = (&dk_tilde.p - &one) * (&dk_tilde.q - &one);
-    let h1 = BigInt::sample_below(&ek_tilde.n);
+    let h1 = rng::sample_below(&ek_tilde.n, rng);
     let (mut xhi, mut xhi_inv) = loop {
-        let xhi_ = BigInt::sample_below(&phi);
+        let xhi_ = rng::sample_below(&phi, rng);
         match BigInt::mod_inv(&xhi_, &phi) {
             Some(inv) => break (xhi_, inv),
             None => continue,
@@ -152,63 +270,75 @@ pub fn generate_h1_h2_N_tilde() -> (BigInt, BigInt, BigInt, BigInt, BigInt) {
     xhi = BigInt::sub(&phi, &xhi);
     xhi_inv = BigInt::sub(&phi, &xhi_inv);
 
-    (ek_tilde.n, h1, h2, xhi, xhi_inv)
+    (dk_tilde, h1, h2, xhi, xhi_inv)
 }
 
 impl Keys {
     pub fn create(index: usize) -> Self {
-        let u = Scalar::<Secp256k1>::random();
-        let y = Point::generator() * &u;
-        let (ek, dk) = Paillier::keypair().keys();
-        let (N_tilde, h1, h2, xhi, xhi_inv) = generate_h1_h2_N_tilde();
+        Self::create_from(Scalar::random(), index)
+    }
 
-        Self {
-            u_i: u,
-            y_i: y,
-            dk,
-            ek,
-            party_index: index,
-            N_tilde,
-            h1,
-            h2,
-            xhi,
-            xhi_inv,
-        }
+    /// Same as [Keys::create], but takes randomness from `rng`
+    ///
+    /// Primes of Paillier modulus and `N_tilde` are still sampled by `paillier` crate from
+    /// operating system.
+    pub fn create_with_rng<R: RngCore + CryptoRng + ?Sized>(index: usize, rng: &mut R) -> Self {
+        let u = rng::random_scalar(rng);
+        Self::create_from_with_rng(u, index, rng)
     }
 
     // we recommend using safe primes if the code is used in production
     pub fn create_safe_prime(index: usize) -> Self {
-        let u = Scalar::<Secp256k1>::random();
-        let y = Point::generator() * &u;
-
         let (ek, dk) = Paillier::keypair_safe_primes().keys();
-        let (N_tilde, h1, h2, xhi, xhi_inv) = generate_h1_h2_N_tilde();
+        Self::from_parts(Scalar::random(), index, (ek, dk), generate_zk_setup())
+    }
 
-        Self {
-            u_i: u,
-            y_i: y,
-            dk,
-            ek,
-            party_index: index,
-            N_tilde,
-            h1,
-            h2,
-            xhi,
-            xhi_inv,
-        }
+    /// Same as [Keys::create_safe_prime], but takes randomness from `rng`
+    ///
+    /// Primes of Paillier modulus and `N_tilde` are still sampled by `paillier` crate from
+    /// operating system.
+    pub fn create_safe_prime_with_rng<R: RngCore + CryptoRng + ?Sized>(
+        index: usize,
+        rng: &mut R,
+    ) -> Self {
+        let u = rng::random_scalar(rng);
+        let paillier = Paillier::keypair_safe_primes().keys();
+        let zk_setup = generate_zk_setup_with_rng(rng);
+        Self::from_parts(u, index, paillier, zk_setup)
     }
     pub fn create_from(u: Scalar<Secp256k1>, index: usize) -> Self {
-        let y = Point::generator() * &u;
-        let (ek, dk) = Paillier::keypair().keys();
-        let (N_tilde, h1, h2, xhi, xhi_inv) = generate_h1_h2_N_tilde();
+        Self::from_parts(u, index, paillier_blum_keypair(), generate_zk_setup())
+    }
+
+    /// Same as [Keys::create_from], but takes randomness from `rng`
+    ///
+    /// Primes of Paillier modulus and `N_tilde` are still sampled by `paillier` crate from
+    /// operating system.
+    pub fn create_from_with_rng<R: RngCore + CryptoRng + ?Sized>(
+        u: Scalar<Secp256k1>,
+        index: usize,
+        rng: &mut R,
+    ) -> Self {
+        let paillier = paillier_blum_keypair();
+        let zk_setup = generate_zk_setup_with_rng(rng);
+        Self::from_parts(u, index, paillier, zk_setup)
+    }
 
+    fn from_parts(
+        u: Scalar<Secp256k1>,
+        index: usize,
+        (ek, dk): (EncryptionKey, DecryptionKey),
+        (dk_tilde, h1, h2, xhi, xhi_inv): (DecryptionKey, BigInt, BigInt, BigInt, BigInt),
+    ) -> Self {
+        let y = Point::generator() * &u;
         Self {
             u_i: u,
             y_i: y,
             dk,
             ek,
             party_index: index,
-            N_tilde,
+            N_tilde: &dk_tilde.p * &dk_tilde.q,
+            dk_tilde,
             h1,
             h2,
             xhi,
@@ -216,10 +346,14 @@ impl Keys {
         }
     }
 
-    pub fn phase1_broadcast_phase3_proof_of_correct_key_proof_of_correct_h1h2(
+    pub fn proof_of_correct_key_proof_of_correct_h1h2(
         &self,
-    ) -> (KeyGenBroadcastMessage1, KeyGenDecommitMessage1) {
-        let blind_factor = BigInt::sample(SECURITY);
+    ) -> (
+        DLogStatement,
+        NiCorrectKeyProof,
+        CompositeDLogProof,
+        CompositeDLogProof,
+    ) {
         let correct_key_proof = NiCorrectKeyProof::proof(&self.dk, None);
 
         let dlog_statement_base_h1 = DLogStatement {
@@ -238,10 +372,42 @@ impl Keys {
         let composite_dlog_proof_base_h2 =
             CompositeDLogProof::prove(&dlog_statement_base_h2, &self.xhi_inv);
 
+        (
+            dlog_statement_base_h1,
+            correct_key_proof,
+            composite_dlog_proof_base_h1,
+            composite_dlog_proof_base_h2,
+        )
+    }
+
+    pub fn phase1_broadcast_phase3_proof_of_correct_key_proof_of_correct_h1h2(
+        &self,
+    ) -> (KeyGenBroadcastMessage1, KeyGenDecommitMessage1) {
+        self.phase1_broadcast_phase3_proof_of_correct_key_proof_of_correct_h1h2_with_rng(&mut OsRng)
+    }
+
+    /// Same as [Keys::phase1_broadcast_phase3_proof_of_correct_key_proof_of_correct_h1h2], but
+    /// takes commitment randomness from `rng`
+    pub fn phase1_broadcast_phase3_proof_of_correct_key_proof_of_correct_h1h2_with_rng<
+        R: RngCore + CryptoRng + ?Sized,
+    >(
+        &self,
+        rng: &mut R,
+    ) -> (KeyGenBroadcastMessage1, KeyGenDecommitMessage1) {
+        let blind_factor = rng::sample_bits(SECURITY, rng);
+        let (
+            dlog_statement_base_h1,
+            correct_key_proof,
+            composite_dlog_proof_base_h1,
+            composite_dlog_proof_base_h2,
+        ) = self.proof_of_correct_key_proof_of_correct_h1h2();
+
         let com = HashCommitment::<Sha256>::create_commitment_with_user_defined_randomness(
             &BigInt::from_bytes(self.y_i.to_bytes(true).as_ref()),
             &blind_factor,
         );
+        let (modulus_proof, n_tilde_modulus_proof) =
+            self.prove_moduli_are_paillier_blum_with_rng(rng);
         let bcm1 = KeyGenBroadcastMessage1 {
             e: self.ek.clone(),
             dlog_statement: dlog_statement_base_h1,
@@ -249,73 +415,207 @@ impl Keys {
             correct_key_proof,
             composite_dlog_proof_base_h1,
             composite_dlog_proof_base_h2,
+            modulus_proof,
+            n_tilde_modulus_proof,
         };
         let decom1 = KeyGenDecommitMessage1 {
             blind_factor,
             y_i: self.y_i.clone(),
+            no_small_factor_proofs: vec![],
         };
         (bcm1, decom1)
     }
 
+    /// Attaches to decommitment proofs that Paillier modulus and `N_tilde` have no small factors
+    ///
+    /// No small factor proof is only sound with respect to verifier's setup, so it's generated for
+    /// every party using `dlog_statement` it broadcasted in the first round.
+    pub fn phase2_prove_no_small_factors(
+        &self,
+        decom: KeyGenDecommitMessage1,
+        bc1_vec: &[KeyGenBroadcastMessage1],
+    ) -> KeyGenDecommitMessage1 {
+        self.phase2_prove_no_small_factors_with_rng(decom, bc1_vec, &mut OsRng)
+    }
+
+    /// Same as [Keys::phase2_prove_no_small_factors], but takes randomness from `rng`
+    pub fn phase2_prove_no_small_factors_with_rng<R: RngCore + CryptoRng + ?Sized>(
+        &self,
+        decom: KeyGenDecommitMessage1,
+        bc1_vec: &[KeyGenBroadcastMessage1],
+        rng: &mut R,
+    ) -> KeyGenDecommitMessage1 {
+        let setups = bc1_vec
+            .iter()
+            .map(|bc1| bc1.dlog_statement.clone())
+            .collect::<Vec<_>>();
+        KeyGenDecommitMessage1 {
+            no_small_factor_proofs: self.prove_no_small_factors_with_rng(&setups, rng),
+            ..decom
+        }
+    }
+
+    /// Proves that Paillier modulus and `N_tilde` are Paillier-Blum moduli
+    ///
+    /// Returns proofs for Paillier modulus and `N_tilde` respectively.
+    pub fn prove_moduli_are_paillier_blum(
+        &self,
+    ) -> (PaillierBlumModulusProof, PaillierBlumModulusProof) {
+        self.prove_moduli_are_paillier_blum_with_rng(&mut OsRng)
+    }
+
+    /// Same as [Keys::prove_moduli_are_paillier_blum], but takes randomness from `rng`
+    pub fn prove_moduli_are_paillier_blum_with_rng<R: RngCore + CryptoRng + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> (PaillierBlumModulusProof, PaillierBlumModulusProof) {
+        let modulus_proof =
+            PaillierBlumModulusProof::prove_with_rng(&self.ek.n, &self.dk.p, &self.dk.q, rng);
+        let n_tilde_modulus_proof = PaillierBlumModulusProof::prove_with_rng(
+            &self.N_tilde,
+            &self.dk_tilde.p,
+            &self.dk_tilde.q,
+            rng,
+        );
+        (modulus_proof, n_tilde_modulus_proof)
+    }
+
+    /// Proves that Paillier modulus and `N_tilde` have no small factors to every party
+    ///
+    /// Proof is only sound with respect to verifier's setup, so one is generated for every setup in
+    /// `setups`.
+    pub fn prove_no_small_factors(&self, setups: &[DLogStatement]) -> Vec<NoSmallFactorProofs> {
+        self.prove_no_small_factors_with_rng(setups, &mut OsRng)
+    }
+
+    /// Same as [Keys::prove_no_small_factors], but takes randomness from `rng`
+    pub fn prove_no_small_factors_with_rng<R: RngCore + CryptoRng + ?Sized>(
+        &self,
+        setups: &[DLogStatement],
+        rng: &mut R,
+    ) -> Vec<NoSmallFactorProofs> {
+        setups
+            .iter()
+            .map(|setup| NoSmallFactorProofs {
+                paillier_n: NoSmallFactorProof::prove_with_rng(
+                    &self.ek.n, &self.dk.p, &self.dk.q, setup, rng,
+                ),
+                n_tilde: NoSmallFactorProof::prove_with_rng(
+                    &self.N_tilde,
+                    &self.dk_tilde.p,
+                    &self.dk_tilde.q,
+                    setup,
+                    rng,
+                ),
+            })
+            .collect()
+    }
+
+    /// Verifies first round messages of keygen and shares party's secret
+    ///
+    /// Proofs of no small factors addressed to this party are found at the position of its own
+    /// message in `bc1_vec`, which is looked up by party's `h1, h2, N_tilde`, so `party_index` may
+    /// be numbered from either 0 or 1.
     pub fn phase1_verify_com_phase3_verify_correct_key_verify_dlog_phase2_distribute(
         &self,
         params: &Parameters,
         decom_vec: &[KeyGenDecommitMessage1],
         bc1_vec: &[KeyGenBroadcastMessage1],
     ) -> Result<(VerifiableSS<Secp256k1>, Vec<Scalar<Secp256k1>>, usize), ErrorType> {
-        let mut bad_actors_vec = Vec::new();
+        let position = bc1_vec
+            .iter()
+            .position(|bc1| {
+                bc1.dlog_statement.N == self.N_tilde
+                    && bc1.dlog_statement.g == self.h1
+                    && bc1.dlog_statement.ni == self.h2
+            })
+            .unwrap_or(bc1_vec.len());
+        self.phase1_verify_com_phase3_verify_correct_key_verify_dlog_phase2_distribute_with_rng(
+            params, decom_vec, bc1_vec, position, &mut OsRng,
+        )
+    }
+
+    /// Same as [Keys::phase1_verify_com_phase3_verify_correct_key_verify_dlog_phase2_distribute],
+    /// but takes VSS coefficients from `rng`
+    ///
+    /// `position` is the position of party's own message in `bc1_vec` (starting from 0), proofs of
+    /// no small factors addressed to this party are taken from that position of every `decom_vec`
+    /// entry.
+    pub fn phase1_verify_com_phase3_verify_correct_key_verify_dlog_phase2_distribute_with_rng<
+        R: RngCore + CryptoRng + ?Sized,
+    >(
+        &self,
+        params: &Parameters,
+        decom_vec: &[KeyGenDecommitMessage1],
+        bc1_vec: &[KeyGenBroadcastMessage1],
+        position: usize,
+        rng: &mut R,
+    ) -> Result<(VerifiableSS<Secp256k1>, Vec<Scalar<Secp256k1>>, usize), ErrorType> {
+        let mut bad_commitments_vec = Vec::new();
+        let mut bad_keys_vec = Vec::new();
         // test length:
         assert_eq!(decom_vec.len(), usize::from(params.share_count));
         assert_eq!(bc1_vec.len(), usize::from(params.share_count));
-        // test paillier correct key, h1,h2 correct generation and test decommitments
-        let correct_key_correct_decom_all = (0..bc1_vec.len())
-            .map(|i| {
-                let dlog_statement_base_h2 = DLogStatement {
-                    N: bc1_vec[i].dlog_statement.N.clone(),
-                    g: bc1_vec[i].dlog_statement.ni.clone(),
-                    ni: bc1_vec[i].dlog_statement.g.clone(),
-                };
-                let test_res =
-                    HashCommitment::<Sha256>::create_commitment_with_user_defined_randomness(
-                        &BigInt::from_bytes(&decom_vec[i].y_i.to_bytes(true)),
-                        &decom_vec[i].blind_factor,
-                    ) == bc1_vec[i].com
-                        && bc1_vec[i]
-                            .correct_key_proof
-                            .verify(&bc1_vec[i].e, zk_paillier::zkproofs::SALT_STRING)
-                            .is_ok()
-                        && bc1_vec[i].e.n.bit_length() >= PAILLIER_MIN_BIT_LENGTH
-                        && bc1_vec[i].e.n.bit_length() <= PAILLIER_MAX_BIT_LENGTH
-                        && bc1_vec[i].dlog_statement.N.bit_length() >= PAILLIER_MIN_BIT_LENGTH
-                        && bc1_vec[i].dlog_statement.N.bit_length() <= PAILLIER_MAX_BIT_LENGTH
-                        && bc1_vec[i]
-                            .composite_dlog_proof_base_h1
-                            .verify(&bc1_vec[i].dlog_statement)
-                            .is_ok()
-                        && bc1_vec[i]
-                            .composite_dlog_proof_base_h2
-                            .verify(&dlog_statement_base_h2)
-                            .is_ok();
-                if !test_res {
-                    bad_actors_vec.push(i);
-                    false
-                } else {
-                    true
-                }
-            })
-            .all(|x| x);
+        // proofs of no small factors addressed to us are generated with respect to our setup
+        let my_dlog_statement = DLogStatement {
+            N: self.N_tilde.clone(),
+            g: self.h1.clone(),
+            ni: self.h2.clone(),
+        };
+        // test paillier correct key, h1,h2 correct generation, moduli are well formed and
+        // test decommitments
+        for (i, (bc1, decom)) in bc1_vec.iter().zip(decom_vec).enumerate() {
+            let correct_decom =
+                HashCommitment::<Sha256>::create_commitment_with_user_defined_randomness(
+                    &BigInt::from_bytes(&decom.y_i.to_bytes(true)),
+                    &decom.blind_factor,
+                ) == bc1.com;
+            if !correct_decom {
+                bad_commitments_vec.push(i);
+                continue;
+            }
+            let correct_key = verify_correct_key_correct_h1h2(
+                &bc1.e,
+                &bc1.dlog_statement,
+                &bc1.correct_key_proof,
+                &bc1.composite_dlog_proof_base_h1,
+                &bc1.composite_dlog_proof_base_h2,
+            ) && verify_moduli_are_paillier_blum(
+                &bc1.e,
+                &bc1.dlog_statement,
+                &bc1.modulus_proof,
+                &bc1.n_tilde_modulus_proof,
+            ) && verify_no_small_factors(
+                &bc1.e,
+                &bc1.dlog_statement,
+                decom.no_small_factor_proofs.get(position),
+                &my_dlog_statement,
+            );
+            if !correct_key {
+                bad_keys_vec.push(i);
+            }
+        }
 
-        let err_type = ErrorType {
-            error_type: "invalid key".to_string(),
-            bad_actors: bad_actors_vec,
+        // parties who can't open their commitments are blamed first, keys are not checked for them
+        let err_type = if !bad_commitments_vec.is_empty() {
+            Some(ErrorType {
+                kind: ErrorKind::BadCommitment,
+                bad_actors: bad_commitments_vec,
+            })
+        } else if !bad_keys_vec.is_empty() {
+            Some(ErrorType {
+                kind: ErrorKind::InvalidKey,
+                bad_actors: bad_keys_vec,
+            })
+        } else {
+            None
         };
 
         let (vss_scheme, secret_shares) =
-            VerifiableSS::share(params.threshold, params.share_count, &self.u_i);
-        if correct_key_correct_decom_all {
-            Ok((vss_scheme, secret_shares.to_vec(), self.party_index))
-        } else {
-            Err(err_type)
+            rng::share(params.threshold, params.share_count, &self.u_i, rng);
+        match err_type {
+            None => Ok((vss_scheme, secret_shares, self.party_index)),
+            Some(err_type) => Err(err_type),
         }
     }
 
@@ -348,7 +648,7 @@ impl Keys {
             .all(|x| x);
 
         let err_type = ErrorType {
-            error_type: "invalid vss".to_string(),
+            kind: ErrorKind::BadVssShare,
             bad_actors: bad_actors_vec,
         };
 
@@ -426,7 +726,7 @@ impl Keys {
             .all(|x| x);
 
         let err_type = ErrorType {
-            error_type: "bad dlog proof".to_string(),
+            kind: ErrorKind::BadDLogProof,
             bad_actors: bad_actors_vec,
         };
 
@@ -436,6 +736,236 @@ impl Keys {
             Err(err_type)
         }
     }
+
+    /// Generates sharing of zero and proves correctness of new Paillier key and `h1, h2, N_tilde`
+    ///
+    /// `h1_h2_n_tilde_vec` are setups of all parties from the key being refreshed, proofs of no small
+    /// factors are generated with respect to them.
+    pub fn refresh_phase1_broadcast_zero_shares(
+        &self,
+        params: &Parameters,
+        h1_h2_n_tilde_vec: &[DLogStatement],
+    ) -> (RefreshBroadcastMessage1, Vec<Scalar<Secp256k1>>) {
+        self.refresh_phase1_broadcast_zero_shares_with_rng(params, h1_h2_n_tilde_vec, &mut OsRng)
+    }
+
+    /// Same as [Keys::refresh_phase1_broadcast_zero_shares], but takes VSS coefficients and proofs
+    /// randomness from `rng`
+    pub fn refresh_phase1_broadcast_zero_shares_with_rng<R: RngCore + CryptoRng + ?Sized>(
+        &self,
+        params: &Parameters,
+        h1_h2_n_tilde_vec: &[DLogStatement],
+        rng: &mut R,
+    ) -> (RefreshBroadcastMessage1, Vec<Scalar<Secp256k1>>) {
+        let (
+            dlog_statement,
+            correct_key_proof,
+            composite_dlog_proof_base_h1,
+            composite_dlog_proof_base_h2,
+        ) = self.proof_of_correct_key_proof_of_correct_h1h2();
+        let (modulus_proof, n_tilde_modulus_proof) =
+            self.prove_moduli_are_paillier_blum_with_rng(rng);
+        let no_small_factor_proofs = self.prove_no_small_factors_with_rng(h1_h2_n_tilde_vec, rng);
+        // sharing of zero: adding it to the existing shares re-randomizes them while keeping
+        // the shared secret (and therefore the public key) unchanged
+        let (vss_scheme, zero_shares) = rng::share(
+            params.threshold,
+            params.share_count,
+            &Scalar::<Secp256k1>::zero(),
+            rng,
+        );
+        let bcm1 = RefreshBroadcastMessage1 {
+            e: self.ek.clone(),
+            dlog_statement,
+            correct_key_proof,
+            composite_dlog_proof_base_h1,
+            composite_dlog_proof_base_h2,
+            modulus_proof,
+            n_tilde_modulus_proof,
+            no_small_factor_proofs,
+            vss_scheme,
+        };
+        (bcm1, zero_shares)
+    }
+
+    /// Verifies new Paillier keys, `h1, h2, N_tilde` and sharings of zero
+    ///
+    /// `index` is index of verifying party (starting from 1), and `setup` is its `h1, h2, N_tilde`
+    /// from the key being refreshed.
+    pub fn refresh_phase1_verify_correct_key_verify_dlog_verify_zero_vss(
+        params: &Parameters,
+        bc1_vec: &[RefreshBroadcastMessage1],
+        index: usize,
+        setup: &DLogStatement,
+    ) -> Result<(), ErrorType> {
+        assert_eq!(bc1_vec.len(), usize::from(params.share_count));
+        // Every message is checked, so that all misbehaving parties are blamed
+        let bad_actors_vec: Vec<usize> = (0..bc1_vec.len())
+            .filter(|&i| {
+                let vss_scheme = &bc1_vec[i].vss_scheme;
+                let valid = verify_correct_key_correct_h1h2(
+                    &bc1_vec[i].e,
+                    &bc1_vec[i].dlog_statement,
+                    &bc1_vec[i].correct_key_proof,
+                    &bc1_vec[i].composite_dlog_proof_base_h1,
+                    &bc1_vec[i].composite_dlog_proof_base_h2,
+                ) && verify_moduli_are_paillier_blum(
+                    &bc1_vec[i].e,
+                    &bc1_vec[i].dlog_statement,
+                    &bc1_vec[i].modulus_proof,
+                    &bc1_vec[i].n_tilde_modulus_proof,
+                ) && verify_no_small_factors(
+                    &bc1_vec[i].e,
+                    &bc1_vec[i].dlog_statement,
+                    index
+                        .checked_sub(1)
+                        .and_then(|j| bc1_vec[i].no_small_factor_proofs.get(j)),
+                    setup,
+                ) && vss_scheme.parameters.threshold == params.threshold
+                    && vss_scheme.parameters.share_count == params.share_count
+                    && vss_scheme.commitments.len() == usize::from(params.threshold) + 1
+                    && vss_scheme.commitments[0].is_zero();
+                !valid
+            })
+            .collect();
+
+        if bad_actors_vec.is_empty() {
+            Ok(())
+        } else {
+            Err(ErrorType {
+                kind: ErrorKind::InvalidKey,
+                bad_actors: bad_actors_vec,
+            })
+        }
+    }
+
+    pub fn refresh_phase2_verify_zero_shares_update_keypair_pok_dlog(
+        shared_keys: &SharedKeys,
+        params: &Parameters,
+        zero_shares_vec: &[Scalar<Secp256k1>],
+        vss_scheme_vec: &[VerifiableSS<Secp256k1>],
+        index: usize,
+    ) -> Result<(SharedKeys, DLogProof<Secp256k1, Sha256>), ErrorType> {
+        assert_eq!(zero_shares_vec.len(), usize::from(params.share_count));
+        assert_eq!(vss_scheme_vec.len(), usize::from(params.share_count));
+
+        let bad_actors_vec: Vec<usize> = (0..zero_shares_vec.len())
+            .filter(|&i| {
+                vss_scheme_vec[i]
+                    .validate_share(&zero_shares_vec[i], index.try_into().unwrap())
+                    .is_err()
+            })
+            .collect();
+
+        if bad_actors_vec.is_empty() {
+            let x_i = zero_shares_vec
+                .iter()
+                .fold(shared_keys.x_i.clone(), |acc, x| acc + x);
+            let dlog_proof = DLogProof::prove(&x_i);
+            Ok((
+                SharedKeys {
+                    y: shared_keys.y.clone(),
+                    x_i,
+                },
+                dlog_proof,
+            ))
+        } else {
+            Err(ErrorType {
+                kind: ErrorKind::BadVssShare,
+                bad_actors: bad_actors_vec,
+            })
+        }
+    }
+
+    pub fn refresh_phase3_update_pk_vec(
+        pk_vec: &[Point<Secp256k1>],
+        vss_scheme_vec: &[VerifiableSS<Secp256k1>],
+    ) -> Vec<Point<Secp256k1>> {
+        (0..pk_vec.len())
+            .map(|i| {
+                let index = u16::try_from(i + 1).unwrap();
+                vss_scheme_vec.iter().fold(pk_vec[i].clone(), |acc, vss| {
+                    acc + vss.get_point_commitment(index)
+                })
+            })
+            .collect()
+    }
+
+    pub fn refresh_phase3_verify_dlog_proofs(
+        params: &Parameters,
+        dlog_proofs_vec: &[DLogProof<Secp256k1, Sha256>],
+        pk_vec: &[Point<Secp256k1>],
+    ) -> Result<(), ErrorType> {
+        assert_eq!(pk_vec.len(), usize::from(params.share_count));
+        assert_eq!(dlog_proofs_vec.len(), usize::from(params.share_count));
+        let bad_actors_vec: Vec<usize> = (0..pk_vec.len())
+            .filter(|&i| {
+                DLogProof::verify(&dlog_proofs_vec[i]).is_err()
+                    || dlog_proofs_vec[i].pk != pk_vec[i]
+            })
+            .collect();
+
+        if bad_actors_vec.is_empty() {
+            Ok(())
+        } else {
+            Err(ErrorType {
+                kind: ErrorKind::BadDLogProof,
+                bad_actors: bad_actors_vec,
+            })
+        }
+    }
+}
+
+pub fn verify_correct_key_correct_h1h2(
+    ek: &EncryptionKey,
+    dlog_statement: &DLogStatement,
+    correct_key_proof: &NiCorrectKeyProof,
+    composite_dlog_proof_base_h1: &CompositeDLogProof,
+    composite_dlog_proof_base_h2: &CompositeDLogProof,
+) -> bool {
+    let dlog_statement_base_h2 = DLogStatement {
+        N: dlog_statement.N.clone(),
+        g: dlog_statement.ni.clone(),
+        ni: dlog_statement.g.clone(),
+    };
+    correct_key_proof
+        .verify(ek, zk_paillier::zkproofs::SALT_STRING)
+        .is_ok()
+        && ek.n.bit_length() >= PAILLIER_MIN_BIT_LENGTH
+        && ek.n.bit_length() <= PAILLIER_MAX_BIT_LENGTH
+        && dlog_statement.N.bit_length() >= PAILLIER_MIN_BIT_LENGTH
+        && dlog_statement.N.bit_length() <= PAILLIER_MAX_BIT_LENGTH
+        && composite_dlog_proof_base_h1.verify(dlog_statement).is_ok()
+        && composite_dlog_proof_base_h2
+            .verify(&dlog_statement_base_h2)
+            .is_ok()
+}
+
+/// Verifies proofs that Paillier modulus and `N_tilde` are Paillier-Blum moduli
+pub fn verify_moduli_are_paillier_blum(
+    ek: &EncryptionKey,
+    dlog_statement: &DLogStatement,
+    modulus_proof: &PaillierBlumModulusProof,
+    n_tilde_modulus_proof: &PaillierBlumModulusProof,
+) -> bool {
+    modulus_proof.verify(&ek.n).is_ok() && n_tilde_modulus_proof.verify(&dlog_statement.N).is_ok()
+}
+
+/// Verifies proofs that Paillier modulus and `N_tilde` have no small factors
+///
+/// `proofs` must be generated with respect to verifier's own `setup`, missing proofs are invalid.
+pub fn verify_no_small_factors(
+    ek: &EncryptionKey,
+    dlog_statement: &DLogStatement,
+    proofs: Option<&NoSmallFactorProofs>,
+    setup: &DLogStatement,
+) -> bool {
+    proofs
+        .map(|proofs| {
+            proofs.paillier_n.verify(&ek.n, setup).is_ok()
+                && proofs.n_tilde.verify(&dlog_statement.N, setup).is_ok()
+        })
+        .unwrap_or(false)
 }
 
 impl PartyPrivate {
@@ -458,45 +988,14 @@ impl PartyPrivate {
 
     pub fn refresh_private_key(&self, factor: &Scalar<Secp256k1>, index: usize) -> Keys {
         let u: Scalar<Secp256k1> = &self.u_i + factor;
-        let y = Point::generator() * &u;
-        let (ek, dk) = Paillier::keypair().keys();
-
-        let (N_tilde, h1, h2, xhi, xhi_inv) = generate_h1_h2_N_tilde();
-
-        Keys {
-            u_i: u,
-            y_i: y,
-            dk,
-            ek,
-            party_index: index,
-            N_tilde,
-            h1,
-            h2,
-            xhi,
-            xhi_inv,
-        }
+        Keys::from_parts(u, index, paillier_blum_keypair(), generate_zk_setup())
     }
 
     // we recommend using safe primes if the code is used in production
     pub fn refresh_private_key_safe_prime(&self, factor: &Scalar<Secp256k1>, index: usize) -> Keys {
         let u: Scalar<Secp256k1> = &self.u_i + factor;
-        let y = Point::generator() * &u;
         let (ek, dk) = Paillier::keypair_safe_primes().keys();
-
-        let (N_tilde, h1, h2, xhi, xhi_inv) = generate_h1_h2_N_tilde();
-
-        Keys {
-            u_i: u,
-            y_i: y,
-            dk,
-            ek,
-            party_index: index,
-            N_tilde,
-            h1,
-            h2,
-            xhi,
-            xhi_inv,
-        }
+        Keys::from_parts(u, index, (ek, dk), generate_zk_setup())
     }
 
     // used for verifiable recovery
@@ -548,6 +1047,17 @@ impl SignKeys {
         vss_scheme: &VerifiableSS<Secp256k1>,
         index: usize,
         s: &[usize],
+    ) -> Self {
+        Self::create_with_rng(private_x_i, vss_scheme, index, s, &mut OsRng)
+    }
+
+    /// Same as [SignKeys::create], but samples `k_i` and `gamma_i` from `rng`
+    pub fn create_with_rng<R: RngCore + CryptoRng + ?Sized>(
+        private_x_i: &Scalar<Secp256k1>,
+        vss_scheme: &VerifiableSS<Secp256k1>,
+        index: usize,
+        s: &[usize],
+        rng: &mut R,
     ) -> Self {
         let s: Vec<u16> = s.iter().map(|&i| i.try_into().unwrap()).collect();
         let li = VerifiableSS::<Secp256k1>::map_share_to_new_params(
@@ -558,9 +1068,9 @@ impl SignKeys {
         let w_i = li * private_x_i;
         let g = Point::generator();
         let g_w_i = g * &w_i;
-        let gamma_i = Scalar::<Secp256k1>::random();
+        let gamma_i = rng::random_scalar(rng);
         let g_gamma_i = g * &gamma_i;
-        let k_i = Scalar::<Secp256k1>::random();
+        let k_i = rng::random_scalar(rng);
         Self {
             w_i,
             g_w_i,
@@ -571,7 +1081,15 @@ impl SignKeys {
     }
 
     pub fn phase1_broadcast(&self) -> (SignBroadcastPhase1, SignDecommitPhase1) {
-        let blind_factor = BigInt::sample(SECURITY);
+        self.phase1_broadcast_with_rng(&mut OsRng)
+    }
+
+    /// Same as [SignKeys::phase1_broadcast], but takes commitment randomness from `rng`
+    pub fn phase1_broadcast_with_rng<R: RngCore + CryptoRng + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> (SignBroadcastPhase1, SignDecommitPhase1) {
+        let blind_factor = rng::sample_bits(SECURITY, rng);
         let g = Point::generator();
         let g_gamma_i = g * &self.gamma_i;
         let com = HashCommitment::<Sha256>::create_commitment_with_user_defined_randomness(
@@ -623,9 +1141,23 @@ impl SignKeys {
         Point<Secp256k1>,
         Scalar<Secp256k1>,
         PedersenProof<Secp256k1, Sha256>,
+    ) {
+        Self::phase3_compute_t_i_with_rng(sigma_i, &mut OsRng)
+    }
+
+    /// Same as [SignKeys::phase3_compute_t_i], but samples blinding `l` from `rng`
+    ///
+    /// Nonces of Pedersen proof are still sampled by `curv` from operating system.
+    pub fn phase3_compute_t_i_with_rng<R: RngCore + CryptoRng + ?Sized>(
+        sigma_i: &Scalar<Secp256k1>,
+        rng: &mut R,
+    ) -> (
+        Point<Secp256k1>,
+        Scalar<Secp256k1>,
+        PedersenProof<Secp256k1, Sha256>,
     ) {
         let g_sigma_i = Point::generator() * sigma_i;
-        let l = Scalar::<Secp256k1>::random();
+        let l = rng::random_scalar(rng);
         let h_l = Point::<Secp256k1>::base_point2() * &l;
         let T = g_sigma_i + h_l;
         let T_zk_proof = PedersenProof::<Secp256k1, Sha256>::prove(sigma_i, &l);
@@ -671,7 +1203,7 @@ impl SignKeys {
         let tail = g_gamma_i_iter;
 
         let err_type = ErrorType {
-            error_type: "bad gamma_i decommit".to_string(),
+            kind: ErrorKind::BadCommitment,
             bad_actors: bad_actors_vec,
         };
 
@@ -696,6 +1228,30 @@ impl LocalSignature {
         k_i: &Scalar<Secp256k1>,
         k_enc_randomness: &BigInt,
         dlog_statement: &DLogStatement,
+    ) -> PDLwSlackProof {
+        Self::phase5_proof_pdl_with_rng(
+            R_dash,
+            R,
+            k_ciphertext,
+            ek,
+            k_i,
+            k_enc_randomness,
+            dlog_statement,
+            &mut OsRng,
+        )
+    }
+
+    /// Same as [LocalSignature::phase5_proof_pdl], but takes proof randomness from `rng`
+    #[allow(clippy::too_many_arguments)]
+    pub fn phase5_proof_pdl_with_rng<R: RngCore + CryptoRng + ?Sized>(
+        R_dash: &Point<Secp256k1>,
+        R: &Point<Secp256k1>,
+        k_ciphertext: &BigInt,
+        ek: &EncryptionKey,
+        k_i: &Scalar<Secp256k1>,
+        k_enc_randomness: &BigInt,
+        dlog_statement: &DLogStatement,
+        rng: &mut R,
     ) -> PDLwSlackProof {
         // Generate PDL with slack statement, witness and proof
         let pdl_w_slack_statement = PDLwSlackStatement {
@@ -713,7 +1269,7 @@ impl LocalSignature {
             r: k_enc_randomness.clone(),
         };
 
-        PDLwSlackProof::prove(&pdl_w_slack_witness, &pdl_w_slack_statement)
+        PDLwSlackProof::prove_with_rng(&pdl_w_slack_witness, &pdl_w_slack_statement, rng)
     }
 
     pub fn phase5_verify_pdl(
@@ -759,7 +1315,7 @@ impl LocalSignature {
         }
 
         let err_type = ErrorType {
-            error_type: "Bad PDLwSlack proof".to_string(),
+            kind: ErrorKind::BadPdlProof,
             bad_actors: bad_actors_vec,
         };
         Err(err_type)
@@ -824,7 +1380,7 @@ impl LocalSignature {
             true => Ok(()),
             false => {
                 let err_type = ErrorType {
-                    error_type: "phase6".to_string(),
+                    kind: ErrorKind::BadHomoElGamalProof,
                     bad_actors: bad_actors_vec,
                 };
                 Err(err_type)
@@ -6,7 +6,8 @@ use std::time::Duration;
 
 use curv::cryptographic_primitives::proofs::sigma_dlog::DLogProof;
 use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
-use curv::elliptic::curves::{secp256_k1::Secp256k1, Scalar};
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
+use rand::rngs::OsRng;
 use round_based::containers::{
     push::{Push, PushExt},
     *,
@@ -17,9 +18,19 @@ use sha2::Sha256;
 use thiserror::Error;
 
 use crate::protocols::multi_party_ecdsa::gg_2020;
+use crate::utilities::rng::CryptoRngCore;
+use gg_2020::state_machine::wire::{self, Protocol, WireError};
 
+mod derivation;
+mod encrypted;
+mod import;
+mod precomputed;
 mod rounds;
 
+pub use derivation::{derive_tweak, ChainCode, DeriveError, HARDENED_INDEX};
+pub use encrypted::{EncryptedKeyError, Kdf, LocalKeyHeader, PBKDF2_ITERATIONS};
+pub use import::AdditiveShare;
+pub use precomputed::{InvalidMaterial, PrecomputedMaterial};
 use private::InternalError;
 pub use rounds::{LocalKey, ProceedError};
 use rounds::{Round0, Round1, Round2, Round3, Round4};
@@ -38,6 +49,10 @@ pub struct Keygen {
 
     msgs_queue: Vec<Msg<ProtocolMessage>>,
 
+    rng: Box<dyn CryptoRngCore + Send>,
+    round_timeout: Option<Duration>,
+    expected_public_key: Option<Point<Secp256k1>>,
+
     party_i: u16,
     party_n: u16,
 }
@@ -54,6 +69,55 @@ impl Keygen {
     /// * `t` is not in range `[1; n-1]`, returns [Error::InvalidThreshold]
     /// * `i` is not in range `[1; n]`, returns [Error::InvalidPartyIndex]
     pub fn new(i: u16, t: u16, n: u16) -> Result<Self> {
+        Self::with_rng(i, t, n, OsRng)
+    }
+
+    /// Constructs a party of keygen protocol which takes randomness from `rng`
+    ///
+    /// Same as [Keygen::new], but secret share, VSS coefficients and `h1, h2` trapdoor are sampled
+    /// from `rng`. Primes of Paillier modulus and `N_tilde` are generated by `paillier` crate from
+    /// operating system, as are nonces of DLog and composite DLog proofs, so keygen output is not
+    /// reproducible even with a seeded `rng`. To generate Paillier keys and ZK setup with another
+    /// source of randomness, use [Keygen::with_precomputed].
+    pub fn with_rng<Rng>(i: u16, t: u16, n: u16, rng: Rng) -> Result<Self>
+    where
+        Rng: CryptoRngCore + Send + 'static,
+    {
+        Self::construct(i, t, n, None, None, Box::new(rng))
+    }
+
+    /// Constructs a party of keygen protocol which uses pre-generated Paillier keys and ZK setup
+    ///
+    /// Same as [Keygen::new], but skips the most expensive part of the first round. `material`
+    /// must be freshly generated and never used in another keygen.
+    ///
+    /// Returns [Error::InvalidPrecomputedMaterial] if `material` is inconsistent, see
+    /// [PrecomputedMaterial::validate].
+    pub fn with_precomputed(i: u16, t: u16, n: u16, material: PrecomputedMaterial) -> Result<Self> {
+        material
+            .validate()
+            .map_err(Error::InvalidPrecomputedMaterial)?;
+        Self::construct(i, t, n, Some(material), None, Box::new(OsRng))
+    }
+
+    /// Constructs a party of keygen protocol which shares an existing key instead of a random one
+    ///
+    /// Same as [Keygen::new], but party contributes its additive `share` of the key. Every one of
+    /// `n` parties must provide its share, otherwise resulting key will differ. Once keygen is
+    /// completed, it checks that public key matches [share.public_key](AdditiveShare::public_key)
+    /// and returns [Error::PublicKeyMismatch] if it doesn't.
+    pub fn import(i: u16, t: u16, n: u16, share: AdditiveShare) -> Result<Self> {
+        Self::construct(i, t, n, None, Some(share), Box::new(OsRng))
+    }
+
+    fn construct(
+        i: u16,
+        t: u16,
+        n: u16,
+        material: Option<PrecomputedMaterial>,
+        share: Option<AdditiveShare>,
+        rng: Box<dyn CryptoRngCore + Send>,
+    ) -> Result<Self> {
         if n < 2 {
             return Err(Error::TooFewParties);
         }
@@ -63,8 +127,18 @@ impl Keygen {
         if i == 0 || i > n {
             return Err(Error::InvalidPartyIndex);
         }
+        let (secret, expected_public_key) = match share {
+            Some(share) => (Some(share.u_i), Some(share.public_key)),
+            None => (None, None),
+        };
         let mut state = Self {
-            round: R::Round0(Round0 { party_i: i, t, n }),
+            round: R::Round0(Round0 {
+                party_i: i,
+                t,
+                n,
+                material,
+                secret,
+            }),
 
             msgs1: Some(Round1::expects_messages(i, n)),
             msgs2: Some(Round2::expects_messages(i, n)),
@@ -73,6 +147,10 @@ impl Keygen {
 
             msgs_queue: vec![],
 
+            rng,
+            round_timeout: None,
+            expected_public_key,
+
             party_i: i,
             party_n: n,
         };
@@ -81,11 +159,23 @@ impl Keygen {
         Ok(state)
     }
 
-    fn gmap_queue<'a, T, F>(&'a mut self, mut f: F) -> impl Push<Msg<T>> + 'a
+    /// Sets how long every round may wait for messages from other parties
+    ///
+    /// Once timeout is reached, protocol execution fails with [Error::RoundTimeout] listing
+    /// parties which didn't deliver their messages. By default, there's no timeout and the
+    /// protocol waits forever.
+    pub fn set_round_timeout(&mut self, timeout: Option<Duration>) {
+        self.round_timeout = timeout;
+    }
+
+    fn gmap_queue<'a, T, F>(
+        queue: &'a mut Vec<Msg<ProtocolMessage>>,
+        mut f: F,
+    ) -> impl Push<Msg<T>> + 'a
     where
         F: FnMut(T) -> M + 'a,
     {
-        (&mut self.msgs_queue).gmap(move |m: Msg<T>| m.map_body(|m| ProtocolMessage(f(m))))
+        queue.gmap(move |m: Msg<T>| m.map_body(|m| ProtocolMessage(f(m))))
     }
 
     /// Proceeds round state if it received enough messages and if it's cheap to compute or
@@ -100,7 +190,10 @@ impl Keygen {
         let try_again: bool = match replace(&mut self.round, R::Gone) {
             R::Round0(round) if !round.is_expensive() || may_block => {
                 next_state = round
-                    .proceed(self.gmap_queue(M::Round1))
+                    .proceed(
+                        &mut *self.rng,
+                        Self::gmap_queue(&mut self.msgs_queue, M::Round1),
+                    )
                     .map(R::Round1)
                     .map_err(Error::ProceedRound)?;
                 true
@@ -115,7 +208,11 @@ impl Keygen {
                     .finish()
                     .map_err(InternalError::RetrieveRoundMessages)?;
                 next_state = round
-                    .proceed(msgs, self.gmap_queue(M::Round2))
+                    .proceed(
+                        msgs,
+                        &mut *self.rng,
+                        Self::gmap_queue(&mut self.msgs_queue, M::Round2),
+                    )
                     .map(R::Round2)
                     .map_err(Error::ProceedRound)?;
                 true
@@ -130,7 +227,11 @@ impl Keygen {
                     .finish()
                     .map_err(InternalError::RetrieveRoundMessages)?;
                 next_state = round
-                    .proceed(msgs, self.gmap_queue(M::Round3))
+                    .proceed(
+                        msgs,
+                        &mut *self.rng,
+                        Self::gmap_queue(&mut self.msgs_queue, M::Round3),
+                    )
                     .map(R::Round3)
                     .map_err(Error::ProceedRound)?;
                 true
@@ -145,7 +246,7 @@ impl Keygen {
                     .finish()
                     .map_err(InternalError::RetrieveRoundMessages)?;
                 next_state = round
-                    .proceed(msgs, self.gmap_queue(M::Round4))
+                    .proceed(msgs, Self::gmap_queue(&mut self.msgs_queue, M::Round4))
                     .map(R::Round4)
                     .map_err(Error::ProceedRound)?;
                 true
@@ -159,10 +260,13 @@ impl Keygen {
                 let msgs = store
                     .finish()
                     .map_err(InternalError::RetrieveRoundMessages)?;
-                next_state = round
-                    .proceed(msgs)
-                    .map(R::Final)
-                    .map_err(Error::ProceedRound)?;
+                let local_key = round.proceed(msgs).map_err(Error::ProceedRound)?;
+                if let Some(expected) = &self.expected_public_key {
+                    if &local_key.y_sum_s != expected {
+                        return Err(Error::PublicKeyMismatch);
+                    }
+                }
+                next_state = R::Final(local_key);
                 true
             }
             s @ R::Round4(_) => {
@@ -289,11 +393,18 @@ impl StateMachine for Keygen {
     }
 
     fn round_timeout(&self) -> Option<Duration> {
-        None
+        match &self.round {
+            R::Round0(_) | R::Final(_) | R::Gone => None,
+            _ => self.round_timeout,
+        }
     }
 
     fn round_timeout_reached(&mut self) -> Self::Err {
-        panic!("no timeout was set")
+        let (_, parties) = super::traits::RoundBlame::round_blame(self);
+        Error::RoundTimeout {
+            round: self.current_round(),
+            parties,
+        }
     }
 
     fn is_finished(&self) -> bool {
@@ -425,6 +536,31 @@ enum M {
     Round4(DLogProof<Secp256k1, Sha256>),
 }
 
+impl ProtocolMessage {
+    /// Encodes message into [binary wire format](super::wire)
+    pub fn to_bytes(&self) -> std::result::Result<Vec<u8>, WireError> {
+        match &self.0 {
+            M::Round1(m) => wire::encode(Protocol::Keygen, 1, m),
+            M::Round2(m) => wire::encode(Protocol::Keygen, 2, m),
+            M::Round3(m) => wire::encode(Protocol::Keygen, 3, m),
+            M::Round4(m) => wire::encode(Protocol::Keygen, 4, m),
+        }
+    }
+
+    /// Decodes message from [binary wire format](super::wire)
+    pub fn from_bytes(bytes: &[u8]) -> std::result::Result<Self, WireError> {
+        let (header, payload) = wire::decode_frame(Protocol::Keygen, bytes)?;
+        let m = match header.round {
+            1 => M::Round1(wire::decode_payload(payload)?),
+            2 => M::Round2(wire::decode_payload(payload)?),
+            3 => M::Round3(wire::decode_payload(payload)?),
+            4 => M::Round4(wire::decode_payload(payload)?),
+            round => return Err(WireError::UnknownRound(round)),
+        };
+        Ok(ProtocolMessage(m))
+    }
+}
+
 // Error
 
 type Result<T> = std::result::Result<T, Error>;
@@ -446,6 +582,12 @@ pub enum Error {
     /// Party index `i` is not in range `[1; n]`
     #[error("party index is not in range [1; n]")]
     InvalidPartyIndex,
+    /// Pre-generated Paillier keys or ZK setup are inconsistent
+    #[error("invalid precomputed material: {0}")]
+    InvalidPrecomputedMaterial(#[source] InvalidMaterial),
+    /// Public key resulting from [imported](Keygen::import) shares doesn't match the expected one
+    #[error("resulting public key doesn't match the imported one")]
+    PublicKeyMismatch,
 
     /// Received message didn't pass pre-validation
     #[error("received message didn't pass pre-validation: {0}")]
@@ -458,6 +600,9 @@ pub enum Error {
     /// [Keygen::pick_output] called twice
     #[error("pick_output called twice")]
     DoublePickOutput,
+    /// Round timeout is reached, `parties` didn't send their messages for `round`
+    #[error("round {round} timed out, parties {parties:?} didn't send their messages")]
+    RoundTimeout { round: u16, parties: Vec<u16> },
 
     /// Some internal assertions were failed, which is a bug
     #[doc(hidden)]
@@ -494,6 +639,9 @@ pub mod test {
     use round_based::dev::Simulation;
 
     use super::*;
+    use crate::protocols::multi_party_ecdsa::gg_2020::state_machine::test::{
+        run_with_transport, through_json,
+    };
 
     pub fn simulate_keygen(t: u16, n: u16) -> Vec<LocalKey<Secp256k1>> {
         let mut simulation = Simulation::new();
@@ -525,4 +673,92 @@ pub mod test {
     fn simulate_keygen_t2_n3() {
         simulate_keygen(2, 3);
     }
+
+    #[test]
+    fn keygen_survives_serialized_transport() {
+        let parties = (1..=3).map(|i| Keygen::new(i, 1, 3).unwrap()).collect();
+        let keys = run_with_transport(parties, |msg: &ProtocolMessage| {
+            let msg = ProtocolMessage::from_bytes(&msg.to_bytes().unwrap()).unwrap();
+            through_json(&msg)
+        });
+        assert!(keys.iter().all(|k| k.public_key() == keys[0].public_key()));
+    }
+
+    #[test]
+    fn simulate_keygen_with_precomputed_material() {
+        let (t, n) = (1, 3);
+        let material = (0..n)
+            .map(|_| PrecomputedMaterial::generate())
+            .collect::<Vec<_>>();
+
+        let mut simulation = Simulation::new();
+        for (i, m) in (1..).zip(&material) {
+            // material survives serialization
+            let m = serde_json::to_vec(m).unwrap();
+            let m = serde_json::from_slice(&m).unwrap();
+            simulation.add_party(Keygen::with_precomputed(i, t, n, m).unwrap());
+        }
+        let keys = simulation.run().unwrap();
+
+        for (key, m) in keys.iter().zip(&material) {
+            assert_eq!(key.paillier_dk.p, m.dk.p);
+            assert_eq!(key.paillier_key_vec[usize::from(key.i - 1)].n, m.ek.n);
+            assert_eq!(key.h1_h2_n_tilde_vec[usize::from(key.i - 1)].N, m.N_tilde);
+        }
+    }
+
+    #[test]
+    fn inconsistent_precomputed_material_is_rejected() {
+        let mut material = PrecomputedMaterial::generate();
+        material.h2 = &material.h2 + curv::BigInt::from(1);
+        assert!(matches!(
+            Keygen::with_precomputed(1, 1, 2, material),
+            Err(Error::InvalidPrecomputedMaterial(
+                InvalidMaterial::InvalidZkSetup
+            ))
+        ));
+    }
+
+    #[test]
+    fn precomputed_material_with_composite_factor_is_rejected() {
+        let mut material = PrecomputedMaterial::generate();
+        // 9 = 1 mod 4, so the factor stays 3 mod 4
+        material.dk.p = &material.dk.p * curv::BigInt::from(9);
+        material.ek = paillier::EncryptionKey::from(&(&material.dk.p * &material.dk.q));
+        assert!(matches!(
+            material.validate(),
+            Err(InvalidMaterial::CompositeFactor)
+        ));
+    }
+
+    #[test]
+    fn precomputed_material_debug_omits_secrets() {
+        let material = PrecomputedMaterial::generate();
+        let debug = format!("{:?}", material);
+        assert!(!debug.contains(&format!("{:?}", material.dk.p)));
+        assert!(!debug.contains(&format!("{:?}", material.xhi)));
+    }
+
+    #[test]
+    fn round_timeout_blames_silent_parties() {
+        let mut parties = (1..=3)
+            .map(|i| Keygen::new(i, 1, 3).unwrap())
+            .collect::<Vec<_>>();
+        parties[0].set_round_timeout(Some(Duration::from_secs(10)));
+        assert_eq!(parties[0].round_timeout(), None);
+
+        // party 3 stays silent
+        parties[0].proceed().unwrap();
+        parties[1].proceed().unwrap();
+        let msgs = parties[1].message_queue().drain(..).collect::<Vec<_>>();
+        for msg in msgs {
+            parties[0].handle_incoming(msg).unwrap();
+        }
+
+        assert_eq!(parties[0].round_timeout(), Some(Duration::from_secs(10)));
+        assert!(matches!(
+            parties[0].round_timeout_reached(),
+            Error::RoundTimeout { round: 1, parties } if parties == [3]
+        ));
+    }
 }
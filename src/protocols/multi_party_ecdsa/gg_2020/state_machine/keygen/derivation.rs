@@ -0,0 +1,246 @@
+//! Non-hardened BIP32 child key derivation
+//!
+//! Child public key is `Y + δ·G`, where tweak `δ` is derived from parent public key `Y` and chain
+//! code as specified by [BIP32](https://github.com/bitcoin/bips/blob/master/bip-0032.mediawiki#public-parent-key--public-child-key).
+//! Since derivation is public, every party obtains the same `δ` and adds it to its secret share.
+//! This shifts the shared polynomial by a constant, so any `t+1` derived shares interpolate into
+//! the child secret key, and derived [LocalKey] can be used in [OfflineStage](super::super::sign::OfflineStage)
+//! as is.
+
+use curv::arithmetic::traits::*;
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
+use curv::BigInt;
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha512;
+use thiserror::Error;
+
+use super::LocalKey;
+use crate::protocols::multi_party_ecdsa::gg_2020::party_i::SharedKeys;
+
+/// Indexes starting from this one are hardened and can't be derived from public key
+pub const HARDENED_INDEX: u32 = 1 << 31;
+
+/// BIP32 chain code
+pub type ChainCode = [u8; 32];
+
+/// Computes tweak `δ` and child chain code for non-hardened derivation `path` from public key `y`
+///
+/// Child public key equals to `y + δ·G`.
+pub fn derive_tweak(
+    y: &Point<Secp256k1>,
+    chain_code: &ChainCode,
+    path: &[u32],
+) -> Result<(Scalar<Secp256k1>, ChainCode)> {
+    let mut tweak = Scalar::<Secp256k1>::zero();
+    let mut public_key = y.clone();
+    let mut chain_code = *chain_code;
+
+    for &index in path {
+        if index >= HARDENED_INDEX {
+            return Err(DeriveError::HardenedIndex(index));
+        }
+        let mut mac =
+            Hmac::<Sha512>::new_from_slice(&chain_code).expect("HMAC accepts keys of any length");
+        mac.update(&public_key.to_bytes(true));
+        mac.update(&index.to_be_bytes());
+        let i = mac.finalize().into_bytes();
+        let (il, ir) = i.split_at(32);
+
+        let il = BigInt::from_bytes(il);
+        if &il >= Scalar::<Secp256k1>::group_order() {
+            return Err(DeriveError::InvalidChild(index));
+        }
+        let il = Scalar::from(&il);
+        public_key = public_key + Point::generator() * &il;
+        if public_key.is_zero() {
+            return Err(DeriveError::InvalidChild(index));
+        }
+        tweak = tweak + il;
+        chain_code.copy_from_slice(ir);
+    }
+
+    Ok((tweak, chain_code))
+}
+
+impl LocalKey<Secp256k1> {
+    /// Derives child key at non-hardened `path`
+    ///
+    /// Returns derived key and its chain code. Every party must derive its key with the same
+    /// `chain_code` and `path` before signing with the child key.
+    pub fn derive_child(
+        &self,
+        chain_code: &ChainCode,
+        path: &[u32],
+    ) -> Result<(LocalKey<Secp256k1>, ChainCode)> {
+        let (tweak, child_chain_code) = derive_tweak(&self.y_sum_s, chain_code, path)?;
+        Ok((self.tweak(&tweak)?, child_chain_code))
+    }
+
+    /// Shifts shared secret by `tweak`
+    ///
+    /// Secret share of every party is shifted by `tweak`, as well as public key and public shares.
+    /// Constant term of party's VSS dealing is shifted by `tweak / n`, so that sum of all dealings
+    /// stays consistent with shifted shares. If `vss_scheme` commits to the joint polynomial (as
+    /// it does for keys obtained via reshare or recovery), its constant term is shifted by `tweak`.
+    ///
+    /// Returns error if `vss_scheme` has no commitments, as such key can't be tweaked consistently.
+    pub fn tweak(&self, tweak: &Scalar<Secp256k1>) -> Result<LocalKey<Secp256k1>> {
+        let g_tweak = Point::generator() * tweak;
+        let n_inv = Scalar::<Secp256k1>::from(&BigInt::from(u64::from(self.n)))
+            .invert()
+            .expect("n is not zero");
+
+        let mut vss_scheme = self.vss_scheme.clone();
+        let c0 = vss_scheme
+            .commitments
+            .first_mut()
+            .ok_or(DeriveError::MissingCommitments)?;
+        if *c0 == self.y_sum_s {
+            *c0 = &*c0 + &g_tweak;
+        } else {
+            *c0 = &*c0 + Point::generator() * &(tweak * &n_inv);
+        }
+
+        Ok(LocalKey {
+            paillier_dk: self.paillier_dk.clone(),
+            pk_vec: self.pk_vec.iter().map(|pk| pk + &g_tweak).collect(),
+            keys_linear: SharedKeys {
+                y: &self.keys_linear.y + &g_tweak,
+                x_i: &self.keys_linear.x_i + tweak,
+            },
+            paillier_key_vec: self.paillier_key_vec.clone(),
+            y_sum_s: &self.y_sum_s + &g_tweak,
+            h1_h2_n_tilde_vec: self.h1_h2_n_tilde_vec.clone(),
+            vss_scheme,
+            i: self.i,
+            t: self.t,
+            n: self.n,
+        })
+    }
+}
+
+type Result<T> = std::result::Result<T, DeriveError>;
+
+/// Error of child key derivation
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum DeriveError {
+    /// Path contains hardened index which can't be derived from public key
+    #[error("hardened index {0} can't be derived from public key")]
+    HardenedIndex(u32),
+    /// Derived child key is invalid (probability is lower than 2^-127), next index should be used
+    #[error("derived child key at index {0} is invalid")]
+    InvalidChild(u32),
+    /// Local key has no VSS commitments, so they can't be shifted along with the shares
+    #[error("local key doesn't have vss commitments")]
+    MissingCommitments,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::protocols::multi_party_ecdsa::gg_2020::state_machine::keygen::test::simulate_keygen;
+    use crate::protocols::multi_party_ecdsa::gg_2020::state_machine::sign::test::{
+        simulate_offline_stage, simulate_signing,
+    };
+
+    #[test]
+    fn derive_child_and_sign() {
+        let local_keys = simulate_keygen(1, 3);
+        let chain_code = [7u8; 32];
+        let path = [0, 1, 42];
+
+        let derived = local_keys
+            .iter()
+            .map(|k| k.derive_child(&chain_code, &path).unwrap())
+            .collect::<Vec<_>>();
+        let (tweak, child_chain_code) =
+            derive_tweak(&local_keys[0].public_key(), &chain_code, &path).unwrap();
+        let child_pk = local_keys[0].public_key() + Point::generator() * &tweak;
+
+        for (key, cc) in &derived {
+            assert_eq!(key.public_key(), child_pk);
+            assert_eq!(cc, &child_chain_code);
+            for (j, pk) in key.pk_vec.iter().enumerate() {
+                assert_eq!(pk, &(Point::generator() * &derived[j].0.keys_linear.x_i));
+            }
+        }
+
+        // deriving step by step gives the same key
+        let (step, cc) = local_keys[0].derive_child(&chain_code, &path[..1]).unwrap();
+        let (step, _) = step.derive_child(&cc, &path[1..]).unwrap();
+        assert_eq!(step.public_key(), child_pk);
+
+        let derived = derived.into_iter().map(|(k, _)| k).collect();
+        let offline_stage = simulate_offline_stage(derived, &[1, 3]);
+        simulate_signing(offline_stage, b"ZenGo");
+    }
+
+    fn check_public_derivation(
+        parent: &str,
+        parent_chain_code: &str,
+        path: &[u32],
+        child: &str,
+        child_chain_code: &str,
+    ) {
+        let parent = Point::<Secp256k1>::from_bytes(&hex::decode(parent).unwrap()).unwrap();
+        let mut chain_code = ChainCode::default();
+        chain_code.copy_from_slice(&hex::decode(parent_chain_code).unwrap());
+
+        let (tweak, derived_chain_code) = derive_tweak(&parent, &chain_code, path).unwrap();
+        let derived = parent + Point::generator() * &tweak;
+        assert_eq!(hex::encode(&*derived.to_bytes(true)), child);
+        assert_eq!(hex::encode(derived_chain_code), child_chain_code);
+    }
+
+    #[test]
+    fn bip32_test_vector_1() {
+        // m/0H -> m/0H/1
+        check_public_derivation(
+            "035a784662a4a20a65bf6aab9ae98a6c068a81c52e4b032c0fb5400c706cfccc56",
+            "47fdacbd0f1097043b78c63c20c34ef4ed9a111d980047ad16282c7ae6236141",
+            &[1],
+            "03501e454bf00751f24b1b489aa925215d66af2234e3891c3b21a52bedb3cd711c",
+            "2a7857631386ba23dacac34180dd1983734e444fdbf774041578e9b6adb37c19",
+        );
+        // m/0H/1/2H -> m/0H/1/2H/2/1000000000
+        check_public_derivation(
+            "0357bfe1e341d01c69fe5654309956cbea516822fba8a601743a012a7896ee8dc2",
+            "04466b9cc8e161e966409ca52986c584f07e9dc81f735db683c3ff6ec7b1503f",
+            &[2, 1000000000],
+            "022a471424da5e657499d1ff51cb43c47481a03b1e77f951fe64cec9f5a48f7011",
+            "c783e67b921d2beb8f6b389cc646d7263b4145701dadd2161548a8b078e65e9e",
+        );
+    }
+
+    #[test]
+    fn bip32_test_vector_2() {
+        // m -> m/0
+        check_public_derivation(
+            "03cbcaa9c98c877a26977d00825c956a238e8dddfbd322cce4f74b0b5bd6ace4a7",
+            "60499f801b896d83179a4374aeb7822aaeaceaa0db1f85ee3e904c4defbd9689",
+            &[0],
+            "02fc9e5af0ac8d9b3cecfe2a888e2117ba3d089d8585886c9c826b6b22a98d12ea",
+            "f0909affaa7ee7abe5dd4e100598d4dc53cd709d5a5c2cac40e7412f232f7c9c",
+        );
+    }
+
+    #[test]
+    fn key_without_commitments_is_not_tweaked() {
+        let mut local_key = simulate_keygen(1, 2).remove(0);
+        local_key.vss_scheme.commitments.clear();
+        assert!(matches!(
+            local_key.tweak(&Scalar::random()),
+            Err(DeriveError::MissingCommitments)
+        ));
+    }
+
+    #[test]
+    fn hardened_index_is_rejected() {
+        let y = Point::generator() * &Scalar::<Secp256k1>::random();
+        assert!(matches!(
+            derive_tweak(&y, &[0u8; 32], &[HARDENED_INDEX]),
+            Err(DeriveError::HardenedIndex(_))
+        ));
+    }
+}
@@ -0,0 +1,363 @@
+//! Encrypted-at-rest serialization of [LocalKey]
+//!
+//! Envelope layout (all integers are big-endian):
+//!
+//! | field        | size                      |
+//! |--------------|---------------------------|
+//! | magic        | 4 bytes, `b"GGLK"`        |
+//! | version      | 1 byte, currently `1`     |
+//! | kdf          | 1 byte, see [Kdf]         |
+//! | iterations   | 4 bytes, see below        |
+//! | salt         | 16 bytes                  |
+//! | nonce        | 12 bytes                  |
+//! | curve        | 1 byte length + name      |
+//! | t, n, i      | 2 bytes each              |
+//! | public key   | 1 byte length + point     |
+//! | ciphertext   | rest of envelope          |
+//!
+//! Everything before ciphertext is a header which is authenticated (but not encrypted) by
+//! AES-256-GCM (see [envelope](crate::utilities::envelope)), so it can be read by
+//! [LocalKeyHeader::from_encrypted_bytes] without knowing the secret, e.g. for listing keys in a
+//! wallet.
+//!
+//! Envelopes encrypted with passphrase are written with [PBKDF2_ITERATIONS] iterations. When
+//! reading, iterations must be in range `[10_000; 10_000_000]` (see
+//! [MIN_PBKDF2_ITERATIONS] and [MAX_PBKDF2_ITERATIONS]), otherwise the envelope is rejected
+//! before deriving the key, so a tampered header can't make decryption arbitrarily expensive.
+//! Envelopes encrypted with raw key have iterations set to `0`.
+
+use std::convert::TryInto;
+
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Curve, Point};
+use thiserror::Error;
+use zeroize::Zeroize;
+
+use super::LocalKey;
+use crate::utilities::envelope::{
+    self, EnvelopeError, MAX_PBKDF2_ITERATIONS, MIN_PBKDF2_ITERATIONS, NONCE_LEN, SALT_LEN,
+};
+
+const MAGIC: &[u8; 4] = b"GGLK";
+const VERSION: u8 = 1;
+/// Number of PBKDF2 iterations used when encrypting with passphrase
+pub const PBKDF2_ITERATIONS: u32 = 100_000;
+
+/// Method used to derive encryption key
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kdf {
+    /// Envelope is encrypted with raw 32 bytes key provided by caller
+    RawKey,
+    /// Encryption key is derived from passphrase using PBKDF2-HMAC-SHA256
+    Pbkdf2Sha256,
+}
+
+impl Kdf {
+    fn to_byte(self) -> u8 {
+        match self {
+            Kdf::RawKey => 0,
+            Kdf::Pbkdf2Sha256 => 1,
+        }
+    }
+
+    fn from_byte(b: u8) -> Result<Self> {
+        match b {
+            0 => Ok(Kdf::RawKey),
+            1 => Ok(Kdf::Pbkdf2Sha256),
+            _ => Err(EncryptedKeyError::UnknownKdf(b)),
+        }
+    }
+}
+
+/// Public part of encrypted [LocalKey]
+///
+/// Note that header is authenticated only when the whole envelope is decrypted, values obtained via
+/// [LocalKeyHeader::from_encrypted_bytes] must not be trusted until then.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocalKeyHeader {
+    pub version: u8,
+    pub kdf: Kdf,
+    pub iterations: u32,
+    pub curve: String,
+    pub t: u16,
+    pub n: u16,
+    pub i: u16,
+    pub public_key: Point<Secp256k1>,
+}
+
+impl LocalKeyHeader {
+    /// Parses header of encrypted [LocalKey] without decrypting it
+    pub fn from_encrypted_bytes(bytes: &[u8]) -> Result<Self> {
+        Self::parse(bytes).map(|(header, _, _, _)| header)
+    }
+
+    fn write(&self, salt: &[u8; SALT_LEN], nonce: &[u8; NONCE_LEN]) -> Vec<u8> {
+        let public_key = self.public_key.to_bytes(true);
+        let mut out = Vec::with_capacity(64 + self.curve.len() + public_key.len());
+        out.extend_from_slice(MAGIC);
+        out.push(self.version);
+        out.push(self.kdf.to_byte());
+        out.extend_from_slice(&self.iterations.to_be_bytes());
+        out.extend_from_slice(salt);
+        out.extend_from_slice(nonce);
+        out.push(self.curve.len() as u8);
+        out.extend_from_slice(self.curve.as_bytes());
+        out.extend_from_slice(&self.t.to_be_bytes());
+        out.extend_from_slice(&self.n.to_be_bytes());
+        out.extend_from_slice(&self.i.to_be_bytes());
+        out.push(public_key.len() as u8);
+        out.extend_from_slice(&public_key);
+        out
+    }
+
+    /// Returns parsed header, salt, nonce and header length
+    fn parse(bytes: &[u8]) -> Result<(Self, [u8; SALT_LEN], [u8; NONCE_LEN], usize)> {
+        let mut reader = Reader { bytes, pos: 0 };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(EncryptedKeyError::InvalidFormat);
+        }
+        let version = reader.take(1)?[0];
+        if version != VERSION {
+            return Err(EncryptedKeyError::UnsupportedVersion(version));
+        }
+        let kdf = Kdf::from_byte(reader.take(1)?[0])?;
+        let iterations = u32::from_be_bytes(reader.take(4)?.try_into().unwrap());
+        // iterations are not authenticated until the envelope is decrypted
+        if kdf == Kdf::Pbkdf2Sha256
+            && !(MIN_PBKDF2_ITERATIONS..=MAX_PBKDF2_ITERATIONS).contains(&iterations)
+        {
+            return Err(EncryptedKeyError::InvalidIterations(iterations));
+        }
+        let salt: [u8; SALT_LEN] = reader.take(SALT_LEN)?.try_into().unwrap();
+        let nonce: [u8; NONCE_LEN] = reader.take(NONCE_LEN)?.try_into().unwrap();
+        let curve_len = usize::from(reader.take(1)?[0]);
+        let curve = String::from_utf8(reader.take(curve_len)?.to_vec())
+            .map_err(|_| EncryptedKeyError::InvalidFormat)?;
+        if curve != Secp256k1::CURVE_NAME {
+            return Err(EncryptedKeyError::CurveMismatch(curve));
+        }
+        let t = u16::from_be_bytes(reader.take(2)?.try_into().unwrap());
+        let n = u16::from_be_bytes(reader.take(2)?.try_into().unwrap());
+        let i = u16::from_be_bytes(reader.take(2)?.try_into().unwrap());
+        let pk_len = usize::from(reader.take(1)?[0]);
+        let public_key = Point::from_bytes(reader.take(pk_len)?)
+            .map_err(|_| EncryptedKeyError::InvalidFormat)?;
+
+        let header = LocalKeyHeader {
+            version,
+            kdf,
+            iterations,
+            curve,
+            t,
+            n,
+            i,
+            public_key,
+        };
+        Ok((header, salt, nonce, reader.pos))
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or(EncryptedKeyError::InvalidFormat)?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+}
+
+impl LocalKey<Secp256k1> {
+    /// Returns public header of the key, as it appears in encrypted envelope
+    pub fn header(&self, kdf: Kdf, iterations: u32) -> LocalKeyHeader {
+        LocalKeyHeader {
+            version: VERSION,
+            kdf,
+            iterations,
+            curve: Secp256k1::CURVE_NAME.to_string(),
+            t: self.t,
+            n: self.n,
+            i: self.i,
+            public_key: self.public_key(),
+        }
+    }
+
+    /// Serializes and encrypts the key with a key derived from `passphrase`
+    pub fn to_encrypted_bytes(&self, passphrase: &[u8]) -> Result<Vec<u8>> {
+        let salt = envelope::random_salt();
+        let mut key = derive_key(passphrase, &salt, PBKDF2_ITERATIONS)?;
+        let result = self.encrypt(
+            &key,
+            self.header(Kdf::Pbkdf2Sha256, PBKDF2_ITERATIONS),
+            salt,
+        );
+        key.zeroize();
+        result
+    }
+
+    /// Decrypts and deserializes the key encrypted by [LocalKey::to_encrypted_bytes]
+    pub fn from_encrypted_bytes(bytes: &[u8], passphrase: &[u8]) -> Result<Self> {
+        let (header, salt, _, _) = LocalKeyHeader::parse(bytes)?;
+        if header.kdf != Kdf::Pbkdf2Sha256 {
+            return Err(EncryptedKeyError::KdfMismatch);
+        }
+        let mut key = derive_key(passphrase, &salt, header.iterations)?;
+        let result = Self::decrypt(&key, bytes);
+        key.zeroize();
+        result
+    }
+
+    /// Serializes and encrypts the key with 32 bytes `key` (e.g. obtained from a hardware keystore)
+    pub fn to_encrypted_bytes_with_key(&self, key: &[u8; 32]) -> Result<Vec<u8>> {
+        self.encrypt(key, self.header(Kdf::RawKey, 0), [0u8; SALT_LEN])
+    }
+
+    /// Decrypts and deserializes the key encrypted by [LocalKey::to_encrypted_bytes_with_key]
+    pub fn from_encrypted_bytes_with_key(bytes: &[u8], key: &[u8; 32]) -> Result<Self> {
+        let (header, _, _, _) = LocalKeyHeader::parse(bytes)?;
+        if header.kdf != Kdf::RawKey {
+            return Err(EncryptedKeyError::KdfMismatch);
+        }
+        Self::decrypt(key, bytes)
+    }
+
+    fn encrypt(
+        &self,
+        key: &[u8; 32],
+        header: LocalKeyHeader,
+        salt: [u8; SALT_LEN],
+    ) -> Result<Vec<u8>> {
+        let nonce = envelope::random_nonce();
+        let mut bytes = header.write(&salt, &nonce);
+        let ciphertext = envelope::seal(key, &nonce, &bytes, self)?;
+        bytes.extend(ciphertext);
+        Ok(bytes)
+    }
+
+    fn decrypt(key: &[u8; 32], bytes: &[u8]) -> Result<Self> {
+        let (header, _, nonce, header_len) = LocalKeyHeader::parse(bytes)?;
+        let (aad, ciphertext) = bytes.split_at(header_len);
+        let local_key: Self = envelope::open(key, &nonce, aad, ciphertext)?;
+
+        if local_key.header(header.kdf, header.iterations) != header {
+            return Err(EncryptedKeyError::HeaderMismatch);
+        }
+        Ok(local_key)
+    }
+}
+
+fn derive_key(passphrase: &[u8], salt: &[u8], iterations: u32) -> Result<[u8; 32]> {
+    envelope::derive_key(passphrase, salt, iterations)
+        .ok_or(EncryptedKeyError::InvalidIterations(iterations))
+}
+
+type Result<T> = std::result::Result<T, EncryptedKeyError>;
+
+/// Error of encrypting or decrypting [LocalKey]
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum EncryptedKeyError {
+    /// Envelope is malformed
+    #[error("invalid envelope format")]
+    InvalidFormat,
+    /// Envelope has version which is not supported by this library
+    #[error("unsupported envelope version: {0}")]
+    UnsupportedVersion(u8),
+    /// Envelope specifies unknown key derivation method
+    #[error("unknown key derivation method: {0}")]
+    UnknownKdf(u8),
+    /// Number of PBKDF2 iterations is not in range `[MIN_PBKDF2_ITERATIONS; MAX_PBKDF2_ITERATIONS]`
+    #[error("number of pbkdf2 iterations {0} is out of allowed range")]
+    InvalidIterations(u32),
+    /// Envelope was encrypted using another kind of secret (passphrase instead of raw key or vice versa)
+    #[error("envelope was encrypted using another kind of secret")]
+    KdfMismatch,
+    /// Envelope holds key on another curve
+    #[error("envelope holds key on curve {0}")]
+    CurveMismatch(String),
+    /// Envelope header doesn't match encrypted key
+    #[error("envelope header doesn't match encrypted key")]
+    HeaderMismatch,
+    /// Serializing or deserializing key failed
+    #[error("serialization: {0}")]
+    Serialization(#[source] serde_json::Error),
+    /// AES-GCM encryption failed
+    #[error("encryption failed")]
+    Encryption,
+    /// Decryption failed: secret is wrong or envelope was tampered with
+    #[error("decryption failed: wrong secret or envelope was tampered with")]
+    Decryption,
+}
+
+impl From<EnvelopeError> for EncryptedKeyError {
+    fn from(err: EnvelopeError) -> Self {
+        match err {
+            EnvelopeError::Serialization(err) => EncryptedKeyError::Serialization(err),
+            EnvelopeError::Encryption => EncryptedKeyError::Encryption,
+            EnvelopeError::Decryption => EncryptedKeyError::Decryption,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::protocols::multi_party_ecdsa::gg_2020::state_machine::keygen::test::simulate_keygen;
+
+    #[test]
+    fn encrypted_local_key_roundtrip() {
+        let local_key = simulate_keygen(1, 2).remove(0);
+
+        let bytes = local_key.to_encrypted_bytes(b"passphrase").unwrap();
+        let header = LocalKeyHeader::from_encrypted_bytes(&bytes).unwrap();
+        assert_eq!(
+            header,
+            local_key.header(Kdf::Pbkdf2Sha256, PBKDF2_ITERATIONS)
+        );
+
+        let decrypted = LocalKey::from_encrypted_bytes(&bytes, b"passphrase").unwrap();
+        assert_eq!(decrypted.keys_linear.x_i, local_key.keys_linear.x_i);
+        assert_eq!(decrypted.paillier_dk.p, local_key.paillier_dk.p);
+        assert!(matches!(
+            LocalKey::from_encrypted_bytes(&bytes, b"wrong passphrase"),
+            Err(EncryptedKeyError::Decryption)
+        ));
+
+        // header is authenticated
+        let mut tampered = bytes.clone();
+        let t_offset = MAGIC.len() + 2 + 4 + SALT_LEN + NONCE_LEN + 1 + Secp256k1::CURVE_NAME.len();
+        tampered[t_offset + 1] ^= 1;
+        assert!(matches!(
+            LocalKey::from_encrypted_bytes(&tampered, b"passphrase"),
+            Err(EncryptedKeyError::Decryption)
+        ));
+
+        // iterations are bounded before deriving the key
+        let iterations_offset = MAGIC.len() + 2;
+        for iterations in [0, MAX_PBKDF2_ITERATIONS + 1] {
+            let mut tampered = bytes.clone();
+            tampered[iterations_offset..iterations_offset + 4]
+                .copy_from_slice(&iterations.to_be_bytes());
+            assert!(matches!(
+                LocalKey::from_encrypted_bytes(&tampered, b"passphrase"),
+                Err(EncryptedKeyError::InvalidIterations(i)) if i == iterations
+            ));
+        }
+
+        let key = [42u8; 32];
+        let bytes = local_key.to_encrypted_bytes_with_key(&key).unwrap();
+        let decrypted = LocalKey::from_encrypted_bytes_with_key(&bytes, &key).unwrap();
+        assert_eq!(decrypted.keys_linear.x_i, local_key.keys_linear.x_i);
+        assert!(matches!(
+            LocalKey::from_encrypted_bytes(&bytes, b"passphrase"),
+            Err(EncryptedKeyError::KdfMismatch)
+        ));
+    }
+}
@@ -0,0 +1,22 @@
+//! Importing an existing key
+//!
+//! Parties that already share a key additively (e.g. after [migration] of a two-party key) can
+//! carry out keygen with their shares instead of random ones. Keygen then re-shares the key with
+//! Feldman VSS and generates fresh Paillier keys and ZK setup, producing
+//! [LocalKey](super::LocalKey) with the same public key.
+//!
+//! [migration]: crate::protocols::two_party_ecdsa::lindell_2017::state_machine::migration
+
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
+use serde::{Deserialize, Serialize};
+
+/// Additive share `u_i` of a secret key `x = u_1 + ... + u_n`
+///
+/// Passed to [Keygen::import](super::Keygen::import).
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AdditiveShare {
+    /// Secret share `u_i`
+    pub u_i: Scalar<Secp256k1>,
+    /// Public key `x * G`, keygen fails if shares of all parties don't add up to it
+    pub public_key: Point<Secp256k1>,
+}
@@ -0,0 +1,172 @@
+//! Paillier keys and ZK setup generated in advance
+//!
+//! Generating Paillier keypair and `(N_tilde, h1, h2)` for range proofs takes the most time of
+//! keygen. [PrecomputedMaterial] can be generated offline (e.g. in a pool filled up by a background
+//! task), serialized, and later passed to [Keygen::with_precomputed](super::Keygen::with_precomputed).
+//!
+//! Material must never be reused: every keygen needs its own fresh [PrecomputedMaterial].
+
+#![allow(non_snake_case)]
+
+use std::fmt;
+
+use curv::arithmetic::traits::*;
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
+use curv::BigInt;
+use paillier::{DecryptionKey, EncryptionKey, KeyGeneration, Paillier};
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::protocols::multi_party_ecdsa::gg_2020::party_i::{
+    generate_zk_setup, generate_zk_setup_with_rng, paillier_blum_keypair, Keys,
+};
+
+/// Number of Miller-Rabin rounds used to test primality of factors
+const PRIMALITY_TEST_ROUNDS: u32 = 64;
+
+/// Paillier keypair and ZK setup `(N_tilde, h1, h2)` with its trapdoor
+///
+/// Factors of both Paillier modulus and `N_tilde` must be Blum primes, as keygen proves that
+/// moduli are well formed.
+///
+/// [Debug] output omits secret values (factors of moduli and trapdoors).
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PrecomputedMaterial {
+    pub ek: EncryptionKey,
+    pub dk: DecryptionKey,
+    pub N_tilde: BigInt,
+    pub dk_tilde: DecryptionKey,
+    pub h1: BigInt,
+    pub h2: BigInt,
+    pub xhi: BigInt,
+    pub xhi_inv: BigInt,
+}
+
+impl PrecomputedMaterial {
+    /// Generates Paillier keypair and ZK setup
+    pub fn generate() -> Self {
+        let (ek, dk) = paillier_blum_keypair();
+        Self::from_parts(ek, dk, generate_zk_setup())
+    }
+
+    /// Generates Paillier keypair made of safe primes and ZK setup
+    pub fn generate_safe_primes() -> Self {
+        let (ek, dk) = Paillier::keypair_safe_primes().keys();
+        Self::from_parts(ek, dk, generate_zk_setup())
+    }
+
+    /// Same as [PrecomputedMaterial::generate], but takes `h1` and the trapdoor from `rng`
+    ///
+    /// Primes of Paillier modulus and `N_tilde` are still sampled by `paillier` crate from
+    /// operating system.
+    pub fn generate_with_rng<R: RngCore + CryptoRng + ?Sized>(rng: &mut R) -> Self {
+        let (ek, dk) = paillier_blum_keypair();
+        let zk_setup = generate_zk_setup_with_rng(rng);
+        Self::from_parts(ek, dk, zk_setup)
+    }
+
+    fn from_parts(
+        ek: EncryptionKey,
+        dk: DecryptionKey,
+        (dk_tilde, h1, h2, xhi, xhi_inv): (DecryptionKey, BigInt, BigInt, BigInt, BigInt),
+    ) -> Self {
+        Self {
+            ek,
+            dk,
+            N_tilde: &dk_tilde.p * &dk_tilde.q,
+            dk_tilde,
+            h1,
+            h2,
+            xhi,
+            xhi_inv,
+        }
+    }
+
+    /// Checks that material is consistent
+    ///
+    /// Ensures that Paillier keys match each other, that trapdoors match `h1`, `h2`, and that all
+    /// factors are primes which are `3 mod 4`. Primality is checked with Miller-Rabin test.
+    pub fn validate(&self) -> Result<(), InvalidMaterial> {
+        if self.ek.n != &self.dk.p * &self.dk.q || self.ek.nn != &self.ek.n * &self.ek.n {
+            return Err(InvalidMaterial::PaillierKeysMismatch);
+        }
+        let one = BigInt::one();
+        if self.N_tilde <= one || self.N_tilde != &self.dk_tilde.p * &self.dk_tilde.q {
+            return Err(InvalidMaterial::InvalidZkSetup);
+        }
+        let factors = [&self.dk.p, &self.dk.q, &self.dk_tilde.p, &self.dk_tilde.q];
+        let is_blum = |x: &BigInt| x.test_bit(0) && x.test_bit(1);
+        if !factors.iter().all(|x| is_blum(x)) {
+            return Err(InvalidMaterial::NotBlumModulus);
+        }
+        if !factors
+            .iter()
+            .all(|x| x.is_probable_prime(PRIMALITY_TEST_ROUNDS))
+        {
+            return Err(InvalidMaterial::CompositeFactor);
+        }
+        // `xhi` and `xhi_inv` are stored negated: h1^xhi = h2^-1 and h2^xhi_inv = h1^-1
+        let h1_xhi_h2 = BigInt::mod_mul(
+            &BigInt::mod_pow(&self.h1, &self.xhi, &self.N_tilde),
+            &self.h2,
+            &self.N_tilde,
+        );
+        let h2_xhi_inv_h1 = BigInt::mod_mul(
+            &BigInt::mod_pow(&self.h2, &self.xhi_inv, &self.N_tilde),
+            &self.h1,
+            &self.N_tilde,
+        );
+        if h1_xhi_h2 != one || h2_xhi_inv_h1 != one {
+            return Err(InvalidMaterial::InvalidZkSetup);
+        }
+        Ok(())
+    }
+
+    /// Constructs party keys with secret share `u` out of the material
+    pub(crate) fn into_keys(self, u: Scalar<Secp256k1>, index: usize) -> Keys {
+        Keys {
+            y_i: Point::generator() * &u,
+            u_i: u,
+            dk: self.dk,
+            ek: self.ek,
+            party_index: index,
+            N_tilde: self.N_tilde,
+            dk_tilde: self.dk_tilde,
+            h1: self.h1,
+            h2: self.h2,
+            xhi: self.xhi,
+            xhi_inv: self.xhi_inv,
+        }
+    }
+}
+
+impl fmt::Debug for PrecomputedMaterial {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PrecomputedMaterial")
+            .field("ek", &self.ek)
+            .field("N_tilde", &self.N_tilde)
+            .field("h1", &self.h1)
+            .field("h2", &self.h2)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Explains why [PrecomputedMaterial] is invalid
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum InvalidMaterial {
+    /// Paillier encryption key doesn't match decryption key
+    #[error("paillier encryption key doesn't match decryption key")]
+    PaillierKeysMismatch,
+    /// `N_tilde` doesn't match its factors, or `h1`, `h2` don't match their trapdoors `xhi`,
+    /// `xhi_inv`
+    #[error("N_tilde doesn't match its factors, or h1, h2 don't match xhi, xhi_inv")]
+    InvalidZkSetup,
+    /// Factors of Paillier modulus or `N_tilde` are not `3 mod 4`
+    #[error("moduli factors are not 3 mod 4")]
+    NotBlumModulus,
+    /// Factor of Paillier modulus or `N_tilde` is not a prime
+    #[error("moduli factors are not primes")]
+    CompositeFactor,
+}
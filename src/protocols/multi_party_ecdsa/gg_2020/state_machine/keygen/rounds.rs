@@ -1,3 +1,5 @@
+use std::convert::TryFrom;
+
 use curv::cryptographic_primitives::proofs::sigma_dlog::DLogProof;
 use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
 use curv::elliptic::curves::{secp256_k1::Secp256k1, Curve, Point, Scalar};
@@ -7,6 +9,7 @@ use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use paillier::EncryptionKey;
+use rand::{CryptoRng, RngCore};
 use round_based::containers::push::Push;
 use round_based::containers::{self, BroadcastMsgs, P2PMsgs, Store};
 use round_based::Msg;
@@ -16,21 +19,34 @@ use crate::protocols::multi_party_ecdsa::gg_2020::party_i::{
     KeyGenBroadcastMessage1, KeyGenDecommitMessage1, Keys,
 };
 use crate::protocols::multi_party_ecdsa::gg_2020::{self, ErrorType};
+use crate::utilities::rng;
+
+use super::PrecomputedMaterial;
 
 pub struct Round0 {
     pub party_i: u16,
     pub t: u16,
     pub n: u16,
+    pub material: Option<PrecomputedMaterial>,
+    pub secret: Option<Scalar<Secp256k1>>,
 }
 
 impl Round0 {
-    pub fn proceed<O>(self, mut output: O) -> Result<Round1>
+    pub fn proceed<O, R>(self, rng: &mut R, mut output: O) -> Result<Round1>
     where
         O: Push<Msg<gg_2020::party_i::KeyGenBroadcastMessage1>>,
+        R: RngCore + CryptoRng + ?Sized,
     {
-        let party_keys = Keys::create(self.party_i as usize);
-        let (bc1, decom1) =
-            party_keys.phase1_broadcast_phase3_proof_of_correct_key_proof_of_correct_h1h2();
+        let party_keys = match (self.material, self.secret) {
+            (Some(material), secret) => {
+                let u = secret.unwrap_or_else(|| rng::random_scalar(rng));
+                material.into_keys(u, self.party_i as usize)
+            }
+            (None, Some(u)) => Keys::create_from_with_rng(u, self.party_i as usize, rng),
+            (None, None) => Keys::create_with_rng(self.party_i as usize, rng),
+        };
+        let (bc1, decom1) = party_keys
+            .phase1_broadcast_phase3_proof_of_correct_key_proof_of_correct_h1h2_with_rng(rng);
 
         output.push(Msg {
             sender: self.party_i,
@@ -61,23 +77,29 @@ pub struct Round1 {
 }
 
 impl Round1 {
-    pub fn proceed<O>(
+    pub fn proceed<O, R>(
         self,
         input: BroadcastMsgs<KeyGenBroadcastMessage1>,
+        rng: &mut R,
         mut output: O,
     ) -> Result<Round2>
     where
         O: Push<Msg<gg_2020::party_i::KeyGenDecommitMessage1>>,
+        R: RngCore + CryptoRng + ?Sized,
     {
+        let received_comm = input.into_vec_including_me(self.bc1);
+        let decom1 =
+            self.keys
+                .phase2_prove_no_small_factors_with_rng(self.decom1, &received_comm, rng);
         output.push(Msg {
             sender: self.party_i,
             receiver: None,
-            body: self.decom1.clone(),
+            body: decom1.clone(),
         });
         Ok(Round2 {
             keys: self.keys,
-            received_comm: input.into_vec_including_me(self.bc1),
-            decom: self.decom1,
+            received_comm,
+            decom: decom1,
 
             party_i: self.party_i,
             t: self.t,
@@ -85,7 +107,7 @@ impl Round1 {
         })
     }
     pub fn is_expensive(&self) -> bool {
-        false
+        true
     }
     pub fn expects_messages(i: u16, n: u16) -> Store<BroadcastMsgs<KeyGenBroadcastMessage1>> {
         containers::BroadcastMsgsStore::new(i, n)
@@ -103,13 +125,15 @@ pub struct Round2 {
 }
 
 impl Round2 {
-    pub fn proceed<O>(
+    pub fn proceed<O, R>(
         self,
         input: BroadcastMsgs<KeyGenDecommitMessage1>,
+        rng: &mut R,
         mut output: O,
     ) -> Result<Round3>
     where
         O: Push<Msg<(VerifiableSS<Secp256k1>, Scalar<Secp256k1>)>>,
+        R: RngCore + CryptoRng + ?Sized,
     {
         let params = gg_2020::party_i::Parameters {
             threshold: self.t,
@@ -119,10 +143,12 @@ impl Round2 {
 
         let vss_result = self
             .keys
-            .phase1_verify_com_phase3_verify_correct_key_verify_dlog_phase2_distribute(
+            .phase1_verify_com_phase3_verify_correct_key_verify_dlog_phase2_distribute_with_rng(
                 &params,
                 &received_decom,
                 &self.received_comm,
+                usize::from(self.party_i - 1),
+                rng,
             )
             .map_err(ProceedError::Round2VerifyCommitments)?;
 
@@ -345,3 +371,25 @@ pub enum ProceedError {
     #[error("round 4: verify dlog proof: {0:?}")]
     Round4VerifyDLogProof(ErrorType),
 }
+
+impl ProceedError {
+    /// Failed check and parties blamed for it
+    pub fn error_type(&self) -> &ErrorType {
+        match self {
+            ProceedError::Round2VerifyCommitments(e)
+            | ProceedError::Round3VerifyVssConstruct(e)
+            | ProceedError::Round4VerifyDLogProof(e) => e,
+        }
+    }
+
+    /// Parties blamed for the error
+    ///
+    /// Parties are identified by their index (in range `[1; n]`).
+    pub fn bad_parties(&self) -> Vec<u16> {
+        self.error_type()
+            .bad_actors()
+            .iter()
+            .map(|&i| u16::try_from(i + 1).unwrap())
+            .collect()
+    }
+}
@@ -1,3 +1,71 @@
 pub mod keygen;
+pub mod recovery;
+pub mod refresh;
+pub mod reshare;
 pub mod sign;
 pub mod traits;
+pub mod wire;
+
+#[cfg(test)]
+pub mod test {
+    use std::fmt::Debug;
+
+    use round_based::StateMachine;
+    use serde::{de::DeserializeOwned, Serialize};
+
+    /// Carries out the protocol, passing every message through `transport` before delivering it
+    ///
+    /// Unlike [Simulation](round_based::dev::Simulation), which hands messages over as they are,
+    /// this catches messages that don't survive being sent over the network.
+    pub fn run_with_transport<P, F>(mut parties: Vec<P>, transport: F) -> Vec<P::Output>
+    where
+        P: StateMachine,
+        P::Err: Debug,
+        P::MessageBody: Clone,
+        F: Fn(&P::MessageBody) -> P::MessageBody,
+    {
+        loop {
+            let mut progress = false;
+            for i in 0..parties.len() {
+                if parties[i].wants_to_proceed() {
+                    parties[i].proceed().unwrap();
+                    progress = true;
+                }
+                let msgs = parties[i].message_queue().drain(..).collect::<Vec<_>>();
+                for msg in msgs {
+                    progress = true;
+                    let body = transport(&msg.body);
+                    for party in parties.iter_mut() {
+                        let receiver = party.party_ind();
+                        if receiver == msg.sender
+                            || matches!(msg.receiver, Some(r) if r != receiver)
+                        {
+                            continue;
+                        }
+                        party
+                            .handle_incoming(round_based::Msg {
+                                sender: msg.sender,
+                                receiver: msg.receiver,
+                                body: body.clone(),
+                            })
+                            .unwrap();
+                    }
+                }
+            }
+            if parties.iter().all(|p| p.is_finished()) {
+                break;
+            }
+            assert!(progress, "protocol got stuck");
+        }
+
+        parties
+            .iter_mut()
+            .map(|p| p.pick_output().unwrap().unwrap())
+            .collect()
+    }
+
+    /// Serializes message to JSON and parses it back
+    pub fn through_json<T: Serialize + DeserializeOwned>(msg: &T) -> T {
+        serde_json::from_str(&serde_json::to_string(msg).unwrap()).unwrap()
+    }
+}
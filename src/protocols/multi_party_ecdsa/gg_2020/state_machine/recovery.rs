@@ -0,0 +1,668 @@
+//! High-level share recovery protocol implementation
+//!
+//! Restores [LocalKey] of a party that lost it, without changing public key. At least `t+1` holders
+//! of [LocalKey] (helpers) interpolate secret share of recovering party at its index: every helper
+//! splits its lagrange-weighted share into additive shares and distributes them among helpers, so
+//! no one but recovering party learns the recovered share. Recovering party generates new Paillier
+//! key and `h1, h2, N_tilde`; helpers output their [LocalKey]s updated with them.
+//!
+//! Recovering party proves in the first round that its Paillier modulus and `N_tilde` are
+//! Paillier-Blum moduli. As in [keygen](super::keygen), it also proves that the moduli have no
+//! small factors: such proof is only sound with respect to verifier's `h1, h2, N_tilde`, so it's
+//! sent to every helper in the second round, along with helpers' additive shares.
+//!
+//! Nobody stores keygen dealing of the lost party, so (same as after resharing) recovered
+//! [LocalKey::vss_scheme] holds commitments to the joint polynomial, interpolated from public
+//! shares `pk_vec`. Recovering party checks that every helper reports the same public data.
+//!
+//! Parties of recovery session are indexed as follows: helpers take indexes `1..=m` (in order of
+//! `helpers`), recovering party takes index `m+1`.
+//!
+//! Parties that didn't take part in recovery as helpers must update `paillier_key_vec` and
+//! `h1_h2_n_tilde_vec` in their [LocalKey]s before signing with recovered party, thus it's best to
+//! invite every remaining party to help.
+
+use std::fmt;
+use std::mem::replace;
+use std::time::Duration;
+
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Scalar};
+use round_based::containers::{
+    push::{Push, PushExt},
+    *,
+};
+use round_based::{IsCritical, Msg, StateMachine};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::protocols::multi_party_ecdsa::gg_2020;
+use gg_2020::state_machine::keygen::LocalKey;
+
+mod rounds;
+
+use private::InternalError;
+pub use rounds::{ProceedError, RecoveryBroadcastMessage1, RecoveryP2PMessage2};
+use rounds::{Round0, Round1, Round2, Round3};
+
+/// Share recovery protocol state machine
+///
+/// Successfully completed share recovery produces [LocalKey] for recovering party, and updated
+/// [LocalKey]s for helpers. Public key stays the same.
+pub struct ShareRecovery {
+    round: R,
+
+    msgs1: Option<Store<BroadcastMsgs<RecoveryBroadcastMessage1>>>,
+    msgs2: Option<Store<P2PMsgs<RecoveryP2PMessage2>>>,
+    msgs3: Option<Store<P2PMsgs<Option<Scalar<Secp256k1>>>>>,
+
+    msgs_queue: Vec<Msg<ProtocolMessage>>,
+
+    party_i: u16,
+    party_n: u16,
+
+    round_timeout: Option<Duration>,
+}
+
+impl ShareRecovery {
+    /// Constructs a helper party
+    ///
+    /// Takes party index `i` in recovery session, list of helpers `helpers` (their indexes in
+    /// keygen), index `recover_i` of party which share is being recovered, and party local secret
+    /// share `local_key`.
+    ///
+    /// Returns error if:
+    /// * `helpers` has less than `t+1` distinct indexes, contains `recover_i` or indexes out of range,
+    ///   returns [Error::InvalidHelpers]
+    /// * `recover_i` is not in range `[1; n]`, returns [Error::InvalidRecoveredIndex]
+    /// * `i` is not in range `[1; m]` or `helpers[i-1] != local_key.i`, returns
+    ///   [Error::InvalidPartyIndex]
+    pub fn helper(
+        i: u16,
+        helpers: Vec<u16>,
+        recover_i: u16,
+        local_key: LocalKey<Secp256k1>,
+    ) -> Result<Self> {
+        Self::validate_params(&helpers, recover_i, local_key.t, local_key.n)?;
+        if i == 0 || usize::from(i) > helpers.len() || helpers[usize::from(i - 1)] != local_key.i {
+            return Err(Error::InvalidPartyIndex);
+        }
+        let (t, n) = (local_key.t, local_key.n);
+        Self::new(i, helpers, recover_i, t, n, Some(local_key))
+    }
+
+    /// Constructs a recovering party
+    ///
+    /// Takes list of helpers `helpers` (their indexes in keygen), index `recover_i` of party
+    /// which share is being recovered, threshold `t` and number of parties `n` of the key.
+    /// Recovering party takes index `m+1` in recovery session, where `m = helpers.len()`. Every
+    /// helper must report the same `t` and `n`.
+    ///
+    /// Returns error if:
+    /// * `helpers` has less than `t+1` distinct indexes, contains `recover_i` or indexes out of range,
+    ///   returns [Error::InvalidHelpers]
+    /// * `recover_i` is not in range `[1; n]`, returns [Error::InvalidRecoveredIndex]
+    pub fn recovering_party(helpers: Vec<u16>, recover_i: u16, t: u16, n: u16) -> Result<Self> {
+        Self::validate_params(&helpers, recover_i, t, n)?;
+        let i = helpers.len() as u16 + 1;
+        Self::new(i, helpers, recover_i, t, n, None)
+    }
+
+    fn validate_params(helpers: &[u16], recover_i: u16, t: u16, n: u16) -> Result<()> {
+        if recover_i == 0 || recover_i > n {
+            return Err(Error::InvalidRecoveredIndex);
+        }
+        let mut sorted = helpers.to_vec();
+        sorted.sort_unstable();
+        sorted.dedup();
+        if sorted.len() != helpers.len()
+            || helpers.len() <= usize::from(t)
+            || helpers.len() >= usize::from(u16::MAX)
+            || helpers.iter().any(|&j| j == 0 || j > n || j == recover_i)
+        {
+            return Err(Error::InvalidHelpers);
+        }
+        Ok(())
+    }
+
+    fn new(
+        i: u16,
+        helpers: Vec<u16>,
+        recover_i: u16,
+        t: u16,
+        n: u16,
+        local_key: Option<LocalKey<Secp256k1>>,
+    ) -> Result<Self> {
+        let parties = helpers.len() as u16 + 1;
+        let mut state = Self {
+            round: R::Round0(Round0 {
+                i,
+                helpers,
+                recover_i,
+                t,
+                n,
+                local_key,
+            }),
+
+            msgs1: Some(Round1::expects_messages(i, parties)),
+            msgs2: Some(Round2::expects_messages(i, parties)),
+            msgs3: Some(Round3::expects_messages(i, parties)),
+
+            msgs_queue: vec![],
+
+            party_i: i,
+            party_n: parties,
+
+            round_timeout: None,
+        };
+
+        state.proceed_round(false)?;
+        Ok(state)
+    }
+
+    /// Sets how long every round may wait for messages from other parties
+    ///
+    /// Once timeout is reached, protocol execution fails with [Error::RoundTimeout] listing
+    /// parties which didn't deliver their messages. By default, there's no timeout and the
+    /// protocol waits forever.
+    pub fn set_round_timeout(&mut self, timeout: Option<Duration>) {
+        self.round_timeout = timeout;
+    }
+
+    fn gmap_queue<'a, T, F>(&'a mut self, mut f: F) -> impl Push<Msg<T>> + 'a
+    where
+        F: FnMut(T) -> M + 'a,
+    {
+        (&mut self.msgs_queue).gmap(move |m: Msg<T>| m.map_body(|m| ProtocolMessage(f(m))))
+    }
+
+    /// Proceeds round state if it received enough messages and if it's cheap to compute or
+    /// `may_block == true`
+    fn proceed_round(&mut self, may_block: bool) -> Result<()> {
+        let store1_wants_more = self.msgs1.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+        let store2_wants_more = self.msgs2.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+        let store3_wants_more = self.msgs3.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+
+        let next_state: R;
+        let try_again: bool = match replace(&mut self.round, R::Gone) {
+            R::Round0(round) if !round.is_expensive() || may_block => {
+                next_state = round
+                    .proceed(self.gmap_queue(M::Round1))
+                    .map(R::Round1)
+                    .map_err(Error::ProceedRound)?;
+                true
+            }
+            s @ R::Round0(_) => {
+                next_state = s;
+                false
+            }
+            R::Round1(round) if !store1_wants_more && (!round.is_expensive() || may_block) => {
+                let store = self.msgs1.take().ok_or(InternalError::StoreGone)?;
+                let msgs = store
+                    .finish()
+                    .map_err(InternalError::RetrieveRoundMessages)?;
+                next_state = round
+                    .proceed(msgs, self.gmap_queue(M::Round2))
+                    .map(R::Round2)
+                    .map_err(Error::ProceedRound)?;
+                true
+            }
+            s @ R::Round1(_) => {
+                next_state = s;
+                false
+            }
+            R::Round2(round) if !store2_wants_more && (!round.is_expensive() || may_block) => {
+                let store = self.msgs2.take().ok_or(InternalError::StoreGone)?;
+                let msgs = store
+                    .finish()
+                    .map_err(InternalError::RetrieveRoundMessages)?;
+                next_state = round
+                    .proceed(msgs, self.gmap_queue(M::Round3))
+                    .map(R::Round3)
+                    .map_err(Error::ProceedRound)?;
+                true
+            }
+            s @ R::Round2(_) => {
+                next_state = s;
+                false
+            }
+            R::Round3(round) if !store3_wants_more && (!round.is_expensive() || may_block) => {
+                let store = self.msgs3.take().ok_or(InternalError::StoreGone)?;
+                let msgs = store
+                    .finish()
+                    .map_err(InternalError::RetrieveRoundMessages)?;
+                next_state = round
+                    .proceed(msgs)
+                    .map(R::Final)
+                    .map_err(Error::ProceedRound)?;
+                true
+            }
+            s @ R::Round3(_) => {
+                next_state = s;
+                false
+            }
+            s @ R::Final(_) | s @ R::Gone => {
+                next_state = s;
+                false
+            }
+        };
+
+        self.round = next_state;
+        if try_again {
+            self.proceed_round(may_block)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl StateMachine for ShareRecovery {
+    type MessageBody = ProtocolMessage;
+    type Err = Error;
+    type Output = LocalKey<Secp256k1>;
+
+    fn handle_incoming(&mut self, msg: Msg<Self::MessageBody>) -> Result<()> {
+        let current_round = self.current_round();
+
+        match msg.body {
+            ProtocolMessage(M::Round1(m)) => {
+                let store = self
+                    .msgs1
+                    .as_mut()
+                    .ok_or(Error::ReceivedOutOfOrderMessage {
+                        current_round,
+                        msg_round: 1,
+                    })?;
+                store
+                    .push_msg(Msg {
+                        sender: msg.sender,
+                        receiver: msg.receiver,
+                        body: m,
+                    })
+                    .map_err(Error::HandleMessage)?;
+                self.proceed_round(false)
+            }
+            ProtocolMessage(M::Round2(m)) => {
+                let store = self
+                    .msgs2
+                    .as_mut()
+                    .ok_or(Error::ReceivedOutOfOrderMessage {
+                        current_round,
+                        msg_round: 2,
+                    })?;
+                store
+                    .push_msg(Msg {
+                        sender: msg.sender,
+                        receiver: msg.receiver,
+                        body: m,
+                    })
+                    .map_err(Error::HandleMessage)?;
+                self.proceed_round(false)
+            }
+            ProtocolMessage(M::Round3(m)) => {
+                let store = self
+                    .msgs3
+                    .as_mut()
+                    .ok_or(Error::ReceivedOutOfOrderMessage {
+                        current_round,
+                        msg_round: 3,
+                    })?;
+                store
+                    .push_msg(Msg {
+                        sender: msg.sender,
+                        receiver: msg.receiver,
+                        body: m,
+                    })
+                    .map_err(Error::HandleMessage)?;
+                self.proceed_round(false)
+            }
+        }
+    }
+
+    fn message_queue(&mut self) -> &mut Vec<Msg<Self::MessageBody>> {
+        &mut self.msgs_queue
+    }
+
+    fn wants_to_proceed(&self) -> bool {
+        let store1_wants_more = self.msgs1.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+        let store2_wants_more = self.msgs2.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+        let store3_wants_more = self.msgs3.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+
+        match &self.round {
+            R::Round0(_) => true,
+            R::Round1(_) => !store1_wants_more,
+            R::Round2(_) => !store2_wants_more,
+            R::Round3(_) => !store3_wants_more,
+            R::Final(_) | R::Gone => false,
+        }
+    }
+
+    fn proceed(&mut self) -> Result<()> {
+        self.proceed_round(true)
+    }
+
+    fn round_timeout(&self) -> Option<Duration> {
+        match &self.round {
+            R::Round0(_) | R::Final(_) | R::Gone => None,
+            _ => self.round_timeout,
+        }
+    }
+
+    fn round_timeout_reached(&mut self) -> Self::Err {
+        let (_, parties) = super::traits::RoundBlame::round_blame(self);
+        Error::RoundTimeout {
+            round: self.current_round(),
+            parties,
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(self.round, R::Final(_))
+    }
+
+    fn pick_output(&mut self) -> Option<Result<Self::Output>> {
+        match self.round {
+            R::Final(_) => (),
+            R::Gone => return Some(Err(Error::DoublePickOutput)),
+            _ => return None,
+        }
+
+        match replace(&mut self.round, R::Gone) {
+            R::Final(result) => Some(Ok(result)),
+            _ => unreachable!("guaranteed by match expression above"),
+        }
+    }
+
+    fn current_round(&self) -> u16 {
+        match &self.round {
+            R::Round0(_) => 0,
+            R::Round1(_) => 1,
+            R::Round2(_) => 2,
+            R::Round3(_) => 3,
+            R::Final(_) | R::Gone => 4,
+        }
+    }
+
+    fn total_rounds(&self) -> Option<u16> {
+        Some(3)
+    }
+
+    fn party_ind(&self) -> u16 {
+        self.party_i
+    }
+
+    fn parties(&self) -> u16 {
+        self.party_n
+    }
+}
+
+impl super::traits::RoundBlame for ShareRecovery {
+    /// Returns number of unwilling parties and a vector of their party indexes.
+    fn round_blame(&self) -> (u16, Vec<u16>) {
+        let store1_blame = self.msgs1.as_ref().map(|s| s.blame()).unwrap_or_default();
+        let store2_blame = self.msgs2.as_ref().map(|s| s.blame()).unwrap_or_default();
+        let store3_blame = self.msgs3.as_ref().map(|s| s.blame()).unwrap_or_default();
+
+        let default = (0, vec![]);
+        match &self.round {
+            R::Round0(_) => default,
+            R::Round1(_) => store1_blame,
+            R::Round2(_) => store2_blame,
+            R::Round3(_) => store3_blame,
+            R::Final(_) | R::Gone => default,
+        }
+    }
+}
+
+impl fmt::Debug for ShareRecovery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let current_round = match &self.round {
+            R::Round0(_) => "0",
+            R::Round1(_) => "1",
+            R::Round2(_) => "2",
+            R::Round3(_) => "3",
+            R::Final(_) => "[Final]",
+            R::Gone => "[Gone]",
+        };
+        let msgs1 = match self.msgs1.as_ref() {
+            Some(msgs) => format!("[{}/{}]", msgs.messages_received(), msgs.messages_total()),
+            None => "[None]".into(),
+        };
+        let msgs2 = match self.msgs2.as_ref() {
+            Some(msgs) => format!("[{}/{}]", msgs.messages_received(), msgs.messages_total()),
+            None => "[None]".into(),
+        };
+        let msgs3 = match self.msgs3.as_ref() {
+            Some(msgs) => format!("[{}/{}]", msgs.messages_received(), msgs.messages_total()),
+            None => "[None]".into(),
+        };
+        write!(
+            f,
+            "{{ShareRecovery at round={} msgs1={} msgs2={} msgs3={} queue=[len={}]}}",
+            current_round,
+            msgs1,
+            msgs2,
+            msgs3,
+            self.msgs_queue.len()
+        )
+    }
+}
+
+// Rounds
+
+enum R {
+    Round0(Round0),
+    Round1(Round1),
+    Round2(Round2),
+    Round3(Round3),
+    Final(LocalKey<Secp256k1>),
+    Gone,
+}
+
+// Messages
+
+/// Protocol message which parties send on wire
+///
+/// Hides actual messages structure so it could be changed without breaking semver policy.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProtocolMessage(M);
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+enum M {
+    Round1(RecoveryBroadcastMessage1),
+    Round2(RecoveryP2PMessage2),
+    Round3(Option<Scalar<Secp256k1>>),
+}
+
+// Error
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Error type of share recovery protocol
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// Round proceeding resulted in error
+    #[error("proceed round: {0}")]
+    ProceedRound(#[source] ProceedError),
+
+    /// List of helpers is invalid (too short, has duplicates or indexes out of range)
+    #[error("invalid list of helpers")]
+    InvalidHelpers,
+    /// Index of recovered party is not in range `[1; n]`
+    #[error("recovered party index is not in range [1; n]")]
+    InvalidRecoveredIndex,
+    /// Party index `i` doesn't match party role
+    #[error("party index is not valid for this role")]
+    InvalidPartyIndex,
+
+    /// Received message didn't pass pre-validation
+    #[error("received message didn't pass pre-validation: {0}")]
+    HandleMessage(#[source] StoreErr),
+    /// Received message which we didn't expect to receive now (e.g. message from previous round)
+    #[error(
+        "didn't expect to receive message from round {msg_round} (being at round {current_round})"
+    )]
+    ReceivedOutOfOrderMessage { current_round: u16, msg_round: u16 },
+    /// [ShareRecovery::pick_output] called twice
+    #[error("pick_output called twice")]
+    DoublePickOutput,
+    /// Round timeout is reached, `parties` didn't send their messages for `round`
+    #[error("round {round} timed out, parties {parties:?} didn't send their messages")]
+    RoundTimeout { round: u16, parties: Vec<u16> },
+
+    /// Some internal assertions were failed, which is a bug
+    #[doc(hidden)]
+    #[error("internal error: {0:?}")]
+    InternalError(InternalError),
+}
+
+impl IsCritical for Error {
+    fn is_critical(&self) -> bool {
+        true
+    }
+}
+
+impl From<InternalError> for Error {
+    fn from(err: InternalError) -> Self {
+        Self::InternalError(err)
+    }
+}
+
+mod private {
+    #[derive(Debug)]
+    #[non_exhaustive]
+    pub enum InternalError {
+        /// [Messages store](super::MessageStore) reported that it received all messages it wanted to receive,
+        /// but refused to return message container
+        RetrieveRoundMessages(super::StoreErr),
+        #[doc(hidden)]
+        StoreGone,
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use round_based::dev::Simulation;
+
+    use super::*;
+    use curv::elliptic::curves::Point;
+    use gg_2020::state_machine::keygen::test::simulate_keygen;
+    use gg_2020::state_machine::sign::test::{simulate_offline_stage, simulate_signing};
+    use gg_2020::state_machine::test::{run_with_transport, through_json};
+
+    pub fn simulate_recovery(
+        local_keys: &[LocalKey<Secp256k1>],
+        helpers: &[u16],
+        recover_i: u16,
+    ) -> Vec<LocalKey<Secp256k1>> {
+        let mut simulation = Simulation::new();
+        simulation.enable_benchmarks(true);
+
+        for (i, &helper) in (1..).zip(helpers) {
+            let local_key = local_keys[usize::from(helper - 1)].clone();
+            simulation.add_party(
+                ShareRecovery::helper(i, helpers.to_vec(), recover_i, local_key).unwrap(),
+            );
+        }
+        let (t, n) = (local_keys[0].t, local_keys[0].n);
+        simulation
+            .add_party(ShareRecovery::recovering_party(helpers.to_vec(), recover_i, t, n).unwrap());
+
+        let keys = simulation.run().unwrap();
+
+        println!("Benchmark results:");
+        println!("{:#?}", simulation.benchmark_results().unwrap());
+
+        keys
+    }
+
+    #[test]
+    fn simulate_recovery_t1_n3_and_sign() {
+        let old_keys = simulate_keygen(1, 3);
+        let keys = simulate_recovery(&old_keys, &[1, 3], 2);
+
+        let recovered = &keys[2];
+        assert_eq!(recovered.i, 2);
+        assert_eq!(recovered.public_key(), old_keys[1].public_key());
+        assert_eq!(recovered.keys_linear.x_i, old_keys[1].keys_linear.x_i);
+        assert_eq!(recovered.vss_scheme.commitments.len(), 2);
+        assert_eq!(recovered.vss_scheme.commitments[0], recovered.public_key());
+        assert_eq!(
+            recovered.vss_scheme.get_point_commitment(2),
+            recovered.pk_vec[1]
+        );
+        assert_ne!(
+            recovered.paillier_key_vec[1].n,
+            old_keys[1].paillier_key_vec[1].n
+        );
+        for helper in &keys[..2] {
+            assert_eq!(
+                helper.paillier_key_vec[1].n,
+                recovered.paillier_key_vec[1].n
+            );
+            assert_eq!(
+                helper.h1_h2_n_tilde_vec[1].N,
+                recovered.h1_h2_n_tilde_vec[1].N
+            );
+        }
+
+        let local_keys = vec![keys[0].clone(), recovered.clone(), keys[1].clone()];
+        let offline_stage = simulate_offline_stage(local_keys, &[1, 2]);
+        simulate_signing(offline_stage, b"ZenGo");
+    }
+
+    #[test]
+    fn recovery_survives_serialized_transport() {
+        let old_keys = simulate_keygen(1, 3);
+        let helpers = vec![1, 2];
+        let mut parties = vec![];
+        for (i, &helper) in (1..).zip(&helpers) {
+            let local_key = old_keys[usize::from(helper - 1)].clone();
+            parties.push(ShareRecovery::helper(i, helpers.clone(), 3, local_key).unwrap());
+        }
+        parties.push(ShareRecovery::recovering_party(helpers, 3, 1, 3).unwrap());
+
+        let keys = run_with_transport(parties, through_json);
+        assert_eq!(keys[2].keys_linear.x_i, old_keys[2].keys_linear.x_i);
+    }
+
+    #[test]
+    fn recovery_rejects_recovered_party_as_helper() {
+        let result = ShareRecovery::recovering_party(vec![1, 2], 2, 1, 3);
+        assert!(matches!(result, Err(Error::InvalidHelpers)));
+    }
+
+    #[test]
+    fn recovering_party_blames_inconsistent_helper() {
+        let old_keys = simulate_keygen(1, 4);
+        let helpers = vec![1, 3, 4];
+
+        // first helper reports public shares that don't match the key
+        let mut bad_key = old_keys[0].clone();
+        bad_key.pk_vec[1] = Point::generator() * Scalar::<Secp256k1>::random();
+        let mut parties = vec![ShareRecovery::helper(1, helpers.clone(), 2, bad_key).unwrap()];
+        for (i, &helper) in (2..).zip(&helpers[1..]) {
+            let local_key = old_keys[usize::from(helper - 1)].clone();
+            parties.push(ShareRecovery::helper(i, helpers.clone(), 2, local_key).unwrap());
+        }
+        let mut recovering = ShareRecovery::recovering_party(helpers, 2, 1, 4).unwrap();
+
+        for party in &mut parties {
+            party.proceed().unwrap();
+            for msg in party.message_queue().drain(..) {
+                recovering.handle_incoming(msg).unwrap();
+            }
+        }
+        let result = recovering.proceed();
+        assert!(
+            matches!(
+                &result,
+                Err(Error::ProceedRound(ProceedError::Round1VerifyBroadcast(err)))
+                    if err.bad_actors() == [0]
+            ),
+            "{:?}",
+            result
+        );
+    }
+}
@@ -0,0 +1,731 @@
+use std::convert::TryFrom;
+use std::slice;
+
+use curv::arithmetic::traits::*;
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::{
+    ShamirSecretSharing, VerifiableSS,
+};
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
+use curv::BigInt;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use paillier::EncryptionKey;
+use round_based::containers::push::Push;
+use round_based::containers::{self, BroadcastMsgs, P2PMsgs, Store};
+use round_based::Msg;
+use zk_paillier::zkproofs::{CompositeDLogProof, DLogStatement, NiCorrectKeyProof};
+
+use crate::protocols::multi_party_ecdsa::gg_2020::party_i::{
+    verify_correct_key_correct_h1h2, verify_moduli_are_paillier_blum, verify_no_small_factors,
+    Keys, NoSmallFactorProofs, SharedKeys,
+};
+use crate::protocols::multi_party_ecdsa::gg_2020::state_machine::keygen::LocalKey;
+use crate::protocols::multi_party_ecdsa::gg_2020::{ErrorKind, ErrorType};
+use crate::utilities::zk_paillier_blum_modulus::PaillierBlumModulusProof;
+
+/// First message of share recovery protocol
+///
+/// Every helper splits its lagrange-weighted share into additive shares (one per helper) and
+/// commits to them, along with the public data of the key. Recovering party publishes its new
+/// Paillier key and `h1, h2, N_tilde` along with proofs of correctness.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum RecoveryBroadcastMessage1 {
+    Helper {
+        i: u16,
+        parameters: ShamirSecretSharing,
+        y_sum_s: Point<Secp256k1>,
+        pk_vec: Vec<Point<Secp256k1>>,
+        paillier_key_vec: Vec<EncryptionKey>,
+        h1_h2_n_tilde_vec: Vec<DLogStatement>,
+        delta_commitments: Vec<Point<Secp256k1>>,
+    },
+    Recovering {
+        e: EncryptionKey,
+        dlog_statement: DLogStatement,
+        correct_key_proof: NiCorrectKeyProof,
+        composite_dlog_proof_base_h1: CompositeDLogProof,
+        composite_dlog_proof_base_h2: CompositeDLogProof,
+        modulus_proof: PaillierBlumModulusProof,
+        n_tilde_modulus_proof: PaillierBlumModulusProof,
+    },
+}
+
+/// Second message of share recovery protocol
+///
+/// Every helper sends additive shares of its lagrange-weighted share to other helpers. Recovering
+/// party proves to every helper that its Paillier modulus and `N_tilde` have no small factors: such
+/// proof is only sound with respect to verifier's `h1, h2, N_tilde`, so it's generated for every
+/// recipient.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecoveryP2PMessage2 {
+    pub delta: Option<Scalar<Secp256k1>>,
+    pub no_small_factor_proofs: Option<NoSmallFactorProofs>,
+}
+
+enum PartySecrets {
+    Helper {
+        local_key: LocalKey<Secp256k1>,
+        deltas: Vec<Scalar<Secp256k1>>,
+    },
+    Recovering {
+        keys: Keys,
+    },
+}
+
+pub struct Round0 {
+    pub i: u16,
+    pub helpers: Vec<u16>,
+    pub recover_i: u16,
+    pub t: u16,
+    pub n: u16,
+    pub local_key: Option<LocalKey<Secp256k1>>,
+}
+
+impl Round0 {
+    pub fn proceed<O>(self, mut output: O) -> Result<Round1>
+    where
+        O: Push<Msg<RecoveryBroadcastMessage1>>,
+    {
+        let (bc1, secrets) = match self.local_key {
+            Some(local_key) => {
+                let lambda_i = lagrange_coefficient_at(self.recover_i, local_key.i, &self.helpers);
+                let w_i = lambda_i * &local_key.keys_linear.x_i;
+
+                // w_i is split into additive shares, one for every helper
+                let mut deltas = (1..self.helpers.len())
+                    .map(|_| Scalar::random())
+                    .collect::<Vec<_>>();
+                let deltas_sum = deltas.iter().fold(Scalar::zero(), |acc, d| acc + d);
+                deltas.push(w_i - deltas_sum);
+
+                let delta_commitments = deltas.iter().map(|d| Point::generator() * d).collect();
+                let bc1 = RecoveryBroadcastMessage1::Helper {
+                    i: local_key.i,
+                    parameters: local_key.vss_scheme.parameters.clone(),
+                    y_sum_s: local_key.y_sum_s.clone(),
+                    pk_vec: local_key.pk_vec.clone(),
+                    paillier_key_vec: local_key.paillier_key_vec.clone(),
+                    h1_h2_n_tilde_vec: local_key.h1_h2_n_tilde_vec.clone(),
+                    delta_commitments,
+                };
+                (bc1, PartySecrets::Helper { local_key, deltas })
+            }
+            None => {
+                let keys = Keys::create(usize::from(self.recover_i));
+                let (
+                    dlog_statement,
+                    correct_key_proof,
+                    composite_dlog_proof_base_h1,
+                    composite_dlog_proof_base_h2,
+                ) = keys.proof_of_correct_key_proof_of_correct_h1h2();
+                let (modulus_proof, n_tilde_modulus_proof) = keys.prove_moduli_are_paillier_blum();
+                let bc1 = RecoveryBroadcastMessage1::Recovering {
+                    e: keys.ek.clone(),
+                    dlog_statement,
+                    correct_key_proof,
+                    composite_dlog_proof_base_h1,
+                    composite_dlog_proof_base_h2,
+                    modulus_proof,
+                    n_tilde_modulus_proof,
+                };
+                (bc1, PartySecrets::Recovering { keys })
+            }
+        };
+
+        output.push(Msg {
+            sender: self.i,
+            receiver: None,
+            body: bc1.clone(),
+        });
+
+        Ok(Round1 {
+            i: self.i,
+            helpers: self.helpers,
+            recover_i: self.recover_i,
+            t: self.t,
+            n: self.n,
+            bc1,
+            secrets,
+        })
+    }
+    pub fn is_expensive(&self) -> bool {
+        true
+    }
+}
+
+pub struct Round1 {
+    i: u16,
+    helpers: Vec<u16>,
+    recover_i: u16,
+    t: u16,
+    n: u16,
+    bc1: RecoveryBroadcastMessage1,
+    secrets: PartySecrets,
+}
+
+impl Round1 {
+    pub fn proceed<O>(
+        self,
+        input: BroadcastMsgs<RecoveryBroadcastMessage1>,
+        mut output: O,
+    ) -> Result<Round2>
+    where
+        O: Push<Msg<RecoveryP2PMessage2>>,
+    {
+        let bc1_vec = input.into_vec_including_me(self.bc1);
+        // helpers take public data of the key from their own local key
+        let own_index = match &self.secrets {
+            PartySecrets::Helper { .. } => Some(usize::from(self.i - 1)),
+            PartySecrets::Recovering { .. } => None,
+        };
+        let public = verify_broadcast(
+            &bc1_vec,
+            &self.helpers,
+            self.recover_i,
+            (self.t, self.n),
+            own_index,
+        )
+        .map_err(ProceedError::Round1VerifyBroadcast)?;
+
+        let m = self.helpers.len();
+        let own_delta = match &self.secrets {
+            PartySecrets::Helper { deltas, .. } => Some(deltas[usize::from(self.i - 1)].clone()),
+            PartySecrets::Recovering { .. } => None,
+        };
+        for j in 1..=m + 1 {
+            if j == usize::from(self.i) {
+                continue;
+            }
+            // additive shares are sent to helpers only
+            let delta = match &self.secrets {
+                PartySecrets::Helper { deltas, .. } if j <= m => Some(deltas[j - 1].clone()),
+                _ => None,
+            };
+            // recovering party proves its key to every helper
+            let no_small_factor_proofs = match &self.secrets {
+                PartySecrets::Recovering { keys } => {
+                    let setup = &public.h1_h2_n_tilde_vec[usize::from(self.helpers[j - 1] - 1)];
+                    keys.prove_no_small_factors(slice::from_ref(setup)).pop()
+                }
+                PartySecrets::Helper { .. } => None,
+            };
+            output.push(Msg {
+                sender: self.i,
+                receiver: Some(u16::try_from(j).unwrap()),
+                body: RecoveryP2PMessage2 {
+                    delta,
+                    no_small_factor_proofs,
+                },
+            })
+        }
+
+        Ok(Round2 {
+            i: self.i,
+            m: m as u16,
+            public,
+            own_delta,
+            secrets: self.secrets,
+        })
+    }
+    pub fn is_expensive(&self) -> bool {
+        true
+    }
+    pub fn expects_messages(i: u16, n: u16) -> Store<BroadcastMsgs<RecoveryBroadcastMessage1>> {
+        containers::BroadcastMsgsStore::new(i, n)
+    }
+}
+
+pub struct Round2 {
+    i: u16,
+    m: u16,
+    public: PublicData,
+    own_delta: Option<Scalar<Secp256k1>>,
+    secrets: PartySecrets,
+}
+
+impl Round2 {
+    pub fn proceed<O>(self, input: P2PMsgs<RecoveryP2PMessage2>, mut output: O) -> Result<Round3>
+    where
+        O: Push<Msg<Option<Scalar<Secp256k1>>>>,
+    {
+        let msgs = input.into_vec_including_me(RecoveryP2PMessage2 {
+            delta: self.own_delta,
+            no_small_factor_proofs: None,
+        });
+
+        let sigma = match &self.secrets {
+            PartySecrets::Helper { local_key, .. } => {
+                let sigma = verify_deltas(&self.public.delta_commitments, &msgs, self.i)
+                    .map_err(ProceedError::Round2VerifyShares)?;
+                let own_setup = &local_key.h1_h2_n_tilde_vec[usize::from(local_key.i - 1)];
+                verify_no_small_factor_proofs(&self.public, &msgs[usize::from(self.m)], own_setup)
+                    .map_err(ProceedError::Round2VerifyNoSmallFactorProofs)?;
+                Some(sigma)
+            }
+            PartySecrets::Recovering { .. } => None,
+        };
+
+        // sum of additive shares is sent to recovering party only
+        for j in 1..=self.m + 1 {
+            if j == self.i {
+                continue;
+            }
+            output.push(Msg {
+                sender: self.i,
+                receiver: Some(j),
+                body: if j == self.m + 1 { sigma.clone() } else { None },
+            })
+        }
+
+        Ok(Round3 {
+            i: self.i,
+            m: self.m,
+            public: self.public,
+            secrets: self.secrets,
+        })
+    }
+    pub fn is_expensive(&self) -> bool {
+        true
+    }
+    pub fn expects_messages(i: u16, n: u16) -> Store<P2PMsgs<RecoveryP2PMessage2>> {
+        containers::P2PMsgsStore::new(i, n)
+    }
+}
+
+pub struct Round3 {
+    i: u16,
+    m: u16,
+    public: PublicData,
+    secrets: PartySecrets,
+}
+
+impl Round3 {
+    pub fn proceed(self, input: P2PMsgs<Option<Scalar<Secp256k1>>>) -> Result<LocalKey<Secp256k1>> {
+        let public = self.public;
+        let index = usize::from(public.recover_i - 1);
+
+        match self.secrets {
+            PartySecrets::Helper { mut local_key, .. } => {
+                local_key.paillier_key_vec[index] = public.paillier_key_vec[index].clone();
+                local_key.h1_h2_n_tilde_vec[index] = public.h1_h2_n_tilde_vec[index].clone();
+                Ok(local_key)
+            }
+            PartySecrets::Recovering { keys } => {
+                let sigmas = input.into_vec_including_me(None);
+                let x_i = verify_sigmas(
+                    &public.delta_commitments,
+                    &sigmas[..usize::from(self.m)],
+                    &public.pk_vec[index],
+                )
+                .map_err(ProceedError::Round3VerifyRecoveredShare)?;
+                debug_assert_eq!(self.i, self.m + 1);
+
+                let (t, n) = (public.parameters.threshold, public.parameters.share_count);
+                Ok(LocalKey {
+                    paillier_dk: keys.dk,
+                    pk_vec: public.pk_vec,
+
+                    keys_linear: SharedKeys {
+                        y: public.y_sum_s.clone(),
+                        x_i,
+                    },
+                    paillier_key_vec: public.paillier_key_vec,
+                    y_sum_s: public.y_sum_s,
+                    h1_h2_n_tilde_vec: public.h1_h2_n_tilde_vec,
+
+                    // nobody knows our own keygen dealing, so as after resharing, the key holds
+                    // commitments to the joint polynomial
+                    vss_scheme: VerifiableSS {
+                        parameters: public.parameters,
+                        commitments: public.commitments,
+                    },
+
+                    i: public.recover_i,
+                    t,
+                    n,
+                })
+            }
+        }
+    }
+    pub fn is_expensive(&self) -> bool {
+        true
+    }
+    pub fn expects_messages(i: u16, n: u16) -> Store<P2PMsgs<Option<Scalar<Secp256k1>>>> {
+        containers::P2PMsgsStore::new(i, n)
+    }
+}
+
+/// Data every party learns from first round messages
+struct PublicData {
+    recover_i: u16,
+    parameters: ShamirSecretSharing,
+    y_sum_s: Point<Secp256k1>,
+    pk_vec: Vec<Point<Secp256k1>>,
+    paillier_key_vec: Vec<EncryptionKey>,
+    h1_h2_n_tilde_vec: Vec<DLogStatement>,
+    /// Commitments to coefficients of the joint polynomial, interpolated from `pk_vec`
+    commitments: Vec<Point<Secp256k1>>,
+    /// `delta_commitments[j][k]` is commitment to additive share sent by helper `j` to helper `k`
+    delta_commitments: Vec<Vec<Point<Secp256k1>>>,
+}
+
+/// Lagrange coefficient of party `j` for interpolating shared polynomial at point `x` from
+/// evaluations at points `s` (all indexes are 1-based)
+fn lagrange_coefficient_at(x: u16, j: u16, s: &[u16]) -> Scalar<Secp256k1> {
+    let to_scalar = |i: u16| Scalar::<Secp256k1>::from(&BigInt::from(u64::from(i)));
+    let (x, x_j) = (to_scalar(x), to_scalar(j));
+    let (num, denom) = s.iter().filter(|&&k| k != j).map(|&k| to_scalar(k)).fold(
+        (Scalar::from(&BigInt::one()), Scalar::from(&BigInt::one())),
+        |(num, denom), x_k| (num * (&x - &x_k), denom * (&x_j - &x_k)),
+    );
+    num * denom
+        .invert()
+        .expect("helpers indexes are distinct, so denominator is not zero")
+}
+
+/// Verifies first round messages
+///
+/// Helpers must agree on public data of the key, public shares must be consistent with recovered
+/// share, and every helper must commit to additive shares of its lagrange-weighted share.
+/// Recovering party must prove correctness of its Paillier key and `h1, h2, N_tilde`. Bad actors
+/// are identified by their (0-based) index in the recovery session.
+///
+/// Public data is compared against the message at `own_index` for helpers (i.e. against their own
+/// local key). Recovering party takes the first helper message which matches expected `(t, n)` and
+/// has public shares lying on a polynomial of degree `t` through the public key.
+fn verify_broadcast(
+    bc1_vec: &[RecoveryBroadcastMessage1],
+    helpers: &[u16],
+    recover_i: u16,
+    (t, n): (u16, u16),
+    own_index: Option<usize>,
+) -> std::result::Result<PublicData, ErrorType> {
+    let m = helpers.len();
+    assert_eq!(bc1_vec.len(), m + 1);
+
+    let public_data = |j: usize| match &bc1_vec[j] {
+        RecoveryBroadcastMessage1::Helper {
+            parameters,
+            y_sum_s,
+            pk_vec,
+            paillier_key_vec,
+            h1_h2_n_tilde_vec,
+            ..
+        } if parameters.threshold == t && parameters.share_count == n => Some((
+            parameters,
+            y_sum_s,
+            pk_vec,
+            paillier_key_vec,
+            h1_h2_n_tilde_vec,
+        )),
+        _ => None,
+    };
+    let reference = match own_index {
+        Some(j) => Some(j),
+        None => (0..m).find(|&j| {
+            public_data(j).is_some_and(|(parameters, y_sum_s, pk_vec, ..)| {
+                joint_commitments(parameters, pk_vec).is_some_and(|c| &c[0] == y_sum_s)
+            })
+        }),
+    };
+    let (parameters, y_sum_s, pk_vec, paillier_key_vec, h1_h2_n_tilde_vec) =
+        match reference.and_then(public_data) {
+            Some(data) => data,
+            None => {
+                return Err(ErrorType {
+                    kind: ErrorKind::InvalidKey,
+                    bad_actors: (0..m).collect(),
+                })
+            }
+        };
+    let n = usize::from(parameters.share_count);
+    if m <= usize::from(parameters.threshold)
+        || recover_i == 0
+        || usize::from(recover_i) > n
+        || pk_vec.len() != n
+        || paillier_key_vec.len() != n
+        || h1_h2_n_tilde_vec.len() != n
+    {
+        return Err(ErrorType {
+            kind: ErrorKind::InvalidParameters,
+            bad_actors: vec![],
+        });
+    }
+
+    let mut bad_actors_vec = Vec::new();
+    let mut delta_commitments = Vec::with_capacity(m);
+    let mut new_ek = None;
+    for (j, bc1) in bc1_vec.iter().enumerate() {
+        let valid = match bc1 {
+            RecoveryBroadcastMessage1::Helper {
+                i,
+                parameters: params,
+                y_sum_s: y,
+                pk_vec: pks,
+                paillier_key_vec: eks,
+                h1_h2_n_tilde_vec: dlog_statements,
+                delta_commitments: commitments,
+            } if j < m => {
+                // recovering party's Paillier key is replaced, helpers may disagree on it
+                let others = |k: &usize| *k != usize::from(recover_i - 1);
+                let valid = *i == helpers[j]
+                    && params.threshold == parameters.threshold
+                    && params.share_count == parameters.share_count
+                    && y == y_sum_s
+                    && pks == pk_vec
+                    && eks.len() == n
+                    && dlog_statements.len() == n
+                    && (0..n)
+                        .filter(others)
+                        .all(|k| eks[k].n == paillier_key_vec[k].n)
+                    && (0..n)
+                        .filter(others)
+                        .all(|k| dlog_statements[k].N == h1_h2_n_tilde_vec[k].N)
+                    && commitments.len() == m
+                    && {
+                        let lambda = lagrange_coefficient_at(recover_i, *i, helpers);
+                        let w = commitments.iter().fold(Point::zero(), |acc, d| acc + d);
+                        w == &pk_vec[usize::from(i - 1)] * &lambda
+                    };
+                delta_commitments.push(commitments.clone());
+                valid
+            }
+            RecoveryBroadcastMessage1::Recovering {
+                e,
+                dlog_statement,
+                correct_key_proof,
+                composite_dlog_proof_base_h1,
+                composite_dlog_proof_base_h2,
+                modulus_proof,
+                n_tilde_modulus_proof,
+            } if j == m => {
+                new_ek = Some((e.clone(), dlog_statement.clone()));
+                verify_correct_key_correct_h1h2(
+                    e,
+                    dlog_statement,
+                    correct_key_proof,
+                    composite_dlog_proof_base_h1,
+                    composite_dlog_proof_base_h2,
+                ) && verify_moduli_are_paillier_blum(
+                    e,
+                    dlog_statement,
+                    modulus_proof,
+                    n_tilde_modulus_proof,
+                )
+            }
+            _ => false,
+        };
+        if !valid {
+            bad_actors_vec.push(j);
+        }
+    }
+
+    if !bad_actors_vec.is_empty() {
+        return Err(ErrorType {
+            kind: ErrorKind::InvalidKey,
+            bad_actors: bad_actors_vec,
+        });
+    }
+
+    // public shares must lie on a polynomial of degree t, which free term is the public key
+    let commitments = match joint_commitments(parameters, pk_vec) {
+        Some(commitments) if &commitments[0] == y_sum_s => commitments,
+        _ => {
+            return Err(ErrorType {
+                kind: ErrorKind::PublicKeyMismatch,
+                bad_actors: (0..m).collect(),
+            })
+        }
+    };
+
+    let (e, dlog_statement) = new_ek.expect("recovering party message was checked above");
+    let mut paillier_key_vec = paillier_key_vec.clone();
+    let mut h1_h2_n_tilde_vec = h1_h2_n_tilde_vec.clone();
+    paillier_key_vec[usize::from(recover_i - 1)] = e;
+    h1_h2_n_tilde_vec[usize::from(recover_i - 1)] = dlog_statement;
+
+    Ok(PublicData {
+        recover_i,
+        parameters: parameters.clone(),
+        y_sum_s: y_sum_s.clone(),
+        pk_vec: pk_vec.clone(),
+        paillier_key_vec,
+        h1_h2_n_tilde_vec,
+        commitments,
+        delta_commitments,
+    })
+}
+
+/// Interpolates commitments to coefficients of a polynomial of degree `t` from its evaluations
+/// `pk_vec` at points `1..=n` (in the exponent)
+///
+/// Returns `None` if `pk_vec` doesn't lie on a polynomial of degree `t`.
+fn joint_commitments(
+    parameters: &ShamirSecretSharing,
+    pk_vec: &[Point<Secp256k1>],
+) -> Option<Vec<Point<Secp256k1>>> {
+    let (t, n) = (
+        usize::from(parameters.threshold),
+        usize::from(parameters.share_count),
+    );
+    if pk_vec.len() != n || t >= n {
+        return None;
+    }
+    let points = (1..=parameters.threshold + 1).collect::<Vec<u16>>();
+    let mut commitments = vec![Point::zero(); t + 1];
+    for &j in &points {
+        let coefficients = lagrange_basis_coefficients(j, &points);
+        for (c, coef) in commitments.iter_mut().zip(coefficients) {
+            *c = &*c + &pk_vec[usize::from(j - 1)] * coef;
+        }
+    }
+
+    let vss = VerifiableSS {
+        parameters: parameters.clone(),
+        commitments,
+    };
+    let consistent = (1..=parameters.share_count)
+        .all(|k| vss.get_point_commitment(k) == pk_vec[usize::from(k - 1)]);
+    if consistent {
+        Some(vss.commitments)
+    } else {
+        None
+    }
+}
+
+/// Coefficients (starting from free term) of lagrange basis polynomial of party `j` for points `s`
+fn lagrange_basis_coefficients(j: u16, s: &[u16]) -> Vec<Scalar<Secp256k1>> {
+    let to_scalar = |i: u16| Scalar::<Secp256k1>::from(&BigInt::from(u64::from(i)));
+    let x_j = to_scalar(j);
+    let mut coefficients = vec![Scalar::<Secp256k1>::from(&BigInt::one())];
+    let mut denom = Scalar::<Secp256k1>::from(&BigInt::one());
+    for x_k in s.iter().filter(|&&k| k != j).map(|&k| to_scalar(k)) {
+        // multiply polynomial by (x - x_k)
+        let mut next = vec![Scalar::zero(); coefficients.len() + 1];
+        for (c, coef) in coefficients.iter().enumerate() {
+            next[c + 1] = &next[c + 1] + coef;
+            next[c] = &next[c] - &(coef * &x_k);
+        }
+        coefficients = next;
+        denom = denom * (&x_j - &x_k);
+    }
+    let denom_inv = denom
+        .invert()
+        .expect("points are distinct, so denominator is not zero");
+    coefficients.into_iter().map(|c| c * &denom_inv).collect()
+}
+
+/// Validates additive shares received by helper `i` and sums them up
+fn verify_deltas(
+    delta_commitments: &[Vec<Point<Secp256k1>>],
+    msgs: &[RecoveryP2PMessage2],
+    i: u16,
+) -> std::result::Result<Scalar<Secp256k1>, ErrorType> {
+    let k = usize::from(i - 1);
+    let mut bad_actors_vec = Vec::new();
+    let mut sigma = Scalar::zero();
+    for (j, commitments) in delta_commitments.iter().enumerate() {
+        match &msgs[j].delta {
+            Some(delta) if Point::generator() * delta == commitments[k] => sigma = sigma + delta,
+            _ => bad_actors_vec.push(j),
+        }
+    }
+
+    if bad_actors_vec.is_empty() {
+        Ok(sigma)
+    } else {
+        Err(ErrorType {
+            kind: ErrorKind::BadVssShare,
+            bad_actors: bad_actors_vec,
+        })
+    }
+}
+
+/// Verifies proofs that recovering party's Paillier modulus and `N_tilde` have no small factors
+///
+/// Proofs received by a helper are generated with respect to its own `h1, h2, N_tilde`. Recovering
+/// party is the only bad actor, identified by its (0-based) index in the recovery session.
+fn verify_no_small_factor_proofs(
+    public: &PublicData,
+    msg: &RecoveryP2PMessage2,
+    own_setup: &DLogStatement,
+) -> std::result::Result<(), ErrorType> {
+    let index = usize::from(public.recover_i - 1);
+    if verify_no_small_factors(
+        &public.paillier_key_vec[index],
+        &public.h1_h2_n_tilde_vec[index],
+        msg.no_small_factor_proofs.as_ref(),
+        own_setup,
+    ) {
+        Ok(())
+    } else {
+        Err(ErrorType {
+            kind: ErrorKind::InvalidKey,
+            bad_actors: vec![public.delta_commitments.len()],
+        })
+    }
+}
+
+/// Validates sums received from helpers and combines them into recovered secret share
+fn verify_sigmas(
+    delta_commitments: &[Vec<Point<Secp256k1>>],
+    sigmas: &[Option<Scalar<Secp256k1>>],
+    pk: &Point<Secp256k1>,
+) -> std::result::Result<Scalar<Secp256k1>, ErrorType> {
+    let bad_actors_vec = sigmas
+        .iter()
+        .enumerate()
+        .filter(|(k, sigma)| {
+            let expected = delta_commitments
+                .iter()
+                .fold(Point::zero(), |acc, commitments| acc + &commitments[*k]);
+            match sigma {
+                Some(sigma) => Point::generator() * sigma != expected,
+                None => true,
+            }
+        })
+        .map(|(k, _)| k)
+        .collect::<Vec<_>>();
+
+    if !bad_actors_vec.is_empty() {
+        return Err(ErrorType {
+            kind: ErrorKind::BadVssShare,
+            bad_actors: bad_actors_vec,
+        });
+    }
+
+    let x_i = sigmas
+        .iter()
+        .flatten()
+        .fold(Scalar::zero(), |acc, sigma| acc + sigma);
+    if &(Point::generator() * &x_i) != pk {
+        return Err(ErrorType {
+            kind: ErrorKind::PublicKeyMismatch,
+            bad_actors: vec![],
+        });
+    }
+    Ok(x_i)
+}
+
+// Errors
+
+type Result<T> = std::result::Result<T, ProceedError>;
+
+/// Proceeding protocol error
+///
+/// Subset of [share recovery errors](enum@super::Error) that can occur at protocol proceeding
+/// (i.e. after every message was received and pre-validated).
+#[derive(Debug, Error)]
+pub enum ProceedError {
+    #[error("round 1: verify broadcast: {0:?}")]
+    Round1VerifyBroadcast(ErrorType),
+    #[error("round 2: verify additive shares: {0:?}")]
+    Round2VerifyShares(ErrorType),
+    #[error("round 2: verify no small factor proofs: {0:?}")]
+    Round2VerifyNoSmallFactorProofs(ErrorType),
+    #[error("round 3: verify recovered share: {0:?}")]
+    Round3VerifyRecoveredShare(ErrorType),
+}
@@ -0,0 +1,578 @@
+//! High-level proactive key refresh protocol implementation
+//!
+//! Parties holding [LocalKey]s obtained from [keygen](super::keygen) collectively re-randomize
+//! their secret shares by adding a fresh sharing of zero. Every party also generates new Paillier
+//! key and `h1, h2, N_tilde`. Public key of refreshed shares stays the same, while shares obtained
+//! before refresh can't be combined with refreshed ones.
+
+use std::fmt;
+use std::mem::replace;
+use std::time::Duration;
+
+use curv::cryptographic_primitives::proofs::sigma_dlog::DLogProof;
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Scalar};
+use round_based::containers::{
+    push::{Push, PushExt},
+    *,
+};
+use round_based::{IsCritical, Msg, StateMachine};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use thiserror::Error;
+
+use crate::protocols::multi_party_ecdsa::gg_2020;
+use gg_2020::state_machine::keygen::LocalKey;
+
+mod rounds;
+
+use private::InternalError;
+pub use rounds::ProceedError;
+use rounds::{Round0, Round1, Round2, Round3};
+
+/// Key refresh protocol state machine
+///
+/// All `n` parties that took part in keygen must participate. Successfully completed refresh
+/// protocol produces a new [LocalKey] with the same [public key](LocalKey::public_key), but with
+/// new secret share, Paillier key and `h1, h2, N_tilde`.
+pub struct KeyRefresh {
+    round: R,
+
+    msgs1: Option<Store<BroadcastMsgs<gg_2020::party_i::RefreshBroadcastMessage1>>>,
+    msgs2: Option<Store<P2PMsgs<Scalar<Secp256k1>>>>,
+    msgs3: Option<Store<BroadcastMsgs<DLogProof<Secp256k1, Sha256>>>>,
+
+    msgs_queue: Vec<Msg<ProtocolMessage>>,
+
+    party_i: u16,
+    party_n: u16,
+
+    round_timeout: Option<Duration>,
+}
+
+impl KeyRefresh {
+    /// Constructs a party of key refresh protocol
+    ///
+    /// Takes party local secret share `local_key` obtained from keygen (or previous refresh).
+    /// Party index and number of parties are taken from `local_key`.
+    ///
+    /// Returns error if:
+    /// * `local_key.n` is less than 2, returns [Error::TooFewParties]
+    /// * `local_key.t` is not in range `[1; n-1]`, returns [Error::InvalidThreshold]
+    /// * `local_key.i` is not in range `[1; n]`, returns [Error::InvalidPartyIndex]
+    pub fn new(local_key: LocalKey<Secp256k1>) -> Result<Self> {
+        let (i, t, n) = (local_key.i, local_key.t, local_key.n);
+        if n < 2 {
+            return Err(Error::TooFewParties);
+        }
+        if t == 0 || t >= n {
+            return Err(Error::InvalidThreshold);
+        }
+        if i == 0 || i > n {
+            return Err(Error::InvalidPartyIndex);
+        }
+        let mut state = Self {
+            round: R::Round0(Round0 { local_key }),
+
+            msgs1: Some(Round1::expects_messages(i, n)),
+            msgs2: Some(Round2::expects_messages(i, n)),
+            msgs3: Some(Round3::expects_messages(i, n)),
+
+            msgs_queue: vec![],
+
+            party_i: i,
+            party_n: n,
+
+            round_timeout: None,
+        };
+
+        state.proceed_round(false)?;
+        Ok(state)
+    }
+
+    /// Sets how long every round may wait for messages from other parties
+    ///
+    /// Once timeout is reached, protocol execution fails with [Error::RoundTimeout] listing
+    /// parties which didn't deliver their messages. By default, there's no timeout and the
+    /// protocol waits forever.
+    pub fn set_round_timeout(&mut self, timeout: Option<Duration>) {
+        self.round_timeout = timeout;
+    }
+
+    fn gmap_queue<'a, T, F>(&'a mut self, mut f: F) -> impl Push<Msg<T>> + 'a
+    where
+        F: FnMut(T) -> M + 'a,
+    {
+        (&mut self.msgs_queue).gmap(move |m: Msg<T>| m.map_body(|m| ProtocolMessage(f(m))))
+    }
+
+    /// Proceeds round state if it received enough messages and if it's cheap to compute or
+    /// `may_block == true`
+    fn proceed_round(&mut self, may_block: bool) -> Result<()> {
+        let store1_wants_more = self.msgs1.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+        let store2_wants_more = self.msgs2.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+        let store3_wants_more = self.msgs3.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+
+        let next_state: R;
+        let try_again: bool = match replace(&mut self.round, R::Gone) {
+            R::Round0(round) if !round.is_expensive() || may_block => {
+                next_state = round
+                    .proceed(self.gmap_queue(M::Round1))
+                    .map(R::Round1)
+                    .map_err(Error::ProceedRound)?;
+                true
+            }
+            s @ R::Round0(_) => {
+                next_state = s;
+                false
+            }
+            R::Round1(round) if !store1_wants_more && (!round.is_expensive() || may_block) => {
+                let store = self.msgs1.take().ok_or(InternalError::StoreGone)?;
+                let msgs = store
+                    .finish()
+                    .map_err(InternalError::RetrieveRoundMessages)?;
+                next_state = round
+                    .proceed(msgs, self.gmap_queue(M::Round2))
+                    .map(R::Round2)
+                    .map_err(Error::ProceedRound)?;
+                true
+            }
+            s @ R::Round1(_) => {
+                next_state = s;
+                false
+            }
+            R::Round2(round) if !store2_wants_more && (!round.is_expensive() || may_block) => {
+                let store = self.msgs2.take().ok_or(InternalError::StoreGone)?;
+                let msgs = store
+                    .finish()
+                    .map_err(InternalError::RetrieveRoundMessages)?;
+                next_state = round
+                    .proceed(msgs, self.gmap_queue(M::Round3))
+                    .map(R::Round3)
+                    .map_err(Error::ProceedRound)?;
+                true
+            }
+            s @ R::Round2(_) => {
+                next_state = s;
+                false
+            }
+            R::Round3(round) if !store3_wants_more && (!round.is_expensive() || may_block) => {
+                let store = self.msgs3.take().ok_or(InternalError::StoreGone)?;
+                let msgs = store
+                    .finish()
+                    .map_err(InternalError::RetrieveRoundMessages)?;
+                next_state = round
+                    .proceed(msgs)
+                    .map(R::Final)
+                    .map_err(Error::ProceedRound)?;
+                true
+            }
+            s @ R::Round3(_) => {
+                next_state = s;
+                false
+            }
+            s @ R::Final(_) | s @ R::Gone => {
+                next_state = s;
+                false
+            }
+        };
+
+        self.round = next_state;
+        if try_again {
+            self.proceed_round(may_block)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl StateMachine for KeyRefresh {
+    type MessageBody = ProtocolMessage;
+    type Err = Error;
+    type Output = LocalKey<Secp256k1>;
+
+    fn handle_incoming(&mut self, msg: Msg<Self::MessageBody>) -> Result<()> {
+        let current_round = self.current_round();
+
+        match msg.body {
+            ProtocolMessage(M::Round1(m)) => {
+                let store = self
+                    .msgs1
+                    .as_mut()
+                    .ok_or(Error::ReceivedOutOfOrderMessage {
+                        current_round,
+                        msg_round: 1,
+                    })?;
+                store
+                    .push_msg(Msg {
+                        sender: msg.sender,
+                        receiver: msg.receiver,
+                        body: m,
+                    })
+                    .map_err(Error::HandleMessage)?;
+                self.proceed_round(false)
+            }
+            ProtocolMessage(M::Round2(m)) => {
+                let store = self
+                    .msgs2
+                    .as_mut()
+                    .ok_or(Error::ReceivedOutOfOrderMessage {
+                        current_round,
+                        msg_round: 2,
+                    })?;
+                store
+                    .push_msg(Msg {
+                        sender: msg.sender,
+                        receiver: msg.receiver,
+                        body: m,
+                    })
+                    .map_err(Error::HandleMessage)?;
+                self.proceed_round(false)
+            }
+            ProtocolMessage(M::Round3(m)) => {
+                let store = self
+                    .msgs3
+                    .as_mut()
+                    .ok_or(Error::ReceivedOutOfOrderMessage {
+                        current_round,
+                        msg_round: 3,
+                    })?;
+                store
+                    .push_msg(Msg {
+                        sender: msg.sender,
+                        receiver: msg.receiver,
+                        body: m,
+                    })
+                    .map_err(Error::HandleMessage)?;
+                self.proceed_round(false)
+            }
+        }
+    }
+
+    fn message_queue(&mut self) -> &mut Vec<Msg<Self::MessageBody>> {
+        &mut self.msgs_queue
+    }
+
+    fn wants_to_proceed(&self) -> bool {
+        let store1_wants_more = self.msgs1.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+        let store2_wants_more = self.msgs2.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+        let store3_wants_more = self.msgs3.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+
+        match &self.round {
+            R::Round0(_) => true,
+            R::Round1(_) => !store1_wants_more,
+            R::Round2(_) => !store2_wants_more,
+            R::Round3(_) => !store3_wants_more,
+            R::Final(_) | R::Gone => false,
+        }
+    }
+
+    fn proceed(&mut self) -> Result<()> {
+        self.proceed_round(true)
+    }
+
+    fn round_timeout(&self) -> Option<Duration> {
+        match &self.round {
+            R::Round0(_) | R::Final(_) | R::Gone => None,
+            _ => self.round_timeout,
+        }
+    }
+
+    fn round_timeout_reached(&mut self) -> Self::Err {
+        let (_, parties) = super::traits::RoundBlame::round_blame(self);
+        Error::RoundTimeout {
+            round: self.current_round(),
+            parties,
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(self.round, R::Final(_))
+    }
+
+    fn pick_output(&mut self) -> Option<Result<Self::Output>> {
+        match self.round {
+            R::Final(_) => (),
+            R::Gone => return Some(Err(Error::DoublePickOutput)),
+            _ => return None,
+        }
+
+        match replace(&mut self.round, R::Gone) {
+            R::Final(result) => Some(Ok(result)),
+            _ => unreachable!("guaranteed by match expression above"),
+        }
+    }
+
+    fn current_round(&self) -> u16 {
+        match &self.round {
+            R::Round0(_) => 0,
+            R::Round1(_) => 1,
+            R::Round2(_) => 2,
+            R::Round3(_) => 3,
+            R::Final(_) | R::Gone => 4,
+        }
+    }
+
+    fn total_rounds(&self) -> Option<u16> {
+        Some(3)
+    }
+
+    fn party_ind(&self) -> u16 {
+        self.party_i
+    }
+
+    fn parties(&self) -> u16 {
+        self.party_n
+    }
+}
+
+impl super::traits::RoundBlame for KeyRefresh {
+    /// Returns number of unwilling parties and a vector of their party indexes.
+    fn round_blame(&self) -> (u16, Vec<u16>) {
+        let store1_blame = self.msgs1.as_ref().map(|s| s.blame()).unwrap_or_default();
+        let store2_blame = self.msgs2.as_ref().map(|s| s.blame()).unwrap_or_default();
+        let store3_blame = self.msgs3.as_ref().map(|s| s.blame()).unwrap_or_default();
+
+        let default = (0, vec![]);
+        match &self.round {
+            R::Round0(_) => default,
+            R::Round1(_) => store1_blame,
+            R::Round2(_) => store2_blame,
+            R::Round3(_) => store3_blame,
+            R::Final(_) | R::Gone => default,
+        }
+    }
+}
+
+impl fmt::Debug for KeyRefresh {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let current_round = match &self.round {
+            R::Round0(_) => "0",
+            R::Round1(_) => "1",
+            R::Round2(_) => "2",
+            R::Round3(_) => "3",
+            R::Final(_) => "[Final]",
+            R::Gone => "[Gone]",
+        };
+        let msgs1 = match self.msgs1.as_ref() {
+            Some(msgs) => format!("[{}/{}]", msgs.messages_received(), msgs.messages_total()),
+            None => "[None]".into(),
+        };
+        let msgs2 = match self.msgs2.as_ref() {
+            Some(msgs) => format!("[{}/{}]", msgs.messages_received(), msgs.messages_total()),
+            None => "[None]".into(),
+        };
+        let msgs3 = match self.msgs3.as_ref() {
+            Some(msgs) => format!("[{}/{}]", msgs.messages_received(), msgs.messages_total()),
+            None => "[None]".into(),
+        };
+        write!(
+            f,
+            "{{KeyRefresh at round={} msgs1={} msgs2={} msgs3={} queue=[len={}]}}",
+            current_round,
+            msgs1,
+            msgs2,
+            msgs3,
+            self.msgs_queue.len()
+        )
+    }
+}
+
+// Rounds
+
+enum R {
+    Round0(Round0),
+    Round1(Round1),
+    Round2(Round2),
+    Round3(Round3),
+    Final(LocalKey<Secp256k1>),
+    Gone,
+}
+
+// Messages
+
+/// Protocol message which parties send on wire
+///
+/// Hides actual messages structure so it could be changed without breaking semver policy.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProtocolMessage(M);
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+enum M {
+    Round1(gg_2020::party_i::RefreshBroadcastMessage1),
+    Round2(Scalar<Secp256k1>),
+    Round3(DLogProof<Secp256k1, Sha256>),
+}
+
+// Error
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Error type of key refresh protocol
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// Round proceeding resulted in error
+    #[error("proceed round: {0}")]
+    ProceedRound(#[source] ProceedError),
+
+    /// Too few parties (`n < 2`)
+    #[error("at least 2 parties are required for key refresh")]
+    TooFewParties,
+    /// Threshold value `t` is not in range `[1; n-1]`
+    #[error("threshold is not in range [1; n-1]")]
+    InvalidThreshold,
+    /// Party index `i` is not in range `[1; n]`
+    #[error("party index is not in range [1; n]")]
+    InvalidPartyIndex,
+
+    /// Received message didn't pass pre-validation
+    #[error("received message didn't pass pre-validation: {0}")]
+    HandleMessage(#[source] StoreErr),
+    /// Received message which we didn't expect to receive now (e.g. message from previous round)
+    #[error(
+        "didn't expect to receive message from round {msg_round} (being at round {current_round})"
+    )]
+    ReceivedOutOfOrderMessage { current_round: u16, msg_round: u16 },
+    /// [KeyRefresh::pick_output] called twice
+    #[error("pick_output called twice")]
+    DoublePickOutput,
+    /// Round timeout is reached, `parties` didn't send their messages for `round`
+    #[error("round {round} timed out, parties {parties:?} didn't send their messages")]
+    RoundTimeout { round: u16, parties: Vec<u16> },
+
+    /// Some internal assertions were failed, which is a bug
+    #[doc(hidden)]
+    #[error("internal error: {0:?}")]
+    InternalError(InternalError),
+}
+
+impl IsCritical for Error {
+    fn is_critical(&self) -> bool {
+        true
+    }
+}
+
+impl From<InternalError> for Error {
+    fn from(err: InternalError) -> Self {
+        Self::InternalError(err)
+    }
+}
+
+mod private {
+    #[derive(Debug)]
+    #[non_exhaustive]
+    pub enum InternalError {
+        /// [Messages store](super::MessageStore) reported that it received all messages it wanted to receive,
+        /// but refused to return message container
+        RetrieveRoundMessages(super::StoreErr),
+        #[doc(hidden)]
+        StoreGone,
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+    use curv::elliptic::curves::Point;
+    use round_based::dev::Simulation;
+
+    use super::*;
+    use gg_2020::state_machine::keygen::test::simulate_keygen;
+    use gg_2020::state_machine::sign::test::{simulate_offline_stage, simulate_signing};
+    use gg_2020::state_machine::test::{run_with_transport, through_json};
+
+    pub fn simulate_refresh(local_keys: Vec<LocalKey<Secp256k1>>) -> Vec<LocalKey<Secp256k1>> {
+        let mut simulation = Simulation::new();
+        simulation.enable_benchmarks(true);
+
+        for local_key in local_keys {
+            simulation.add_party(KeyRefresh::new(local_key).unwrap());
+        }
+
+        let keys = simulation.run().unwrap();
+
+        println!("Benchmark results:");
+        println!("{:#?}", simulation.benchmark_results().unwrap());
+
+        keys
+    }
+
+    fn reconstruct(local_keys: &[LocalKey<Secp256k1>]) -> Scalar<Secp256k1> {
+        let vss = VerifiableSS::<Secp256k1> {
+            parameters: local_keys[0].vss_scheme.parameters.clone(),
+            commitments: vec![],
+        };
+        let indices = local_keys.iter().map(|k| k.i - 1).collect::<Vec<_>>();
+        let shares = local_keys
+            .iter()
+            .map(|k| k.keys_linear.x_i.clone())
+            .collect::<Vec<_>>();
+        vss.reconstruct(&indices, &shares)
+    }
+
+    fn refresh_and_check(t: u16, n: u16) -> Vec<LocalKey<Secp256k1>> {
+        let old_keys = simulate_keygen(t, n);
+        let new_keys = simulate_refresh(old_keys.clone());
+
+        for (old, new) in old_keys.iter().zip(&new_keys) {
+            assert_eq!(old.public_key(), new.public_key());
+            assert_ne!(old.keys_linear.x_i, new.keys_linear.x_i);
+            assert_ne!(old.paillier_dk.p, new.paillier_dk.p);
+            assert_ne!(old.h1_h2_n_tilde_vec[0].N, new.h1_h2_n_tilde_vec[0].N);
+            for (j, pk) in new.pk_vec.iter().enumerate() {
+                assert_eq!(pk, &(Point::generator() * &new_keys[j].keys_linear.x_i));
+            }
+        }
+        assert_eq!(reconstruct(&old_keys), reconstruct(&new_keys));
+
+        new_keys
+    }
+
+    #[test]
+    fn simulate_refresh_t1_n2() {
+        refresh_and_check(1, 2);
+    }
+
+    #[test]
+    fn refresh_survives_serialized_transport() {
+        let old_keys = simulate_keygen(1, 3);
+        let parties = old_keys
+            .iter()
+            .map(|local_key| KeyRefresh::new(local_key.clone()).unwrap())
+            .collect();
+        let new_keys = run_with_transport(parties, through_json);
+        assert_eq!(reconstruct(&old_keys), reconstruct(&new_keys));
+    }
+
+    #[test]
+    fn simulate_refresh_t2_n3_and_sign() {
+        let local_keys = refresh_and_check(2, 3);
+        let offline_stage = simulate_offline_stage(local_keys, &[1, 2, 3]);
+        simulate_signing(offline_stage, b"ZenGo");
+    }
+
+    #[test]
+    fn round_timeout_blames_silent_parties() {
+        let mut parties = simulate_keygen(1, 3)
+            .into_iter()
+            .map(|local_key| KeyRefresh::new(local_key).unwrap())
+            .collect::<Vec<_>>();
+        parties[0].set_round_timeout(Some(Duration::from_secs(10)));
+        assert_eq!(parties[0].round_timeout(), None);
+
+        // party 3 stays silent
+        parties[0].proceed().unwrap();
+        parties[1].proceed().unwrap();
+        let msgs = parties[1].message_queue().drain(..).collect::<Vec<_>>();
+        for msg in msgs {
+            parties[0].handle_incoming(msg).unwrap();
+        }
+
+        assert_eq!(parties[0].round_timeout(), Some(Duration::from_secs(10)));
+        assert!(matches!(
+            parties[0].round_timeout_reached(),
+            Error::RoundTimeout { round: 1, parties } if parties == [3]
+        ));
+    }
+}
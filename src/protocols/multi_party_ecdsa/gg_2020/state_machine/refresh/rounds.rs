@@ -0,0 +1,257 @@
+use curv::cryptographic_primitives::proofs::sigma_dlog::DLogProof;
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
+use sha2::Sha256;
+
+use thiserror::Error;
+
+use paillier::EncryptionKey;
+use round_based::containers::push::Push;
+use round_based::containers::{self, BroadcastMsgs, P2PMsgs, Store};
+use round_based::Msg;
+use zk_paillier::zkproofs::DLogStatement;
+
+use crate::protocols::multi_party_ecdsa::gg_2020::party_i::{
+    Keys, Parameters, RefreshBroadcastMessage1,
+};
+use crate::protocols::multi_party_ecdsa::gg_2020::state_machine::keygen::LocalKey;
+use crate::protocols::multi_party_ecdsa::gg_2020::{self, ErrorType};
+
+pub struct Round0 {
+    pub local_key: LocalKey<Secp256k1>,
+}
+
+impl Round0 {
+    pub fn proceed<O>(self, mut output: O) -> Result<Round1>
+    where
+        O: Push<Msg<RefreshBroadcastMessage1>>,
+    {
+        let params = Parameters {
+            threshold: self.local_key.t,
+            share_count: self.local_key.n,
+        };
+        let party_keys = Keys::create(usize::from(self.local_key.i));
+        let (bc1, zero_shares) = party_keys
+            .refresh_phase1_broadcast_zero_shares(&params, &self.local_key.h1_h2_n_tilde_vec);
+
+        output.push(Msg {
+            sender: self.local_key.i,
+            receiver: None,
+            body: bc1.clone(),
+        });
+        Ok(Round1 {
+            keys: party_keys,
+            bc1,
+            zero_shares,
+            local_key: self.local_key,
+        })
+    }
+    pub fn is_expensive(&self) -> bool {
+        true
+    }
+}
+
+pub struct Round1 {
+    keys: Keys,
+    bc1: RefreshBroadcastMessage1,
+    zero_shares: Vec<Scalar<Secp256k1>>,
+    local_key: LocalKey<Secp256k1>,
+}
+
+impl Round1 {
+    pub fn proceed<O>(
+        self,
+        input: BroadcastMsgs<RefreshBroadcastMessage1>,
+        mut output: O,
+    ) -> Result<Round2>
+    where
+        O: Push<Msg<Scalar<Secp256k1>>>,
+    {
+        let params = Parameters {
+            threshold: self.local_key.t,
+            share_count: self.local_key.n,
+        };
+        let bc1_vec = input.into_vec_including_me(self.bc1);
+
+        let party_i = self.local_key.i;
+        Keys::refresh_phase1_verify_correct_key_verify_dlog_verify_zero_vss(
+            &params,
+            &bc1_vec,
+            usize::from(party_i),
+            &self.local_key.h1_h2_n_tilde_vec[usize::from(party_i - 1)],
+        )
+        .map_err(ProceedError::Round1VerifyBroadcast)?;
+
+        for (i, share) in self.zero_shares.iter().enumerate() {
+            if i + 1 == usize::from(party_i) {
+                continue;
+            }
+
+            output.push(Msg {
+                sender: party_i,
+                receiver: Some(i as u16 + 1),
+                body: share.clone(),
+            })
+        }
+
+        Ok(Round2 {
+            keys: self.keys,
+            own_share: self.zero_shares[usize::from(party_i - 1)].clone(),
+            bc1_vec,
+            local_key: self.local_key,
+        })
+    }
+    pub fn is_expensive(&self) -> bool {
+        true
+    }
+    pub fn expects_messages(i: u16, n: u16) -> Store<BroadcastMsgs<RefreshBroadcastMessage1>> {
+        containers::BroadcastMsgsStore::new(i, n)
+    }
+}
+
+pub struct Round2 {
+    keys: Keys,
+    own_share: Scalar<Secp256k1>,
+    bc1_vec: Vec<RefreshBroadcastMessage1>,
+    local_key: LocalKey<Secp256k1>,
+}
+
+impl Round2 {
+    pub fn proceed<O>(self, input: P2PMsgs<Scalar<Secp256k1>>, mut output: O) -> Result<Round3>
+    where
+        O: Push<Msg<DLogProof<Secp256k1, Sha256>>>,
+    {
+        let params = Parameters {
+            threshold: self.local_key.t,
+            share_count: self.local_key.n,
+        };
+        let zero_shares = input.into_vec_including_me(self.own_share);
+        let vss_vec = self
+            .bc1_vec
+            .iter()
+            .map(|bc1| bc1.vss_scheme.clone())
+            .collect::<Vec<_>>();
+
+        let (shared_keys, dlog_proof) =
+            Keys::refresh_phase2_verify_zero_shares_update_keypair_pok_dlog(
+                &self.local_key.keys_linear,
+                &params,
+                &zero_shares,
+                &vss_vec,
+                self.local_key.i.into(),
+            )
+            .map_err(ProceedError::Round2VerifyZeroShares)?;
+
+        output.push(Msg {
+            sender: self.local_key.i,
+            receiver: None,
+            body: dlog_proof.clone(),
+        });
+
+        Ok(Round3 {
+            keys: self.keys,
+            bc1_vec: self.bc1_vec,
+            vss_vec,
+            shared_keys,
+            own_dlog_proof: dlog_proof,
+            local_key: self.local_key,
+        })
+    }
+    pub fn is_expensive(&self) -> bool {
+        true
+    }
+    pub fn expects_messages(i: u16, n: u16) -> Store<P2PMsgs<Scalar<Secp256k1>>> {
+        containers::P2PMsgsStore::new(i, n)
+    }
+}
+
+pub struct Round3 {
+    keys: Keys,
+    bc1_vec: Vec<RefreshBroadcastMessage1>,
+    vss_vec: Vec<VerifiableSS<Secp256k1>>,
+    shared_keys: gg_2020::party_i::SharedKeys,
+    own_dlog_proof: DLogProof<Secp256k1, Sha256>,
+    local_key: LocalKey<Secp256k1>,
+}
+
+impl Round3 {
+    pub fn proceed(
+        self,
+        input: BroadcastMsgs<DLogProof<Secp256k1, Sha256>>,
+    ) -> Result<LocalKey<Secp256k1>> {
+        let params = Parameters {
+            threshold: self.local_key.t,
+            share_count: self.local_key.n,
+        };
+        let dlog_proofs = input.into_vec_including_me(self.own_dlog_proof);
+
+        let pk_vec = Keys::refresh_phase3_update_pk_vec(&self.local_key.pk_vec, &self.vss_vec);
+        Keys::refresh_phase3_verify_dlog_proofs(&params, &dlog_proofs, &pk_vec)
+            .map_err(ProceedError::Round3VerifyDLogProof)?;
+
+        let paillier_key_vec = self
+            .bc1_vec
+            .iter()
+            .map(|bc1| bc1.e.clone())
+            .collect::<Vec<EncryptionKey>>();
+        let h1_h2_n_tilde_vec = self
+            .bc1_vec
+            .iter()
+            .map(|bc1| bc1.dlog_statement.clone())
+            .collect::<Vec<DLogStatement>>();
+
+        // our own dealing is shifted by the zero sharing we've distributed
+        let own_zero_vss = &self.vss_vec[usize::from(self.local_key.i - 1)];
+        let vss_scheme = VerifiableSS {
+            parameters: self.local_key.vss_scheme.parameters.clone(),
+            commitments: self
+                .local_key
+                .vss_scheme
+                .commitments
+                .iter()
+                .zip(&own_zero_vss.commitments)
+                .map(|(c, z)| c + z)
+                .collect::<Vec<Point<Secp256k1>>>(),
+        };
+
+        Ok(LocalKey {
+            paillier_dk: self.keys.dk,
+            pk_vec,
+
+            keys_linear: self.shared_keys,
+            paillier_key_vec,
+            y_sum_s: self.local_key.y_sum_s,
+            h1_h2_n_tilde_vec,
+
+            vss_scheme,
+
+            i: self.local_key.i,
+            t: self.local_key.t,
+            n: self.local_key.n,
+        })
+    }
+    pub fn is_expensive(&self) -> bool {
+        true
+    }
+    pub fn expects_messages(i: u16, n: u16) -> Store<BroadcastMsgs<DLogProof<Secp256k1, Sha256>>> {
+        containers::BroadcastMsgsStore::new(i, n)
+    }
+}
+
+// Errors
+
+type Result<T> = std::result::Result<T, ProceedError>;
+
+/// Proceeding protocol error
+///
+/// Subset of [refresh errors](enum@super::Error) that can occur at protocol proceeding (i.e. after
+/// every message was received and pre-validated).
+#[derive(Debug, Error)]
+pub enum ProceedError {
+    #[error("round 1: verify broadcast: {0:?}")]
+    Round1VerifyBroadcast(ErrorType),
+    #[error("round 2: verify zero shares: {0:?}")]
+    Round2VerifyZeroShares(ErrorType),
+    #[error("round 3: verify dlog proof: {0:?}")]
+    Round3VerifyDLogProof(ErrorType),
+}
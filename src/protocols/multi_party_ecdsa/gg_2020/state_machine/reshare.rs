@@ -0,0 +1,642 @@
+//! High-level resharing protocol implementation
+//!
+//! Moves a key shared among `n` parties with threshold `t` to a new committee of `n'` parties with
+//! threshold `t'`. Any `t+1` holders of old shares act as dealers: every dealer reshares its
+//! lagrange-weighted share among the new committee, so that the new shares are a `(t', n')`
+//! sharing of the same secret. New committee members generate fresh Paillier keys and
+//! `h1, h2, N_tilde`. Public key stays the same.
+//!
+//! New committee members prove in the first round that their Paillier modulus and `N_tilde` are
+//! Paillier-Blum moduli. As in [keygen](super::keygen), they also prove that the moduli have no
+//! small factors: such proof is only sound with respect to verifier's `h1, h2, N_tilde`, so it's
+//! sent to every other new committee member in the second round, along with dealers' shares.
+//!
+//! Parties of resharing session are indexed as follows: old committee members take indexes
+//! `1..=m` (in order of `old_parties`), new committee members take indexes `m+1..=m+n'`. Party
+//! that is a member of both committees runs two instances of protocol.
+
+use std::fmt;
+use std::mem::replace;
+use std::time::Duration;
+
+use curv::cryptographic_primitives::proofs::sigma_dlog::DLogProof;
+use curv::elliptic::curves::secp256_k1::Secp256k1;
+use round_based::containers::{
+    push::{Push, PushExt},
+    *,
+};
+use round_based::{IsCritical, Msg, StateMachine};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use thiserror::Error;
+
+use crate::protocols::multi_party_ecdsa::gg_2020;
+use gg_2020::state_machine::keygen::LocalKey;
+
+mod rounds;
+
+use private::InternalError;
+pub use rounds::{ProceedError, ReshareBroadcastMessage1, ReshareP2PMessage2};
+use rounds::{Round0, Round1, Round2, Round3};
+
+/// Resharing protocol state machine
+///
+/// Successfully completed resharing produces a new [LocalKey] for every member of new committee,
+/// with the same [public key](LocalKey::public_key) as the old one. Members of old committee output
+/// `None`.
+pub struct Reshare {
+    round: R,
+
+    msgs1: Option<Store<BroadcastMsgs<ReshareBroadcastMessage1>>>,
+    msgs2: Option<Store<P2PMsgs<ReshareP2PMessage2>>>,
+    msgs3: Option<Store<BroadcastMsgs<Option<DLogProof<Secp256k1, Sha256>>>>>,
+
+    msgs_queue: Vec<Msg<ProtocolMessage>>,
+
+    party_i: u16,
+    party_n: u16,
+
+    round_timeout: Option<Duration>,
+}
+
+impl Reshare {
+    /// Constructs a party of old committee
+    ///
+    /// Takes party index `i` in resharing session, list of old committee members `old_parties`
+    /// taking part in resharing (their indexes in keygen), new threshold `new_t`, size of new
+    /// committee `new_n`, and party local secret share `local_key`.
+    ///
+    /// Returns error if:
+    /// * `old_parties` has less than `t+1` distinct valid indexes, returns [Error::InvalidOldParties]
+    /// * `new_n` is less than 2, returns [Error::TooFewParties]
+    /// * `new_t` is not in range `[1; new_n-1]`, returns [Error::InvalidThreshold]
+    /// * `i` is not in range `[1; m]` or `old_parties[i-1] != local_key.i`, returns
+    ///   [Error::InvalidPartyIndex]
+    pub fn old_party(
+        i: u16,
+        old_parties: Vec<u16>,
+        new_t: u16,
+        new_n: u16,
+        local_key: LocalKey<Secp256k1>,
+    ) -> Result<Self> {
+        Self::validate_params(&old_parties, local_key.t, local_key.n, new_t, new_n)?;
+        if i == 0
+            || usize::from(i) > old_parties.len()
+            || old_parties[usize::from(i - 1)] != local_key.i
+        {
+            return Err(Error::InvalidPartyIndex);
+        }
+        Self::new(i, old_parties, new_t, new_n, Some(local_key))
+    }
+
+    /// Constructs a party of new committee
+    ///
+    /// Takes party index `i` in resharing session, list of old committee members `old_parties`
+    /// taking part in resharing (their indexes in keygen), new threshold `new_t`, and size of new
+    /// committee `new_n`. Party receives [LocalKey] with index `i - m`, where `m = old_parties.len()`.
+    ///
+    /// Returns error if:
+    /// * `old_parties` is empty or has repeating indexes, returns [Error::InvalidOldParties]
+    /// * `new_n` is less than 2, returns [Error::TooFewParties]
+    /// * `new_t` is not in range `[1; new_n-1]`, returns [Error::InvalidThreshold]
+    /// * `i` is not in range `[m+1; m+new_n]`, returns [Error::InvalidPartyIndex]
+    pub fn new_party(i: u16, old_parties: Vec<u16>, new_t: u16, new_n: u16) -> Result<Self> {
+        let m = old_parties.len();
+        Self::validate_params(&old_parties, 0, u16::MAX, new_t, new_n)?;
+        if usize::from(i) <= m || usize::from(i) > m + usize::from(new_n) {
+            return Err(Error::InvalidPartyIndex);
+        }
+        Self::new(i, old_parties, new_t, new_n, None)
+    }
+
+    fn validate_params(
+        old_parties: &[u16],
+        old_t: u16,
+        old_n: u16,
+        new_t: u16,
+        new_n: u16,
+    ) -> Result<()> {
+        if new_n < 2 {
+            return Err(Error::TooFewParties);
+        }
+        if new_t == 0 || new_t >= new_n {
+            return Err(Error::InvalidThreshold);
+        }
+        let mut sorted = old_parties.to_vec();
+        sorted.sort_unstable();
+        sorted.dedup();
+        if sorted.len() != old_parties.len()
+            || old_parties.len() <= usize::from(old_t)
+            || old_parties.iter().any(|&j| j == 0 || j > old_n)
+            || old_parties.len() + usize::from(new_n) > usize::from(u16::MAX)
+        {
+            return Err(Error::InvalidOldParties);
+        }
+        Ok(())
+    }
+
+    fn new(
+        i: u16,
+        old_parties: Vec<u16>,
+        new_t: u16,
+        new_n: u16,
+        local_key: Option<LocalKey<Secp256k1>>,
+    ) -> Result<Self> {
+        let n = old_parties.len() as u16 + new_n;
+        let mut state = Self {
+            round: R::Round0(Round0 {
+                i,
+                old_parties,
+                new_t,
+                new_n,
+                local_key,
+            }),
+
+            msgs1: Some(Round1::expects_messages(i, n)),
+            msgs2: Some(Round2::expects_messages(i, n)),
+            msgs3: Some(Round3::expects_messages(i, n)),
+
+            msgs_queue: vec![],
+
+            party_i: i,
+            party_n: n,
+
+            round_timeout: None,
+        };
+
+        state.proceed_round(false)?;
+        Ok(state)
+    }
+
+    /// Sets how long every round may wait for messages from other parties
+    ///
+    /// Once timeout is reached, protocol execution fails with [Error::RoundTimeout] listing
+    /// parties which didn't deliver their messages. By default, there's no timeout and the
+    /// protocol waits forever.
+    pub fn set_round_timeout(&mut self, timeout: Option<Duration>) {
+        self.round_timeout = timeout;
+    }
+
+    fn gmap_queue<'a, T, F>(&'a mut self, mut f: F) -> impl Push<Msg<T>> + 'a
+    where
+        F: FnMut(T) -> M + 'a,
+    {
+        (&mut self.msgs_queue).gmap(move |m: Msg<T>| m.map_body(|m| ProtocolMessage(f(m))))
+    }
+
+    /// Proceeds round state if it received enough messages and if it's cheap to compute or
+    /// `may_block == true`
+    fn proceed_round(&mut self, may_block: bool) -> Result<()> {
+        let store1_wants_more = self.msgs1.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+        let store2_wants_more = self.msgs2.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+        let store3_wants_more = self.msgs3.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+
+        let next_state: R;
+        let try_again: bool = match replace(&mut self.round, R::Gone) {
+            R::Round0(round) if !round.is_expensive() || may_block => {
+                next_state = round
+                    .proceed(self.gmap_queue(M::Round1))
+                    .map(R::Round1)
+                    .map_err(Error::ProceedRound)?;
+                true
+            }
+            s @ R::Round0(_) => {
+                next_state = s;
+                false
+            }
+            R::Round1(round) if !store1_wants_more && (!round.is_expensive() || may_block) => {
+                let store = self.msgs1.take().ok_or(InternalError::StoreGone)?;
+                let msgs = store
+                    .finish()
+                    .map_err(InternalError::RetrieveRoundMessages)?;
+                next_state = round
+                    .proceed(msgs, self.gmap_queue(M::Round2))
+                    .map(R::Round2)
+                    .map_err(Error::ProceedRound)?;
+                true
+            }
+            s @ R::Round1(_) => {
+                next_state = s;
+                false
+            }
+            R::Round2(round) if !store2_wants_more && (!round.is_expensive() || may_block) => {
+                let store = self.msgs2.take().ok_or(InternalError::StoreGone)?;
+                let msgs = store
+                    .finish()
+                    .map_err(InternalError::RetrieveRoundMessages)?;
+                next_state = round
+                    .proceed(msgs, self.gmap_queue(M::Round3))
+                    .map(R::Round3)
+                    .map_err(Error::ProceedRound)?;
+                true
+            }
+            s @ R::Round2(_) => {
+                next_state = s;
+                false
+            }
+            R::Round3(round) if !store3_wants_more && (!round.is_expensive() || may_block) => {
+                let store = self.msgs3.take().ok_or(InternalError::StoreGone)?;
+                let msgs = store
+                    .finish()
+                    .map_err(InternalError::RetrieveRoundMessages)?;
+                next_state = round
+                    .proceed(msgs)
+                    .map(R::Final)
+                    .map_err(Error::ProceedRound)?;
+                true
+            }
+            s @ R::Round3(_) => {
+                next_state = s;
+                false
+            }
+            s @ R::Final(_) | s @ R::Gone => {
+                next_state = s;
+                false
+            }
+        };
+
+        self.round = next_state;
+        if try_again {
+            self.proceed_round(may_block)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl StateMachine for Reshare {
+    type MessageBody = ProtocolMessage;
+    type Err = Error;
+    type Output = Option<LocalKey<Secp256k1>>;
+
+    fn handle_incoming(&mut self, msg: Msg<Self::MessageBody>) -> Result<()> {
+        let current_round = self.current_round();
+
+        match msg.body {
+            ProtocolMessage(M::Round1(m)) => {
+                let store = self
+                    .msgs1
+                    .as_mut()
+                    .ok_or(Error::ReceivedOutOfOrderMessage {
+                        current_round,
+                        msg_round: 1,
+                    })?;
+                store
+                    .push_msg(Msg {
+                        sender: msg.sender,
+                        receiver: msg.receiver,
+                        body: m,
+                    })
+                    .map_err(Error::HandleMessage)?;
+                self.proceed_round(false)
+            }
+            ProtocolMessage(M::Round2(m)) => {
+                let store = self
+                    .msgs2
+                    .as_mut()
+                    .ok_or(Error::ReceivedOutOfOrderMessage {
+                        current_round,
+                        msg_round: 2,
+                    })?;
+                store
+                    .push_msg(Msg {
+                        sender: msg.sender,
+                        receiver: msg.receiver,
+                        body: m,
+                    })
+                    .map_err(Error::HandleMessage)?;
+                self.proceed_round(false)
+            }
+            ProtocolMessage(M::Round3(m)) => {
+                let store = self
+                    .msgs3
+                    .as_mut()
+                    .ok_or(Error::ReceivedOutOfOrderMessage {
+                        current_round,
+                        msg_round: 3,
+                    })?;
+                store
+                    .push_msg(Msg {
+                        sender: msg.sender,
+                        receiver: msg.receiver,
+                        body: m,
+                    })
+                    .map_err(Error::HandleMessage)?;
+                self.proceed_round(false)
+            }
+        }
+    }
+
+    fn message_queue(&mut self) -> &mut Vec<Msg<Self::MessageBody>> {
+        &mut self.msgs_queue
+    }
+
+    fn wants_to_proceed(&self) -> bool {
+        let store1_wants_more = self.msgs1.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+        let store2_wants_more = self.msgs2.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+        let store3_wants_more = self.msgs3.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+
+        match &self.round {
+            R::Round0(_) => true,
+            R::Round1(_) => !store1_wants_more,
+            R::Round2(_) => !store2_wants_more,
+            R::Round3(_) => !store3_wants_more,
+            R::Final(_) | R::Gone => false,
+        }
+    }
+
+    fn proceed(&mut self) -> Result<()> {
+        self.proceed_round(true)
+    }
+
+    fn round_timeout(&self) -> Option<Duration> {
+        match &self.round {
+            R::Round0(_) | R::Final(_) | R::Gone => None,
+            _ => self.round_timeout,
+        }
+    }
+
+    fn round_timeout_reached(&mut self) -> Self::Err {
+        let (_, parties) = super::traits::RoundBlame::round_blame(self);
+        Error::RoundTimeout {
+            round: self.current_round(),
+            parties,
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(self.round, R::Final(_))
+    }
+
+    fn pick_output(&mut self) -> Option<Result<Self::Output>> {
+        match self.round {
+            R::Final(_) => (),
+            R::Gone => return Some(Err(Error::DoublePickOutput)),
+            _ => return None,
+        }
+
+        match replace(&mut self.round, R::Gone) {
+            R::Final(result) => Some(Ok(result)),
+            _ => unreachable!("guaranteed by match expression above"),
+        }
+    }
+
+    fn current_round(&self) -> u16 {
+        match &self.round {
+            R::Round0(_) => 0,
+            R::Round1(_) => 1,
+            R::Round2(_) => 2,
+            R::Round3(_) => 3,
+            R::Final(_) | R::Gone => 4,
+        }
+    }
+
+    fn total_rounds(&self) -> Option<u16> {
+        Some(3)
+    }
+
+    fn party_ind(&self) -> u16 {
+        self.party_i
+    }
+
+    fn parties(&self) -> u16 {
+        self.party_n
+    }
+}
+
+impl super::traits::RoundBlame for Reshare {
+    /// Returns number of unwilling parties and a vector of their party indexes.
+    fn round_blame(&self) -> (u16, Vec<u16>) {
+        let store1_blame = self.msgs1.as_ref().map(|s| s.blame()).unwrap_or_default();
+        let store2_blame = self.msgs2.as_ref().map(|s| s.blame()).unwrap_or_default();
+        let store3_blame = self.msgs3.as_ref().map(|s| s.blame()).unwrap_or_default();
+
+        let default = (0, vec![]);
+        match &self.round {
+            R::Round0(_) => default,
+            R::Round1(_) => store1_blame,
+            R::Round2(_) => store2_blame,
+            R::Round3(_) => store3_blame,
+            R::Final(_) | R::Gone => default,
+        }
+    }
+}
+
+impl fmt::Debug for Reshare {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let current_round = match &self.round {
+            R::Round0(_) => "0",
+            R::Round1(_) => "1",
+            R::Round2(_) => "2",
+            R::Round3(_) => "3",
+            R::Final(_) => "[Final]",
+            R::Gone => "[Gone]",
+        };
+        let msgs1 = match self.msgs1.as_ref() {
+            Some(msgs) => format!("[{}/{}]", msgs.messages_received(), msgs.messages_total()),
+            None => "[None]".into(),
+        };
+        let msgs2 = match self.msgs2.as_ref() {
+            Some(msgs) => format!("[{}/{}]", msgs.messages_received(), msgs.messages_total()),
+            None => "[None]".into(),
+        };
+        let msgs3 = match self.msgs3.as_ref() {
+            Some(msgs) => format!("[{}/{}]", msgs.messages_received(), msgs.messages_total()),
+            None => "[None]".into(),
+        };
+        write!(
+            f,
+            "{{Reshare at round={} msgs1={} msgs2={} msgs3={} queue=[len={}]}}",
+            current_round,
+            msgs1,
+            msgs2,
+            msgs3,
+            self.msgs_queue.len()
+        )
+    }
+}
+
+// Rounds
+
+enum R {
+    Round0(Round0),
+    Round1(Round1),
+    Round2(Round2),
+    Round3(Round3),
+    Final(Option<LocalKey<Secp256k1>>),
+    Gone,
+}
+
+// Messages
+
+/// Protocol message which parties send on wire
+///
+/// Hides actual messages structure so it could be changed without breaking semver policy.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProtocolMessage(M);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+enum M {
+    Round1(ReshareBroadcastMessage1),
+    Round2(ReshareP2PMessage2),
+    Round3(Option<DLogProof<Secp256k1, Sha256>>),
+}
+
+// Error
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Error type of resharing protocol
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// Round proceeding resulted in error
+    #[error("proceed round: {0}")]
+    ProceedRound(#[source] ProceedError),
+
+    /// Too few parties in new committee (`n' < 2`)
+    #[error("at least 2 parties are required in new committee")]
+    TooFewParties,
+    /// New threshold value `t'` is not in range `[1; n'-1]`
+    #[error("new threshold is not in range [1; n'-1]")]
+    InvalidThreshold,
+    /// List of old committee members is invalid (too short, has duplicates or indexes out of range)
+    #[error("invalid list of old committee members")]
+    InvalidOldParties,
+    /// Party index `i` doesn't match party role
+    #[error("party index is not valid for this role")]
+    InvalidPartyIndex,
+
+    /// Received message didn't pass pre-validation
+    #[error("received message didn't pass pre-validation: {0}")]
+    HandleMessage(#[source] StoreErr),
+    /// Received message which we didn't expect to receive now (e.g. message from previous round)
+    #[error(
+        "didn't expect to receive message from round {msg_round} (being at round {current_round})"
+    )]
+    ReceivedOutOfOrderMessage { current_round: u16, msg_round: u16 },
+    /// [Reshare::pick_output] called twice
+    #[error("pick_output called twice")]
+    DoublePickOutput,
+    /// Round timeout is reached, `parties` didn't send their messages for `round`
+    #[error("round {round} timed out, parties {parties:?} didn't send their messages")]
+    RoundTimeout { round: u16, parties: Vec<u16> },
+
+    /// Some internal assertions were failed, which is a bug
+    #[doc(hidden)]
+    #[error("internal error: {0:?}")]
+    InternalError(InternalError),
+}
+
+impl IsCritical for Error {
+    fn is_critical(&self) -> bool {
+        true
+    }
+}
+
+impl From<InternalError> for Error {
+    fn from(err: InternalError) -> Self {
+        Self::InternalError(err)
+    }
+}
+
+mod private {
+    #[derive(Debug)]
+    #[non_exhaustive]
+    pub enum InternalError {
+        /// [Messages store](super::MessageStore) reported that it received all messages it wanted to receive,
+        /// but refused to return message container
+        RetrieveRoundMessages(super::StoreErr),
+        #[doc(hidden)]
+        StoreGone,
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use curv::elliptic::curves::Point;
+    use round_based::dev::Simulation;
+
+    use super::*;
+    use gg_2020::state_machine::keygen::test::simulate_keygen;
+    use gg_2020::state_machine::sign::test::{simulate_offline_stage, simulate_signing};
+    use gg_2020::state_machine::test::{run_with_transport, through_json};
+
+    pub fn simulate_reshare(
+        old_keys: Vec<LocalKey<Secp256k1>>,
+        old_parties: &[u16],
+        new_t: u16,
+        new_n: u16,
+    ) -> Vec<LocalKey<Secp256k1>> {
+        let mut simulation = Simulation::new();
+        simulation.enable_benchmarks(true);
+
+        let m = old_parties.len() as u16;
+        for (i, &old_i) in (1..).zip(old_parties) {
+            let local_key = old_keys[usize::from(old_i - 1)].clone();
+            simulation.add_party(
+                Reshare::old_party(i, old_parties.to_vec(), new_t, new_n, local_key).unwrap(),
+            );
+        }
+        for i in m + 1..=m + new_n {
+            simulation
+                .add_party(Reshare::new_party(i, old_parties.to_vec(), new_t, new_n).unwrap());
+        }
+
+        let outputs = simulation.run().unwrap();
+
+        println!("Benchmark results:");
+        println!("{:#?}", simulation.benchmark_results().unwrap());
+
+        assert!(outputs[..usize::from(m)].iter().all(Option::is_none));
+        outputs.into_iter().flatten().collect()
+    }
+
+    #[test]
+    fn simulate_reshare_t1_n3_to_t2_n4_and_sign() {
+        let old_keys = simulate_keygen(1, 3);
+        let new_keys = simulate_reshare(old_keys.clone(), &[1, 3], 2, 4);
+
+        assert_eq!(new_keys.len(), 4);
+        for (k, new) in (1..).zip(&new_keys) {
+            assert_eq!(new.public_key(), old_keys[0].public_key());
+            assert_eq!((new.i, new.t, new.n), (k, 2, 4));
+            for (j, pk) in new.pk_vec.iter().enumerate() {
+                assert_eq!(pk, &(Point::generator() * &new_keys[j].keys_linear.x_i));
+            }
+        }
+
+        let offline_stage = simulate_offline_stage(new_keys, &[1, 3, 4]);
+        simulate_signing(offline_stage, b"ZenGo");
+    }
+
+    #[test]
+    fn reshare_survives_serialized_transport() {
+        let old_keys = simulate_keygen(1, 3);
+        let old_parties = vec![2, 3];
+        let mut parties = vec![];
+        for (i, &old_i) in (1..).zip(&old_parties) {
+            let local_key = old_keys[usize::from(old_i - 1)].clone();
+            parties.push(Reshare::old_party(i, old_parties.clone(), 1, 2, local_key).unwrap());
+        }
+        for i in 3..=4 {
+            parties.push(Reshare::new_party(i, old_parties.clone(), 1, 2).unwrap());
+        }
+
+        let new_keys = run_with_transport(parties, through_json)
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        assert_eq!(new_keys.len(), 2);
+        for new in &new_keys {
+            assert_eq!(new.public_key(), old_keys[0].public_key());
+        }
+    }
+
+    #[test]
+    fn reshare_rejects_too_few_old_parties() {
+        let old_keys = simulate_keygen(1, 3);
+        let result = Reshare::old_party(1, vec![1], 1, 2, old_keys[0].clone());
+        assert!(matches!(result, Err(Error::InvalidOldParties)));
+    }
+}
@@ -0,0 +1,624 @@
+use std::convert::TryFrom;
+use std::slice;
+
+use curv::cryptographic_primitives::proofs::sigma_dlog::DLogProof;
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::{
+    ShamirSecretSharing, VerifiableSS,
+};
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
+use sha2::Sha256;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use paillier::EncryptionKey;
+use round_based::containers::push::Push;
+use round_based::containers::{self, BroadcastMsgs, P2PMsgs, Store};
+use round_based::Msg;
+use zk_paillier::zkproofs::{CompositeDLogProof, DLogStatement, NiCorrectKeyProof};
+
+use crate::protocols::multi_party_ecdsa::gg_2020::party_i::{
+    verify_correct_key_correct_h1h2, verify_moduli_are_paillier_blum, verify_no_small_factors,
+    Keys, NoSmallFactorProofs, SharedKeys,
+};
+use crate::protocols::multi_party_ecdsa::gg_2020::state_machine::keygen::LocalKey;
+use crate::protocols::multi_party_ecdsa::gg_2020::{ErrorKind, ErrorType};
+use crate::utilities::zk_paillier_blum_modulus::PaillierBlumModulusProof;
+
+/// First message of resharing protocol
+///
+/// Members of old committee commit to a sharing of their lagrange-weighted secret share, members
+/// of new committee publish their Paillier key and `h1, h2, N_tilde` along with proofs of
+/// correctness.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum ReshareBroadcastMessage1 {
+    OldParty {
+        old_i: u16,
+        old_t: u16,
+        old_n: u16,
+        y_sum_s: Point<Secp256k1>,
+        pk_vec: Vec<Point<Secp256k1>>,
+        vss_scheme: VerifiableSS<Secp256k1>,
+    },
+    NewParty {
+        e: EncryptionKey,
+        dlog_statement: DLogStatement,
+        correct_key_proof: NiCorrectKeyProof,
+        composite_dlog_proof_base_h1: CompositeDLogProof,
+        composite_dlog_proof_base_h2: CompositeDLogProof,
+        modulus_proof: PaillierBlumModulusProof,
+        n_tilde_modulus_proof: PaillierBlumModulusProof,
+    },
+}
+
+/// Second message of resharing protocol
+///
+/// Members of old committee send shares of their dealing to members of new committee. Members of
+/// new committee prove to each other that their Paillier modulus and `N_tilde` have no small
+/// factors: such proof is only sound with respect to verifier's `h1, h2, N_tilde`, so it's
+/// generated for every recipient.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReshareP2PMessage2 {
+    pub share: Option<Scalar<Secp256k1>>,
+    pub no_small_factor_proofs: Option<NoSmallFactorProofs>,
+}
+
+#[allow(clippy::large_enum_variant)]
+enum PartySecrets {
+    Old { shares: Vec<Scalar<Secp256k1>> },
+    New { keys: Keys },
+}
+
+pub struct Round0 {
+    pub i: u16,
+    pub old_parties: Vec<u16>,
+    pub new_t: u16,
+    pub new_n: u16,
+    pub local_key: Option<LocalKey<Secp256k1>>,
+}
+
+impl Round0 {
+    pub fn proceed<O>(self, mut output: O) -> Result<Round1>
+    where
+        O: Push<Msg<ReshareBroadcastMessage1>>,
+    {
+        let (bc1, secrets) = match self.local_key {
+            Some(local_key) => {
+                let s = self.old_parties.iter().map(|j| j - 1).collect::<Vec<_>>();
+                let lambda_i = VerifiableSS::<Secp256k1>::map_share_to_new_params(
+                    &local_key.vss_scheme.parameters,
+                    local_key.i - 1,
+                    &s,
+                );
+                let w_i = lambda_i * &local_key.keys_linear.x_i;
+                let (vss_scheme, shares) = VerifiableSS::share(self.new_t, self.new_n, &w_i);
+                let bc1 = ReshareBroadcastMessage1::OldParty {
+                    old_i: local_key.i,
+                    old_t: local_key.t,
+                    old_n: local_key.n,
+                    y_sum_s: local_key.y_sum_s,
+                    pk_vec: local_key.pk_vec,
+                    vss_scheme,
+                };
+                let secrets = PartySecrets::Old {
+                    shares: shares.to_vec(),
+                };
+                (bc1, secrets)
+            }
+            None => {
+                let m = self.old_parties.len() as u16;
+                let keys = Keys::create(usize::from(self.i - m));
+                let (
+                    dlog_statement,
+                    correct_key_proof,
+                    composite_dlog_proof_base_h1,
+                    composite_dlog_proof_base_h2,
+                ) = keys.proof_of_correct_key_proof_of_correct_h1h2();
+                let (modulus_proof, n_tilde_modulus_proof) = keys.prove_moduli_are_paillier_blum();
+                let bc1 = ReshareBroadcastMessage1::NewParty {
+                    e: keys.ek.clone(),
+                    dlog_statement,
+                    correct_key_proof,
+                    composite_dlog_proof_base_h1,
+                    composite_dlog_proof_base_h2,
+                    modulus_proof,
+                    n_tilde_modulus_proof,
+                };
+                (bc1, PartySecrets::New { keys })
+            }
+        };
+
+        output.push(Msg {
+            sender: self.i,
+            receiver: None,
+            body: bc1.clone(),
+        });
+
+        Ok(Round1 {
+            i: self.i,
+            old_parties: self.old_parties,
+            new_t: self.new_t,
+            new_n: self.new_n,
+            bc1,
+            secrets,
+        })
+    }
+    pub fn is_expensive(&self) -> bool {
+        true
+    }
+}
+
+pub struct Round1 {
+    i: u16,
+    old_parties: Vec<u16>,
+    new_t: u16,
+    new_n: u16,
+    bc1: ReshareBroadcastMessage1,
+    secrets: PartySecrets,
+}
+
+impl Round1 {
+    pub fn proceed<O>(
+        self,
+        input: BroadcastMsgs<ReshareBroadcastMessage1>,
+        mut output: O,
+    ) -> Result<Round2>
+    where
+        O: Push<Msg<ReshareP2PMessage2>>,
+    {
+        let bc1_vec = input.into_vec_including_me(self.bc1);
+        let public = verify_broadcast(&bc1_vec, &self.old_parties, self.new_t, self.new_n)
+            .map_err(ProceedError::Round1VerifyBroadcast)?;
+
+        let m = self.old_parties.len();
+        let total = m + usize::from(self.new_n);
+        for j in 1..=total {
+            if j == usize::from(self.i) {
+                continue;
+            }
+            // only old committee members send shares, and only to new committee members
+            let share = match &self.secrets {
+                PartySecrets::Old { shares } if j > m => Some(shares[j - m - 1].clone()),
+                _ => None,
+            };
+            // only new committee members prove their keys, and only to each other
+            let no_small_factor_proofs = match &self.secrets {
+                PartySecrets::New { keys } if j > m => keys
+                    .prove_no_small_factors(slice::from_ref(&public.h1_h2_n_tilde_vec[j - m - 1]))
+                    .pop(),
+                _ => None,
+            };
+            output.push(Msg {
+                sender: self.i,
+                receiver: Some(u16::try_from(j).unwrap()),
+                body: ReshareP2PMessage2 {
+                    share,
+                    no_small_factor_proofs,
+                },
+            })
+        }
+
+        let keys = match self.secrets {
+            PartySecrets::Old { .. } => None,
+            PartySecrets::New { keys } => Some(keys),
+        };
+
+        Ok(Round2 {
+            i: self.i,
+            m: self.old_parties.len() as u16,
+            new_t: self.new_t,
+            new_n: self.new_n,
+            public,
+            keys,
+        })
+    }
+    pub fn is_expensive(&self) -> bool {
+        true
+    }
+    pub fn expects_messages(i: u16, n: u16) -> Store<BroadcastMsgs<ReshareBroadcastMessage1>> {
+        containers::BroadcastMsgsStore::new(i, n)
+    }
+}
+
+pub struct Round2 {
+    i: u16,
+    m: u16,
+    new_t: u16,
+    new_n: u16,
+    public: PublicData,
+    keys: Option<Keys>,
+}
+
+impl Round2 {
+    pub fn proceed<O>(self, input: P2PMsgs<ReshareP2PMessage2>, mut output: O) -> Result<Round3>
+    where
+        O: Push<Msg<Option<DLogProof<Secp256k1, Sha256>>>>,
+    {
+        let msgs = input.into_vec_including_me(ReshareP2PMessage2 {
+            share: None,
+            no_small_factor_proofs: None,
+        });
+
+        let shared_keys = match &self.keys {
+            Some(_) => {
+                let x_i = verify_shares(&self.public.vss_vec, &msgs, self.i - self.m)
+                    .map_err(ProceedError::Round2VerifyShares)?;
+                verify_no_small_factor_proofs(&self.public, &msgs, self.i, self.m)
+                    .map_err(ProceedError::Round2VerifyNoSmallFactorProofs)?;
+                Some(SharedKeys {
+                    y: self.public.y_sum_s.clone(),
+                    x_i,
+                })
+            }
+            None => None,
+        };
+        let dlog_proof = shared_keys.as_ref().map(|k| DLogProof::prove(&k.x_i));
+
+        output.push(Msg {
+            sender: self.i,
+            receiver: None,
+            body: dlog_proof.clone(),
+        });
+
+        Ok(Round3 {
+            i: self.i,
+            m: self.m,
+            new_t: self.new_t,
+            new_n: self.new_n,
+            public: self.public,
+            keys: self.keys,
+            shared_keys,
+            own_dlog_proof: dlog_proof,
+        })
+    }
+    pub fn is_expensive(&self) -> bool {
+        true
+    }
+    pub fn expects_messages(i: u16, n: u16) -> Store<P2PMsgs<ReshareP2PMessage2>> {
+        containers::P2PMsgsStore::new(i, n)
+    }
+}
+
+pub struct Round3 {
+    i: u16,
+    m: u16,
+    new_t: u16,
+    new_n: u16,
+    public: PublicData,
+    keys: Option<Keys>,
+    shared_keys: Option<SharedKeys>,
+    own_dlog_proof: Option<DLogProof<Secp256k1, Sha256>>,
+}
+
+impl Round3 {
+    pub fn proceed(
+        self,
+        input: BroadcastMsgs<Option<DLogProof<Secp256k1, Sha256>>>,
+    ) -> Result<Option<LocalKey<Secp256k1>>> {
+        let Round3 {
+            i,
+            m,
+            new_t,
+            new_n,
+            public,
+            keys,
+            shared_keys,
+            own_dlog_proof,
+        } = self;
+        let dlog_proofs = input.into_vec_including_me(own_dlog_proof);
+
+        let pk_vec = (1..=new_n)
+            .map(|k| {
+                public
+                    .vss_vec
+                    .iter()
+                    .map(|vss| vss.get_point_commitment(k))
+                    .fold(Point::zero(), |acc, p| acc + p)
+            })
+            .collect::<Vec<_>>();
+        verify_dlog_proofs(&dlog_proofs[usize::from(m)..], &pk_vec, m)
+            .map_err(ProceedError::Round3VerifyDLogProof)?;
+
+        let (keys, shared_keys) = match (keys, shared_keys) {
+            (Some(keys), Some(shared_keys)) => (keys, shared_keys),
+            _ => return Ok(None),
+        };
+
+        // new parties' shares lie on the sum of dealers' polynomials
+        let vss_scheme = VerifiableSS {
+            parameters: ShamirSecretSharing {
+                threshold: new_t,
+                share_count: new_n,
+            },
+            commitments: (0..=usize::from(new_t))
+                .map(|c| {
+                    public
+                        .vss_vec
+                        .iter()
+                        .fold(Point::zero(), |acc, vss| acc + &vss.commitments[c])
+                })
+                .collect(),
+        };
+
+        Ok(Some(LocalKey {
+            paillier_dk: keys.dk,
+            pk_vec,
+
+            keys_linear: shared_keys,
+            paillier_key_vec: public.paillier_key_vec,
+            y_sum_s: public.y_sum_s,
+            h1_h2_n_tilde_vec: public.h1_h2_n_tilde_vec,
+
+            vss_scheme,
+
+            i: i - m,
+            t: new_t,
+            n: new_n,
+        }))
+    }
+    pub fn is_expensive(&self) -> bool {
+        true
+    }
+    pub fn expects_messages(
+        i: u16,
+        n: u16,
+    ) -> Store<BroadcastMsgs<Option<DLogProof<Secp256k1, Sha256>>>> {
+        containers::BroadcastMsgsStore::new(i, n)
+    }
+}
+
+/// Data every party learns from first round messages
+struct PublicData {
+    y_sum_s: Point<Secp256k1>,
+    vss_vec: Vec<VerifiableSS<Secp256k1>>,
+    paillier_key_vec: Vec<EncryptionKey>,
+    h1_h2_n_tilde_vec: Vec<DLogStatement>,
+}
+
+/// Verifies first round messages
+///
+/// Old committee members must agree on public key and public shares, each of them must deal
+/// exactly its lagrange-weighted share, and dealings must sum up to the public key. New committee
+/// members must prove correctness of their Paillier keys and `h1, h2, N_tilde`. Bad actors are
+/// identified by their (0-based) index in the resharing session.
+fn verify_broadcast(
+    bc1_vec: &[ReshareBroadcastMessage1],
+    old_parties: &[u16],
+    new_t: u16,
+    new_n: u16,
+) -> std::result::Result<PublicData, ErrorType> {
+    let m = old_parties.len();
+    assert_eq!(bc1_vec.len(), m + usize::from(new_n));
+
+    let (y_sum_s, pk_vec, old_t, old_n) = match &bc1_vec[0] {
+        ReshareBroadcastMessage1::OldParty {
+            y_sum_s,
+            pk_vec,
+            old_t,
+            old_n,
+            ..
+        } => (y_sum_s, pk_vec, *old_t, *old_n),
+        ReshareBroadcastMessage1::NewParty { .. } => {
+            return Err(ErrorType {
+                kind: ErrorKind::UnexpectedMessage,
+                bad_actors: vec![0],
+            })
+        }
+    };
+    if m <= usize::from(old_t) || pk_vec.len() != usize::from(old_n) {
+        return Err(ErrorType {
+            kind: ErrorKind::InvalidParameters,
+            bad_actors: vec![],
+        });
+    }
+    let s = old_parties.iter().map(|j| j - 1).collect::<Vec<_>>();
+    let old_params = ShamirSecretSharing {
+        threshold: old_t,
+        share_count: old_n,
+    };
+
+    let mut bad_actors_vec = Vec::new();
+    let mut vss_vec = Vec::with_capacity(m);
+    let mut paillier_key_vec = Vec::with_capacity(usize::from(new_n));
+    let mut h1_h2_n_tilde_vec = Vec::with_capacity(usize::from(new_n));
+    for (j, bc1) in bc1_vec.iter().enumerate() {
+        let valid = match bc1 {
+            ReshareBroadcastMessage1::OldParty {
+                old_i,
+                old_t: t,
+                old_n: n,
+                y_sum_s: y,
+                pk_vec: pks,
+                vss_scheme,
+            } if j < m => {
+                let valid = *old_i == old_parties[j]
+                    && *t == old_t
+                    && *n == old_n
+                    && y == y_sum_s
+                    && pks == pk_vec
+                    && vss_scheme.parameters.threshold == new_t
+                    && vss_scheme.parameters.share_count == new_n
+                    && vss_scheme.commitments.len() == usize::from(new_t) + 1
+                    && (1..=old_n).contains(old_i)
+                    && {
+                        let lambda = VerifiableSS::<Secp256k1>::map_share_to_new_params(
+                            &old_params,
+                            old_i - 1,
+                            &s,
+                        );
+                        vss_scheme.commitments[0] == &pk_vec[usize::from(old_i - 1)] * &lambda
+                    };
+                vss_vec.push(vss_scheme.clone());
+                valid
+            }
+            ReshareBroadcastMessage1::NewParty {
+                e,
+                dlog_statement,
+                correct_key_proof,
+                composite_dlog_proof_base_h1,
+                composite_dlog_proof_base_h2,
+                modulus_proof,
+                n_tilde_modulus_proof,
+            } if j >= m => {
+                paillier_key_vec.push(e.clone());
+                h1_h2_n_tilde_vec.push(dlog_statement.clone());
+                verify_correct_key_correct_h1h2(
+                    e,
+                    dlog_statement,
+                    correct_key_proof,
+                    composite_dlog_proof_base_h1,
+                    composite_dlog_proof_base_h2,
+                ) && verify_moduli_are_paillier_blum(
+                    e,
+                    dlog_statement,
+                    modulus_proof,
+                    n_tilde_modulus_proof,
+                )
+            }
+            _ => false,
+        };
+        if !valid {
+            bad_actors_vec.push(j);
+        }
+    }
+
+    if !bad_actors_vec.is_empty() {
+        return Err(ErrorType {
+            kind: ErrorKind::InvalidKey,
+            bad_actors: bad_actors_vec,
+        });
+    }
+
+    let y_reshared = vss_vec
+        .iter()
+        .fold(Point::zero(), |acc, vss| acc + &vss.commitments[0]);
+    if &y_reshared != y_sum_s {
+        return Err(ErrorType {
+            kind: ErrorKind::PublicKeyMismatch,
+            bad_actors: (0..m).collect(),
+        });
+    }
+
+    Ok(PublicData {
+        y_sum_s: y_sum_s.clone(),
+        vss_vec,
+        paillier_key_vec,
+        h1_h2_n_tilde_vec,
+    })
+}
+
+/// Validates shares received from old committee and sums them up into new secret share
+fn verify_shares(
+    vss_vec: &[VerifiableSS<Secp256k1>],
+    msgs: &[ReshareP2PMessage2],
+    index: u16,
+) -> std::result::Result<Scalar<Secp256k1>, ErrorType> {
+    let mut bad_actors_vec = Vec::new();
+    let mut x_i = Scalar::zero();
+    for (j, vss) in vss_vec.iter().enumerate() {
+        match &msgs[j].share {
+            Some(share) if vss.validate_share(share, index).is_ok() => x_i = x_i + share,
+            _ => bad_actors_vec.push(j),
+        }
+    }
+
+    if bad_actors_vec.is_empty() {
+        Ok(x_i)
+    } else {
+        Err(ErrorType {
+            kind: ErrorKind::BadVssShare,
+            bad_actors: bad_actors_vec,
+        })
+    }
+}
+
+/// Verifies proofs that Paillier moduli and `N_tilde` of new committee members have no small factors
+///
+/// Proofs received by new committee member `i` are generated with respect to its own `h1, h2,
+/// N_tilde`, published in the first round.
+fn verify_no_small_factor_proofs(
+    public: &PublicData,
+    msgs: &[ReshareP2PMessage2],
+    i: u16,
+    m: u16,
+) -> std::result::Result<(), ErrorType> {
+    let own_setup = &public.h1_h2_n_tilde_vec[usize::from(i - m - 1)];
+    let bad_actors_vec = msgs[usize::from(m)..]
+        .iter()
+        .zip(
+            public
+                .paillier_key_vec
+                .iter()
+                .zip(&public.h1_h2_n_tilde_vec),
+        )
+        .enumerate()
+        .map(|(k, msg)| (usize::from(m) + k, msg))
+        .filter(|(j, (msg, (e, dlog_statement)))| {
+            *j != usize::from(i - 1)
+                && !verify_no_small_factors(
+                    e,
+                    dlog_statement,
+                    msg.no_small_factor_proofs.as_ref(),
+                    own_setup,
+                )
+        })
+        .map(|(j, _)| j)
+        .collect::<Vec<_>>();
+
+    if bad_actors_vec.is_empty() {
+        Ok(())
+    } else {
+        Err(ErrorType {
+            kind: ErrorKind::InvalidKey,
+            bad_actors: bad_actors_vec,
+        })
+    }
+}
+
+/// Verifies new committee members know their secret shares
+fn verify_dlog_proofs(
+    dlog_proofs_vec: &[Option<DLogProof<Secp256k1, Sha256>>],
+    pk_vec: &[Point<Secp256k1>],
+    m: u16,
+) -> std::result::Result<(), ErrorType> {
+    let bad_actors_vec = dlog_proofs_vec
+        .iter()
+        .zip(pk_vec)
+        .enumerate()
+        .filter(|(_, (proof, pk))| match proof {
+            Some(proof) => DLogProof::verify(proof).is_err() || &proof.pk != *pk,
+            None => true,
+        })
+        .map(|(k, _)| usize::from(m) + k)
+        .collect::<Vec<_>>();
+
+    if bad_actors_vec.is_empty() {
+        Ok(())
+    } else {
+        Err(ErrorType {
+            kind: ErrorKind::BadDLogProof,
+            bad_actors: bad_actors_vec,
+        })
+    }
+}
+
+// Errors
+
+type Result<T> = std::result::Result<T, ProceedError>;
+
+/// Proceeding protocol error
+///
+/// Subset of [resharing errors](enum@super::Error) that can occur at protocol proceeding (i.e. after
+/// every message was received and pre-validated).
+#[derive(Debug, Error)]
+pub enum ProceedError {
+    #[error("round 1: verify broadcast: {0:?}")]
+    Round1VerifyBroadcast(ErrorType),
+    #[error("round 2: verify shares: {0:?}")]
+    Round2VerifyShares(ErrorType),
+    #[error("round 2: verify no small factor proofs: {0:?}")]
+    Round2VerifyNoSmallFactorProofs(ErrorType),
+    #[error("round 3: verify dlog proof: {0:?}")]
+    Round3VerifyDLogProof(ErrorType),
+}
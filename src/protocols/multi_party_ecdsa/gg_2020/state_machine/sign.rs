@@ -15,6 +15,14 @@
 //! `StateMachine`, but rather provides methods to construct messages and final signature manually
 //! (refer to [SignManual] documentation to see how to use it).
 //!
+//! `CompletedOfflineStage` can be computed in advance and stored (see [presignature] module), but
+//! it must be used to sign exactly one message.
+//!
+//! [BatchOfflineStage] produces many presignatures in a single protocol execution.
+//!
+//! Set of parties carrying out the offline stage can be picked by [SignerSelection], which also
+//! restarts the offline stage without parties that caused it to fail.
+//!
 //! [keygen module]: super::keygen
 //! [Keygen]: super::keygen::Keygen
 //! [LocalKey]: super::keygen::LocalKey
@@ -24,25 +32,36 @@ use std::convert::TryFrom;
 use std::mem::replace;
 use std::time::Duration;
 
+use rand::rngs::OsRng;
 use round_based::containers::{push::Push, BroadcastMsgs, MessageStore, P2PMsgs, Store, StoreErr};
 use round_based::{IsCritical, Msg, StateMachine};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::utilities::digest::MessageDigest;
 use crate::utilities::mta::MessageA;
+use crate::utilities::rng::CryptoRngCore;
 
 use crate::protocols::multi_party_ecdsa::gg_2020 as gg20;
 use curv::elliptic::curves::secp256_k1::Secp256k1;
 use gg20::party_i::{SignBroadcastPhase1, SignDecommitPhase1, SignatureRecid};
 use gg20::state_machine::keygen::LocalKey;
+use gg20::state_machine::wire::{self, Protocol, WireError};
 
+mod batch;
 mod fmt;
+pub mod presignature;
 mod rounds;
+pub mod signers;
 
 use crate::utilities::zk_pdl_with_slack::PDLwSlackProof;
-use curv::BigInt;
+pub use batch::{BatchOfflineProtocolMessage, BatchOfflineStage};
+pub use presignature::{
+    InMemoryPresignatureStore, PresignatureError, PresignatureId, PresignatureStore,
+};
 use rounds::*;
 pub use rounds::{CompletedOfflineStage, Error as ProceedError, PartialSignature};
+pub use signers::{BlameSigners, RetryError, SignerSelection};
 
 /// Offline Stage of GG20 signing
 ///
@@ -57,9 +76,15 @@ pub struct OfflineStage {
     msgs4: Option<Store<BroadcastMsgs<SignDecommitPhase1>>>,
     msgs5: Option<Store<BroadcastMsgs<(RDash, Vec<PDLwSlackProof>)>>>,
     msgs6: Option<Store<BroadcastMsgs<(SI, HEGProof)>>>,
+    msgs_blame5: Option<Store<BroadcastMsgs<Phase5Reveal>>>,
+    msgs_blame6: Option<Store<BroadcastMsgs<Phase6Reveal>>>,
 
     msgs_queue: MsgQueue,
 
+    rng: Box<dyn CryptoRngCore + Send>,
+    round_timeout: Option<Duration>,
+    identifiable_abort: bool,
+
     party_i: u16,
     party_n: u16,
 }
@@ -76,30 +101,24 @@ impl OfflineStage {
     ///
     /// Returns error if given arguments are contradicting.
     pub fn new(i: u16, s_l: Vec<u16>, local_key: LocalKey<Secp256k1>) -> Result<Self> {
-        if s_l.len() < 2 {
-            return Err(Error::TooFewParties);
-        }
-        if i == 0 || usize::from(i) > s_l.len() {
-            return Err(Error::InvalidPartyIndex);
-        }
-
-        let keygen_n = local_key.n;
-        if s_l.iter().any(|&i| i == 0 || i > keygen_n) {
-            return Err(Error::InvalidSl);
-        }
-        {
-            // Check if s_l has duplicates
-            let mut s_l_sorted = s_l.clone();
-            s_l_sorted.sort_unstable();
-            let mut s_l_sorted_deduped = s_l_sorted.clone();
-            s_l_sorted_deduped.dedup();
-
-            if s_l_sorted != s_l_sorted_deduped {
-                return Err(Error::InvalidSl);
-            }
-        }
+        Self::with_rng(i, s_l, local_key, OsRng)
+    }
 
-        let n = u16::try_from(s_l.len()).map_err(|_| Error::TooManyParties { n: s_l.len() })?;
+    /// Construct a party of offline stage which takes randomness from `rng`
+    ///
+    /// Same as [OfflineStage::new], but nonce shares, MtA masks, Paillier encryption randomness and
+    /// range proofs randomness are sampled from `rng`. Nonces of DLog, Pedersen and homomorphic
+    /// ElGamal proofs are sampled by `curv` from operating system.
+    pub fn with_rng<Rng>(
+        i: u16,
+        s_l: Vec<u16>,
+        local_key: LocalKey<Secp256k1>,
+        rng: Rng,
+    ) -> Result<Self>
+    where
+        Rng: CryptoRngCore + Send + 'static,
+    {
+        let n = validate_parties(i, &s_l, &local_key)?;
 
         Ok(Self {
             round: OfflineR::R0(Round0 { i, s_l, local_key }),
@@ -110,14 +129,41 @@ impl OfflineStage {
             msgs4: Some(Round4::expects_messages(i, n)),
             msgs5: Some(Round5::expects_messages(i, n)),
             msgs6: Some(Round6::expects_messages(i, n)),
+            msgs_blame5: Some(Phase5Blame::expects_messages(i, n)),
+            msgs_blame6: Some(Phase6Blame::expects_messages(i, n)),
 
             msgs_queue: MsgQueue(vec![]),
 
+            rng: Box::new(rng),
+            round_timeout: None,
+            identifiable_abort: false,
+
             party_i: i,
             party_n: n,
         })
     }
 
+    /// Sets how long every round may wait for messages from other parties
+    ///
+    /// Once timeout is reached, protocol execution fails with [Error::RoundTimeout] listing
+    /// parties which didn't deliver their messages. By default, there's no timeout.
+    pub fn set_round_timeout(&mut self, timeout: Option<Duration>) {
+        self.round_timeout = timeout;
+    }
+
+    /// Enables identifiable abort
+    ///
+    /// If checks of rounds 5 or 6 fail, parties run an extra round revealing their nonce shares and
+    /// MtA secrets, so that misbehaving parties can be identified. Protocol then fails with
+    /// [ProceedError::IdentifiedAbort] listing them. Revealed values are useless for signing, as
+    /// the offline stage is aborted anyway.
+    ///
+    /// All parties of the offline stage must agree on this setting, otherwise honest parties will
+    /// be blamed for not revealing their secrets.
+    pub fn set_identifiable_abort(&mut self, enabled: bool) {
+        self.identifiable_abort = enabled;
+    }
+
     // fn proceed_state(&mut self, may_block: bool) -> Result<()> {
     //     self.proceed_round(may_block)?;
     //     self.proceed_decommit_round(may_block)
@@ -130,12 +176,22 @@ impl OfflineStage {
         let store4_wants_more = self.msgs4.as_ref().map(|s| s.wants_more()).unwrap_or(false);
         let store5_wants_more = self.msgs5.as_ref().map(|s| s.wants_more()).unwrap_or(false);
         let store6_wants_more = self.msgs6.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+        let blame5_wants_more = self
+            .msgs_blame5
+            .as_ref()
+            .map(|s| s.wants_more())
+            .unwrap_or(false);
+        let blame6_wants_more = self
+            .msgs_blame6
+            .as_ref()
+            .map(|s| s.wants_more())
+            .unwrap_or(false);
 
         let next_state: OfflineR;
         let try_again: bool = match replace(&mut self.round, OfflineR::Gone) {
             OfflineR::R0(round) if !round.is_expensive() || may_block => {
                 next_state = round
-                    .proceed(&mut self.msgs_queue)
+                    .proceed(&mut *self.rng, &mut self.msgs_queue)
                     .map(OfflineR::R1)
                     .map_err(Error::ProceedRound)?;
                 true
@@ -150,7 +206,7 @@ impl OfflineStage {
                     .finish()
                     .map_err(InternalError::RetrieveMessagesFromStore)?;
                 next_state = round
-                    .proceed(msgs, &mut self.msgs_queue)
+                    .proceed(msgs, &mut *self.rng, &mut self.msgs_queue)
                     .map(OfflineR::R2)
                     .map_err(Error::ProceedRound)?;
                 true
@@ -165,7 +221,7 @@ impl OfflineStage {
                     .finish()
                     .map_err(InternalError::RetrieveMessagesFromStore)?;
                 next_state = round
-                    .proceed(msgs, &mut self.msgs_queue)
+                    .proceed(msgs, &mut *self.rng, &mut self.msgs_queue)
                     .map(OfflineR::R3)
                     .map_err(Error::ProceedRound)?;
                 true
@@ -195,7 +251,7 @@ impl OfflineStage {
                     .finish()
                     .map_err(InternalError::RetrieveMessagesFromStore)?;
                 next_state = round
-                    .proceed(msgs, &mut self.msgs_queue)
+                    .proceed(msgs, &mut *self.rng, &mut self.msgs_queue)
                     .map(OfflineR::R5)
                     .map_err(Error::ProceedRound)?;
                 false
@@ -209,10 +265,13 @@ impl OfflineStage {
                 let msgs = store
                     .finish()
                     .map_err(InternalError::RetrieveMessagesFromStore)?;
-                next_state = round
-                    .proceed(msgs, &mut self.msgs_queue)
-                    .map(OfflineR::R6)
-                    .map_err(Error::ProceedRound)?;
+                next_state = match round
+                    .proceed(msgs, self.identifiable_abort, &mut self.msgs_queue)
+                    .map_err(Error::ProceedRound)?
+                {
+                    Next::Round(round) => OfflineR::R6(round),
+                    Next::Blame(blame) => OfflineR::Blame5(blame),
+                };
                 false
             }
             s @ OfflineR::R5(_) => {
@@ -224,16 +283,45 @@ impl OfflineStage {
                 let msgs = store
                     .finish()
                     .map_err(InternalError::RetrieveMessagesFromStore)?;
-                next_state = round
-                    .proceed(msgs)
-                    .map(OfflineR::Finished)
-                    .map_err(Error::ProceedRound)?;
+                next_state = match round
+                    .proceed(msgs, self.identifiable_abort, &mut self.msgs_queue)
+                    .map_err(Error::ProceedRound)?
+                {
+                    Next::Round(output) => OfflineR::Finished(output),
+                    Next::Blame(blame) => OfflineR::Blame6(blame),
+                };
                 false
             }
             s @ OfflineR::R6(_) => {
                 next_state = s;
                 false
             }
+            OfflineR::Blame5(round)
+                if !blame5_wants_more && (!round.is_expensive() || may_block) =>
+            {
+                let store = self.msgs_blame5.take().ok_or(InternalError::StoreGone)?;
+                let msgs = store
+                    .finish()
+                    .map_err(InternalError::RetrieveMessagesFromStore)?;
+                return Err(Error::ProceedRound(round.proceed(msgs)));
+            }
+            s @ OfflineR::Blame5(_) => {
+                next_state = s;
+                false
+            }
+            OfflineR::Blame6(round)
+                if !blame6_wants_more && (!round.is_expensive() || may_block) =>
+            {
+                let store = self.msgs_blame6.take().ok_or(InternalError::StoreGone)?;
+                let msgs = store
+                    .finish()
+                    .map_err(InternalError::RetrieveMessagesFromStore)?;
+                return Err(Error::ProceedRound(round.proceed(msgs)));
+            }
+            s @ OfflineR::Blame6(_) => {
+                next_state = s;
+                false
+            }
             s @ OfflineR::Finished(_) | s @ OfflineR::Gone => {
                 next_state = s;
                 false
@@ -249,6 +337,34 @@ impl OfflineStage {
     }
 }
 
+/// Checks arguments of the offline stage and returns number of signers
+fn validate_parties(i: u16, s_l: &[u16], local_key: &LocalKey<Secp256k1>) -> Result<u16> {
+    if s_l.len() < 2 {
+        return Err(Error::TooFewParties);
+    }
+    if i == 0 || usize::from(i) > s_l.len() {
+        return Err(Error::InvalidPartyIndex);
+    }
+
+    let keygen_n = local_key.n;
+    if s_l.iter().any(|&i| i == 0 || i > keygen_n) {
+        return Err(Error::InvalidSl);
+    }
+    {
+        // Check if s_l has duplicates
+        let mut s_l_sorted = s_l.to_vec();
+        s_l_sorted.sort_unstable();
+        let mut s_l_sorted_deduped = s_l_sorted.clone();
+        s_l_sorted_deduped.dedup();
+
+        if s_l_sorted != s_l_sorted_deduped {
+            return Err(Error::InvalidSl);
+        }
+    }
+
+    u16::try_from(s_l.len()).map_err(|_| Error::TooManyParties { n: s_l.len() })
+}
+
 impl StateMachine for OfflineStage {
     type MessageBody = OfflineProtocolMessage;
     type Err = Error;
@@ -354,6 +470,38 @@ impl StateMachine for OfflineStage {
                     })
                     .map_err(Error::HandleMessage)?;
             }
+            OfflineProtocolMessage(OfflineM::Blame5(m)) => {
+                let store = self
+                    .msgs_blame5
+                    .as_mut()
+                    .ok_or(Error::ReceivedOutOfOrderMessage {
+                        current_round,
+                        msg_round: 7,
+                    })?;
+                store
+                    .push_msg(Msg {
+                        sender: msg.sender,
+                        receiver: msg.receiver,
+                        body: m,
+                    })
+                    .map_err(Error::HandleMessage)?;
+            }
+            OfflineProtocolMessage(OfflineM::Blame6(m)) => {
+                let store = self
+                    .msgs_blame6
+                    .as_mut()
+                    .ok_or(Error::ReceivedOutOfOrderMessage {
+                        current_round,
+                        msg_round: 8,
+                    })?;
+                store
+                    .push_msg(Msg {
+                        sender: msg.sender,
+                        receiver: msg.receiver,
+                        body: m,
+                    })
+                    .map_err(Error::HandleMessage)?;
+            }
         }
         self.proceed_round(false)
     }
@@ -369,6 +517,16 @@ impl StateMachine for OfflineStage {
         let store4_wants_more = self.msgs4.as_ref().map(|s| s.wants_more()).unwrap_or(false);
         let store5_wants_more = self.msgs5.as_ref().map(|s| s.wants_more()).unwrap_or(false);
         let store6_wants_more = self.msgs6.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+        let blame5_wants_more = self
+            .msgs_blame5
+            .as_ref()
+            .map(|s| s.wants_more())
+            .unwrap_or(false);
+        let blame6_wants_more = self
+            .msgs_blame6
+            .as_ref()
+            .map(|s| s.wants_more())
+            .unwrap_or(false);
 
         match &self.round {
             OfflineR::R0(_) => true,
@@ -378,6 +536,8 @@ impl StateMachine for OfflineStage {
             OfflineR::R4(_) => !store4_wants_more,
             OfflineR::R5(_) => !store5_wants_more,
             OfflineR::R6(_) => !store6_wants_more,
+            OfflineR::Blame5(_) => !blame5_wants_more,
+            OfflineR::Blame6(_) => !blame6_wants_more,
             OfflineR::Finished(_) | OfflineR::Gone => false,
         }
     }
@@ -387,11 +547,18 @@ impl StateMachine for OfflineStage {
     }
 
     fn round_timeout(&self) -> Option<Duration> {
-        None
+        match &self.round {
+            OfflineR::R0(_) | OfflineR::Finished(_) | OfflineR::Gone => None,
+            _ => self.round_timeout,
+        }
     }
 
     fn round_timeout_reached(&mut self) -> Self::Err {
-        panic!("no timeout was set")
+        let (_, parties) = super::traits::RoundBlame::round_blame(self);
+        Error::RoundTimeout {
+            round: self.current_round(),
+            parties,
+        }
     }
 
     fn is_finished(&self) -> bool {
@@ -421,6 +588,8 @@ impl StateMachine for OfflineStage {
             OfflineR::R5(_) => 5,
             OfflineR::R6(_) => 6,
             OfflineR::Finished(_) | OfflineR::Gone => 7,
+            OfflineR::Blame5(_) => 7,
+            OfflineR::Blame6(_) => 8,
         }
     }
 
@@ -446,6 +615,16 @@ impl super::traits::RoundBlame for OfflineStage {
         let store4_blame = self.msgs4.as_ref().map(|s| s.blame()).unwrap_or_default();
         let store5_blame = self.msgs5.as_ref().map(|s| s.blame()).unwrap_or_default();
         let store6_blame = self.msgs6.as_ref().map(|s| s.blame()).unwrap_or_default();
+        let blame5_blame = self
+            .msgs_blame5
+            .as_ref()
+            .map(|s| s.blame())
+            .unwrap_or_default();
+        let blame6_blame = self
+            .msgs_blame6
+            .as_ref()
+            .map(|s| s.blame())
+            .unwrap_or_default();
 
         let default = (0, vec![]);
         match &self.round {
@@ -456,6 +635,8 @@ impl super::traits::RoundBlame for OfflineStage {
             OfflineR::R4(_) => store4_blame,
             OfflineR::R5(_) => store5_blame,
             OfflineR::R6(_) => store6_blame,
+            OfflineR::Blame5(_) => blame5_blame,
+            OfflineR::Blame6(_) => blame6_blame,
             OfflineR::Finished(_) => store6_blame,
             OfflineR::Gone => default,
         }
@@ -471,6 +652,8 @@ enum OfflineR {
     R4(Round4),
     R5(Round5),
     R6(Round6),
+    Blame5(Phase5Blame),
+    Blame6(Phase6Blame),
     Finished(CompletedOfflineStage),
     Gone,
 }
@@ -487,6 +670,41 @@ enum OfflineM {
     M4(SignDecommitPhase1),
     M5((RDash, Vec<PDLwSlackProof>)),
     M6((SI, HEGProof)),
+    Blame5(Phase5Reveal),
+    Blame6(Phase6Reveal),
+}
+
+impl OfflineProtocolMessage {
+    /// Encodes message into [binary wire format](super::wire)
+    pub fn to_bytes(&self) -> std::result::Result<Vec<u8>, WireError> {
+        match &self.0 {
+            OfflineM::M1(m) => wire::encode(Protocol::OfflineStage, 1, m),
+            OfflineM::M2(m) => wire::encode(Protocol::OfflineStage, 2, m),
+            OfflineM::M3(m) => wire::encode(Protocol::OfflineStage, 3, m),
+            OfflineM::M4(m) => wire::encode(Protocol::OfflineStage, 4, m),
+            OfflineM::M5(m) => wire::encode(Protocol::OfflineStage, 5, m),
+            OfflineM::M6(m) => wire::encode(Protocol::OfflineStage, 6, m),
+            OfflineM::Blame5(m) => wire::encode(Protocol::OfflineStage, 7, m),
+            OfflineM::Blame6(m) => wire::encode(Protocol::OfflineStage, 8, m),
+        }
+    }
+
+    /// Decodes message from [binary wire format](super::wire)
+    pub fn from_bytes(bytes: &[u8]) -> std::result::Result<Self, WireError> {
+        let (header, payload) = wire::decode_frame(Protocol::OfflineStage, bytes)?;
+        let m = match header.round {
+            1 => OfflineM::M1(wire::decode_payload(payload)?),
+            2 => OfflineM::M2(wire::decode_payload(payload)?),
+            3 => OfflineM::M3(wire::decode_payload(payload)?),
+            4 => OfflineM::M4(wire::decode_payload(payload)?),
+            5 => OfflineM::M5(wire::decode_payload(payload)?),
+            6 => OfflineM::M6(wire::decode_payload(payload)?),
+            7 => OfflineM::Blame5(wire::decode_payload(payload)?),
+            8 => OfflineM::Blame6(wire::decode_payload(payload)?),
+            round => return Err(WireError::UnknownRound(round)),
+        };
+        Ok(OfflineProtocolMessage(m))
+    }
 }
 
 struct MsgQueue(Vec<Msg<OfflineProtocolMessage>>);
@@ -514,6 +732,8 @@ make_pushable! {
     M4 SignDecommitPhase1,
     M5 (RDash, Vec<PDLwSlackProof>),
     M6 (SI, HEGProof),
+    Blame5 Phase5Reveal,
+    Blame6 Phase6Reveal,
 }
 
 type Result<T, E = Error> = std::result::Result<T, E>;
@@ -551,6 +771,23 @@ pub enum Error {
     /// [OfflineStage::pick_output] called twice
     #[error("pick_output called twice")]
     DoublePickOutput,
+    /// Round timeout is reached, `parties` didn't send their messages for `round`
+    ///
+    /// Parties are identified by their index in the offline stage (in range `[1; n]`), party `j`
+    /// has index `s_l[j-1]` in keygen.
+    #[error("round {round} timed out, parties {parties:?} didn't send their messages")]
+    RoundTimeout { round: u16, parties: Vec<u16> },
+
+    /// Batch size of [BatchOfflineStage] is zero
+    #[error("batch size must be at least 1")]
+    EmptyBatch,
+    /// Party `sender` sent batch of `received` messages, while batch size is `expected`
+    #[error("party {sender} sent {received} messages in a batch, expected {expected}")]
+    InvalidBatchSize {
+        sender: u16,
+        expected: usize,
+        received: usize,
+    },
 
     /// A bug in protocol implementation
     #[error("offline stage protocol bug: {0}")]
@@ -565,6 +802,25 @@ pub enum InternalError {
     RetrieveMessagesFromStore(StoreErr),
     #[error("decommit round expected to be in NotStarted state")]
     DecommitRoundWasntInInitialState,
+    #[error("couldn't split batch of messages: {0}")]
+    SplitBatch(StoreErr),
+    #[error("round requested identifiable abort while it's disabled")]
+    UnexpectedBlame,
+}
+
+impl Error {
+    /// Parties blamed for the error
+    ///
+    /// Parties are identified by their index in the offline stage (in range `[1; n]`). List is
+    /// empty if no party could be blamed.
+    pub fn bad_parties(&self) -> Vec<u16> {
+        match self {
+            Error::ProceedRound(err) => err.bad_parties(),
+            Error::RoundTimeout { parties, .. } => parties.clone(),
+            Error::InvalidBatchSize { sender, .. } => vec![*sender],
+            _ => vec![],
+        }
+    }
 }
 
 impl From<InternalError> for Error {
@@ -584,6 +840,9 @@ impl IsCritical for Error {
             Error::ReceivedOutOfOrderMessage { .. } => false,
             Error::HandleMessage(_) => false,
             Error::DoublePickOutput => true,
+            Error::RoundTimeout { .. } => true,
+            Error::EmptyBatch => true,
+            Error::InvalidBatchSize { .. } => false,
             Error::Bug(_) => true,
         }
     }
@@ -600,24 +859,24 @@ impl IsCritical for Error {
 /// #     state_machine::sign::{CompletedOfflineStage, SignManual, PartialSignature},
 /// #     party_i::{LocalSignature, verify},
 /// # };
-/// # use curv::arithmetic::{BigInt, Converter};
+/// # use multi_party_ecdsa::utilities::digest::MessageDigest;
 /// # type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 /// # fn broadcast(msg: PartialSignature) -> Result<()> { panic!() }
-/// # fn wait_messages() -> Result<Vec<PartialSignature>> { panic!() }
+/// # fn wait_messages() -> Result<Vec<(u16, PartialSignature)>> { panic!() }
 /// # fn main() -> Result<()> {
 /// # let completed_offline_stage: CompletedOfflineStage = panic!();
-/// let data = BigInt::from_bytes(b"a message");
+/// let digest = MessageDigest::sha256(b"a message");
 ///
 /// // Sign a message locally
-/// let (sign, msg) = SignManual::new(data.clone(), completed_offline_stage)?;
+/// let (sign, msg) = SignManual::new(digest, completed_offline_stage)?;
 /// // Broadcast local partial signature
 /// broadcast(msg)?;
-/// // Collect partial signatures from other parties
-/// let sigs: Vec<PartialSignature> = wait_messages()?;
+/// // Collect partial signatures from other parties along with indexes of their senders
+/// let sigs: Vec<(u16, PartialSignature)> = wait_messages()?;
 /// // Complete signing
 /// let signature = sign.complete(&sigs)?;
 /// // Verify that signature matches joint public key
-/// assert!(verify(&signature, completed_offline_stage.public_key(), &data).is_ok());
+/// assert!(verify(&signature, completed_offline_stage.public_key(), &digest.to_bigint()).is_ok());
 /// # Ok(())
 /// # }
 /// ```
@@ -627,22 +886,45 @@ pub struct SignManual {
 }
 
 impl SignManual {
+    /// Signs message `digest` locally, returns partial signature to be broadcasted
+    ///
+    /// Message must be hashed by caller, see [MessageDigest] constructors.
     pub fn new(
-        message: BigInt,
+        digest: MessageDigest,
         completed_offline_stage: CompletedOfflineStage,
     ) -> Result<(Self, PartialSignature), SignError> {
-        Round7::new(&message, completed_offline_stage)
+        Round7::new(&digest.to_bigint(), completed_offline_stage)
             .map(|(state, m)| (Self { state }, m))
             .map_err(SignError::LocalSigning)
     }
 
     /// `sigs` must not include partial signature produced by local party (only partial signatures produced
     /// by other parties)
-    pub fn complete(self, sigs: &[PartialSignature]) -> Result<SignatureRecid, SignError> {
+    ///
+    /// Every partial signature comes with index of the party which produced it (index in the
+    /// offline stage), each of other parties must appear exactly once. Every partial signature
+    /// is checked before aggregation, if some are invalid, [ProceedError::IdentifiedAbort] is
+    /// returned listing parties that sent them.
+    pub fn complete(self, sigs: &[(u16, PartialSignature)]) -> Result<SignatureRecid, SignError> {
         self.state
             .proceed_manual(sigs)
             .map_err(SignError::CompleteSigning)
     }
+
+    /// Checks partial signature received from party `sender` (index in the offline stage)
+    ///
+    /// Allows rejecting invalid partial signatures as soon as they arrive.
+    pub fn verify_partial_signature(
+        &self,
+        sender: u16,
+        sig: &PartialSignature,
+    ) -> Result<(), SignError> {
+        if self.state.verify_partial(sender, sig) {
+            Ok(())
+        } else {
+            Err(SignError::InvalidPartialSignature { sender })
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -651,20 +933,24 @@ pub enum SignError {
     LocalSigning(rounds::Error),
     #[error("couldn't complete signing: {0}")]
     CompleteSigning(rounds::Error),
+    #[error("party {sender} sent invalid partial signature")]
+    InvalidPartialSignature { sender: u16 },
 }
 
 #[cfg(test)]
-mod test {
-    use curv::arithmetic::Converter;
-    use curv::cryptographic_primitives::hashing::{Digest, DigestExt};
+pub mod test {
+    use curv::elliptic::curves::Scalar;
+    use curv::BigInt;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
     use round_based::dev::Simulation;
-    use sha2::Sha256;
 
     use super::*;
+    use crate::utilities::signature::SignatureEncoding;
     use gg20::party_i::verify;
     use gg20::state_machine::keygen::test::simulate_keygen;
 
-    fn simulate_offline_stage(
+    pub fn simulate_offline_stage(
         local_keys: Vec<LocalKey<Secp256k1>>,
         s_l: &[u16],
     ) -> Vec<CompletedOfflineStage> {
@@ -690,33 +976,31 @@ mod test {
         stages
     }
 
-    fn simulate_signing(offline: Vec<CompletedOfflineStage>, message: &[u8]) {
-        let message = Sha256::new()
-            .chain_bigint(&BigInt::from_bytes(message))
-            .result_bigint();
+    pub fn simulate_signing(offline: Vec<CompletedOfflineStage>, message: &[u8]) {
+        let digest = MessageDigest::sha256(message);
         let pk = offline[0].public_key().clone();
 
         let parties = offline
             .iter()
-            .map(|o| SignManual::new(message.clone(), o.clone()))
+            .map(|o| SignManual::new(digest, o.clone()))
             .collect::<Result<Vec<_>, _>>()
             .unwrap();
         let (parties, local_sigs): (Vec<_>, Vec<_>) = parties.into_iter().unzip();
-        // parties.remove(0).complete(&local_sigs[1..]).unwrap();
         let local_sigs_except = |i: usize| {
-            let mut v = vec![];
-            v.extend_from_slice(&local_sigs[..i]);
-            if i + 1 < local_sigs.len() {
-                v.extend_from_slice(&local_sigs[i + 1..]);
-            }
-            v
+            let mut sigs: Vec<(u16, _)> = (1..)
+                .zip(local_sigs.iter().cloned())
+                .filter(|(j, _)| usize::from(*j) != i + 1)
+                .collect();
+            // Pass partial signatures in reverse order: they're matched by sender index
+            sigs.reverse();
+            sigs
         };
 
-        assert!(parties
-            .into_iter()
-            .enumerate()
-            .map(|(i, p)| p.complete(&local_sigs_except(i)).unwrap())
-            .all(|signature| verify(&signature, &pk, &message).is_ok()));
+        for (i, p) in parties.into_iter().enumerate() {
+            let signature = p.complete(&local_sigs_except(i)).unwrap();
+            assert!(verify(&signature, &pk, &digest.to_bigint()).is_ok());
+            assert_eq!(signature.recover_public_key(&digest).unwrap(), pk);
+        }
     }
 
     #[test]
@@ -761,4 +1045,245 @@ mod test {
         let offline_stage = simulate_offline_stage(local_keys, &[1, 2, 3]);
         simulate_signing(offline_stage, b"ZenGo")
     }
+
+    #[test]
+    fn seeded_offline_stage_produces_same_signature() {
+        let local_keys = simulate_keygen(1, 2);
+        let digest = MessageDigest::from_bigint(&BigInt::from(42)).unwrap();
+
+        let sign_with_seed = |seed: u64| {
+            let mut simulation = Simulation::new();
+            for i in 1..=2 {
+                let rng = StdRng::seed_from_u64(seed + u64::from(i));
+                simulation.add_party(
+                    OfflineStage::with_rng(
+                        i,
+                        vec![1, 2],
+                        local_keys[usize::from(i - 1)].clone(),
+                        rng,
+                    )
+                    .unwrap(),
+                );
+            }
+            let offline = simulation.run().unwrap();
+            let (parties, sigs): (Vec<_>, Vec<_>) = offline
+                .into_iter()
+                .map(|o| SignManual::new(digest, o).unwrap())
+                .unzip();
+            let sigs: Vec<_> = (2..).zip(sigs.into_iter().skip(1)).collect();
+            parties.into_iter().next().unwrap().complete(&sigs).unwrap()
+        };
+
+        let sig1 = sign_with_seed(1);
+        let sig2 = sign_with_seed(1);
+        assert!(verify(&sig1, &local_keys[0].public_key(), &digest.to_bigint()).is_ok());
+        assert_eq!((&sig1.r, &sig1.s), (&sig2.r, &sig2.s));
+
+        let sig3 = sign_with_seed(2);
+        assert_ne!(sig1.r, sig3.r);
+    }
+
+    #[test]
+    fn round_timeout_blames_silent_parties() {
+        let local_keys = simulate_keygen(1, 3);
+        let s_l = vec![1, 2, 3];
+        let mut parties = (1..=3)
+            .map(|i| OfflineStage::new(i, s_l.clone(), local_keys[usize::from(i - 1)].clone()))
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        parties[0].set_round_timeout(Some(Duration::from_secs(10)));
+
+        // party 3 stays silent
+        parties[0].proceed().unwrap();
+        parties[1].proceed().unwrap();
+        let msgs = parties[1].message_queue().drain(..).collect::<Vec<_>>();
+        for msg in msgs {
+            if msg.receiver.is_none() || msg.receiver == Some(1) {
+                parties[0].handle_incoming(msg).unwrap();
+            }
+        }
+
+        assert_eq!(parties[0].round_timeout(), Some(Duration::from_secs(10)));
+        assert!(matches!(
+            parties[0].round_timeout_reached(),
+            Error::RoundTimeout { round: 1, parties } if parties == [3]
+        ));
+    }
+
+    #[test]
+    fn offline_stage_with_identifiable_abort_enabled() {
+        let local_keys = simulate_keygen(1, 3);
+        let s_l = vec![1, 3];
+
+        let mut simulation = Simulation::new();
+        for (i, &keygen_i) in (1..).zip(&s_l) {
+            let mut party = OfflineStage::new(
+                i,
+                s_l.clone(),
+                local_keys[usize::from(keygen_i - 1)].clone(),
+            )
+            .unwrap();
+            party.set_identifiable_abort(true);
+            simulation.add_party(party);
+        }
+        let offline_stage = simulation.run().unwrap();
+        simulate_signing(offline_stage, b"ZenGo");
+    }
+
+    #[test]
+    fn invalid_partial_signature_is_blamed() {
+        let local_keys = simulate_keygen(2, 3);
+        let offline_stage = simulate_offline_stage(local_keys, &[1, 2, 3]);
+        let digest = MessageDigest::from_bigint(&BigInt::from(1)).unwrap();
+
+        let (parties, mut sigs): (Vec<_>, Vec<_>) = offline_stage
+            .iter()
+            .map(|o| SignManual::new(digest, o.clone()).unwrap())
+            .unzip();
+        // party 3 signs another message
+        let another_digest = MessageDigest::from_bigint(&BigInt::from(2)).unwrap();
+        sigs[2] = SignManual::new(another_digest, offline_stage[2].clone())
+            .unwrap()
+            .1;
+
+        assert!(parties[0].verify_partial_signature(2, &sigs[1]).is_ok());
+        assert!(matches!(
+            parties[0].verify_partial_signature(3, &sigs[2]),
+            Err(SignError::InvalidPartialSignature { sender: 3 })
+        ));
+        assert!(parties[0].verify_partial_signature(4, &sigs[1]).is_err());
+
+        let party = parties.into_iter().next().unwrap();
+        let duplicated = [(2, sigs[1].clone()), (2, sigs[1].clone())];
+        assert!(matches!(
+            party.clone().complete(&duplicated),
+            Err(SignError::CompleteSigning(
+                ProceedError::Round7UnexpectedSender { sender: 2 }
+            ))
+        ));
+
+        let result = party.complete(&[(3, sigs[2].clone()), (2, sigs[1].clone())]);
+        assert!(matches!(
+            result,
+            Err(SignError::CompleteSigning(ProceedError::IdentifiedAbort { round: 7, cheaters }))
+                if cheaters == [3]
+        ));
+    }
+
+    /// Runs the offline stage with identifiable abort enabled, party `cheater` shifts its
+    /// `delta_i` and `sigma_i` right after computing them. Returns the error each party ends with.
+    fn run_offline_stage_with_cheater(
+        local_keys: Vec<LocalKey<Secp256k1>>,
+        cheater: u16,
+        delta_shift: Scalar<Secp256k1>,
+        sigma_shift: Scalar<Secp256k1>,
+    ) -> Vec<Error> {
+        let n = u16::try_from(local_keys.len()).unwrap();
+        let s_l = (1..=n).collect::<Vec<_>>();
+        let mut parties = (1..=n)
+            .map(|i| {
+                let mut party =
+                    OfflineStage::new(i, s_l.clone(), local_keys[usize::from(i - 1)].clone())?;
+                party.set_identifiable_abort(true);
+                Ok(party)
+            })
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        let mut errors: Vec<Option<Error>> = (0..n).map(|_| None).collect();
+
+        // Round 3 messages addressed to the cheater are held back until it has cheated, otherwise
+        // it might complete round 3 honestly in the same `proceed` call
+        let mut cheated = false;
+        let mut held_back = vec![];
+
+        loop {
+            let mut progress = false;
+            for i in 0..usize::from(n) {
+                if errors[i].is_some() {
+                    continue;
+                }
+                if parties[i].wants_to_proceed() {
+                    if let Err(err) = parties[i].proceed() {
+                        errors[i] = Some(err);
+                        continue;
+                    }
+                    progress = true;
+                }
+                if i + 1 == usize::from(cheater) && !cheated {
+                    if let OfflineR::R3(round) = &mut parties[i].round {
+                        let m3 = round.cheat(&delta_shift, &sigma_shift);
+                        for msg in parties[i].message_queue() {
+                            if let OfflineProtocolMessage(OfflineM::M3(_)) = msg.body {
+                                msg.body = OfflineProtocolMessage(OfflineM::M3(m3.clone()));
+                            }
+                        }
+                        cheated = true;
+                        for msg in held_back.drain(..) {
+                            if let Err(err) = parties[i].handle_incoming(msg) {
+                                errors[i] = Some(err);
+                            }
+                        }
+                    }
+                }
+
+                let msgs = parties[i].message_queue().drain(..).collect::<Vec<_>>();
+                for msg in msgs {
+                    progress = true;
+                    for j in 0..usize::from(n) {
+                        let receiver = u16::try_from(j + 1).unwrap();
+                        if j == i
+                            || errors[j].is_some()
+                            || matches!(msg.receiver, Some(r) if r != receiver)
+                        {
+                            continue;
+                        }
+                        if receiver == cheater
+                            && !cheated
+                            && matches!(msg.body, OfflineProtocolMessage(OfflineM::M3(_)))
+                        {
+                            held_back.push(msg.clone());
+                            continue;
+                        }
+                        if let Err(err) = parties[j].handle_incoming(msg.clone()) {
+                            errors[j] = Some(err);
+                        }
+                    }
+                }
+            }
+            if !progress {
+                break;
+            }
+        }
+
+        errors
+            .into_iter()
+            .map(|err| err.expect("every party must abort"))
+            .collect()
+    }
+
+    #[test]
+    fn party_broadcasting_wrong_delta_i_is_blamed() {
+        let local_keys = simulate_keygen(1, 3);
+        let errors = run_offline_stage_with_cheater(local_keys, 2, Scalar::from(1), Scalar::zero());
+        for err in errors {
+            assert!(matches!(
+                err,
+                Error::ProceedRound(ProceedError::IdentifiedAbort { round: 5, cheaters })
+                    if cheaters == [2]
+            ));
+        }
+    }
+
+    #[test]
+    fn party_broadcasting_wrong_s_i_is_blamed() {
+        let local_keys = simulate_keygen(1, 3);
+        let errors = run_offline_stage_with_cheater(local_keys, 3, Scalar::zero(), Scalar::from(1));
+        for err in errors {
+            assert!(matches!(
+                err,
+                Error::ProceedRound(ProceedError::IdentifiedAbort { round: 6, cheaters })
+                    if cheaters == [3]
+            ));
+        }
+    }
 }
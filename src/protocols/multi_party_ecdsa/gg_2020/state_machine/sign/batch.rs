@@ -0,0 +1,715 @@
+//! Offline stage producing many presignatures at once
+//!
+//! [BatchOfflineStage] carries out `batch_size` independent instances of [OfflineStage] in a
+//! single protocol execution: every message carries messages of all instances, so computing many
+//! presignatures takes the same 6 rounds of communication as computing a single one.
+//!
+//! Instances share nothing but the set of signers, every resulting [CompletedOfflineStage] is a
+//! separate presignature and must be used to sign exactly one message. If any instance fails, the
+//! whole batch is aborted. Identifiable abort is not supported, but parties who sent invalid proofs
+//! are still blamed via [Error::bad_parties].
+//!
+//! [OfflineStage]: super::OfflineStage
+
+use std::fmt;
+use std::iter;
+use std::mem::replace;
+use std::time::Duration;
+
+use curv::elliptic::curves::secp256_k1::Secp256k1;
+use rand::rngs::OsRng;
+use round_based::containers::{
+    self, push::Push, BroadcastMsgs, MessageStore, P2PMsgs, Store, StoreErr,
+};
+use round_based::{Msg, StateMachine};
+use serde::{Deserialize, Serialize};
+
+use super::rounds::*;
+use super::{validate_parties, Error, InternalError, Result};
+use crate::protocols::multi_party_ecdsa::gg_2020 as gg20;
+use crate::utilities::mta::MessageA;
+use crate::utilities::rng::CryptoRngCore;
+use crate::utilities::zk_pdl_with_slack::PDLwSlackProof;
+use gg20::party_i::{SignBroadcastPhase1, SignDecommitPhase1};
+use gg20::state_machine::keygen::LocalKey;
+use gg20::state_machine::wire::{self, Protocol, WireError};
+
+/// Offline stage of GG20 signing producing `batch_size` presignatures
+pub struct BatchOfflineStage {
+    round: BatchR,
+
+    msgs1: Option<Store<BroadcastMsgs<Vec<(MessageA, SignBroadcastPhase1)>>>>,
+    msgs2: Option<Store<P2PMsgs<Vec<(GammaI, WI)>>>>,
+    msgs3: Option<Store<BroadcastMsgs<Vec<(DeltaI, TI, TIProof)>>>>,
+    msgs4: Option<Store<BroadcastMsgs<Vec<SignDecommitPhase1>>>>,
+    msgs5: Option<Store<BroadcastMsgs<Vec<(RDash, Vec<PDLwSlackProof>)>>>>,
+    msgs6: Option<Store<BroadcastMsgs<Vec<(SI, HEGProof)>>>>,
+
+    msgs_queue: Vec<Msg<BatchOfflineProtocolMessage>>,
+
+    rng: Box<dyn CryptoRngCore + Send>,
+    round_timeout: Option<Duration>,
+    batch_size: usize,
+
+    party_i: u16,
+    party_n: u16,
+}
+
+impl BatchOfflineStage {
+    /// Construct a party of batched offline stage
+    ///
+    /// Takes the same arguments as [OfflineStage::new](super::OfflineStage::new), and number of
+    /// presignatures to produce `batch_size`. All parties must use the same `batch_size`.
+    pub fn new(
+        i: u16,
+        s_l: Vec<u16>,
+        local_key: LocalKey<Secp256k1>,
+        batch_size: usize,
+    ) -> Result<Self> {
+        Self::with_rng(i, s_l, local_key, batch_size, OsRng)
+    }
+
+    /// Construct a party of batched offline stage which takes randomness from `rng`
+    ///
+    /// See [OfflineStage::with_rng](super::OfflineStage::with_rng).
+    pub fn with_rng<Rng>(
+        i: u16,
+        s_l: Vec<u16>,
+        local_key: LocalKey<Secp256k1>,
+        batch_size: usize,
+        rng: Rng,
+    ) -> Result<Self>
+    where
+        Rng: CryptoRngCore + Send + 'static,
+    {
+        if batch_size == 0 {
+            return Err(Error::EmptyBatch);
+        }
+        let n = validate_parties(i, &s_l, &local_key)?;
+
+        let rounds = iter::repeat_with(|| Round0 {
+            i,
+            s_l: s_l.clone(),
+            local_key: local_key.clone(),
+        })
+        .take(batch_size)
+        .collect();
+
+        Ok(Self {
+            round: BatchR::R0(rounds),
+
+            msgs1: Some(containers::BroadcastMsgsStore::new(i, n)),
+            msgs2: Some(containers::P2PMsgsStore::new(i, n)),
+            msgs3: Some(containers::BroadcastMsgsStore::new(i, n)),
+            msgs4: Some(containers::BroadcastMsgsStore::new(i, n)),
+            msgs5: Some(containers::BroadcastMsgsStore::new(i, n)),
+            msgs6: Some(containers::BroadcastMsgsStore::new(i, n)),
+
+            msgs_queue: vec![],
+
+            rng: Box::new(rng),
+            round_timeout: None,
+            batch_size,
+
+            party_i: i,
+            party_n: n,
+        })
+    }
+
+    /// Sets how long every round may wait for messages from other parties
+    ///
+    /// See [OfflineStage::set_round_timeout](super::OfflineStage::set_round_timeout).
+    pub fn set_round_timeout(&mut self, timeout: Option<Duration>) {
+        self.round_timeout = timeout;
+    }
+
+    /// Number of presignatures produced by the protocol
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    fn proceed_round(&mut self, may_block: bool) -> Result<()> {
+        let store1_wants_more = self.msgs1.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+        let store2_wants_more = self.msgs2.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+        let store3_wants_more = self.msgs3.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+        let store4_wants_more = self.msgs4.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+        let store5_wants_more = self.msgs5.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+        let store6_wants_more = self.msgs6.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+
+        let (i, n, batch_size) = (self.party_i, self.party_n, self.batch_size);
+        let rng = &mut *self.rng;
+        let queue = &mut self.msgs_queue;
+
+        let next_state: BatchR;
+        let try_again: bool = match replace(&mut self.round, BatchR::Gone) {
+            BatchR::R0(rounds) if !rounds.iter().any(Round0::is_expensive) || may_block => {
+                let (rounds, msgs) =
+                    proceed_all(rounds, |round, output| round.proceed(&mut *rng, output))?;
+                enqueue(queue, msgs, BatchM::M1);
+                next_state = BatchR::R1(rounds);
+                true
+            }
+            s @ BatchR::R0(_) => {
+                next_state = s;
+                false
+            }
+            BatchR::R1(rounds)
+                if !store1_wants_more
+                    && (!rounds.iter().any(Round1::is_expensive) || may_block) =>
+            {
+                let store = self.msgs1.take().ok_or(InternalError::StoreGone)?;
+                let inputs = split_broadcast(i, n, batch_size, finish(store)?)?;
+                let (rounds, msgs) = proceed_all(rounds.into_iter().zip(inputs), |r, output| {
+                    r.0.proceed(r.1, &mut *rng, output)
+                })?;
+                enqueue(queue, msgs, BatchM::M2);
+                next_state = BatchR::R2(rounds);
+                true
+            }
+            s @ BatchR::R1(_) => {
+                next_state = s;
+                false
+            }
+            BatchR::R2(rounds)
+                if !store2_wants_more
+                    && (!rounds.iter().any(Round2::is_expensive) || may_block) =>
+            {
+                let store = self.msgs2.take().ok_or(InternalError::StoreGone)?;
+                let inputs = split_p2p(i, n, batch_size, finish(store)?)?;
+                let (rounds, msgs) = proceed_all(rounds.into_iter().zip(inputs), |r, output| {
+                    r.0.proceed(r.1, &mut *rng, output)
+                })?;
+                enqueue(queue, msgs, BatchM::M3);
+                next_state = BatchR::R3(rounds);
+                true
+            }
+            s @ BatchR::R2(_) => {
+                next_state = s;
+                false
+            }
+            BatchR::R3(rounds)
+                if !store3_wants_more
+                    && (!rounds.iter().any(Round3::is_expensive) || may_block) =>
+            {
+                let store = self.msgs3.take().ok_or(InternalError::StoreGone)?;
+                let inputs = split_broadcast(i, n, batch_size, finish(store)?)?;
+                let (rounds, msgs) = proceed_all(rounds.into_iter().zip(inputs), |r, output| {
+                    r.0.proceed(r.1, output)
+                })?;
+                enqueue(queue, msgs, BatchM::M4);
+                next_state = BatchR::R4(rounds);
+                true
+            }
+            s @ BatchR::R3(_) => {
+                next_state = s;
+                false
+            }
+            BatchR::R4(rounds)
+                if !store4_wants_more
+                    && (!rounds.iter().any(Round4::is_expensive) || may_block) =>
+            {
+                let store = self.msgs4.take().ok_or(InternalError::StoreGone)?;
+                let inputs = split_broadcast(i, n, batch_size, finish(store)?)?;
+                let (rounds, msgs) = proceed_all(rounds.into_iter().zip(inputs), |r, output| {
+                    r.0.proceed(r.1, &mut *rng, output)
+                })?;
+                enqueue(queue, msgs, BatchM::M5);
+                next_state = BatchR::R5(rounds);
+                false
+            }
+            s @ BatchR::R4(_) => {
+                next_state = s;
+                false
+            }
+            BatchR::R5(rounds)
+                if !store5_wants_more
+                    && (!rounds.iter().any(Round5::is_expensive) || may_block) =>
+            {
+                let store = self.msgs5.take().ok_or(InternalError::StoreGone)?;
+                let inputs = split_broadcast(i, n, batch_size, finish(store)?)?;
+                let (rounds, msgs) = proceed_all(rounds.into_iter().zip(inputs), |r, output| {
+                    r.0.proceed(r.1, false, output).map(Next::into_round)
+                })?;
+                enqueue(queue, msgs, BatchM::M6);
+                next_state = BatchR::R6(rounds.into_iter().collect::<Result<_>>()?);
+                false
+            }
+            s @ BatchR::R5(_) => {
+                next_state = s;
+                false
+            }
+            BatchR::R6(rounds)
+                if !store6_wants_more
+                    && (!rounds.iter().any(Round6::is_expensive) || may_block) =>
+            {
+                let store = self.msgs6.take().ok_or(InternalError::StoreGone)?;
+                let inputs = split_broadcast(i, n, batch_size, finish(store)?)?;
+                let (outputs, _) = proceed_all(
+                    rounds.into_iter().zip(inputs),
+                    |r, output: &mut InstanceQueue<Phase6Reveal>| {
+                        r.0.proceed(r.1, false, output).map(Next::into_round)
+                    },
+                )?;
+                next_state = BatchR::Finished(outputs.into_iter().collect::<Result<_>>()?);
+                false
+            }
+            s @ BatchR::R6(_) => {
+                next_state = s;
+                false
+            }
+            s @ BatchR::Finished(_) | s @ BatchR::Gone => {
+                next_state = s;
+                false
+            }
+        };
+
+        self.round = next_state;
+        if try_again {
+            self.proceed_round(may_block)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn push_batch<S, T>(
+        batch_size: usize,
+        store: Option<&mut S>,
+        msg: Msg<Vec<T>>,
+        current_round: u16,
+        msg_round: u16,
+    ) -> Result<()>
+    where
+        S: MessageStore<M = Vec<T>, Err = StoreErr>,
+    {
+        let store = store.ok_or(Error::ReceivedOutOfOrderMessage {
+            current_round,
+            msg_round,
+        })?;
+        if msg.body.len() != batch_size {
+            return Err(Error::InvalidBatchSize {
+                sender: msg.sender,
+                expected: batch_size,
+                received: msg.body.len(),
+            });
+        }
+        store.push_msg(msg).map_err(Error::HandleMessage)
+    }
+}
+
+impl StateMachine for BatchOfflineStage {
+    type MessageBody = BatchOfflineProtocolMessage;
+    type Err = Error;
+    type Output = Vec<CompletedOfflineStage>;
+
+    fn handle_incoming(&mut self, msg: Msg<Self::MessageBody>) -> Result<(), Self::Err> {
+        let current_round = self.current_round();
+        let batch_size = self.batch_size;
+        let (sender, receiver) = (msg.sender, msg.receiver);
+
+        match msg.body.0 {
+            BatchM::M1(body) => Self::push_batch(
+                batch_size,
+                self.msgs1.as_mut(),
+                Msg {
+                    sender,
+                    receiver,
+                    body,
+                },
+                current_round,
+                1,
+            )?,
+            BatchM::M2(body) => Self::push_batch(
+                batch_size,
+                self.msgs2.as_mut(),
+                Msg {
+                    sender,
+                    receiver,
+                    body,
+                },
+                current_round,
+                2,
+            )?,
+            BatchM::M3(body) => Self::push_batch(
+                batch_size,
+                self.msgs3.as_mut(),
+                Msg {
+                    sender,
+                    receiver,
+                    body,
+                },
+                current_round,
+                3,
+            )?,
+            BatchM::M4(body) => Self::push_batch(
+                batch_size,
+                self.msgs4.as_mut(),
+                Msg {
+                    sender,
+                    receiver,
+                    body,
+                },
+                current_round,
+                4,
+            )?,
+            BatchM::M5(body) => Self::push_batch(
+                batch_size,
+                self.msgs5.as_mut(),
+                Msg {
+                    sender,
+                    receiver,
+                    body,
+                },
+                current_round,
+                5,
+            )?,
+            BatchM::M6(body) => Self::push_batch(
+                batch_size,
+                self.msgs6.as_mut(),
+                Msg {
+                    sender,
+                    receiver,
+                    body,
+                },
+                current_round,
+                6,
+            )?,
+        }
+        self.proceed_round(false)
+    }
+
+    fn message_queue(&mut self) -> &mut Vec<Msg<Self::MessageBody>> {
+        &mut self.msgs_queue
+    }
+
+    fn wants_to_proceed(&self) -> bool {
+        let store1_wants_more = self.msgs1.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+        let store2_wants_more = self.msgs2.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+        let store3_wants_more = self.msgs3.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+        let store4_wants_more = self.msgs4.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+        let store5_wants_more = self.msgs5.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+        let store6_wants_more = self.msgs6.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+
+        match &self.round {
+            BatchR::R0(_) => true,
+            BatchR::R1(_) => !store1_wants_more,
+            BatchR::R2(_) => !store2_wants_more,
+            BatchR::R3(_) => !store3_wants_more,
+            BatchR::R4(_) => !store4_wants_more,
+            BatchR::R5(_) => !store5_wants_more,
+            BatchR::R6(_) => !store6_wants_more,
+            BatchR::Finished(_) | BatchR::Gone => false,
+        }
+    }
+
+    fn proceed(&mut self) -> Result<(), Self::Err> {
+        self.proceed_round(true)
+    }
+
+    fn round_timeout(&self) -> Option<Duration> {
+        match &self.round {
+            BatchR::R0(_) | BatchR::Finished(_) | BatchR::Gone => None,
+            _ => self.round_timeout,
+        }
+    }
+
+    fn round_timeout_reached(&mut self) -> Self::Err {
+        let (_, parties) = super::super::traits::RoundBlame::round_blame(self);
+        Error::RoundTimeout {
+            round: self.current_round(),
+            parties,
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(&self.round, BatchR::Finished(_))
+    }
+
+    fn pick_output(&mut self) -> Option<Result<Self::Output, Self::Err>> {
+        match self.round {
+            BatchR::Finished(_) => (),
+            BatchR::Gone => return Some(Err(Error::DoublePickOutput)),
+            _ => return None,
+        }
+
+        match replace(&mut self.round, BatchR::Gone) {
+            BatchR::Finished(result) => Some(Ok(result)),
+            _ => unreachable!("guaranteed by match expression above"),
+        }
+    }
+
+    fn current_round(&self) -> u16 {
+        match &self.round {
+            BatchR::R0(_) => 0,
+            BatchR::R1(_) => 1,
+            BatchR::R2(_) => 2,
+            BatchR::R3(_) => 3,
+            BatchR::R4(_) => 4,
+            BatchR::R5(_) => 5,
+            BatchR::R6(_) => 6,
+            BatchR::Finished(_) | BatchR::Gone => 7,
+        }
+    }
+
+    fn total_rounds(&self) -> Option<u16> {
+        Some(6)
+    }
+
+    fn party_ind(&self) -> u16 {
+        self.party_i
+    }
+
+    fn parties(&self) -> u16 {
+        self.party_n
+    }
+}
+
+impl super::super::traits::RoundBlame for BatchOfflineStage {
+    /// RoundBlame returns number of unwilling parties and a vector of their party indexes.
+    fn round_blame(&self) -> (u16, Vec<u16>) {
+        let store1_blame = self.msgs1.as_ref().map(|s| s.blame()).unwrap_or_default();
+        let store2_blame = self.msgs2.as_ref().map(|s| s.blame()).unwrap_or_default();
+        let store3_blame = self.msgs3.as_ref().map(|s| s.blame()).unwrap_or_default();
+        let store4_blame = self.msgs4.as_ref().map(|s| s.blame()).unwrap_or_default();
+        let store5_blame = self.msgs5.as_ref().map(|s| s.blame()).unwrap_or_default();
+        let store6_blame = self.msgs6.as_ref().map(|s| s.blame()).unwrap_or_default();
+
+        let default = (0, vec![]);
+        match &self.round {
+            BatchR::R0(_) => default,
+            BatchR::R1(_) => store1_blame,
+            BatchR::R2(_) => store2_blame,
+            BatchR::R3(_) => store3_blame,
+            BatchR::R4(_) => store4_blame,
+            BatchR::R5(_) => store5_blame,
+            BatchR::R6(_) => store6_blame,
+            BatchR::Finished(_) => store6_blame,
+            BatchR::Gone => default,
+        }
+    }
+}
+
+impl fmt::Debug for BatchOfflineStage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BatchOfflineStage")
+            .field("round", &self.current_round())
+            .field("batch_size", &self.batch_size)
+            .field("msgs_queue", &self.msgs_queue.len())
+            .finish()
+    }
+}
+
+enum BatchR {
+    R0(Vec<Round0>),
+    R1(Vec<Round1>),
+    R2(Vec<Round2>),
+    R3(Vec<Round3>),
+    R4(Vec<Round4>),
+    R5(Vec<Round5>),
+    R6(Vec<Round6>),
+    Finished(Vec<CompletedOfflineStage>),
+    Gone,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchOfflineProtocolMessage(BatchM);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+enum BatchM {
+    M1(Vec<(MessageA, SignBroadcastPhase1)>),
+    M2(Vec<(GammaI, WI)>),
+    M3(Vec<(DeltaI, TI, TIProof)>),
+    M4(Vec<SignDecommitPhase1>),
+    M5(Vec<(RDash, Vec<PDLwSlackProof>)>),
+    M6(Vec<(SI, HEGProof)>),
+}
+
+impl BatchOfflineProtocolMessage {
+    /// Encodes message into [binary wire format](super::super::wire)
+    pub fn to_bytes(&self) -> std::result::Result<Vec<u8>, WireError> {
+        match &self.0 {
+            BatchM::M1(m) => wire::encode(Protocol::BatchOfflineStage, 1, m),
+            BatchM::M2(m) => wire::encode(Protocol::BatchOfflineStage, 2, m),
+            BatchM::M3(m) => wire::encode(Protocol::BatchOfflineStage, 3, m),
+            BatchM::M4(m) => wire::encode(Protocol::BatchOfflineStage, 4, m),
+            BatchM::M5(m) => wire::encode(Protocol::BatchOfflineStage, 5, m),
+            BatchM::M6(m) => wire::encode(Protocol::BatchOfflineStage, 6, m),
+        }
+    }
+
+    /// Decodes message from [binary wire format](super::super::wire)
+    pub fn from_bytes(bytes: &[u8]) -> std::result::Result<Self, WireError> {
+        let (header, payload) = wire::decode_frame(Protocol::BatchOfflineStage, bytes)?;
+        let m = match header.round {
+            1 => BatchM::M1(wire::decode_payload(payload)?),
+            2 => BatchM::M2(wire::decode_payload(payload)?),
+            3 => BatchM::M3(wire::decode_payload(payload)?),
+            4 => BatchM::M4(wire::decode_payload(payload)?),
+            5 => BatchM::M5(wire::decode_payload(payload)?),
+            6 => BatchM::M6(wire::decode_payload(payload)?),
+            round => return Err(WireError::UnknownRound(round)),
+        };
+        Ok(BatchOfflineProtocolMessage(m))
+    }
+}
+
+/// Collects outgoing messages of a single instance
+///
+/// Identifiable abort is disabled in batched offline stage, so reveals are never sent.
+struct InstanceQueue<T>(Vec<Msg<T>>);
+
+impl<T> Push<Msg<T>> for InstanceQueue<T> {
+    fn push(&mut self, m: Msg<T>) {
+        self.0.push(m)
+    }
+}
+
+impl Push<Msg<Phase5Reveal>> for InstanceQueue<(SI, HEGProof)> {
+    fn push(&mut self, _m: Msg<Phase5Reveal>) {}
+}
+
+impl<R, B> Next<R, B> {
+    fn into_round(self) -> Result<R> {
+        match self {
+            Next::Round(round) => Ok(round),
+            Next::Blame(_) => Err(InternalError::UnexpectedBlame.into()),
+        }
+    }
+}
+
+/// Proceeds every instance and merges their outgoing messages into batches
+fn proceed_all<I, N, T, F>(instances: I, mut proceed: F) -> Result<(Vec<N>, Vec<Msg<Vec<T>>>)>
+where
+    I: IntoIterator,
+    F: FnMut(I::Item, &mut InstanceQueue<T>) -> std::result::Result<N, super::ProceedError>,
+{
+    let mut next = vec![];
+    let mut merged: Vec<Msg<Vec<T>>> = vec![];
+    for instance in instances {
+        let mut output = InstanceQueue(vec![]);
+        next.push(proceed(instance, &mut output).map_err(Error::ProceedRound)?);
+        // every instance sends messages to the same parties in the same order
+        for (j, msg) in output.0.into_iter().enumerate() {
+            match merged.get_mut(j) {
+                Some(batch) => batch.body.push(msg.body),
+                None => merged.push(Msg {
+                    sender: msg.sender,
+                    receiver: msg.receiver,
+                    body: vec![msg.body],
+                }),
+            }
+        }
+    }
+    Ok((next, merged))
+}
+
+fn enqueue<T>(
+    queue: &mut Vec<Msg<BatchOfflineProtocolMessage>>,
+    msgs: Vec<Msg<Vec<T>>>,
+    constructor: fn(Vec<T>) -> BatchM,
+) {
+    queue.extend(msgs.into_iter().map(|m| Msg {
+        sender: m.sender,
+        receiver: m.receiver,
+        body: BatchOfflineProtocolMessage(constructor(m.body)),
+    }))
+}
+
+fn finish<S: MessageStore<Err = StoreErr>>(store: S) -> Result<S::Output> {
+    store
+        .finish()
+        .map_err(|e| InternalError::RetrieveMessagesFromStore(e).into())
+}
+
+/// Splits batches received from other parties into inputs of every instance
+fn split_broadcast<T: 'static>(
+    i: u16,
+    n: u16,
+    batch_size: usize,
+    msgs: BroadcastMsgs<Vec<T>>,
+) -> Result<Vec<BroadcastMsgs<T>>> {
+    let stores = (0..batch_size)
+        .map(|_| containers::BroadcastMsgsStore::new(i, n))
+        .collect();
+    split(i, n, None, msgs.into_vec(), stores)
+}
+
+/// Splits batches received from other parties into inputs of every instance
+fn split_p2p<T: 'static>(
+    i: u16,
+    n: u16,
+    batch_size: usize,
+    msgs: P2PMsgs<Vec<T>>,
+) -> Result<Vec<P2PMsgs<T>>> {
+    let stores = (0..batch_size)
+        .map(|_| containers::P2PMsgsStore::new(i, n))
+        .collect();
+    split(i, n, Some(i), msgs.into_vec(), stores)
+}
+
+fn split<S, T>(
+    i: u16,
+    n: u16,
+    receiver: Option<u16>,
+    batches: Vec<Vec<T>>,
+    mut stores: Vec<S>,
+) -> Result<Vec<S::Output>>
+where
+    S: MessageStore<M = T, Err = StoreErr>,
+{
+    let senders = (1..=n).filter(|&j| j != i);
+    for (sender, batch) in senders.zip(batches) {
+        for (store, body) in stores.iter_mut().zip(batch) {
+            store
+                .push_msg(Msg {
+                    sender,
+                    receiver,
+                    body,
+                })
+                .map_err(InternalError::SplitBatch)?;
+        }
+    }
+    stores.into_iter().map(finish).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use round_based::dev::Simulation;
+
+    use super::*;
+    use gg20::state_machine::keygen::test::simulate_keygen;
+    use gg20::state_machine::sign::test::simulate_signing;
+
+    #[test]
+    fn batch_produces_independent_presignatures() {
+        let local_keys = simulate_keygen(1, 3);
+        let s_l = vec![1, 3];
+
+        let mut simulation = Simulation::new();
+        for (i, &keygen_i) in (1..).zip(&s_l) {
+            simulation.add_party(
+                BatchOfflineStage::new(
+                    i,
+                    s_l.clone(),
+                    local_keys[usize::from(keygen_i - 1)].clone(),
+                    3,
+                )
+                .unwrap(),
+            );
+        }
+        let mut batches = simulation.run().unwrap();
+        assert!(batches.iter().all(|batch| batch.len() == 3));
+
+        let mut ids = vec![];
+        while !batches[0].is_empty() {
+            let presignatures = batches
+                .iter_mut()
+                .map(|batch| batch.remove(0))
+                .collect::<Vec<_>>();
+            ids.push(presignatures[0].id());
+            simulate_signing(presignatures, b"ZenGo");
+        }
+        ids.sort_unstable_by_key(|id| id.0);
+        ids.dedup();
+        assert_eq!(ids.len(), 3);
+    }
+}
@@ -32,6 +32,8 @@ impl From<&super::OfflineStage> for OfflineStageProgress {
                 super::OfflineR::R4(_) => OfflineR::R4,
                 super::OfflineR::R5(_) => OfflineR::R5,
                 super::OfflineR::R6(_) => OfflineR::R6,
+                super::OfflineR::Blame5(_) => OfflineR::Blame5,
+                super::OfflineR::Blame6(_) => OfflineR::Blame6,
                 super::OfflineR::Finished(_) => OfflineR::Finished,
                 super::OfflineR::Gone => OfflineR::Gone,
             },
@@ -58,6 +60,8 @@ pub enum OfflineR {
     R4,
     R5,
     R6,
+    Blame5,
+    Blame6,
     Finished,
     Gone,
 }
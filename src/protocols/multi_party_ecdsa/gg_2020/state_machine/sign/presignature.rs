@@ -0,0 +1,241 @@
+//! Storing presignatures for later use
+//!
+//! [CompletedOfflineStage] (aka presignature) can be computed in advance and used to sign a message
+//! in a single round later. Presignature must never be used twice: two signatures produced with the
+//! same presignature reveal the secret key. [PresignatureStore] is an abstraction over storage
+//! which hands out every presignature exactly once.
+//!
+//! Store can only account for presignatures that go through it: [CompletedOfflineStage] is `Clone`
+//! and serializable, so any copy made outside of the store (a clone, an unencrypted or encrypted
+//! backup) bypasses it. Preventing reuse of such copies is the caller's responsibility: put the
+//! presignature into the store right after offline stage completes, keep no other copies, and sign
+//! only with presignatures obtained from [take](PresignatureStore::take).
+//!
+//! Presignatures contain secret values, so they should be encrypted at rest. Envelope layout:
+//!
+//! | field        | size                           |
+//! |--------------|--------------------------------|
+//! | magic        | 4 bytes, `b"GGPS"`             |
+//! | version      | 1 byte, currently `1`          |
+//! | id           | 32 bytes, see [PresignatureId] |
+//! | nonce        | 12 bytes                       |
+//! | ciphertext   | rest of envelope               |
+//!
+//! Header (everything before ciphertext) is authenticated by AES-256-GCM, see
+//! [envelope](crate::utilities::envelope).
+
+use std::collections::{HashSet, VecDeque};
+use std::convert::TryInto;
+
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use super::CompletedOfflineStage;
+use crate::utilities::envelope::{self, EnvelopeError, NONCE_LEN};
+
+const MAGIC: &[u8; 4] = b"GGPS";
+const VERSION: u8 = 1;
+const ID_LEN: usize = 32;
+const HEADER_LEN: usize = MAGIC.len() + 1 + ID_LEN + NONCE_LEN;
+
+/// Unique identifier of a presignature
+///
+/// Derived from randomness `R` shared by all parties of the offline stage and index of the party,
+/// so it's different for every presignature and doesn't reveal any secrets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PresignatureId(pub [u8; ID_LEN]);
+
+impl CompletedOfflineStage {
+    /// Returns unique identifier of the presignature
+    pub fn id(&self) -> PresignatureId {
+        let hash = Sha256::new()
+            .chain(b"GG20 presignature")
+            .chain(&*self.R().to_bytes(true))
+            .chain(self.party_index().to_be_bytes())
+            .finalize();
+        PresignatureId(hash.into())
+    }
+
+    /// Serializes and encrypts the presignature with 32 bytes `key`
+    pub fn to_encrypted_bytes(&self, key: &[u8; 32]) -> Result<Vec<u8>> {
+        let nonce = envelope::random_nonce();
+
+        let mut bytes = Vec::with_capacity(HEADER_LEN);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&self.id().0);
+        bytes.extend_from_slice(&nonce);
+
+        let ciphertext = envelope::seal(key, &nonce, &bytes, self)?;
+        bytes.extend(ciphertext);
+        Ok(bytes)
+    }
+
+    /// Decrypts and deserializes presignature encrypted by
+    /// [CompletedOfflineStage::to_encrypted_bytes]
+    pub fn from_encrypted_bytes(bytes: &[u8], key: &[u8; 32]) -> Result<Self> {
+        let id = PresignatureId::from_encrypted_bytes(bytes)?;
+        let (aad, ciphertext) = bytes.split_at(HEADER_LEN);
+        let nonce: [u8; NONCE_LEN] = aad[HEADER_LEN - NONCE_LEN..].try_into().unwrap();
+        let presignature: Self = envelope::open(key, &nonce, aad, ciphertext)?;
+
+        if presignature.id() != id {
+            return Err(PresignatureError::IdMismatch);
+        }
+        Ok(presignature)
+    }
+}
+
+impl PresignatureId {
+    /// Reads identifier of encrypted presignature without decrypting it
+    ///
+    /// Identifier is authenticated only when the whole envelope is decrypted.
+    pub fn from_encrypted_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < HEADER_LEN || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(PresignatureError::InvalidFormat);
+        }
+        let version = bytes[MAGIC.len()];
+        if version != VERSION {
+            return Err(PresignatureError::UnsupportedVersion(version));
+        }
+        let id_offset = MAGIC.len() + 1;
+        Ok(PresignatureId(
+            bytes[id_offset..id_offset + ID_LEN].try_into().unwrap(),
+        ))
+    }
+}
+
+/// Storage of presignatures which hands out every presignature at most once
+///
+/// Implementations must guarantee that presignature returned by [take](Self::take) is never
+/// returned again, and that presignature which has ever been stored can't be [put](Self::put) once
+/// more, even across restarts (e.g. by keeping identifiers of used presignatures in a database).
+///
+/// Store can't detect copies of a presignature that never went through it, see
+/// [module level docs](self).
+pub trait PresignatureStore {
+    type Error;
+
+    /// Stores a fresh presignature
+    ///
+    /// Returns error if presignature with the same [id](CompletedOfflineStage::id) has been stored
+    /// before.
+    fn put(&mut self, presignature: CompletedOfflineStage) -> std::result::Result<(), Self::Error>;
+
+    /// Removes a presignature from the store and returns it, or returns `None` if store is empty
+    fn take(&mut self) -> std::result::Result<Option<CompletedOfflineStage>, Self::Error>;
+
+    /// Number of presignatures available for signing
+    fn available(&self) -> usize;
+}
+
+/// [PresignatureStore] which keeps presignatures in memory
+///
+/// Remembers identifiers of every presignature it has ever seen, so it refuses reuse within the
+/// process lifetime only. Persistent stores should be implemented on top of a database.
+#[derive(Default)]
+pub struct InMemoryPresignatureStore {
+    presignatures: VecDeque<CompletedOfflineStage>,
+    seen: HashSet<PresignatureId>,
+}
+
+impl InMemoryPresignatureStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl PresignatureStore for InMemoryPresignatureStore {
+    type Error = PresignatureError;
+
+    fn put(&mut self, presignature: CompletedOfflineStage) -> Result<()> {
+        if !self.seen.insert(presignature.id()) {
+            return Err(PresignatureError::AlreadyStored);
+        }
+        self.presignatures.push_back(presignature);
+        Ok(())
+    }
+
+    fn take(&mut self) -> Result<Option<CompletedOfflineStage>> {
+        Ok(self.presignatures.pop_front())
+    }
+
+    fn available(&self) -> usize {
+        self.presignatures.len()
+    }
+}
+
+type Result<T> = std::result::Result<T, PresignatureError>;
+
+impl From<EnvelopeError> for PresignatureError {
+    fn from(err: EnvelopeError) -> Self {
+        match err {
+            EnvelopeError::Serialization(err) => PresignatureError::Serialization(err),
+            EnvelopeError::Encryption => PresignatureError::Encryption,
+            EnvelopeError::Decryption => PresignatureError::Decryption,
+        }
+    }
+}
+
+/// Error of storing presignature
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum PresignatureError {
+    /// Envelope is malformed
+    #[error("invalid envelope format")]
+    InvalidFormat,
+    /// Envelope has version which is not supported by this library
+    #[error("unsupported envelope version: {0}")]
+    UnsupportedVersion(u8),
+    /// Identifier in envelope header doesn't match encrypted presignature
+    #[error("presignature id doesn't match encrypted presignature")]
+    IdMismatch,
+    /// Serializing or deserializing presignature failed
+    #[error("serialization: {0}")]
+    Serialization(#[source] serde_json::Error),
+    /// AES-GCM encryption failed
+    #[error("encryption failed")]
+    Encryption,
+    /// Decryption failed: key is wrong or envelope was tampered with
+    #[error("decryption failed: wrong key or envelope was tampered with")]
+    Decryption,
+    /// Presignature has already been stored, storing it again would allow its reuse
+    #[error("presignature has already been stored")]
+    AlreadyStored,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::protocols::multi_party_ecdsa::gg_2020::state_machine::keygen::test::simulate_keygen;
+    use crate::protocols::multi_party_ecdsa::gg_2020::state_machine::sign::test::simulate_offline_stage;
+
+    #[test]
+    fn presignature_is_handed_out_once() {
+        let local_keys = simulate_keygen(1, 2);
+        let presignature = simulate_offline_stage(local_keys, &[1, 2]).remove(0);
+
+        let key = [7u8; 32];
+        let bytes = presignature.to_encrypted_bytes(&key).unwrap();
+        assert_eq!(
+            PresignatureId::from_encrypted_bytes(&bytes).unwrap(),
+            presignature.id()
+        );
+        let decrypted = CompletedOfflineStage::from_encrypted_bytes(&bytes, &key).unwrap();
+        assert_eq!(decrypted.id(), presignature.id());
+        assert!(matches!(
+            CompletedOfflineStage::from_encrypted_bytes(&bytes, &[8u8; 32]),
+            Err(PresignatureError::Decryption)
+        ));
+
+        let mut store = InMemoryPresignatureStore::new();
+        store.put(decrypted).unwrap();
+        assert_eq!(store.available(), 1);
+        assert_eq!(store.take().unwrap().unwrap().id(), presignature.id());
+        assert!(store.take().unwrap().is_none());
+        assert!(matches!(
+            store.put(presignature),
+            Err(PresignatureError::AlreadyStored)
+        ));
+    }
+}
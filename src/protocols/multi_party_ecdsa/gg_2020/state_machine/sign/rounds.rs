@@ -10,21 +10,24 @@ use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
 use curv::BigInt;
 use sha2::Sha256;
 
+use rand::{CryptoRng, RngCore};
 use round_based::containers::push::Push;
 use round_based::containers::{self, BroadcastMsgs, P2PMsgs, Store};
 use round_based::Msg;
 
 use crate::utilities::mta::{MessageA, MessageB};
+use crate::utilities::{parallel, rng as rng_utils};
 
 use crate::protocols::multi_party_ecdsa::gg_2020 as gg20;
 use crate::utilities::zk_pdl_with_slack::PDLwSlackProof;
 use curv::cryptographic_primitives::proofs::sigma_correct_homomorphic_elgamal_enc::HomoELGamalProof;
 use curv::cryptographic_primitives::proofs::sigma_valid_pedersen::PedersenProof;
+use gg20::blame::{GlobalStatePhase5, GlobalStatePhase6, LocalStatePhase5, LocalStatePhase6};
 use gg20::party_i::{
     LocalSignature, SignBroadcastPhase1, SignDecommitPhase1, SignKeys, SignatureRecid,
 };
 use gg20::state_machine::keygen::LocalKey;
-use gg20::ErrorType;
+use gg20::{ErrorKind, ErrorType};
 
 type Result<T, E = Error> = std::result::Result<T, E>;
 
@@ -47,6 +50,28 @@ pub struct RDash(Point<Secp256k1>);
 pub struct SI(pub Point<Secp256k1>);
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct HEGProof(pub HomoELGamalProof<Secp256k1, Sha256>);
+/// Secrets revealed by party in identifiable abort after failed check of `R_dash` sum, along with
+/// MtA messages it received
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Phase5Reveal {
+    local_state: LocalStatePhase5,
+    m_b_gamma: Vec<MessageB>,
+}
+/// Secrets revealed by party in identifiable abort after failed check of `S_i` sum, along with
+/// MtA messages it received
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Phase6Reveal {
+    local_state: LocalStatePhase6,
+    m_b_w: Vec<MessageB>,
+}
+
+/// Outcome of a round which can switch protocol to identifiable abort
+pub enum Next<R, B> {
+    /// All checks passed, protocol proceeds to the next round
+    Round(R),
+    /// Check failed, party revealed its secrets and waits for other parties to reveal theirs
+    Blame(B),
+}
 
 pub struct Round0 {
     /// Index of this party
@@ -65,11 +90,12 @@ pub struct Round0 {
 }
 
 impl Round0 {
-    pub fn proceed<O>(self, mut output: O) -> Result<Round1>
+    pub fn proceed<O, R>(self, rng: &mut R, mut output: O) -> Result<Round1>
     where
         O: Push<Msg<(MessageA, SignBroadcastPhase1)>>,
+        R: RngCore + CryptoRng + ?Sized,
     {
-        let sign_keys = SignKeys::create(
+        let sign_keys = SignKeys::create_with_rng(
             &self.local_key.keys_linear.x_i,
             &self.local_key.vss_scheme.clone(),
             usize::from(self.s_l[usize::from(self.i - 1)]) - 1,
@@ -78,11 +104,17 @@ impl Round0 {
                 .iter()
                 .map(|&i| usize::from(i) - 1)
                 .collect::<Vec<_>>(),
+            rng,
         );
-        let (bc1, decom1) = sign_keys.phase1_broadcast();
+        let (bc1, decom1) = sign_keys.phase1_broadcast_with_rng(rng);
 
         let party_ek = self.local_key.paillier_key_vec[usize::from(self.local_key.i - 1)].clone();
-        let m_a = MessageA::a(&sign_keys.k_i, &party_ek, &self.local_key.h1_h2_n_tilde_vec);
+        let m_a = MessageA::a_with_rng(
+            &sign_keys.k_i,
+            &party_ek,
+            &self.local_key.h1_h2_n_tilde_vec,
+            rng,
+        );
 
         output.push(Msg {
             sender: self.i,
@@ -119,24 +151,21 @@ pub struct Round1 {
 }
 
 impl Round1 {
-    pub fn proceed<O>(
+    pub fn proceed<O, R>(
         self,
         input: BroadcastMsgs<(MessageA, SignBroadcastPhase1)>,
+        rng: &mut R,
         mut output: O,
     ) -> Result<Round2>
     where
         O: Push<Msg<(GammaI, WI)>>,
+        R: RngCore + CryptoRng + ?Sized,
     {
         let (m_a_vec, bc_vec): (Vec<_>, Vec<_>) = input
             .into_vec_including_me((self.m_a.0.clone(), self.phase1_com.clone()))
             .into_iter()
             .unzip();
 
-        let mut m_b_gamma_vec = Vec::new();
-        let mut beta_vec = Vec::new();
-        let mut m_b_w_vec = Vec::new();
-        let mut ni_vec = Vec::new();
-
         let ttag = self.s_l.len();
         let l_s: Vec<_> = self
             .s_l
@@ -145,37 +174,50 @@ impl Round1 {
             .map(|i| usize::from(i) - 1)
             .collect();
         let i = usize::from(self.i - 1);
-        for j in 0..ttag - 1 {
-            let ind = if j < i { j } else { j + 1 };
+        let others = (0..ttag - 1).map(|j| if j < i { j } else { j + 1 });
+        let peers: Vec<_> = others.zip(rng_utils::fork(rng, ttag - 1)).collect();
 
-            let (m_b_gamma, beta_gamma, _beta_randomness, _beta_tag) = MessageB::b(
+        // MtA with every other party is independent from the others, so it's computed in parallel
+        // if `rayon` feature is enabled
+        let mta = parallel::map(peers, |(ind, mut rng)| {
+            let bad_range_proof = |_| {
+                Error::Round1(ErrorType {
+                    kind: ErrorKind::BadRangeProof,
+                    bad_actors: vec![ind],
+                })
+            };
+            let gamma = MessageB::b_with_rng(
                 &self.sign_keys.gamma_i,
                 &self.local_key.paillier_key_vec[l_s[ind]],
                 m_a_vec[ind].clone(),
                 &self.local_key.h1_h2_n_tilde_vec,
+                &mut rng,
             )
-            .map_err(|e| {
-                Error::Round1(ErrorType {
-                    error_type: e.to_string(),
-                    bad_actors: vec![],
-                })
-            })?;
-
-            let (m_b_w, beta_wi, _, _) = MessageB::b(
+            .map_err(bad_range_proof)?;
+            let w = MessageB::b_with_rng(
                 &self.sign_keys.w_i,
                 &self.local_key.paillier_key_vec[l_s[ind]],
                 m_a_vec[ind].clone(),
                 &self.local_key.h1_h2_n_tilde_vec,
+                &mut rng,
             )
-            .map_err(|e| {
-                Error::Round1(ErrorType {
-                    error_type: e.to_string(),
-                    bad_actors: vec![],
-                })
-            })?;
-
+            .map_err(bad_range_proof)?;
+            Ok((gamma, w))
+        })
+        .into_iter()
+        .collect::<Result<Vec<_>>>()?;
+
+        let mut m_b_gamma_vec = Vec::with_capacity(ttag - 1);
+        let mut beta_vec = Vec::with_capacity(ttag - 1);
+        let mut beta_randomness_vec = Vec::with_capacity(ttag - 1);
+        let mut beta_tag_vec = Vec::with_capacity(ttag - 1);
+        let mut m_b_w_vec = Vec::with_capacity(ttag - 1);
+        let mut ni_vec = Vec::with_capacity(ttag - 1);
+        for ((m_b_gamma, beta_gamma, beta_randomness, beta_tag), (m_b_w, beta_wi, _, _)) in mta {
             m_b_gamma_vec.push(m_b_gamma);
             beta_vec.push(beta_gamma);
+            beta_randomness_vec.push(beta_randomness);
+            beta_tag_vec.push(beta_tag);
             m_b_w_vec.push(m_b_w);
             ni_vec.push(beta_wi);
         }
@@ -198,6 +240,8 @@ impl Round1 {
             sign_keys: self.sign_keys,
             m_a: self.m_a,
             beta_vec,
+            beta_randomness_vec,
+            beta_tag_vec,
             ni_vec,
             bc_vec,
             m_a_vec,
@@ -224,6 +268,8 @@ pub struct Round2 {
     sign_keys: SignKeys,
     m_a: (MessageA, BigInt),
     beta_vec: Vec<Scalar<Secp256k1>>,
+    beta_randomness_vec: Vec<BigInt>,
+    beta_tag_vec: Vec<BigInt>,
     ni_vec: Vec<Scalar<Secp256k1>>,
     bc_vec: Vec<SignBroadcastPhase1>,
     m_a_vec: Vec<MessageA>,
@@ -231,9 +277,15 @@ pub struct Round2 {
 }
 
 impl Round2 {
-    pub fn proceed<O>(self, input_p2p: P2PMsgs<(GammaI, WI)>, mut output: O) -> Result<Round3>
+    pub fn proceed<O, R>(
+        self,
+        input_p2p: P2PMsgs<(GammaI, WI)>,
+        rng: &mut R,
+        mut output: O,
+    ) -> Result<Round3>
     where
         O: Push<Msg<(DeltaI, TI, TIProof)>>, // TODO: unify TI and TIProof
+        R: RngCore + CryptoRng + ?Sized,
     {
         let (m_b_gamma_s, m_b_w_s): (Vec<_>, Vec<_>) = input_p2p
             .into_vec()
@@ -241,9 +293,6 @@ impl Round2 {
             .map(|(gamma_i, w_i)| (gamma_i.0, w_i.0))
             .unzip();
 
-        let mut alpha_vec = Vec::new();
-        let mut miu_vec = Vec::new();
-
         let ttag = self.s_l.len();
         let index = usize::from(self.i) - 1;
         let l_s: Vec<_> = self
@@ -257,37 +306,46 @@ impl Round2 {
             &l_s[..],
             &self.local_key.vss_scheme,
         );
-        for j in 0..ttag - 1 {
-            let ind = if j < index { j } else { j + 1 };
-            let m_b = m_b_gamma_s[j].clone();
+        let others = (0..ttag - 1).map(|j| if j < index { j } else { j + 1 });
+        let peers: Vec<_> = others.zip(m_b_gamma_s.iter().zip(&m_b_w_s)).collect();
 
-            let alpha_ij_gamma = m_b
+        let alphas = parallel::map(peers, |(ind, (m_b_gamma, m_b_w))| {
+            let bad_range_proof = |_| {
+                Error::Round3(ErrorType {
+                    kind: ErrorKind::BadRangeProof,
+                    bad_actors: vec![ind],
+                })
+            };
+            let alpha_ij_gamma = m_b_gamma
                 .verify_proofs_get_alpha(&self.local_key.paillier_dk, &self.sign_keys.k_i)
-                .map_err(|e| {
-                    Error::Round3(ErrorType {
-                        error_type: e.to_string(),
-                        bad_actors: vec![],
-                    })
-                })?;
-            let m_b = m_b_w_s[j].clone();
-            let alpha_ij_wi = m_b
+                .map_err(bad_range_proof)?;
+            let alpha_ij_wi = m_b_w
                 .verify_proofs_get_alpha(&self.local_key.paillier_dk, &self.sign_keys.k_i)
-                .map_err(|e| {
-                    Error::Round3(ErrorType {
-                        error_type: e.to_string(),
-                        bad_actors: vec![],
-                    })
-                })?;
-            assert_eq!(m_b.b_proof.pk, g_w_vec[ind]); //TODO: return error
-
-            alpha_vec.push(alpha_ij_gamma.0);
-            miu_vec.push(alpha_ij_wi.0);
+                .map_err(bad_range_proof)?;
+            if m_b_w.b_proof.pk != g_w_vec[ind] {
+                return Err(Error::Round3(ErrorType {
+                    kind: ErrorKind::BadRangeProof,
+                    bad_actors: vec![ind],
+                }));
+            }
+            Ok((alpha_ij_gamma.0, alpha_ij_wi))
+        })
+        .into_iter()
+        .collect::<Result<Vec<_>>>()?;
+
+        let mut alpha_vec = Vec::with_capacity(ttag - 1);
+        let mut miu_vec = Vec::with_capacity(ttag - 1);
+        let mut miu_bigint_vec = Vec::with_capacity(ttag - 1);
+        for (alpha_ij_gamma, (miu, miu_bigint)) in alphas {
+            alpha_vec.push(alpha_ij_gamma);
+            miu_vec.push(miu);
+            miu_bigint_vec.push(miu_bigint);
         }
 
         let delta_i = self.sign_keys.phase2_delta_i(&alpha_vec, &self.beta_vec);
 
         let sigma_i = self.sign_keys.phase2_sigma_i(&miu_vec, &self.ni_vec);
-        let (t_i, l_i, t_i_proof) = SignKeys::phase3_compute_t_i(&sigma_i);
+        let (t_i, l_i, t_i_proof) = SignKeys::phase3_compute_t_i_with_rng(&sigma_i, rng);
         output.push(Msg {
             sender: self.i,
             receiver: None,
@@ -305,6 +363,10 @@ impl Round2 {
             sign_keys: self.sign_keys,
             m_a: self.m_a,
             mb_gamma_s: m_b_gamma_s,
+            mb_w_s: m_b_w_s,
+            beta_randomness_vec: self.beta_randomness_vec,
+            beta_tag_vec: self.beta_tag_vec,
+            miu_bigint_vec,
             bc_vec: self.bc_vec,
             m_a_vec: self.m_a_vec,
             delta_i,
@@ -332,6 +394,10 @@ pub struct Round3 {
     sign_keys: SignKeys,
     m_a: (MessageA, BigInt),
     mb_gamma_s: Vec<MessageB>,
+    mb_w_s: Vec<MessageB>,
+    beta_randomness_vec: Vec<BigInt>,
+    beta_tag_vec: Vec<BigInt>,
+    miu_bigint_vec: Vec<BigInt>,
     bc_vec: Vec<SignBroadcastPhase1>,
     m_a_vec: Vec<MessageA>,
     delta_i: Scalar<Secp256k1>,
@@ -368,11 +434,11 @@ impl Round3 {
 
         let delta_inv = SignKeys::phase3_reconstruct_delta(&delta_vec);
         let ttag = self.s_l.len();
-        for proof in t_proof_vec.iter().take(ttag) {
-            PedersenProof::verify(proof).map_err(|e| {
+        for (i, proof) in t_proof_vec.iter().take(ttag).enumerate() {
+            PedersenProof::verify(proof).map_err(|_| {
                 Error::Round3(ErrorType {
-                    error_type: e.to_string(),
-                    bad_actors: vec![],
+                    kind: ErrorKind::BadPedersenProof,
+                    bad_actors: vec![i],
                 })
             })?;
         }
@@ -390,6 +456,10 @@ impl Round3 {
             sign_keys: self.sign_keys,
             m_a: self.m_a,
             mb_gamma_s: self.mb_gamma_s,
+            mb_w_s: self.mb_w_s,
+            beta_randomness_vec: self.beta_randomness_vec,
+            beta_tag_vec: self.beta_tag_vec,
+            miu_bigint_vec: self.miu_bigint_vec,
             bc_vec: self.bc_vec,
             m_a_vec: self.m_a_vec,
             t_i: self.t_i,
@@ -397,6 +467,7 @@ impl Round3 {
             sigma_i: self.sigma_i,
             phase1_decom: self.phase1_decom,
             delta_inv,
+            delta_vec,
             t_vec,
         })
     }
@@ -410,6 +481,29 @@ impl Round3 {
     }
 }
 
+#[cfg(test)]
+impl Round3 {
+    /// Makes the party cheat: shifts its `delta_i` and `sigma_i` (recommitting to the latter) and
+    /// returns the round 3 message it should broadcast instead of the honest one
+    pub(super) fn cheat(
+        &mut self,
+        delta_shift: &Scalar<Secp256k1>,
+        sigma_shift: &Scalar<Secp256k1>,
+    ) -> (DeltaI, TI, TIProof) {
+        self.delta_i = &self.delta_i + delta_shift;
+        self.sigma_i = &self.sigma_i + sigma_shift;
+        let (t_i, l_i, t_i_proof) = SignKeys::phase3_compute_t_i(&self.sigma_i);
+        self.t_i = t_i;
+        self.l_i = l_i;
+        self.t_i_proof = t_i_proof;
+        (
+            DeltaI(self.delta_i.clone()),
+            TI(self.t_i.clone()),
+            TIProof(self.t_i_proof.clone()),
+        )
+    }
+}
+
 pub struct Round4 {
     i: u16,
     s_l: Vec<u16>,
@@ -417,26 +511,34 @@ pub struct Round4 {
     sign_keys: SignKeys,
     m_a: (MessageA, BigInt),
     mb_gamma_s: Vec<MessageB>,
+    mb_w_s: Vec<MessageB>,
+    beta_randomness_vec: Vec<BigInt>,
+    beta_tag_vec: Vec<BigInt>,
+    miu_bigint_vec: Vec<BigInt>,
     bc_vec: Vec<SignBroadcastPhase1>,
     m_a_vec: Vec<MessageA>,
     t_i: Point<Secp256k1>,
     l_i: Scalar<Secp256k1>,
     sigma_i: Scalar<Secp256k1>,
     delta_inv: Scalar<Secp256k1>,
+    delta_vec: Vec<Scalar<Secp256k1>>,
     t_vec: Vec<Point<Secp256k1>>,
     phase1_decom: SignDecommitPhase1,
 }
 
 impl Round4 {
-    pub fn proceed<O>(
+    pub fn proceed<O, R>(
         self,
         decommit_round1: BroadcastMsgs<SignDecommitPhase1>,
+        rng: &mut R,
         mut output: O,
     ) -> Result<Round5>
     where
         O: Push<Msg<(RDash, Vec<PDLwSlackProof>)>>,
+        R: RngCore + CryptoRng + ?Sized,
     {
         let decom_vec: Vec<_> = decommit_round1.into_vec_including_me(self.phase1_decom.clone());
+        let g_gamma_vec: Vec<_> = decom_vec.iter().map(|d| d.g_gamma_i.clone()).collect();
 
         let ttag = self.s_l.len();
         let b_proof_vec: Vec<_> = (0..ttag - 1).map(|i| &self.mb_gamma_s[i].b_proof).collect();
@@ -452,7 +554,6 @@ impl Round4 {
         let R_dash = &R * &self.sign_keys.k_i;
 
         // each party sends first message to all other parties
-        let mut phase5_proofs_vec = Vec::new();
         let l_s: Vec<_> = self
             .s_l
             .iter()
@@ -460,9 +561,10 @@ impl Round4 {
             .map(|i| usize::from(i) - 1)
             .collect();
         let index = usize::from(self.i - 1);
-        for j in 0..ttag - 1 {
-            let ind = if j < index { j } else { j + 1 };
-            let proof = LocalSignature::phase5_proof_pdl(
+        let others = (0..ttag - 1).map(|j| if j < index { j } else { j + 1 });
+        let peers: Vec<_> = others.zip(rng_utils::fork(rng, ttag - 1)).collect();
+        let phase5_proofs_vec = parallel::map(peers, |(ind, mut rng)| {
+            LocalSignature::phase5_proof_pdl_with_rng(
                 &R_dash,
                 &R,
                 &self.m_a.0.c,
@@ -470,10 +572,9 @@ impl Round4 {
                 &self.sign_keys.k_i,
                 &self.m_a.1,
                 &self.local_key.h1_h2_n_tilde_vec[l_s[ind]],
-            );
-
-            phase5_proofs_vec.push(proof);
-        }
+                &mut rng,
+            )
+        });
 
         output.push(Msg {
             sender: self.i,
@@ -487,7 +588,15 @@ impl Round4 {
             local_key: self.local_key,
             sign_keys: self.sign_keys,
             t_vec: self.t_vec,
+            m_a: self.m_a,
             m_a_vec: self.m_a_vec,
+            mb_gamma_s: self.mb_gamma_s,
+            mb_w_s: self.mb_w_s,
+            beta_randomness_vec: self.beta_randomness_vec,
+            beta_tag_vec: self.beta_tag_vec,
+            miu_bigint_vec: self.miu_bigint_vec,
+            delta_vec: self.delta_vec,
+            g_gamma_vec,
             t_i: self.t_i,
             l_i: self.l_i,
             sigma_i: self.sigma_i,
@@ -512,7 +621,15 @@ pub struct Round5 {
     local_key: LocalKey<Secp256k1>,
     sign_keys: SignKeys,
     t_vec: Vec<Point<Secp256k1>>,
+    m_a: (MessageA, BigInt),
     m_a_vec: Vec<MessageA>,
+    mb_gamma_s: Vec<MessageB>,
+    mb_w_s: Vec<MessageB>,
+    beta_randomness_vec: Vec<BigInt>,
+    beta_tag_vec: Vec<BigInt>,
+    miu_bigint_vec: Vec<BigInt>,
+    delta_vec: Vec<Scalar<Secp256k1>>,
+    g_gamma_vec: Vec<Point<Secp256k1>>,
     t_i: Point<Secp256k1>,
     l_i: Scalar<Secp256k1>,
     sigma_i: Scalar<Secp256k1>,
@@ -525,10 +642,11 @@ impl Round5 {
     pub fn proceed<O>(
         self,
         input: BroadcastMsgs<(RDash, Vec<PDLwSlackProof>)>,
+        identifiable_abort: bool,
         mut output: O,
-    ) -> Result<Round6>
+    ) -> Result<Next<Round6, Phase5Blame>>
     where
-        O: Push<Msg<(SI, HEGProof)>>,
+        O: Push<Msg<(SI, HEGProof)>> + Push<Msg<Phase5Reveal>>,
     {
         let (r_dash_vec, pdl_proof_mat_inc_me): (Vec<_>, Vec<_>) = input
             .into_vec_including_me((RDash(self.R_dash), self.phase5_proofs_vec))
@@ -543,25 +661,57 @@ impl Round5 {
             .map(|i| usize::from(i) - 1)
             .collect();
         let ttag = self.s_l.len();
-        for i in 0..ttag {
+        // `self` is partially moved, so the closure must only capture fields it uses
+        let (R, m_a_vec, local_key) = (&self.R, &self.m_a_vec, &self.local_key);
+        parallel::map((0..ttag).collect(), |i| {
             LocalSignature::phase5_verify_pdl(
                 &pdl_proof_mat_inc_me[i],
                 &r_dash_vec[i],
-                &self.R,
-                &self.m_a_vec[i].c,
-                &self.local_key.paillier_key_vec[l_s[i]],
-                &self.local_key.h1_h2_n_tilde_vec,
+                R,
+                &m_a_vec[i].c,
+                &local_key.paillier_key_vec[l_s[i]],
+                &local_key.h1_h2_n_tilde_vec,
                 &l_s,
                 i,
             )
-            .map_err(|e| Error::Round5(e))?;
+        })
+        .into_iter()
+        .collect::<std::result::Result<(), _>>()
+        .map_err(Error::Round5)?;
+        if LocalSignature::phase5_check_R_dash_sum(&r_dash_vec).is_err() {
+            if !identifiable_abort {
+                return Err(Error::Round5(ErrorType {
+                    kind: ErrorKind::BadRDashSum,
+                    bad_actors: vec![],
+                }));
+            }
+
+            let reveal = Phase5Reveal {
+                local_state: LocalStatePhase5 {
+                    k: self.sign_keys.k_i.clone(),
+                    k_randomness: self.m_a.1.clone(),
+                    gamma: self.sign_keys.gamma_i.clone(),
+                    beta_randomness: self.beta_randomness_vec,
+                    beta_tag: self.beta_tag_vec,
+                    encryption_key: self.local_key.paillier_key_vec[l_s[usize::from(self.i - 1)]]
+                        .clone(),
+                },
+                m_b_gamma: self.mb_gamma_s,
+            };
+            output.push(Msg {
+                sender: self.i,
+                receiver: None,
+                body: reveal.clone(),
+            });
+            return Ok(Next::Blame(Phase5Blame {
+                s_l: self.s_l,
+                local_key: self.local_key,
+                reveal,
+                delta_vec: self.delta_vec,
+                g_gamma_vec: self.g_gamma_vec,
+                m_a_vec: self.m_a_vec,
+            }));
         }
-        LocalSignature::phase5_check_R_dash_sum(&r_dash_vec).map_err(|e| {
-            Error::Round5(ErrorType {
-                error_type: e.to_string(),
-                bad_actors: vec![],
-            })
-        })?;
 
         let (S_i, homo_elgamal_proof) = LocalSignature::phase6_compute_S_i_and_proof_of_consistency(
             &self.R,
@@ -576,10 +726,14 @@ impl Round5 {
             body: (SI(S_i.clone()), HEGProof(homo_elgamal_proof.clone())),
         });
 
-        Ok(Round6 {
+        Ok(Next::Round(Round6 {
             S_i,
             homo_elgamal_proof,
             s_l: self.s_l,
+            k_randomness: self.m_a.1,
+            m_a_vec: self.m_a_vec,
+            mb_w_s: self.mb_w_s,
+            miu_bigint_vec: self.miu_bigint_vec,
             protocol_output: CompletedOfflineStage {
                 i: self.i,
                 local_key: self.local_key,
@@ -587,8 +741,10 @@ impl Round5 {
                 t_vec: self.t_vec,
                 R: self.R,
                 sigma_i: self.sigma_i,
+                R_dash_vec: r_dash_vec,
+                S_vec: vec![],
             },
-        })
+        }))
     }
 
     pub fn expects_messages(i: u16, n: u16) -> Store<BroadcastMsgs<(RDash, Vec<PDLwSlackProof>)>> {
@@ -600,21 +756,101 @@ impl Round5 {
     }
 }
 
+/// Identifiable abort round carried out instead of round 6 if `R_dash` sum check failed
+pub struct Phase5Blame {
+    s_l: Vec<u16>,
+    local_key: LocalKey<Secp256k1>,
+    reveal: Phase5Reveal,
+    delta_vec: Vec<Scalar<Secp256k1>>,
+    g_gamma_vec: Vec<Point<Secp256k1>>,
+    m_a_vec: Vec<MessageA>,
+}
+
+impl Phase5Blame {
+    /// Checks revealed secrets, always returns [Error::IdentifiedAbort]
+    pub fn proceed(self, input: BroadcastMsgs<Phase5Reveal>) -> Error {
+        let Phase5Blame {
+            s_l,
+            local_key,
+            reveal,
+            delta_vec,
+            g_gamma_vec,
+            m_a_vec,
+        } = self;
+        let reveals = input.into_vec_including_me(reveal);
+        let ttag = s_l.len();
+
+        let mut bad_actors: Vec<usize> = reveals
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| {
+                r.local_state.beta_randomness.len() != ttag - 1
+                    || r.local_state.beta_tag.len() != ttag - 1
+                    || r.m_b_gamma.len() != ttag - 1
+            })
+            .map(|(i, _)| i)
+            .collect();
+        if bad_actors.is_empty() {
+            let ek_vec: Vec<_> = s_l
+                .iter()
+                .map(|&i| local_key.paillier_key_vec[usize::from(i) - 1].clone())
+                .collect();
+            let (local_state_vec, m_b_mat): (Vec<_>, Vec<_>) = reveals
+                .into_iter()
+                .map(|r| (r.local_state, r.m_b_gamma))
+                .unzip();
+            let global_state = GlobalStatePhase5::local_state_to_global_state(
+                &ek_vec,
+                &delta_vec,
+                &g_gamma_vec,
+                &m_a_vec,
+                m_b_mat,
+                &local_state_vec,
+            );
+            if let Err(e) = global_state.phase5_blame() {
+                bad_actors = e.bad_actors;
+            }
+        }
+
+        Error::IdentifiedAbort {
+            round: 5,
+            cheaters: party_indexes(bad_actors),
+        }
+    }
+
+    pub fn expects_messages(i: u16, n: u16) -> Store<BroadcastMsgs<Phase5Reveal>> {
+        containers::BroadcastMsgsStore::new(i, n)
+    }
+
+    pub fn is_expensive(&self) -> bool {
+        true
+    }
+}
+
 pub struct Round6 {
     S_i: Point<Secp256k1>,
     homo_elgamal_proof: HomoELGamalProof<Secp256k1, Sha256>,
     s_l: Vec<u16>,
+    k_randomness: BigInt,
+    m_a_vec: Vec<MessageA>,
+    mb_w_s: Vec<MessageB>,
+    miu_bigint_vec: Vec<BigInt>,
     /// Round 6 guards protocol output until final checks are taken the place
     protocol_output: CompletedOfflineStage,
 }
 
 impl Round6 {
-    pub fn proceed(
-        self,
+    pub fn proceed<O>(
+        mut self,
         input: BroadcastMsgs<(SI, HEGProof)>,
-    ) -> Result<CompletedOfflineStage, Error> {
+        identifiable_abort: bool,
+        mut output: O,
+    ) -> Result<Next<CompletedOfflineStage, Phase6Blame>>
+    where
+        O: Push<Msg<Phase6Reveal>>,
+    {
         let (S_i_vec, hegp_vec): (Vec<_>, Vec<_>) = input
-            .into_vec_including_me((SI(self.S_i), HEGProof(self.homo_elgamal_proof)))
+            .into_vec_including_me((SI(self.S_i.clone()), HEGProof(self.homo_elgamal_proof)))
             .into_iter()
             .map(|(s_i, hegp_i)| (s_i.0, hegp_i.0))
             .unzip();
@@ -629,10 +865,55 @@ impl Round6 {
             &self.protocol_output.t_vec,
         )
         .map_err(Error::Round6VerifyProof)?;
-        LocalSignature::phase6_check_S_i_sum(&self.protocol_output.local_key.y_sum_s, &S_i_vec)
-            .map_err(Error::Round6CheckSig)?;
+        if let Err(e) =
+            LocalSignature::phase6_check_S_i_sum(&self.protocol_output.local_key.y_sum_s, &S_i_vec)
+        {
+            if !identifiable_abort {
+                return Err(Error::Round6CheckSig(e));
+            }
+
+            let stage = &self.protocol_output;
+            let miu_randomness = self
+                .mb_w_s
+                .iter()
+                .map(|m_b| {
+                    GlobalStatePhase6::extract_paillier_randomness(
+                        &m_b.c,
+                        &stage.local_key.paillier_dk,
+                    )
+                })
+                .collect();
+            let reveal = Phase6Reveal {
+                local_state: LocalStatePhase6 {
+                    k: stage.sign_keys.k_i.clone(),
+                    k_randomness: self.k_randomness,
+                    miu: self.miu_bigint_vec,
+                    miu_randomness,
+                    proof_of_eq_dlog: GlobalStatePhase6::ecddh_proof(
+                        &stage.sigma_i,
+                        &stage.R,
+                        &self.S_i,
+                    ),
+                },
+                m_b_w: self.mb_w_s,
+            };
+            output.push(Msg {
+                sender: stage.i,
+                receiver: None,
+                body: reveal.clone(),
+            });
+            return Ok(Next::Blame(Phase6Blame {
+                s_l: self.s_l,
+                local_key: self.protocol_output.local_key,
+                reveal,
+                R: self.protocol_output.R,
+                S_vec: S_i_vec,
+                m_a_vec: self.m_a_vec,
+            }));
+        }
 
-        Ok(self.protocol_output)
+        self.protocol_output.S_vec = S_i_vec;
+        Ok(Next::Round(self.protocol_output))
     }
 
     pub fn expects_messages(i: u16, n: u16) -> Store<BroadcastMsgs<(SI, HEGProof)>> {
@@ -644,7 +925,94 @@ impl Round6 {
     }
 }
 
-#[derive(Clone)]
+/// Identifiable abort round carried out instead of finishing the protocol if `S_i` sum check
+/// failed
+pub struct Phase6Blame {
+    s_l: Vec<u16>,
+    local_key: LocalKey<Secp256k1>,
+    reveal: Phase6Reveal,
+    R: Point<Secp256k1>,
+    S_vec: Vec<Point<Secp256k1>>,
+    m_a_vec: Vec<MessageA>,
+}
+
+impl Phase6Blame {
+    /// Checks revealed secrets, always returns [Error::IdentifiedAbort]
+    pub fn proceed(self, input: BroadcastMsgs<Phase6Reveal>) -> Error {
+        let Phase6Blame {
+            s_l,
+            local_key,
+            reveal,
+            R,
+            S_vec,
+            m_a_vec,
+        } = self;
+        let reveals = input.into_vec_including_me(reveal);
+        let ttag = s_l.len();
+
+        let mut bad_actors: Vec<usize> = reveals
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| {
+                r.local_state.miu.len() != ttag - 1
+                    || r.local_state.miu_randomness.len() != ttag - 1
+                    || r.m_b_w.len() != ttag - 1
+            })
+            .map(|(i, _)| i)
+            .collect();
+        if bad_actors.is_empty() {
+            let l_s: Vec<_> = s_l.iter().map(|&i| usize::from(i) - 1).collect();
+            let ek_vec: Vec<_> = l_s
+                .iter()
+                .map(|&i| local_key.paillier_key_vec[i].clone())
+                .collect();
+            let g_w_vec = SignKeys::g_w_vec(&local_key.pk_vec[..], &l_s[..], &local_key.vss_scheme);
+            let (local_state_vec, m_b_mat): (Vec<_>, Vec<_>) = reveals
+                .into_iter()
+                .map(|r| (r.local_state, r.m_b_w))
+                .unzip();
+            let global_state = GlobalStatePhase6::local_state_to_global_state(
+                &ek_vec,
+                &S_vec,
+                &g_w_vec,
+                &m_a_vec,
+                m_b_mat,
+                &local_state_vec,
+            );
+            if let Err(e) = global_state.phase6_blame(&R) {
+                bad_actors = e.bad_actors;
+            }
+        }
+
+        Error::IdentifiedAbort {
+            round: 6,
+            cheaters: party_indexes(bad_actors),
+        }
+    }
+
+    pub fn expects_messages(i: u16, n: u16) -> Store<BroadcastMsgs<Phase6Reveal>> {
+        containers::BroadcastMsgsStore::new(i, n)
+    }
+
+    pub fn is_expensive(&self) -> bool {
+        true
+    }
+}
+
+/// Converts positions of blamed parties (starting from `0`) into party indexes (starting from `1`)
+fn party_indexes(bad_actors: Vec<usize>) -> Vec<u16> {
+    bad_actors
+        .into_iter()
+        .map(|i| u16::try_from(i + 1).unwrap())
+        .collect()
+}
+
+/// Presignature: output of offline stage which lets party sign one message in a single round
+///
+/// It can be serialized to be used later (e.g. after restart), but it must be used for signing
+/// exactly once: signing two different messages with the same presignature leaks the secret key.
+/// See [presignature module](super::presignature) for encrypted storage that enforces that.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct CompletedOfflineStage {
     i: u16,
     local_key: LocalKey<Secp256k1>,
@@ -652,12 +1020,24 @@ pub struct CompletedOfflineStage {
     t_vec: Vec<Point<Secp256k1>>,
     R: Point<Secp256k1>,
     sigma_i: Scalar<Secp256k1>,
+    R_dash_vec: Vec<Point<Secp256k1>>,
+    S_vec: Vec<Point<Secp256k1>>,
 }
 
 impl CompletedOfflineStage {
     pub fn public_key(&self) -> &Point<Secp256k1> {
         &self.local_key.y_sum_s
     }
+
+    /// Index of this party in the offline stage
+    pub fn party_index(&self) -> u16 {
+        self.i
+    }
+
+    /// Randomness `R` shared by all parties of the offline stage
+    pub fn R(&self) -> &Point<Secp256k1> {
+        &self.R
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -665,7 +1045,10 @@ pub struct PartialSignature(Scalar<Secp256k1>);
 
 #[derive(Clone)]
 pub struct Round7 {
+    i: u16,
     local_signature: LocalSignature,
+    R_dash_vec: Vec<Point<Secp256k1>>,
+    S_vec: Vec<Point<Secp256k1>>,
 }
 
 impl Round7 {
@@ -681,11 +1064,65 @@ impl Round7 {
             &completed_offline_stage.local_key.y_sum_s,
         );
         let partial = PartialSignature(local_signature.s_i.clone());
-        Ok((Self { local_signature }, partial))
+        Ok((
+            Self {
+                i: completed_offline_stage.i,
+                local_signature,
+                R_dash_vec: completed_offline_stage.R_dash_vec,
+                S_vec: completed_offline_stage.S_vec,
+            },
+            partial,
+        ))
+    }
+
+    /// Checks that partial signature `sig` was honestly produced by party `j`
+    ///
+    /// Partial signature `s_j = m k_j + r sigma_j` is valid iff `s_j R = m R_dash_j + r S_j`, where
+    /// `R_dash_j = k_j R` and `S_j = sigma_j R` were published during offline stage.
+    pub fn verify_partial(&self, j: u16, sig: &PartialSignature) -> bool {
+        let j = match usize::from(j).checked_sub(1) {
+            Some(j) if j < self.S_vec.len() => j,
+            _ => return false,
+        };
+        let m = Scalar::<Secp256k1>::from(&self.local_signature.m);
+        &self.local_signature.R * &sig.0
+            == &self.R_dash_vec[j] * &m + &self.S_vec[j] * &self.local_signature.r
     }
 
-    pub fn proceed_manual(self, sigs: &[PartialSignature]) -> Result<SignatureRecid> {
-        let sigs = sigs.iter().map(|s_i| s_i.0.clone()).collect::<Vec<_>>();
+    /// Checks every partial signature and outputs signature
+    ///
+    /// `sigs` are pairs of index of the party which produced partial signature and the signature
+    /// itself. Every other party of the offline stage must appear exactly once, order doesn't matter.
+    pub fn proceed_manual(self, sigs: &[(u16, PartialSignature)]) -> Result<SignatureRecid> {
+        let expected = self.S_vec.len() - 1;
+        if sigs.len() != expected {
+            return Err(Error::Round7WrongNumberOfSignatures {
+                expected,
+                received: sigs.len(),
+            });
+        }
+        let mut seen = vec![false; self.S_vec.len()];
+        for &(sender, _) in sigs {
+            let j = match usize::from(sender).checked_sub(1) {
+                Some(j) if j < seen.len() && sender != self.i && !seen[j] => j,
+                _ => return Err(Error::Round7UnexpectedSender { sender }),
+            };
+            seen[j] = true;
+        }
+        let mut cheaters: Vec<u16> = sigs
+            .iter()
+            .filter(|(j, sig)| !self.verify_partial(*j, sig))
+            .map(|(j, _)| *j)
+            .collect();
+        if !cheaters.is_empty() {
+            cheaters.sort_unstable();
+            return Err(Error::IdentifiedAbort { round: 7, cheaters });
+        }
+
+        let sigs = sigs
+            .iter()
+            .map(|(_, s_j)| s_j.0.clone())
+            .collect::<Vec<_>>();
         self.local_signature
             .output_signature(&sigs)
             .map_err(Error::Round7)
@@ -710,6 +1147,46 @@ pub enum Error {
     Round6CheckSig(crate::Error),
     #[error("round 7: {0:?}")]
     Round7(crate::Error),
+    #[error("round 7: expected {expected} partial signatures, received {received}")]
+    Round7WrongNumberOfSignatures { expected: usize, received: usize },
+    /// Partial signature is attributed to a party which is not in the offline stage, to local
+    /// party, or to a party which already sent one
+    #[error("round 7: unexpected partial signature from party {sender}")]
+    Round7UnexpectedSender { sender: u16 },
+    /// Check at `round` failed, and revealed values show that `cheaters` misbehaved
+    ///
+    /// Parties are identified by their index in the offline stage (in range `[1; n]`). List might
+    /// be empty if no party could be blamed.
+    #[error("identifiable abort at round {round}, cheaters: {cheaters:?}")]
+    IdentifiedAbort { round: u16, cheaters: Vec<u16> },
+}
+
+impl Error {
+    /// Failed check and parties blamed for it, if error was caused by one
+    pub fn error_type(&self) -> Option<&ErrorType> {
+        match self {
+            Error::Round1(e)
+            | Error::Round2Stage4(e)
+            | Error::Round3(e)
+            | Error::Round5(e)
+            | Error::Round6VerifyProof(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// Parties blamed for the error
+    ///
+    /// Parties are identified by their index in the offline stage (in range `[1; n]`). List is
+    /// empty if no party could be blamed.
+    pub fn bad_parties(&self) -> Vec<u16> {
+        match self {
+            Error::IdentifiedAbort { cheaters, .. } => cheaters.clone(),
+            _ => self
+                .error_type()
+                .map(|e| party_indexes(e.bad_actors().to_vec()))
+                .unwrap_or_default(),
+        }
+    }
 }
 
 trait IteratorExt: Iterator {
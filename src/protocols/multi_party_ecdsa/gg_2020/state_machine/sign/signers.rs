@@ -0,0 +1,233 @@
+//! Choosing parties for the offline stage
+//!
+//! Offline stage needs exactly `t+1` parties out of `n` who participated in keygen. If any of them
+//! misbehaves or goes offline, offline stage fails and has to be started over with another set of
+//! parties. [SignerSelection] keeps track of parties that are still trusted, picks signers `s_l`
+//! for the next attempt and [runs](SignerSelection::run) the offline stage until either it succeeds
+//! or there are too few parties left to form a quorum.
+//!
+//! Party with keygen index `s_l[j]` must run the offline stage with index `i = j + 1`, as
+//! [OfflineStage::new](super::OfflineStage::new) expects.
+
+use std::future::Future;
+
+use curv::elliptic::curves::secp256_k1::Secp256k1;
+
+use super::Error;
+use crate::protocols::multi_party_ecdsa::gg_2020::state_machine::keygen::LocalKey;
+
+/// Error of an offline stage attempt that might blame some of the signers
+pub trait BlameSigners {
+    /// Signers blamed for the failure
+    ///
+    /// Signers are identified by their index in the offline stage (in range `[1; s_l.len()]`).
+    /// Returns empty list if no signer could be blamed.
+    fn blamed_signers(&self) -> Vec<u16>;
+}
+
+impl BlameSigners for Error {
+    fn blamed_signers(&self) -> Vec<u16> {
+        self.bad_parties()
+    }
+}
+
+/// Tracks parties able to sign and picks signers for the offline stage
+#[derive(Clone, Debug)]
+pub struct SignerSelection {
+    t: u16,
+    available: Vec<u16>,
+    excluded: Vec<u16>,
+}
+
+impl SignerSelection {
+    /// Constructs selection out of parties `available` for signing, identified by their keygen
+    /// index (in range `[1; n]`)
+    ///
+    /// Returns [Error::InvalidSl] if some of indexes are out of range.
+    pub fn new(available: &[u16], local_key: &LocalKey<Secp256k1>) -> Result<Self, Error> {
+        Self::with_params(available, local_key.t, local_key.n)
+    }
+
+    /// Same as [SignerSelection::new], but takes threshold `t` and number of parties `n`
+    /// explicitly
+    ///
+    /// Handy for coordinator which doesn't hold any key share.
+    pub fn with_params(available: &[u16], t: u16, n: u16) -> Result<Self, Error> {
+        if available.iter().any(|&i| i == 0 || i > n) {
+            return Err(Error::InvalidSl);
+        }
+        let mut available = available.to_vec();
+        available.sort_unstable();
+        available.dedup();
+        Ok(Self {
+            t,
+            available,
+            excluded: vec![],
+        })
+    }
+
+    /// Picks `t+1` signers out of parties that are not excluded
+    ///
+    /// Signers are identified by their keygen index, and ordered by it. Returns `None` if there
+    /// are not enough parties left.
+    pub fn pick(&self) -> Option<Vec<u16>> {
+        let quorum = usize::from(self.t) + 1;
+        if self.available.len() < quorum {
+            return None;
+        }
+        Some(self.available[..quorum].to_vec())
+    }
+
+    /// Excludes party with keygen index `i` from further attempts
+    pub fn exclude(&mut self, i: u16) {
+        if let Some(pos) = self.available.iter().position(|&j| j == i) {
+            self.available.remove(pos);
+            self.excluded.push(i);
+        }
+    }
+
+    /// Excludes parties blamed by `err` which occurred in offline stage carried out by signers
+    /// `s_l`
+    ///
+    /// Returns keygen indexes of excluded parties, it's empty if no party could be blamed.
+    pub fn exclude_blamed<E: BlameSigners>(&mut self, s_l: &[u16], err: &E) -> Vec<u16> {
+        let blamed = err
+            .blamed_signers()
+            .into_iter()
+            .filter_map(|j| s_l.get(usize::from(j).checked_sub(1)?).copied())
+            .collect::<Vec<_>>();
+        for &i in &blamed {
+            self.exclude(i);
+        }
+        blamed
+    }
+
+    /// Parties excluded so far, identified by their keygen index
+    pub fn excluded(&self) -> &[u16] {
+        &self.excluded
+    }
+
+    /// Carries out offline stage until it succeeds
+    ///
+    /// `offline_stage` is given signers `s_l` picked for the attempt, it's meant to make these
+    /// parties run [OfflineStage](super::OfflineStage) and collect the result. If attempt fails
+    /// and some signers are blamed (either they misbehaved, or didn't respond within
+    /// [round timeout](super::OfflineStage::set_round_timeout)), they're excluded and offline
+    /// stage is started over with another set of signers.
+    pub fn run<T, E, F>(&mut self, mut offline_stage: F) -> Result<T, RetryError<E>>
+    where
+        E: BlameSigners,
+        F: FnMut(&[u16]) -> Result<T, E>,
+    {
+        loop {
+            let s_l = self.pick_or_fail()?;
+            match offline_stage(&s_l) {
+                Ok(output) => return Ok(output),
+                Err(err) => self.handle_failure(&s_l, err)?,
+            }
+        }
+    }
+
+    /// Async version of [SignerSelection::run]
+    pub async fn run_async<T, E, F, Fut>(
+        &mut self,
+        mut offline_stage: F,
+    ) -> Result<T, RetryError<E>>
+    where
+        E: BlameSigners,
+        F: FnMut(Vec<u16>) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        loop {
+            let s_l = self.pick_or_fail()?;
+            match offline_stage(s_l.clone()).await {
+                Ok(output) => return Ok(output),
+                Err(err) => self.handle_failure(&s_l, err)?,
+            }
+        }
+    }
+
+    fn pick_or_fail<E>(&self) -> Result<Vec<u16>, RetryError<E>> {
+        self.pick().ok_or_else(|| RetryError::NotEnoughParties {
+            required: self.t + 1,
+            excluded: self.excluded.clone(),
+        })
+    }
+
+    fn handle_failure<E: BlameSigners>(
+        &mut self,
+        s_l: &[u16],
+        err: E,
+    ) -> Result<(), RetryError<E>> {
+        if self.exclude_blamed(s_l, &err).is_empty() {
+            Err(RetryError::Unattributable(err))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Error of carrying out offline stage with [SignerSelection]
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum RetryError<E> {
+    /// Too few parties left after excluding misbehaving ones
+    #[error("at least {required} parties are required, excluded parties: {excluded:?}")]
+    NotEnoughParties { required: u16, excluded: Vec<u16> },
+    /// Offline stage failed, but no party could be blamed, so retrying won't help
+    #[error("offline stage failed without blaming any party: {0}")]
+    Unattributable(#[source] E),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug)]
+    struct Blame(Vec<u16>);
+
+    impl BlameSigners for Blame {
+        fn blamed_signers(&self) -> Vec<u16> {
+            self.0.clone()
+        }
+    }
+
+    #[test]
+    fn misbehaving_parties_are_excluded_until_quorum_succeeds() {
+        let mut selection = SignerSelection::with_params(&[5, 1, 2, 4], 1, 5).unwrap();
+        let mut attempts = vec![];
+        let output = selection
+            .run(|s_l| {
+                attempts.push(s_l.to_vec());
+                match attempts.len() {
+                    1 => Err(Blame(vec![2])),
+                    2 => Err(Blame(vec![1])),
+                    _ => Ok(s_l.to_vec()),
+                }
+            })
+            .unwrap();
+
+        assert_eq!(attempts, vec![vec![1, 2], vec![1, 4], vec![4, 5]]);
+        assert_eq!(output, vec![4, 5]);
+        assert_eq!(selection.excluded(), &[2, 1]);
+    }
+
+    #[test]
+    fn retry_stops_when_quorum_is_impossible() {
+        let mut selection = SignerSelection::with_params(&[1, 2, 4, 5], 1, 5).unwrap();
+        let result = selection.run(|_| Err::<(), _>(Blame(vec![1])));
+        assert!(matches!(
+            result,
+            Err(RetryError::NotEnoughParties { required: 2, excluded }) if excluded == [1, 2, 4]
+        ));
+
+        let mut selection = SignerSelection::with_params(&[1, 2, 4, 5], 1, 5).unwrap();
+        let result = selection.run(|_| Err::<(), _>(Blame(vec![])));
+        assert!(matches!(result, Err(RetryError::Unattributable(_))));
+
+        assert!(matches!(
+            SignerSelection::with_params(&[1, 6], 1, 5),
+            Err(Error::InvalidSl)
+        ));
+    }
+}
@@ -0,0 +1,304 @@
+//! Compact binary wire format for protocol messages
+//!
+//! Every message is encoded into a frame (all integers are big-endian):
+//!
+//! | field          | size                      |
+//! |----------------|---------------------------|
+//! | magic          | 2 bytes, `b"G2"`          |
+//! | version        | 1 byte, currently `1`     |
+//! | protocol tag   | 1 byte, see [Protocol]    |
+//! | round          | 1 byte, starting from `1` |
+//! | payload length | 4 bytes                   |
+//! | payload        | `payload length` bytes    |
+//!
+//! Header has fixed size [HEADER_LEN], so relays can route messages by [WireHeader::parse]-ing
+//! first bytes of the frame without deserializing payload.
+//!
+//! Payload is the message of corresponding round encoded with fixed-size big-endian integers,
+//! length-prefixed sequences, big-endian bigints and compressed points.
+
+use std::convert::{TryFrom, TryInto};
+use std::marker::PhantomData;
+
+use bincode::Options;
+use serde::de::{DeserializeOwned, DeserializeSeed, Deserializer};
+use serde::Serialize;
+use thiserror::Error;
+
+mod structs_as_maps;
+
+use structs_as_maps::StructsAsMaps;
+
+const MAGIC: &[u8; 2] = b"G2";
+/// Current version of wire format
+pub const VERSION: u8 = 1;
+/// Size of frame header in bytes
+pub const HEADER_LEN: usize = 9;
+/// Maximum size of a payload in bytes, larger frames are rejected before decoding
+pub const MAX_MESSAGE_SIZE: u32 = 16 * 1024 * 1024;
+
+/// Protocol that message belongs to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Protocol {
+    /// [Keygen](super::keygen::Keygen) message, tag `1`, rounds `1..=4`
+    Keygen,
+    /// [OfflineStage](super::sign::OfflineStage) message, tag `2`, rounds `1..=6`, identifiable
+    /// abort rounds `7..=8`
+    OfflineStage,
+    /// [BatchOfflineStage](super::sign::BatchOfflineStage) message, tag `3`, rounds `1..=6`
+    BatchOfflineStage,
+}
+
+impl Protocol {
+    /// Tag of the protocol as it appears in frame header
+    pub fn tag(self) -> u8 {
+        match self {
+            Protocol::Keygen => 1,
+            Protocol::OfflineStage => 2,
+            Protocol::BatchOfflineStage => 3,
+        }
+    }
+
+    /// Parses protocol tag
+    pub fn from_tag(tag: u8) -> Result<Self> {
+        match tag {
+            1 => Ok(Protocol::Keygen),
+            2 => Ok(Protocol::OfflineStage),
+            3 => Ok(Protocol::BatchOfflineStage),
+            _ => Err(WireError::UnknownProtocol(tag)),
+        }
+    }
+}
+
+/// Header of a frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WireHeader {
+    pub version: u8,
+    pub protocol: Protocol,
+    pub round: u8,
+    pub payload_len: u32,
+}
+
+impl WireHeader {
+    /// Parses frame header
+    ///
+    /// Only first [HEADER_LEN] bytes of `bytes` are read.
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < HEADER_LEN {
+            return Err(WireError::TooShort);
+        }
+        if &bytes[..2] != MAGIC {
+            return Err(WireError::InvalidMagic);
+        }
+        let version = bytes[2];
+        if version != VERSION {
+            return Err(WireError::UnsupportedVersion(version));
+        }
+        Ok(WireHeader {
+            version,
+            protocol: Protocol::from_tag(bytes[3])?,
+            round: bytes[4],
+            payload_len: u32::from_be_bytes(bytes[5..HEADER_LEN].try_into().unwrap()),
+        })
+    }
+}
+
+fn options() -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_big_endian()
+        .with_fixint_encoding()
+        .reject_trailing_bytes()
+        .with_limit(u64::from(MAX_MESSAGE_SIZE))
+}
+
+pub(crate) fn encode<T: Serialize>(protocol: Protocol, round: u8, payload: &T) -> Result<Vec<u8>> {
+    let payload = options().serialize(payload).map_err(WireError::Payload)?;
+    let payload_len = u32::try_from(payload.len())
+        .ok()
+        .filter(|&len| len <= MAX_MESSAGE_SIZE)
+        .ok_or(WireError::TooLong)?;
+
+    let mut frame = Vec::with_capacity(HEADER_LEN + payload.len());
+    frame.extend_from_slice(MAGIC);
+    frame.push(VERSION);
+    frame.push(protocol.tag());
+    frame.push(round);
+    frame.extend_from_slice(&payload_len.to_be_bytes());
+    frame.extend_from_slice(&payload);
+    Ok(frame)
+}
+
+/// Parses frame header, checks that frame belongs to `protocol`, and returns header and payload
+pub(crate) fn decode_frame(protocol: Protocol, bytes: &[u8]) -> Result<(WireHeader, &[u8])> {
+    let header = WireHeader::parse(bytes)?;
+    if header.protocol != protocol {
+        return Err(WireError::ProtocolMismatch {
+            expected: protocol,
+            actual: header.protocol,
+        });
+    }
+    if header.payload_len > MAX_MESSAGE_SIZE {
+        return Err(WireError::TooLong);
+    }
+    let payload = &bytes[HEADER_LEN..];
+    if payload.len() != header.payload_len as usize {
+        return Err(WireError::PayloadLengthMismatch);
+    }
+    Ok((header, payload))
+}
+
+pub(crate) fn decode_payload<T: DeserializeOwned>(payload: &[u8]) -> Result<T> {
+    options()
+        .deserialize_seed(Payload(PhantomData), payload)
+        .map_err(WireError::Payload)
+}
+
+/// Deserializes `T` presenting structs as maps (see [structs_as_maps])
+struct Payload<T>(PhantomData<T>);
+
+impl<'de, T: DeserializeOwned> DeserializeSeed<'de> for Payload<T> {
+    type Value = T;
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<T, D::Error> {
+        T::deserialize(StructsAsMaps(deserializer))
+    }
+}
+
+type Result<T> = std::result::Result<T, WireError>;
+
+/// Error of encoding or decoding a frame
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum WireError {
+    /// Frame is shorter than its header
+    #[error("frame is too short")]
+    TooShort,
+    /// Payload is larger than [MAX_MESSAGE_SIZE]
+    #[error("payload is too long")]
+    TooLong,
+    /// Frame doesn't start with magic bytes
+    #[error("invalid magic bytes")]
+    InvalidMagic,
+    /// Frame has version which is not supported by this library
+    #[error("unsupported wire format version: {0}")]
+    UnsupportedVersion(u8),
+    /// Unknown protocol tag
+    #[error("unknown protocol tag: {0}")]
+    UnknownProtocol(u8),
+    /// Frame belongs to another protocol
+    #[error("expected message of {expected:?} protocol, got {actual:?}")]
+    ProtocolMismatch {
+        expected: Protocol,
+        actual: Protocol,
+    },
+    /// Protocol doesn't have such round
+    #[error("unknown round: {0}")]
+    UnknownRound(u8),
+    /// Length of payload doesn't match length in header
+    #[error("payload length doesn't match header")]
+    PayloadLengthMismatch,
+    /// Payload encoding or decoding failed
+    #[error("payload: {0}")]
+    Payload(#[source] bincode::Error),
+}
+
+#[cfg(test)]
+mod test {
+    use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+    use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
+
+    use super::*;
+    use crate::protocols::multi_party_ecdsa::gg_2020::state_machine::keygen::{
+        Keygen, ProtocolMessage,
+    };
+    use crate::protocols::multi_party_ecdsa::gg_2020::state_machine::sign::{
+        test::simulate_signing, OfflineProtocolMessage, OfflineStage,
+    };
+    use crate::protocols::multi_party_ecdsa::gg_2020::state_machine::test::run_with_transport;
+
+    #[test]
+    fn keygen_and_offline_stage_messages_roundtrip() {
+        let parties = (1..=3).map(|i| Keygen::new(i, 1, 3).unwrap()).collect();
+        let local_keys = run_with_transport(parties, |msg: &ProtocolMessage| {
+            let bytes = msg.to_bytes().unwrap();
+            let header = WireHeader::parse(&bytes).unwrap();
+            assert_eq!(header.protocol, Protocol::Keygen);
+            assert!((1..=4).contains(&header.round));
+            assert!(matches!(
+                OfflineProtocolMessage::from_bytes(&bytes),
+                Err(WireError::ProtocolMismatch { .. })
+            ));
+            // parties receive decoded message, so a field changed by decoding breaks keygen
+            ProtocolMessage::from_bytes(&bytes).unwrap()
+        });
+
+        let s_l = vec![1, 3];
+        let parties = (1..)
+            .zip(&s_l)
+            .map(|(i, &keygen_i)| {
+                let local_key = local_keys[usize::from(keygen_i - 1)].clone();
+                OfflineStage::new(i, s_l.clone(), local_key).unwrap()
+            })
+            .collect();
+        let offline_stage = run_with_transport(parties, |msg: &OfflineProtocolMessage| {
+            let bytes = msg.to_bytes().unwrap();
+            let header = WireHeader::parse(&bytes).unwrap();
+            assert_eq!(header.protocol, Protocol::OfflineStage);
+            assert!((1..=6).contains(&header.round));
+            assert_eq!(header.payload_len as usize, bytes.len() - HEADER_LEN);
+            OfflineProtocolMessage::from_bytes(&bytes).unwrap()
+        });
+        simulate_signing(offline_stage, b"ZenGo");
+    }
+
+    #[test]
+    fn payload_with_points_and_scalars_roundtrip() {
+        let secret = Scalar::<Secp256k1>::random();
+        let (vss, shares) = VerifiableSS::<Secp256k1>::share(1, 3, &secret);
+        let payload = (vss, shares[0].clone(), Some(Point::generator() * &secret));
+
+        let bytes = encode(Protocol::Keygen, 3, &payload).unwrap();
+        let (_, decoded) = decode_frame(Protocol::Keygen, &bytes).unwrap();
+        let (decoded_vss, decoded_share, decoded_point): (
+            VerifiableSS<Secp256k1>,
+            Scalar<Secp256k1>,
+            Option<Point<Secp256k1>>,
+        ) = decode_payload(decoded).unwrap();
+        assert_eq!(decoded_vss, payload.0);
+        assert_eq!(decoded_share, payload.1);
+        assert_eq!(decoded_point, payload.2);
+
+        // trailing bytes are still rejected
+        let mut payload = options().serialize(&payload).unwrap();
+        payload.push(0);
+        assert!(decode_payload::<(
+            VerifiableSS<Secp256k1>,
+            Scalar<Secp256k1>,
+            Option<Point<Secp256k1>>
+        )>(&payload)
+        .is_err());
+    }
+
+    #[test]
+    fn oversized_messages_are_rejected() {
+        // length prefix claims a huge string: reads from a slice are bounded by its length, so
+        // decoding fails without allocating
+        let payload = u64::MAX.to_be_bytes();
+        assert!(matches!(
+            decode_payload::<String>(&payload),
+            Err(WireError::Payload(err)) if matches!(*err, bincode::ErrorKind::Io(_))
+        ));
+
+        let mut frame = MAGIC.to_vec();
+        frame.extend_from_slice(&[VERSION, Protocol::Keygen.tag(), 1]);
+        frame.extend_from_slice(&(MAX_MESSAGE_SIZE + 1).to_be_bytes());
+        assert!(matches!(
+            decode_frame(Protocol::Keygen, &frame),
+            Err(WireError::TooLong)
+        ));
+    }
+}
@@ -23,10 +23,11 @@ use crate::protocols::multi_party_ecdsa::gg_2020::party_i::{
     KeyGenBroadcastMessage1, KeyGenDecommitMessage1, Keys, LocalSignature, Parameters, SharedKeys,
     SignKeys,
 };
+use crate::utilities::backup;
 use crate::utilities::mta::{MessageA, MessageB};
 use curv::arithmetic::traits::Converter;
 
-use crate::protocols::multi_party_ecdsa::gg_2020::ErrorType;
+use crate::protocols::multi_party_ecdsa::gg_2020::{ErrorKind, ErrorType};
 use crate::utilities::zk_pdl_with_slack::PDLwSlackProof;
 use curv::cryptographic_primitives::hashing::{Digest, DigestExt};
 use curv::cryptographic_primitives::proofs::sigma_dlog::DLogProof;
@@ -52,6 +53,36 @@ fn test_keygen_t2_n4() {
     assert!(keygen_t_n_parties(2, 4).is_ok());
 }
 
+#[test]
+fn test_verifiable_backup_t2_n4() {
+    let (_, shared_keys_vec, pk_vec, y, vss_scheme, _, _) = keygen_t_n_parties(2, 4).unwrap();
+    let recovery_secret = Scalar::<Secp256k1>::random();
+    let recovery_key = Point::generator() * &recovery_secret;
+
+    let backups = shared_keys_vec
+        .iter()
+        .map(|k| k.to_verifiable_backup(&recovery_key))
+        .collect::<Vec<_>>();
+    for (backup, pk) in backups.iter().zip(&pk_vec) {
+        backup.verify(pk, &recovery_key).expect("backup is invalid");
+    }
+    assert!(backups[0].verify(&pk_vec[1], &recovery_key).is_err());
+
+    let parties = [1, 3, 4];
+    let shares = parties
+        .iter()
+        .map(|&i| {
+            let i = usize::from(i) - 1;
+            backups[i]
+                .decrypt(&recovery_secret, &pk_vec[i])
+                .expect("failed to decrypt backup")
+        })
+        .collect::<Vec<_>>();
+    let x = backup::recover_threshold_key(&vss_scheme.parameters, &parties, &shares, &y)
+        .expect("failed to recover key");
+    assert_eq!(Point::generator() * x, y);
+}
+
 #[test]
 fn test_sign_n2_t1_ttag1() {
     let _ = sign(1, 2, 2, vec![0, 1], 0, &[0]);
@@ -83,8 +114,9 @@ fn test_sign_n2_t1_ttag1_corrupt_step5_party2() {
 // both parties are corrupting step 5
 #[test]
 fn test_sign_n2_t1_ttag1_corrupt_step5_party12() {
-    let res = sign(1, 2, 2, vec![0, 1], 5, &[0, 1]);
-    assert!(&res.err().unwrap().bad_actors[..] == &[0, 1])
+    let err = sign(1, 2, 2, vec![0, 1], 5, &[0, 1]).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::BadDeltaShare);
+    assert_eq!(err.bad_actors(), &[0, 1])
 }
 // party 1 is corrupted
 #[test]
@@ -116,8 +148,9 @@ fn test_sign_n2_t1_ttag1_corrupt_step6_party2() {
 // both parties are corrupting step 6
 #[test]
 fn test_sign_n2_t1_ttag1_corrupt_step6_party12() {
-    let res = sign(1, 2, 2, vec![0, 1], 6, &[0, 1]);
-    assert!(&res.err().unwrap().bad_actors[..] == &[0, 1])
+    let err = sign(1, 2, 2, vec![0, 1], 6, &[0, 1]).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::BadSigmaShare);
+    assert_eq!(err.bad_actors(), &[0, 1])
 }
 // party 1 is corrupted
 #[test]
@@ -143,8 +176,9 @@ fn test_sign_n2_t1_ttag1_corrupt_step7_party2() {
 // party 2,4 are corrupted
 #[test]
 fn test_sign_n5_t2_ttag4_corrupt_step7_party24() {
-    let res = sign(2, 5, 4, vec![0, 2, 3, 4], 7, &[1, 3]);
-    assert!(&res.err().unwrap().bad_actors[..] == &[1, 3])
+    let err = sign(2, 5, 4, vec![0, 2, 3, 4], 7, &[1, 3]).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::BadPartialSignature);
+    assert_eq!(err.bad_actors(), &[1, 3])
 }
 
 fn keygen_t_n_parties(
@@ -173,6 +207,11 @@ fn keygen_t_n_parties(
         .iter()
         .map(|k| k.phase1_broadcast_phase3_proof_of_correct_key_proof_of_correct_h1h2())
         .unzip();
+    let decom_vec = party_keys_vec
+        .iter()
+        .zip(decom_vec)
+        .map(|(k, decom)| k.phase2_prove_no_small_factors(decom, &bc1_vec))
+        .collect::<Vec<_>>();
 
     let e_vec = bc1_vec
         .iter()
@@ -709,7 +748,7 @@ fn sign(
 }
 
 fn check_sig(r: &Scalar<Secp256k1>, s: &Scalar<Secp256k1>, msg: &BigInt, pk: &Point<Secp256k1>) {
-    use secp256k1::{Message, PublicKey, Signature, SECP256K1};
+    use secp256k1::{ecdsa::Signature, Message, PublicKey, SECP256K1};
 
     let raw_msg = BigInt::to_bytes(msg);
     let mut msg: Vec<u8> = Vec::new(); // padding
@@ -743,7 +782,7 @@ fn check_sig(r: &Scalar<Secp256k1>, s: &Scalar<Secp256k1>, msg: &BigInt, pk: &Po
 
     let secp_sig = Signature::from_compact(compact.as_slice()).unwrap();
 
-    let is_correct = SECP256K1.verify(&msg, &secp_sig, &pk).is_ok();
+    let is_correct = SECP256K1.verify_ecdsa(&msg, &secp_sig, &pk).is_ok();
     assert!(is_correct);
 }
 #[test]
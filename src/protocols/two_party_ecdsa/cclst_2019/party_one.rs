@@ -35,6 +35,7 @@ use subtle::ConstantTimeEq;
 use super::party_two::EphKeyGenFirstMsg as Party2EphKeyGenFirstMessage;
 use super::party_two::EphKeyGenSecondMsg as Party2EphKeyGenSecondMessage;
 use super::SECURITY_BITS;
+use crate::utilities::signature::SignatureEncoding;
 use crate::Error::{self, InvalidSig};
 
 //****************** Begin: Party One structs ******************//
@@ -86,6 +87,21 @@ pub struct SignatureRecid {
     pub recid: u8,
 }
 
+impl SignatureEncoding for SignatureRecid {
+    fn r(&self) -> BigInt {
+        self.r.clone()
+    }
+    fn s(&self) -> BigInt {
+        self.s.clone()
+    }
+    fn recid(&self) -> u8 {
+        self.recid
+    }
+    fn from_parts(r: BigInt, s: BigInt, recid: u8) -> Self {
+        Self { s, r, recid }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Signature {
     pub s: BigInt,
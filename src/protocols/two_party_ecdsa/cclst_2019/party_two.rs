@@ -34,6 +34,7 @@ use super::party_one::EphKeyGenFirstMsg as Party1EphKeyGenFirstMsg;
 use super::party_one::KeyGenFirstMsg as Party1KeyGenFirstMessage;
 use super::party_one::KeyGenSecondMsg as Party1KeyGenSecondMessage;
 use super::SECURITY_BITS;
+use crate::utilities::digest::MessageDigest;
 
 //****************** Begin: Party Two structs ******************//
 
@@ -331,7 +332,7 @@ impl PartialSig {
         local_share: &Party2Private,
         ephemeral_local_share: &EphEcKeyPair,
         ephemeral_other_public_share: &Point<Secp256k1>,
-        message: &BigInt,
+        message: &MessageDigest,
     ) -> PartialSig {
         let q = Scalar::<Secp256k1>::group_order();
         //compute r = k2* R1
@@ -341,7 +342,7 @@ impl PartialSig {
         let rx = r.x_coord().unwrap().mod_floor(q);
         let k2 = &ephemeral_local_share.secret_share.to_bigint();
         let k2_inv = BigInt::mod_inv(k2, q).unwrap();
-        let k2_inv_m = BigInt::mod_mul(&k2_inv, message, q);
+        let k2_inv_m = BigInt::mod_mul(&k2_inv, &message.to_bigint(), q);
         let k2_inv_m_fe = Scalar::<Secp256k1>::from(&k2_inv_m);
         let c1 = encrypt(&party_two_public.group, &party_two_public.ek, &k2_inv_m_fe);
         let v = BigInt::mod_mul(&k2_inv, &local_share.x2.to_bigint(), q);
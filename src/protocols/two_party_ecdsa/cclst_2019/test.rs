@@ -5,6 +5,8 @@ use curv::arithmetic::Converter;
 use curv::elliptic::curves::*;
 use curv::BigInt;
 
+use crate::utilities::digest::MessageDigest;
+
 #[test]
 fn test_d_log_proof_party_two_party_one() {
     let (party_one_first_message, comm_witness, _ec_key_pair_party1) =
@@ -118,7 +120,7 @@ fn test_two_party_sign() {
         )
         .expect("failed to verify commitments and DLog proof");
     let party2_private = party_two::Party2Private::set_private_key(&ec_key_pair_party2);
-    let message = BigInt::from(1234);
+    let message = MessageDigest::from_bigint(&BigInt::from(1234)).unwrap();
 
     let partial_sig = party_two::PartialSig::compute(
         party_two_hsmcl_pub,
@@ -138,5 +140,5 @@ fn test_two_party_sign() {
 
     let pubkey =
         party_one::compute_pubkey(&party1_private, &party_two_private_share_gen.public_share);
-    party_one::verify(&signature, &pubkey, &message).expect("Invalid signature")
+    party_one::verify(&signature, &pubkey, &message.to_bigint()).expect("Invalid signature")
 }
@@ -18,6 +18,7 @@ const SECURITY_BITS: usize = 256;
 
 pub mod party_one;
 pub mod party_two;
+pub mod state_machine;
 
 #[cfg(test)]
 mod test;
@@ -34,11 +34,16 @@ use sha2::Sha256;
 use subtle::ConstantTimeEq;
 use zk_paillier::zkproofs::NiCorrectKeyProof;
 
+use super::party_two::AdaptorPartialSig as Party2AdaptorPartialSig;
 use super::party_two::EphKeyGenFirstMsg as Party2EphKeyGenFirstMessage;
 use super::party_two::EphKeyGenSecondMsg as Party2EphKeyGenSecondMessage;
+use super::party_two::RotationFirstMsg as Party2RotationFirstMessage;
 use super::SECURITY_BITS;
 
+use crate::utilities::backup::{self, VerifiableBackup};
+use crate::utilities::digest::MessageDigest;
 use crate::utilities::mta::MessageB;
+use crate::utilities::signature::SignatureEncoding;
 use crate::Error;
 
 use crate::utilities::zk_pdl_with_slack::PDLwSlackProof;
@@ -67,7 +72,7 @@ pub struct KeyGenFirstMsg {
     pub zk_pok_commitment: BigInt,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct KeyGenSecondMsg {
     pub comm_witness: CommWitness,
 }
@@ -80,19 +85,52 @@ pub struct PaillierKeyPair {
     randomness: BigInt,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SignatureRecid {
     pub s: BigInt,
     pub r: BigInt,
     pub recid: u8,
 }
 
+impl SignatureEncoding for SignatureRecid {
+    fn r(&self) -> BigInt {
+        self.r.clone()
+    }
+    fn s(&self) -> BigInt {
+        self.s.clone()
+    }
+    fn recid(&self) -> u8 {
+        self.recid
+    }
+    fn from_parts(r: BigInt, s: BigInt, recid: u8) -> Self {
+        Self { s, r, recid }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Signature {
     pub s: BigInt,
     pub r: BigInt,
 }
 
+/// Adaptor signature: nonce is `R' = k * Y` for the joint ephemeral secret `k = k1 * k2` and
+/// adaptor point `Y`
+///
+/// `s_hat` turns into a valid signature once multiplied by `y^-1`, where `Y = y * G`. Pre-signature
+/// carries `R = k * G` along with two DLEQ proofs: party two shows that `R2 = k2 * G` and
+/// `Y2 = k2 * Y` share a discrete log, and party one shows that `R = k1 * R2` and `R' = k1 * Y2`
+/// do.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PreSignature {
+    pub r: Point<Secp256k1>,
+    pub r_hat: Point<Secp256k1>,
+    pub s_hat: Scalar<Secp256k1>,
+    pub party_two_public_share: Point<Secp256k1>,
+    pub party_two_adaptor_share: Point<Secp256k1>,
+    pub party_two_proof: ECDDHProof<Secp256k1, Sha256>,
+    pub party_one_proof: ECDDHProof<Secp256k1, Sha256>,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Party1Private {
     x1: Scalar<Secp256k1>,
@@ -122,7 +160,7 @@ pub struct EphEcKeyPair {
     secret_share: Scalar<Secp256k1>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct EphKeyGenFirstMsg {
     pub d_log_proof: ECDDHProof<Secp256k1, Sha256>,
     pub public_share: Point<Secp256k1>,
@@ -132,6 +170,34 @@ pub struct EphKeyGenFirstMsg {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct EphKeyGenSecondMsg {}
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AdaptorFirstMsg {
+    pub adaptor_share: Point<Secp256k1>, // k1 * Y
+    pub adaptor_share_proof: ECDDHProof<Secp256k1, Sha256>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RotationFirstMsg {
+    pub seed_commitment: BigInt,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RotationCommWitness {
+    pub seed: Scalar<Secp256k1>,
+    pub blind_factor: BigInt,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RotationSecondMsg {
+    pub comm_witness: RotationCommWitness,
+    pub ek_new: EncryptionKey,
+    pub encrypted_share_new: BigInt,
+    pub correct_key_proof: NiCorrectKeyProof,
+    pub pdl_statement: PDLwSlackStatement,
+    pub pdl_proof: PDLwSlackProof,
+    pub composite_dlog_proof: CompositeDLogProof,
+}
+
 //****************** End: Party One structs ******************//
 
 impl KeyGenFirstMsg {
@@ -306,6 +372,18 @@ impl Party1Private {
         Msegmentation::to_encrypted_segments(&self.x1, &segment_size, num_of_segments, pub_ke_y, g)
     }
 
+    /// Encrypts the secret share under `recovery_key` along with a proof that the counterparty
+    /// can check against our public share
+    pub fn to_verifiable_backup(&self, recovery_key: &Point<Secp256k1>) -> VerifiableBackup {
+        let (witness, encryptions) = self.to_encrypted_segment(
+            backup::SEGMENT_SIZE,
+            backup::NUM_OF_SEGMENTS,
+            recovery_key,
+            &Point::generator().to_point(),
+        );
+        VerifiableBackup::from_segments(&witness, encryptions, recovery_key)
+    }
+
     // used to transform lindell master key to gg18 master key
     pub fn to_mta_message_b(
         &self,
@@ -433,6 +511,64 @@ impl EphKeyGenFirstMsg {
     }
 }
 
+// Key rotation: parties toss a coin to agree on a random factor `r = seed1 + seed2`, then party
+// one multiplies its share by `r` and party two multiplies its share by `r^-1`, so the joint
+// public key stays the same.
+impl RotationFirstMsg {
+    pub fn commit() -> (RotationFirstMsg, RotationCommWitness) {
+        let seed = Scalar::<Secp256k1>::random();
+        let blind_factor = BigInt::sample(SECURITY_BITS);
+        let seed_commitment =
+            HashCommitment::<Sha256>::create_commitment_with_user_defined_randomness(
+                &seed.to_bigint(),
+                &blind_factor,
+            );
+        (
+            RotationFirstMsg { seed_commitment },
+            RotationCommWitness { seed, blind_factor },
+        )
+    }
+}
+
+impl RotationSecondMsg {
+    /// Reveals party one's seed and refreshes its share and Paillier key with the rotation factor
+    ///
+    /// Returns new private key along with the rotation factor, or [Error::InvalidKey] if the factor
+    /// is zero (checked before generating new Paillier key).
+    pub fn reveal_and_rotate(
+        comm_witness: RotationCommWitness,
+        party_two_first_message: &Party2RotationFirstMessage,
+        party_one_private: &Party1Private,
+    ) -> Result<(RotationSecondMsg, Party1Private, Scalar<Secp256k1>), Error> {
+        let factor = &comm_witness.seed + &party_two_first_message.seed;
+        if factor.is_zero() {
+            return Err(Error::InvalidKey);
+        }
+        let (
+            ek_new,
+            encrypted_share_new,
+            party_one_private_new,
+            correct_key_proof,
+            pdl_statement,
+            pdl_proof,
+            composite_dlog_proof,
+        ) = Party1Private::refresh_private_key(party_one_private, &factor.to_bigint());
+        Ok((
+            RotationSecondMsg {
+                comm_witness,
+                ek_new,
+                encrypted_share_new,
+                correct_key_proof,
+                pdl_statement,
+                pdl_proof,
+                composite_dlog_proof,
+            },
+            party_one_private_new,
+            factor,
+        ))
+    }
+}
+
 impl EphKeyGenSecondMsg {
     pub fn verify_commitments_and_dlog_proof(
         party_two_first_message: &Party2EphKeyGenFirstMessage,
@@ -564,6 +700,155 @@ impl Signature {
     }
 }
 
+impl AdaptorFirstMsg {
+    pub fn create(
+        ephemeral_local_share: &EphEcKeyPair,
+        adaptor_point: &Point<Secp256k1>,
+    ) -> AdaptorFirstMsg {
+        let adaptor_share = adaptor_point * &ephemeral_local_share.secret_share;
+        let w = ECDDHWitness {
+            x: ephemeral_local_share.secret_share.clone(),
+        };
+        let delta = ECDDHStatement {
+            g1: Point::generator().to_point(),
+            h1: ephemeral_local_share.public_share.clone(),
+            g2: adaptor_point.clone(),
+            h2: adaptor_share.clone(),
+        };
+        AdaptorFirstMsg {
+            adaptor_share_proof: ECDDHProof::prove(&w, &delta),
+            adaptor_share,
+        }
+    }
+}
+
+impl PreSignature {
+    pub fn compute(
+        party_one_private: &Party1Private,
+        partial_sig: &Party2AdaptorPartialSig,
+        ephemeral_local_share: &EphEcKeyPair,
+        ephemeral_other_public_share: &Point<Secp256k1>,
+        adaptor_point: &Point<Secp256k1>,
+    ) -> Result<PreSignature, ProofError> {
+        let delta = ECDDHStatement {
+            g1: Point::generator().to_point(),
+            h1: ephemeral_other_public_share.clone(),
+            g2: adaptor_point.clone(),
+            h2: partial_sig.adaptor_share.clone(),
+        };
+        partial_sig.adaptor_share_proof.verify(&delta)?;
+
+        let k1 = &ephemeral_local_share.secret_share;
+        let r = ephemeral_other_public_share * k1;
+        let r_hat = &partial_sig.adaptor_share * k1;
+
+        let s_tag = Paillier::decrypt(
+            &party_one_private.paillier_priv,
+            &RawCiphertext::from(&partial_sig.c3),
+        )
+        .0;
+        let s_hat = Scalar::<Secp256k1>::from(s_tag.as_ref()) * k1.invert().unwrap();
+
+        let w = ECDDHWitness { x: k1.clone() };
+        let delta = ECDDHStatement {
+            g1: ephemeral_other_public_share.clone(),
+            h1: r.clone(),
+            g2: partial_sig.adaptor_share.clone(),
+            h2: r_hat.clone(),
+        };
+        let party_one_proof = ECDDHProof::prove(&w, &delta);
+
+        Ok(PreSignature {
+            r,
+            r_hat,
+            s_hat,
+            party_two_public_share: ephemeral_other_public_share.clone(),
+            party_two_adaptor_share: partial_sig.adaptor_share.clone(),
+            party_two_proof: partial_sig.adaptor_share_proof.clone(),
+            party_one_proof,
+        })
+    }
+
+    /// Checks that pre-signature turns into a valid signature of `message` under `pubkey` once
+    /// adapted with discrete log of `adaptor_point`
+    pub fn verify(
+        &self,
+        pubkey: &Point<Secp256k1>,
+        adaptor_point: &Point<Secp256k1>,
+        message: &MessageDigest,
+    ) -> Result<(), Error> {
+        let party_two_delta = ECDDHStatement {
+            g1: Point::generator().to_point(),
+            h1: self.party_two_public_share.clone(),
+            g2: adaptor_point.clone(),
+            h2: self.party_two_adaptor_share.clone(),
+        };
+        let party_one_delta = ECDDHStatement {
+            g1: self.party_two_public_share.clone(),
+            h1: self.r.clone(),
+            g2: self.party_two_adaptor_share.clone(),
+            h2: self.r_hat.clone(),
+        };
+        if self.party_two_proof.verify(&party_two_delta).is_err()
+            || self.party_one_proof.verify(&party_one_delta).is_err()
+        {
+            return Err(Error::InvalidSig);
+        }
+
+        let s_hat_inv = self.s_hat.invert().ok_or(Error::InvalidSig)?;
+        let rx = Scalar::<Secp256k1>::from(&self.rx());
+        let r = (Point::generator() * message.to_scalar() + pubkey * rx) * s_hat_inv;
+        if r == self.r {
+            Ok(())
+        } else {
+            Err(Error::InvalidSig)
+        }
+    }
+
+    /// Completes pre-signature into a regular signature using discrete log of the adaptor point
+    ///
+    /// Returns [Error::InvalidKey] if `adaptor_secret` is zero.
+    pub fn adapt(&self, adaptor_secret: &Scalar<Secp256k1>) -> Result<Signature, Error> {
+        let adaptor_secret_inv = adaptor_secret.invert().ok_or(Error::InvalidKey)?;
+        let s = (&self.s_hat * adaptor_secret_inv).to_bigint();
+        Ok(Signature {
+            s: cmp::min(s.clone(), Scalar::<Secp256k1>::group_order() - &s),
+            r: self.rx(),
+        })
+    }
+
+    /// Extracts discrete log of `adaptor_point` out of a signature completed from this
+    /// pre-signature
+    pub fn extract(
+        &self,
+        signature: &Signature,
+        adaptor_point: &Point<Secp256k1>,
+    ) -> Result<Scalar<Secp256k1>, Error> {
+        if signature.r != self.rx() {
+            return Err(Error::InvalidSig);
+        }
+        let s_inv = Scalar::<Secp256k1>::from(&signature.s)
+            .invert()
+            .ok_or(Error::InvalidSig)?;
+        // signature is normalized to low s, so adaptor secret is defined up to a sign
+        let y = &self.s_hat * s_inv;
+        if &(Point::generator() * &y) == adaptor_point {
+            Ok(y)
+        } else if &(Point::generator() * -&y) == adaptor_point {
+            Ok(-y)
+        } else {
+            Err(Error::InvalidSig)
+        }
+    }
+
+    fn rx(&self) -> BigInt {
+        self.r_hat
+            .x_coord()
+            .unwrap()
+            .mod_floor(Scalar::<Secp256k1>::group_order())
+    }
+}
+
 pub fn verify(
     signature: &Signature,
     pubkey: &Point<Secp256k1>,
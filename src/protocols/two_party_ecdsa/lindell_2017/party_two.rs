@@ -32,10 +32,15 @@ use serde::{Deserialize, Serialize};
 use sha2::Sha256;
 use zk_paillier::zkproofs::{IncorrectProof, NiCorrectKeyProof};
 
+use super::party_one::AdaptorFirstMsg as Party1AdaptorFirstMsg;
 use super::party_one::EphKeyGenFirstMsg as Party1EphKeyGenFirstMsg;
 use super::party_one::KeyGenFirstMsg as Party1KeyGenFirstMessage;
 use super::party_one::KeyGenSecondMsg as Party1KeyGenSecondMessage;
+use super::party_one::RotationFirstMsg as Party1RotationFirstMessage;
+use super::party_one::RotationSecondMsg as Party1RotationSecondMessage;
 use super::SECURITY_BITS;
+use crate::utilities::backup::{self, VerifiableBackup};
+use crate::utilities::digest::MessageDigest;
 use crate::utilities::mta::{MessageA, MessageB};
 
 use crate::utilities::zk_pdl_with_slack::PDLwSlackProof;
@@ -47,6 +52,12 @@ use zk_paillier::zkproofs::{CompositeDLogProof, DLogStatement};
 pub enum PartyTwoError {
     #[error("party two pdl verify failed (lindell 2017)")]
     PdlVerify,
+    #[error("party two rotation seed decommitment failed (lindell 2017)")]
+    RotationDecommit,
+    #[error("party two correct paillier key verify failed (lindell 2017)")]
+    CorrectKeyVerify,
+    #[error("party two rotation factor is zero (lindell 2017)")]
+    ZeroRotationFactor,
 }
 
 const PAILLIER_KEY_SIZE: usize = 2048;
@@ -67,18 +78,25 @@ pub struct KeyGenFirstMsg {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct KeyGenSecondMsg {}
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PaillierPublic {
     pub ek: EncryptionKey,
     pub encrypted_secret_share: BigInt,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PartialSig {
     pub c3: BigInt,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AdaptorPartialSig {
+    pub c3: BigInt,
+    pub adaptor_share: Point<Secp256k1>, // k2 * Y
+    pub adaptor_share_proof: ECDDHProof<Secp256k1, Sha256>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Party2Private {
     x2: Scalar<Secp256k1>,
 }
@@ -130,11 +148,16 @@ pub struct EphKeyGenFirstMsg {
     pub zk_pok_commitment: BigInt,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct EphKeyGenSecondMsg {
     pub comm_witness: EphCommWitness,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RotationFirstMsg {
+    pub seed: Scalar<Secp256k1>,
+}
+
 //****************** End: Party Two structs ******************//
 
 impl KeyGenFirstMsg {
@@ -256,6 +279,18 @@ impl Party2Private {
         Msegmentation::to_encrypted_segments(&self.x2, &segment_size, num_of_segments, pub_ke_y, g)
     }
 
+    /// Encrypts the secret share under `recovery_key` along with a proof that the counterparty
+    /// can check against our public share
+    pub fn to_verifiable_backup(&self, recovery_key: &Point<Secp256k1>) -> VerifiableBackup {
+        let (witness, encryptions) = self.to_encrypted_segment(
+            backup::SEGMENT_SIZE,
+            backup::NUM_OF_SEGMENTS,
+            recovery_key,
+            &Point::generator().to_point(),
+        );
+        VerifiableBackup::from_segments(&witness, encryptions, recovery_key)
+    }
+
     // used to transform lindell master key to gg18 master key
     pub fn to_mta_message_b(
         &self,
@@ -387,6 +422,65 @@ impl EphKeyGenSecondMsg {
     }
 }
 
+impl RotationFirstMsg {
+    pub fn share() -> RotationFirstMsg {
+        RotationFirstMsg {
+            seed: Scalar::<Secp256k1>::random(),
+        }
+    }
+
+    /// Verifies party one's seed and refreshed Paillier key, and rotates party two's share
+    ///
+    /// Rotation factor is `r = seed1 + seed2`: party one's share is multiplied by `r`, so party
+    /// two's share is multiplied by `r^-1`. Returns new private key, Paillier public key and party
+    /// one's public share.
+    pub fn verify_and_rotate(
+        &self,
+        party_one_first_message: &Party1RotationFirstMessage,
+        party_one_second_message: Party1RotationSecondMessage,
+        party_two_private: &Party2Private,
+        party_one_public_share: &Point<Secp256k1>,
+    ) -> Result<(Party2Private, PaillierPublic, Point<Secp256k1>), PartyTwoError> {
+        let comm_witness = &party_one_second_message.comm_witness;
+        if party_one_first_message.seed_commitment
+            != HashCommitment::<Sha256>::create_commitment_with_user_defined_randomness(
+                &comm_witness.seed.to_bigint(),
+                &comm_witness.blind_factor,
+            )
+        {
+            return Err(PartyTwoError::RotationDecommit);
+        }
+        let factor = &comm_witness.seed + &self.seed;
+        let factor_inv = factor.invert().ok_or(PartyTwoError::ZeroRotationFactor)?;
+
+        PaillierPublic::verify_ni_proof_correct_key(
+            party_one_second_message.correct_key_proof,
+            &party_one_second_message.ek_new,
+        )
+        .map_err(|_| PartyTwoError::CorrectKeyVerify)?;
+        let paillier_public = PaillierPublic {
+            ek: party_one_second_message.ek_new,
+            encrypted_secret_share: party_one_second_message.encrypted_share_new,
+        };
+        let party_one_public_share_new = party_one_public_share * &factor;
+        PaillierPublic::pdl_verify(
+            &party_one_second_message.composite_dlog_proof,
+            &party_one_second_message.pdl_statement,
+            &party_one_second_message.pdl_proof,
+            &paillier_public,
+            &party_one_public_share_new,
+        )?;
+
+        let party_two_private_new =
+            Party2Private::update_private_key(party_two_private, &factor_inv.to_bigint());
+        Ok((
+            party_two_private_new,
+            paillier_public,
+            party_one_public_share_new,
+        ))
+    }
+}
+
 impl PartialSig {
     pub fn compute(
         ek: &EncryptionKey,
@@ -394,31 +488,98 @@ impl PartialSig {
         local_share: &Party2Private,
         ephemeral_local_share: &EphEcKeyPair,
         ephemeral_other_public_share: &Point<Secp256k1>,
-        message: &BigInt,
+        message: &MessageDigest,
     ) -> PartialSig {
-        let q = Scalar::<Secp256k1>::group_order();
         //compute r = k2* R1
         let r = ephemeral_other_public_share * &ephemeral_local_share.secret_share;
+        PartialSig {
+            c3: encrypt_partial_sig(
+                ek,
+                encrypted_secret_share,
+                local_share,
+                ephemeral_local_share,
+                &r,
+                message,
+            ),
+        }
+    }
+}
 
-        let rx = r.x_coord().unwrap().mod_floor(q);
-        let rho = BigInt::sample_below(&q.pow(2));
-        let k2_inv = BigInt::mod_inv(&ephemeral_local_share.secret_share.to_bigint(), q).unwrap();
-        let partial_sig = rho * q + BigInt::mod_mul(&k2_inv, message, q);
+impl AdaptorPartialSig {
+    /// Same as [PartialSig::compute], but signature nonce is `k1 * k2 * Y` for the adaptor point
+    /// `Y`, so party one ends up with a [PreSignature](super::party_one::PreSignature)
+    pub fn compute(
+        ek: &EncryptionKey,
+        encrypted_secret_share: &BigInt,
+        local_share: &Party2Private,
+        ephemeral_local_share: &EphEcKeyPair,
+        ephemeral_other_public_share: &Point<Secp256k1>,
+        party_one_adaptor_msg: &Party1AdaptorFirstMsg,
+        adaptor_point: &Point<Secp256k1>,
+        message: &MessageDigest,
+    ) -> Result<AdaptorPartialSig, ProofError> {
+        let delta = ECDDHStatement {
+            g1: Point::generator().to_point(),
+            h1: ephemeral_other_public_share.clone(),
+            g2: adaptor_point.clone(),
+            h2: party_one_adaptor_msg.adaptor_share.clone(),
+        };
+        party_one_adaptor_msg.adaptor_share_proof.verify(&delta)?;
 
-        let c1 = Paillier::encrypt(ek, RawPlaintext::from(partial_sig));
-        let v = BigInt::mod_mul(
-            &k2_inv,
-            &BigInt::mod_mul(&rx, &local_share.x2.to_bigint(), q),
-            q,
-        );
-        let c2 = Paillier::mul(
+        //compute r = k2 * k1 * Y
+        let r = &party_one_adaptor_msg.adaptor_share * &ephemeral_local_share.secret_share;
+        let c3 = encrypt_partial_sig(
             ek,
-            RawCiphertext::from(encrypted_secret_share.clone()),
-            RawPlaintext::from(v),
+            encrypted_secret_share,
+            local_share,
+            ephemeral_local_share,
+            &r,
+            message,
         );
-        //c3:
-        PartialSig {
-            c3: Paillier::add(ek, c2, c1).0.into_owned(),
-        }
+
+        let adaptor_share = adaptor_point * &ephemeral_local_share.secret_share;
+        let w = ECDDHWitness {
+            x: ephemeral_local_share.secret_share.clone(),
+        };
+        let delta = ECDDHStatement {
+            g1: Point::generator().to_point(),
+            h1: ephemeral_local_share.public_share.clone(),
+            g2: adaptor_point.clone(),
+            h2: adaptor_share.clone(),
+        };
+        Ok(AdaptorPartialSig {
+            c3,
+            adaptor_share_proof: ECDDHProof::prove(&w, &delta),
+            adaptor_share,
+        })
     }
 }
+
+// c3 = Enc(rho * q + k2^-1 * m) + Enc(x1) * (k2^-1 * r * x2)
+fn encrypt_partial_sig(
+    ek: &EncryptionKey,
+    encrypted_secret_share: &BigInt,
+    local_share: &Party2Private,
+    ephemeral_local_share: &EphEcKeyPair,
+    r: &Point<Secp256k1>,
+    message: &MessageDigest,
+) -> BigInt {
+    let q = Scalar::<Secp256k1>::group_order();
+    let rx = r.x_coord().unwrap().mod_floor(q);
+    let rho = BigInt::sample_below(&q.pow(2));
+    let k2_inv = BigInt::mod_inv(&ephemeral_local_share.secret_share.to_bigint(), q).unwrap();
+    let partial_sig = rho * q + BigInt::mod_mul(&k2_inv, &message.to_bigint(), q);
+
+    let c1 = Paillier::encrypt(ek, RawPlaintext::from(partial_sig));
+    let v = BigInt::mod_mul(
+        &k2_inv,
+        &BigInt::mod_mul(&rx, &local_share.x2.to_bigint(), q),
+        q,
+    );
+    let c2 = Paillier::mul(
+        ek,
+        RawCiphertext::from(encrypted_secret_share.clone()),
+        RawPlaintext::from(v),
+    );
+    Paillier::add(ek, c2, c1).0.into_owned()
+}
@@ -0,0 +1,579 @@
+//! Two-party key generation
+//!
+//! Party one commits to its public share, party two replies with its public share and proof of
+//! knowledge of the secret. Party one then decommits, generates Paillier key and sends party two
+//! the encryption of its secret share along with proofs of correctness of both.
+
+use std::fmt;
+use std::mem::replace;
+use std::time::Duration;
+
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Point};
+use curv::BigInt;
+use paillier::EncryptionKey;
+use round_based::containers::*;
+use round_based::{Msg, StateMachine};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    push_msg, store_status, take_msg, wants_more, Error, KeygenDecommitment, ProtocolMessage,
+    Result, M, PARTY_ONE, PARTY_TWO,
+};
+use crate::protocols::two_party_ecdsa::lindell_2017::{party_one, party_two};
+
+/// Party one's share of the key obtained from [Party1Keygen]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Party1LocalKey {
+    /// Secret share `x1` and Paillier decryption key
+    pub private: party_one::Party1Private,
+    /// Paillier encryption key
+    pub paillier_ek: EncryptionKey,
+    /// Encryption of `x1` under [paillier_ek](Self::paillier_ek), known to party two
+    pub encrypted_share: BigInt,
+    /// `x1 * G`
+    pub public_share: Point<Secp256k1>,
+    /// Party two's public share `x2 * G`
+    pub counterparty_public_share: Point<Secp256k1>,
+    /// Joint public key `x1 * x2 * G`
+    pub public_key: Point<Secp256k1>,
+}
+
+/// Party two's share of the key obtained from [Party2Keygen]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Party2LocalKey {
+    /// Secret share `x2`
+    pub private: party_two::Party2Private,
+    /// Party one's Paillier encryption key and encryption of its secret share `x1`
+    pub paillier_public: party_two::PaillierPublic,
+    /// `x2 * G`
+    pub public_share: Point<Secp256k1>,
+    /// Party one's public share `x1 * G`
+    pub counterparty_public_share: Point<Secp256k1>,
+    /// Joint public key `x1 * x2 * G`
+    pub public_key: Point<Secp256k1>,
+}
+
+/// Keygen state machine of party one
+pub struct Party1Keygen {
+    round: R1,
+
+    msgs1: Option<Store<P2PMsgs<party_two::KeyGenFirstMsg>>>,
+
+    msgs_queue: Vec<Msg<ProtocolMessage>>,
+}
+
+impl Party1Keygen {
+    /// Constructs party one of keygen protocol
+    ///
+    /// Party one always has index `1`, and expects messages from party two with index `2`.
+    pub fn new() -> Self {
+        let (first_msg, comm_witness, ec_key_pair) =
+            party_one::KeyGenFirstMsg::create_commitments();
+        Self {
+            round: R1::Round1 {
+                comm_witness,
+                ec_key_pair,
+            },
+
+            msgs1: Some(P2PMsgsStore::new(PARTY_ONE, 2)),
+
+            msgs_queue: vec![Msg {
+                sender: PARTY_ONE,
+                receiver: Some(PARTY_TWO),
+                body: ProtocolMessage(M::Keygen1Party1(first_msg)),
+            }],
+        }
+    }
+
+    /// Proceeds round state if it received enough messages and `may_block == true` (computing
+    /// Paillier key and proofs is expensive)
+    fn proceed_round(&mut self, may_block: bool) -> Result<()> {
+        let store1_wants_more = wants_more(&self.msgs1);
+
+        let next_state = match replace(&mut self.round, R1::Gone) {
+            R1::Round1 {
+                comm_witness,
+                ec_key_pair,
+            } if !store1_wants_more && may_block => {
+                let party_two_first = take_msg(&mut self.msgs1)?;
+                if party_two_first.d_log_proof.pk != party_two_first.public_share {
+                    return Err(Error::InvalidDLogProof);
+                }
+                let second_msg = party_one::KeyGenSecondMsg::verify_and_decommit(
+                    comm_witness,
+                    &party_two_first.d_log_proof,
+                )
+                .map_err(|_| Error::InvalidDLogProof)?;
+
+                let paillier_key_pair =
+                    party_one::PaillierKeyPair::generate_keypair_and_encrypted_share(&ec_key_pair);
+                let private =
+                    party_one::Party1Private::set_private_key(&ec_key_pair, &paillier_key_pair);
+                let correct_key_proof =
+                    party_one::PaillierKeyPair::generate_ni_proof_correct_key(&paillier_key_pair);
+                let (pdl_statement, pdl_proof, composite_dlog_proof) =
+                    party_one::PaillierKeyPair::pdl_proof(&private, &paillier_key_pair);
+
+                self.msgs_queue.push(Msg {
+                    sender: PARTY_ONE,
+                    receiver: Some(PARTY_TWO),
+                    body: ProtocolMessage(M::Keygen2Party1(Box::new(KeygenDecommitment {
+                        second_msg,
+                        paillier_ek: paillier_key_pair.ek.clone(),
+                        encrypted_share: paillier_key_pair.encrypted_share.clone(),
+                        correct_key_proof,
+                        pdl_statement,
+                        pdl_proof,
+                        composite_dlog_proof,
+                    }))),
+                });
+
+                let public_key = party_one::compute_pubkey(&private, &party_two_first.public_share);
+                R1::Final(Party1LocalKey {
+                    private,
+                    paillier_ek: paillier_key_pair.ek,
+                    encrypted_share: paillier_key_pair.encrypted_share,
+                    public_share: ec_key_pair.public_share,
+                    counterparty_public_share: party_two_first.public_share,
+                    public_key,
+                })
+            }
+            s => s,
+        };
+
+        self.round = next_state;
+        Ok(())
+    }
+}
+
+impl Default for Party1Keygen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StateMachine for Party1Keygen {
+    type MessageBody = ProtocolMessage;
+    type Err = Error;
+    type Output = Party1LocalKey;
+
+    fn handle_incoming(&mut self, msg: Msg<Self::MessageBody>) -> Result<()> {
+        let current_round = self.current_round();
+        let Msg {
+            sender,
+            receiver,
+            body: ProtocolMessage(body),
+        } = msg;
+
+        match body {
+            M::Keygen1Party2(m) => push_msg(
+                &mut self.msgs1,
+                Msg {
+                    sender,
+                    receiver,
+                    body: m,
+                },
+                current_round,
+                1,
+            )?,
+            _ => return Err(Error::UnexpectedMessage),
+        }
+        self.proceed_round(false)
+    }
+
+    fn message_queue(&mut self) -> &mut Vec<Msg<Self::MessageBody>> {
+        &mut self.msgs_queue
+    }
+
+    fn wants_to_proceed(&self) -> bool {
+        match &self.round {
+            R1::Round1 { .. } => !wants_more(&self.msgs1),
+            R1::Final(_) | R1::Gone => false,
+        }
+    }
+
+    fn proceed(&mut self) -> Result<()> {
+        self.proceed_round(true)
+    }
+
+    fn round_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    fn round_timeout_reached(&mut self) -> Self::Err {
+        panic!("no timeout was set")
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(self.round, R1::Final(_))
+    }
+
+    fn pick_output(&mut self) -> Option<Result<Self::Output>> {
+        match self.round {
+            R1::Final(_) => (),
+            R1::Gone => return Some(Err(Error::DoublePickOutput)),
+            _ => return None,
+        }
+
+        match replace(&mut self.round, R1::Gone) {
+            R1::Final(result) => Some(Ok(result)),
+            _ => unreachable!("guaranteed by match expression above"),
+        }
+    }
+
+    fn current_round(&self) -> u16 {
+        match &self.round {
+            R1::Round1 { .. } => 1,
+            R1::Final(_) | R1::Gone => 2,
+        }
+    }
+
+    fn total_rounds(&self) -> Option<u16> {
+        Some(2)
+    }
+
+    fn party_ind(&self) -> u16 {
+        PARTY_ONE
+    }
+
+    fn parties(&self) -> u16 {
+        2
+    }
+}
+
+impl fmt::Debug for Party1Keygen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let current_round = match &self.round {
+            R1::Round1 { .. } => "1",
+            R1::Final(_) => "[Final]",
+            R1::Gone => "[Gone]",
+        };
+        write!(
+            f,
+            "{{Party1Keygen at round={} msgs1={} queue=[len={}]}}",
+            current_round,
+            store_status(&self.msgs1),
+            self.msgs_queue.len()
+        )
+    }
+}
+
+/// Keygen state machine of party two
+pub struct Party2Keygen {
+    round: R2,
+
+    msgs1: Option<Store<P2PMsgs<party_one::KeyGenFirstMsg>>>,
+    msgs2: Option<Store<P2PMsgs<KeygenDecommitment>>>,
+
+    msgs_queue: Vec<Msg<ProtocolMessage>>,
+}
+
+impl Party2Keygen {
+    /// Constructs party two of keygen protocol
+    ///
+    /// Party two always has index `2`, and expects messages from party one with index `1`. Its
+    /// public share is sent only once party one's commitment is received.
+    pub fn new() -> Self {
+        let (first_msg, ec_key_pair) = party_two::KeyGenFirstMsg::create();
+        Self {
+            round: R2::Round1 {
+                first_msg,
+                ec_key_pair,
+            },
+
+            msgs1: Some(P2PMsgsStore::new(PARTY_TWO, 2)),
+            msgs2: Some(P2PMsgsStore::new(PARTY_TWO, 2)),
+
+            msgs_queue: vec![],
+        }
+    }
+
+    /// Proceeds round state if it received enough messages and if it's cheap to compute or
+    /// `may_block == true`
+    fn proceed_round(&mut self, may_block: bool) -> Result<()> {
+        let store1_wants_more = wants_more(&self.msgs1);
+        let store2_wants_more = wants_more(&self.msgs2);
+
+        let next_state: R2;
+        let try_again: bool = match replace(&mut self.round, R2::Gone) {
+            R2::Round1 {
+                first_msg,
+                ec_key_pair,
+            } if !store1_wants_more => {
+                let party_one_first = take_msg(&mut self.msgs1)?;
+                self.msgs_queue.push(Msg {
+                    sender: PARTY_TWO,
+                    receiver: Some(PARTY_ONE),
+                    body: ProtocolMessage(M::Keygen1Party2(first_msg)),
+                });
+                next_state = R2::Round2 {
+                    ec_key_pair,
+                    party_one_first,
+                };
+                true
+            }
+            R2::Round2 {
+                ec_key_pair,
+                party_one_first,
+            } if !store2_wants_more && may_block => {
+                let decommitment = take_msg(&mut self.msgs2)?;
+                party_two::KeyGenSecondMsg::verify_commitments_and_dlog_proof(
+                    &party_one_first,
+                    &decommitment.second_msg,
+                )
+                .map_err(|_| Error::InvalidDecommitment)?;
+                party_two::PaillierPublic::verify_ni_proof_correct_key(
+                    decommitment.correct_key_proof,
+                    &decommitment.paillier_ek,
+                )
+                .map_err(|_| Error::InvalidPaillierKey)?;
+
+                let paillier_public = party_two::PaillierPublic {
+                    ek: decommitment.paillier_ek,
+                    encrypted_secret_share: decommitment.encrypted_share,
+                };
+                let party_one_public_share = decommitment.second_msg.comm_witness.public_share;
+                party_two::PaillierPublic::pdl_verify(
+                    &decommitment.composite_dlog_proof,
+                    &decommitment.pdl_statement,
+                    &decommitment.pdl_proof,
+                    &paillier_public,
+                    &party_one_public_share,
+                )
+                .map_err(|_| Error::InvalidPdlProof)?;
+
+                let public_key = party_two::compute_pubkey(&ec_key_pair, &party_one_public_share);
+                next_state = R2::Final(Party2LocalKey {
+                    private: party_two::Party2Private::set_private_key(&ec_key_pair),
+                    paillier_public,
+                    public_share: ec_key_pair.public_share,
+                    counterparty_public_share: party_one_public_share,
+                    public_key,
+                });
+                true
+            }
+            s => {
+                next_state = s;
+                false
+            }
+        };
+
+        self.round = next_state;
+        if try_again {
+            self.proceed_round(may_block)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Default for Party2Keygen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StateMachine for Party2Keygen {
+    type MessageBody = ProtocolMessage;
+    type Err = Error;
+    type Output = Party2LocalKey;
+
+    fn handle_incoming(&mut self, msg: Msg<Self::MessageBody>) -> Result<()> {
+        let current_round = self.current_round();
+        let Msg {
+            sender,
+            receiver,
+            body: ProtocolMessage(body),
+        } = msg;
+
+        match body {
+            M::Keygen1Party1(m) => push_msg(
+                &mut self.msgs1,
+                Msg {
+                    sender,
+                    receiver,
+                    body: m,
+                },
+                current_round,
+                1,
+            )?,
+            M::Keygen2Party1(m) => push_msg(
+                &mut self.msgs2,
+                Msg {
+                    sender,
+                    receiver,
+                    body: *m,
+                },
+                current_round,
+                2,
+            )?,
+            _ => return Err(Error::UnexpectedMessage),
+        }
+        self.proceed_round(false)
+    }
+
+    fn message_queue(&mut self) -> &mut Vec<Msg<Self::MessageBody>> {
+        &mut self.msgs_queue
+    }
+
+    fn wants_to_proceed(&self) -> bool {
+        match &self.round {
+            R2::Round1 { .. } => !wants_more(&self.msgs1),
+            R2::Round2 { .. } => !wants_more(&self.msgs2),
+            R2::Final(_) | R2::Gone => false,
+        }
+    }
+
+    fn proceed(&mut self) -> Result<()> {
+        self.proceed_round(true)
+    }
+
+    fn round_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    fn round_timeout_reached(&mut self) -> Self::Err {
+        panic!("no timeout was set")
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(self.round, R2::Final(_))
+    }
+
+    fn pick_output(&mut self) -> Option<Result<Self::Output>> {
+        match self.round {
+            R2::Final(_) => (),
+            R2::Gone => return Some(Err(Error::DoublePickOutput)),
+            _ => return None,
+        }
+
+        match replace(&mut self.round, R2::Gone) {
+            R2::Final(result) => Some(Ok(result)),
+            _ => unreachable!("guaranteed by match expression above"),
+        }
+    }
+
+    fn current_round(&self) -> u16 {
+        match &self.round {
+            R2::Round1 { .. } => 1,
+            R2::Round2 { .. } => 2,
+            R2::Final(_) | R2::Gone => 3,
+        }
+    }
+
+    fn total_rounds(&self) -> Option<u16> {
+        Some(2)
+    }
+
+    fn party_ind(&self) -> u16 {
+        PARTY_TWO
+    }
+
+    fn parties(&self) -> u16 {
+        2
+    }
+}
+
+impl fmt::Debug for Party2Keygen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let current_round = match &self.round {
+            R2::Round1 { .. } => "1",
+            R2::Round2 { .. } => "2",
+            R2::Final(_) => "[Final]",
+            R2::Gone => "[Gone]",
+        };
+        write!(
+            f,
+            "{{Party2Keygen at round={} msgs1={} msgs2={} queue=[len={}]}}",
+            current_round,
+            store_status(&self.msgs1),
+            store_status(&self.msgs2),
+            self.msgs_queue.len()
+        )
+    }
+}
+
+// Rounds
+
+#[allow(clippy::large_enum_variant)]
+enum R1 {
+    Round1 {
+        comm_witness: party_one::CommWitness,
+        ec_key_pair: party_one::EcKeyPair,
+    },
+    Final(Party1LocalKey),
+    Gone,
+}
+
+#[allow(clippy::large_enum_variant)]
+enum R2 {
+    Round1 {
+        first_msg: party_two::KeyGenFirstMsg,
+        ec_key_pair: party_two::EcKeyPair,
+    },
+    Round2 {
+        ec_key_pair: party_two::EcKeyPair,
+        party_one_first: party_one::KeyGenFirstMsg,
+    },
+    Final(Party2LocalKey),
+    Gone,
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::protocols::two_party_ecdsa::lindell_2017::state_machine::test::run_two_party;
+
+    pub fn simulate_keygen() -> (Party1LocalKey, Party2LocalKey) {
+        run_two_party(Party1Keygen::new(), Party2Keygen::new())
+    }
+
+    #[test]
+    fn parties_agree_on_public_key() {
+        let (party1_key, party2_key) = simulate_keygen();
+
+        assert_eq!(party1_key.public_key, party2_key.public_key);
+        assert_eq!(
+            party1_key.counterparty_public_share,
+            party2_key.public_share
+        );
+        assert_eq!(
+            party2_key.counterparty_public_share,
+            party1_key.public_share
+        );
+        assert_eq!(
+            party1_key.encrypted_share,
+            party2_key.paillier_public.encrypted_secret_share
+        );
+    }
+
+    #[test]
+    fn message_of_another_protocol_is_rejected() {
+        let mut party1 = Party1Keygen::new();
+        let mut party2 = Party2Keygen::new();
+        let msg = party1.message_queue().remove(0);
+        assert!(matches!(
+            party1.handle_incoming(msg.clone()),
+            Err(Error::UnexpectedMessage)
+        ));
+        party2.handle_incoming(msg).unwrap();
+        assert_eq!(party2.current_round(), 2);
+    }
+
+    #[test]
+    fn party_two_replies_only_after_party_one_commits() {
+        let mut party1 = Party1Keygen::new();
+        let mut party2 = Party2Keygen::new();
+        assert!(party2.message_queue().is_empty());
+
+        let commitment = party1.message_queue().remove(0);
+        party2.handle_incoming(commitment).unwrap();
+        assert!(matches!(
+            party2.message_queue().as_slice(),
+            [Msg {
+                body: ProtocolMessage(M::Keygen1Party2(_)),
+                ..
+            }]
+        ));
+    }
+}
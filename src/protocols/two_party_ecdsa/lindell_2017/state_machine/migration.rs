@@ -0,0 +1,435 @@
+//! Migrating a two-party key to GG20
+//!
+//! Lindell 2017 key is shared multiplicatively: `x = x1 * x2`. Migration converts it into additive
+//! shares `x = u1 + u2` using MtA over party one's Paillier encryption of `x1` obtained at keygen:
+//! party two sends [MessageB] computed with `x2`, party one decrypts its share `u1` and replies with
+//! `u1 * G`, so party two can check that shares add up to the public key.
+//!
+//! Resulting [AdditiveShare]s are then passed to [Keygen::import] with `t = 1, n = 2` (party one
+//! takes index `1`, party two takes index `2`), which outputs GG20 [LocalKey]s with the same
+//! public key. They can be used for GG20 signing and [resharing] to more parties. Lindell key must
+//! not be used after migration.
+//!
+//! [Keygen::import]: crate::protocols::multi_party_ecdsa::gg_2020::state_machine::keygen::Keygen::import
+//! [LocalKey]: crate::protocols::multi_party_ecdsa::gg_2020::state_machine::keygen::LocalKey
+//! [resharing]: crate::protocols::multi_party_ecdsa::gg_2020::state_machine::reshare
+
+use std::fmt;
+use std::mem::replace;
+use std::time::Duration;
+
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
+use round_based::containers::*;
+use round_based::{Msg, StateMachine};
+
+use super::keygen::{Party1LocalKey, Party2LocalKey};
+use super::{
+    push_msg, store_status, take_msg, wants_more, Error, ProtocolMessage, Result, M, PARTY_ONE,
+    PARTY_TWO,
+};
+use crate::protocols::multi_party_ecdsa::gg_2020::state_machine::keygen::AdditiveShare;
+use crate::utilities::mta::MessageB;
+
+/// Migration state machine of party one
+pub struct Party1Migration {
+    round: R1,
+
+    msgs1: Option<Store<P2PMsgs<MessageB>>>,
+
+    msgs_queue: Vec<Msg<ProtocolMessage>>,
+}
+
+impl Party1Migration {
+    /// Constructs party one of migration protocol
+    ///
+    /// Takes `local_key` obtained from [Party1Keygen](super::Party1Keygen) (or rotation).
+    pub fn new(local_key: Party1LocalKey) -> Self {
+        Self {
+            round: R1::Round1(local_key),
+
+            msgs1: Some(P2PMsgsStore::new(PARTY_ONE, 2)),
+
+            msgs_queue: vec![],
+        }
+    }
+
+    /// Proceeds round state if it received enough messages and `may_block == true` (decrypting
+    /// MtA message is expensive)
+    fn proceed_round(&mut self, may_block: bool) -> Result<()> {
+        let store1_wants_more = wants_more(&self.msgs1);
+
+        let next_state = match replace(&mut self.round, R1::Gone) {
+            R1::Round1(local_key) if !store1_wants_more && may_block => {
+                let message_b = take_msg(&mut self.msgs1)?;
+                if !MessageB::verify_b_against_public(
+                    &local_key.counterparty_public_share,
+                    &message_b.b_proof.pk,
+                ) {
+                    return Err(Error::InvalidMta);
+                }
+                let (u_i, _) = local_key
+                    .private
+                    .to_mta_message_b(message_b)
+                    .map_err(|_| Error::InvalidMta)?;
+
+                self.msgs_queue.push(Msg {
+                    sender: PARTY_ONE,
+                    receiver: Some(PARTY_TWO),
+                    body: ProtocolMessage(M::Migration2Party1(Point::generator() * &u_i)),
+                });
+                R1::Final(AdditiveShare {
+                    u_i,
+                    public_key: local_key.public_key,
+                })
+            }
+            s => s,
+        };
+
+        self.round = next_state;
+        Ok(())
+    }
+}
+
+impl StateMachine for Party1Migration {
+    type MessageBody = ProtocolMessage;
+    type Err = Error;
+    type Output = AdditiveShare;
+
+    fn handle_incoming(&mut self, msg: Msg<Self::MessageBody>) -> Result<()> {
+        let current_round = self.current_round();
+        let Msg {
+            sender,
+            receiver,
+            body: ProtocolMessage(body),
+        } = msg;
+
+        match body {
+            M::Migration1Party2(m) => push_msg(
+                &mut self.msgs1,
+                Msg {
+                    sender,
+                    receiver,
+                    body: *m,
+                },
+                current_round,
+                1,
+            )?,
+            _ => return Err(Error::UnexpectedMessage),
+        }
+        self.proceed_round(false)
+    }
+
+    fn message_queue(&mut self) -> &mut Vec<Msg<Self::MessageBody>> {
+        &mut self.msgs_queue
+    }
+
+    fn wants_to_proceed(&self) -> bool {
+        match &self.round {
+            R1::Round1(_) => !wants_more(&self.msgs1),
+            R1::Final(_) | R1::Gone => false,
+        }
+    }
+
+    fn proceed(&mut self) -> Result<()> {
+        self.proceed_round(true)
+    }
+
+    fn round_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    fn round_timeout_reached(&mut self) -> Self::Err {
+        panic!("no timeout was set")
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(self.round, R1::Final(_))
+    }
+
+    fn pick_output(&mut self) -> Option<Result<Self::Output>> {
+        match self.round {
+            R1::Final(_) => (),
+            R1::Gone => return Some(Err(Error::DoublePickOutput)),
+            _ => return None,
+        }
+
+        match replace(&mut self.round, R1::Gone) {
+            R1::Final(result) => Some(Ok(result)),
+            _ => unreachable!("guaranteed by match expression above"),
+        }
+    }
+
+    fn current_round(&self) -> u16 {
+        match &self.round {
+            R1::Round1(_) => 1,
+            R1::Final(_) | R1::Gone => 2,
+        }
+    }
+
+    fn total_rounds(&self) -> Option<u16> {
+        Some(2)
+    }
+
+    fn party_ind(&self) -> u16 {
+        PARTY_ONE
+    }
+
+    fn parties(&self) -> u16 {
+        2
+    }
+}
+
+impl fmt::Debug for Party1Migration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let current_round = match &self.round {
+            R1::Round1(_) => "1",
+            R1::Final(_) => "[Final]",
+            R1::Gone => "[Gone]",
+        };
+        write!(
+            f,
+            "{{Party1Migration at round={} msgs1={} queue=[len={}]}}",
+            current_round,
+            store_status(&self.msgs1),
+            self.msgs_queue.len()
+        )
+    }
+}
+
+/// Migration state machine of party two
+pub struct Party2Migration {
+    round: R2,
+
+    msgs2: Option<Store<P2PMsgs<Point<Secp256k1>>>>,
+
+    msgs_queue: Vec<Msg<ProtocolMessage>>,
+}
+
+impl Party2Migration {
+    /// Constructs party two of migration protocol
+    ///
+    /// Takes `local_key` obtained from [Party2Keygen](super::Party2Keygen) (or rotation).
+    pub fn new(local_key: Party2LocalKey) -> Self {
+        let (message_b, u_i) = local_key.private.to_mta_message_b(
+            &local_key.paillier_public.ek,
+            &local_key.paillier_public.encrypted_secret_share,
+        );
+        Self {
+            round: R2::Round2 {
+                u_i,
+                public_key: local_key.public_key,
+            },
+
+            msgs2: Some(P2PMsgsStore::new(PARTY_TWO, 2)),
+
+            msgs_queue: vec![Msg {
+                sender: PARTY_TWO,
+                receiver: Some(PARTY_ONE),
+                body: ProtocolMessage(M::Migration1Party2(Box::new(message_b))),
+            }],
+        }
+    }
+
+    /// Proceeds round state if it received enough messages
+    fn proceed_round(&mut self) -> Result<()> {
+        let store2_wants_more = wants_more(&self.msgs2);
+
+        let next_state = match replace(&mut self.round, R2::Gone) {
+            R2::Round2 { u_i, public_key } if !store2_wants_more => {
+                let party_one_public_share = take_msg(&mut self.msgs2)?;
+                if party_one_public_share + Point::generator() * &u_i != public_key {
+                    return Err(Error::InvalidMta);
+                }
+                R2::Final(AdditiveShare { u_i, public_key })
+            }
+            s => s,
+        };
+
+        self.round = next_state;
+        Ok(())
+    }
+}
+
+impl StateMachine for Party2Migration {
+    type MessageBody = ProtocolMessage;
+    type Err = Error;
+    type Output = AdditiveShare;
+
+    fn handle_incoming(&mut self, msg: Msg<Self::MessageBody>) -> Result<()> {
+        let current_round = self.current_round();
+        let Msg {
+            sender,
+            receiver,
+            body: ProtocolMessage(body),
+        } = msg;
+
+        match body {
+            M::Migration2Party1(m) => push_msg(
+                &mut self.msgs2,
+                Msg {
+                    sender,
+                    receiver,
+                    body: m,
+                },
+                current_round,
+                2,
+            )?,
+            _ => return Err(Error::UnexpectedMessage),
+        }
+        self.proceed_round()
+    }
+
+    fn message_queue(&mut self) -> &mut Vec<Msg<Self::MessageBody>> {
+        &mut self.msgs_queue
+    }
+
+    fn wants_to_proceed(&self) -> bool {
+        match &self.round {
+            R2::Round2 { .. } => !wants_more(&self.msgs2),
+            R2::Final(_) | R2::Gone => false,
+        }
+    }
+
+    fn proceed(&mut self) -> Result<()> {
+        self.proceed_round()
+    }
+
+    fn round_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    fn round_timeout_reached(&mut self) -> Self::Err {
+        panic!("no timeout was set")
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(self.round, R2::Final(_))
+    }
+
+    fn pick_output(&mut self) -> Option<Result<Self::Output>> {
+        match self.round {
+            R2::Final(_) => (),
+            R2::Gone => return Some(Err(Error::DoublePickOutput)),
+            _ => return None,
+        }
+
+        match replace(&mut self.round, R2::Gone) {
+            R2::Final(result) => Some(Ok(result)),
+            _ => unreachable!("guaranteed by match expression above"),
+        }
+    }
+
+    fn current_round(&self) -> u16 {
+        match &self.round {
+            R2::Round2 { .. } => 2,
+            R2::Final(_) | R2::Gone => 3,
+        }
+    }
+
+    fn total_rounds(&self) -> Option<u16> {
+        Some(2)
+    }
+
+    fn party_ind(&self) -> u16 {
+        PARTY_TWO
+    }
+
+    fn parties(&self) -> u16 {
+        2
+    }
+}
+
+impl fmt::Debug for Party2Migration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let current_round = match &self.round {
+            R2::Round2 { .. } => "2",
+            R2::Final(_) => "[Final]",
+            R2::Gone => "[Gone]",
+        };
+        write!(
+            f,
+            "{{Party2Migration at round={} msgs2={} queue=[len={}]}}",
+            current_round,
+            store_status(&self.msgs2),
+            self.msgs_queue.len()
+        )
+    }
+}
+
+// Rounds
+
+#[allow(clippy::large_enum_variant)]
+enum R1 {
+    Round1(Party1LocalKey),
+    Final(AdditiveShare),
+    Gone,
+}
+
+enum R2 {
+    Round2 {
+        u_i: Scalar<Secp256k1>,
+        public_key: Point<Secp256k1>,
+    },
+    Final(AdditiveShare),
+    Gone,
+}
+
+#[cfg(test)]
+mod test {
+    use round_based::dev::Simulation;
+
+    use super::*;
+    use crate::protocols::multi_party_ecdsa::gg_2020::state_machine::keygen::{
+        self, Keygen, LocalKey,
+    };
+    use crate::protocols::multi_party_ecdsa::gg_2020::state_machine::sign::test::{
+        simulate_offline_stage, simulate_signing,
+    };
+    use crate::protocols::two_party_ecdsa::lindell_2017::state_machine::keygen::test::simulate_keygen;
+    use crate::protocols::two_party_ecdsa::lindell_2017::state_machine::test::run_two_party;
+
+    fn import(
+        shares: Vec<AdditiveShare>,
+    ) -> std::result::Result<Vec<LocalKey<Secp256k1>>, keygen::Error> {
+        let mut simulation = Simulation::new();
+        for (i, share) in (1..).zip(shares) {
+            simulation.add_party(Keygen::import(i, 1, 2, share).unwrap());
+        }
+        simulation.run()
+    }
+
+    #[test]
+    fn migrated_key_signs_with_gg20() {
+        let (party1_key, party2_key) = simulate_keygen();
+        let public_key = party1_key.public_key.clone();
+
+        let (share1, share2) = run_two_party(
+            Party1Migration::new(party1_key),
+            Party2Migration::new(party2_key),
+        );
+        assert_eq!(Point::generator() * (&share1.u_i + &share2.u_i), public_key);
+
+        let local_keys = import(vec![share1, share2]).unwrap();
+        for local_key in &local_keys {
+            assert_eq!(local_key.public_key(), public_key);
+        }
+
+        let offline_stage = simulate_offline_stage(local_keys, &[1, 2]);
+        simulate_signing(offline_stage, b"migrated from Lindell 2017");
+    }
+
+    #[test]
+    fn import_detects_mismatching_shares() {
+        let (party1_key, party2_key) = simulate_keygen();
+        let (share1, mut share2) = run_two_party(
+            Party1Migration::new(party1_key),
+            Party2Migration::new(party2_key),
+        );
+        share2.u_i = Scalar::random();
+
+        assert!(matches!(
+            import(vec![share1, share2]),
+            Err(keygen::Error::PublicKeyMismatch)
+        ));
+    }
+}
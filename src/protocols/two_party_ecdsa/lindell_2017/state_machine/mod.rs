@@ -0,0 +1,248 @@
+//! High-level Lindell 2017 two-party protocol implementation
+//!
+//! Keygen and signing are exposed as [round_based::StateMachine]s, so they can be carried out
+//! over the same transport (e.g. [AsyncProtocol](round_based::AsyncProtocol)) as GG20. Party one
+//! always has index `1`, party two has index `2`, and all messages are sent point-to-point.
+//!
+//! Both parties exchange the same [ProtocolMessage] type, so a single channel can serve keygen,
+//! signing, [key rotation](rotation) and [migration](migration) to GG20. Receiving a message of
+//! another protocol (e.g. a signing message while doing keygen) results in
+//! [Error::UnexpectedMessage].
+
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Point};
+use curv::BigInt;
+use paillier::EncryptionKey;
+use round_based::containers::*;
+use round_based::{IsCritical, Msg};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use zk_paillier::zkproofs::{CompositeDLogProof, NiCorrectKeyProof};
+
+use super::{party_one, party_two};
+use crate::utilities::mta::MessageB;
+use crate::utilities::zk_pdl_with_slack::{PDLwSlackProof, PDLwSlackStatement};
+
+pub mod keygen;
+pub mod migration;
+pub mod rotation;
+pub mod sign;
+
+pub use keygen::{Party1Keygen, Party1LocalKey, Party2Keygen, Party2LocalKey};
+pub use migration::{Party1Migration, Party2Migration};
+pub use rotation::{Party1Rotation, Party2Rotation};
+pub use sign::{Party1Sign, Party2Sign};
+
+use private::InternalError;
+
+/// Index of party one
+pub const PARTY_ONE: u16 = 1;
+/// Index of party two
+pub const PARTY_TWO: u16 = 2;
+
+// Messages
+
+/// Protocol message which parties send on wire
+///
+/// Hides actual messages structure so it could be changed without breaking semver policy.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProtocolMessage(M);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+enum M {
+    Keygen1Party1(party_one::KeyGenFirstMsg),
+    Keygen1Party2(party_two::KeyGenFirstMsg),
+    Keygen2Party1(Box<KeygenDecommitment>),
+    Sign1Party1(party_one::EphKeyGenFirstMsg),
+    Sign1Party2(party_two::EphKeyGenFirstMsg),
+    Sign2Party2(Box<SignDecommitment>),
+    Sign3Party1(party_one::SignatureRecid),
+    Rotation1Party1(party_one::RotationFirstMsg),
+    Rotation1Party2(party_two::RotationFirstMsg),
+    Rotation2Party1(Box<party_one::RotationSecondMsg>),
+    Migration1Party2(Box<MessageB>),
+    Migration2Party1(Point<Secp256k1>),
+}
+
+/// Party one decommits its public share and proves correctness of its Paillier key and of the
+/// encrypted secret share
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct KeygenDecommitment {
+    second_msg: party_one::KeyGenSecondMsg,
+    paillier_ek: EncryptionKey,
+    encrypted_share: BigInt,
+    correct_key_proof: NiCorrectKeyProof,
+    pdl_statement: PDLwSlackStatement,
+    pdl_proof: PDLwSlackProof,
+    composite_dlog_proof: CompositeDLogProof,
+}
+
+/// Party two decommits its ephemeral public share and sends encrypted partial signature
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct SignDecommitment {
+    second_msg: party_two::EphKeyGenSecondMsg,
+    partial_sig: party_two::PartialSig,
+}
+
+/// Pushes message received from the counterparty to the store expecting it
+fn push_msg<T>(
+    store: &mut Option<Store<P2PMsgs<T>>>,
+    msg: Msg<T>,
+    current_round: u16,
+    msg_round: u16,
+) -> Result<()> {
+    let store = store.as_mut().ok_or(Error::ReceivedOutOfOrderMessage {
+        current_round,
+        msg_round,
+    })?;
+    store.push_msg(msg).map_err(Error::HandleMessage)
+}
+
+/// Takes the only message received by the store
+fn take_msg<T: 'static>(store: &mut Option<Store<P2PMsgs<T>>>) -> Result<T> {
+    let store = store.take().ok_or(InternalError::StoreGone)?;
+    let msgs = store
+        .finish()
+        .map_err(InternalError::RetrieveRoundMessages)?;
+    msgs.into_vec()
+        .into_iter()
+        .next()
+        .ok_or_else(|| InternalError::StoreGone.into())
+}
+
+fn wants_more<T>(store: &Option<Store<P2PMsgs<T>>>) -> bool {
+    store.as_ref().map(|s| s.wants_more()).unwrap_or(false)
+}
+
+fn store_status<T>(store: &Option<Store<P2PMsgs<T>>>) -> String {
+    match store.as_ref() {
+        Some(msgs) => format!("[{}/{}]", msgs.messages_received(), msgs.messages_total()),
+        None => "[None]".into(),
+    }
+}
+
+// Error
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Error type of Lindell 2017 keygen and signing protocols
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// Counterparty's decommitment doesn't match its commitment, or its proof of knowledge of
+    /// the secret share is invalid
+    #[error("counterparty's decommitment or proof of knowledge is invalid")]
+    InvalidDecommitment,
+    /// Counterparty's discrete log proof is invalid
+    #[error("counterparty's discrete log proof is invalid")]
+    InvalidDLogProof,
+    /// Party one's Paillier key is not proven to be correct
+    #[error("party one's Paillier key is not proven to be correct")]
+    InvalidPaillierKey,
+    /// Party one didn't prove that Paillier ciphertext encrypts its secret share
+    #[error("PDL proof is invalid")]
+    InvalidPdlProof,
+    /// Resulting signature is not valid for the public key, either partial signature computed by
+    /// party two or signature sent by party one is incorrect
+    #[error("resulting signature is invalid")]
+    InvalidSignature,
+    /// Coin toss resulted in zero rotation factor, rotation must be started over
+    #[error("rotation factor is zero")]
+    ZeroRotationFactor,
+    /// Counterparty's MtA message is invalid, or resulting additive shares don't add up to the
+    /// public key
+    #[error("MtA shares are inconsistent with the public key")]
+    InvalidMta,
+
+    /// Received message which belongs to another protocol or is sent by a wrong party
+    #[error("received message which doesn't belong to this protocol")]
+    UnexpectedMessage,
+    /// Received message didn't pass pre-validation
+    #[error("received message didn't pass pre-validation: {0}")]
+    HandleMessage(#[source] StoreErr),
+    /// Received message which we didn't expect to receive now (e.g. message from previous round)
+    #[error(
+        "didn't expect to receive message from round {msg_round} (being at round {current_round})"
+    )]
+    ReceivedOutOfOrderMessage { current_round: u16, msg_round: u16 },
+    /// `pick_output` called twice
+    #[error("pick_output called twice")]
+    DoublePickOutput,
+
+    /// Some internal assertions were failed, which is a bug
+    #[doc(hidden)]
+    #[error("internal error: {0:?}")]
+    InternalError(InternalError),
+}
+
+impl IsCritical for Error {
+    fn is_critical(&self) -> bool {
+        true
+    }
+}
+
+impl From<InternalError> for Error {
+    fn from(err: InternalError) -> Self {
+        Self::InternalError(err)
+    }
+}
+
+mod private {
+    #[derive(Debug)]
+    #[non_exhaustive]
+    pub enum InternalError {
+        /// [Messages store](super::MessageStore) reported that it received all messages it wanted to receive,
+        /// but refused to return message container
+        RetrieveRoundMessages(super::StoreErr),
+        #[doc(hidden)]
+        StoreGone,
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use round_based::{Msg, StateMachine};
+
+    use super::ProtocolMessage;
+
+    /// Carries out the protocol between party one and party two, delivering messages between them
+    /// until both parties are finished
+    pub fn run_two_party<P1, P2>(mut party1: P1, mut party2: P2) -> (P1::Output, P2::Output)
+    where
+        P1: StateMachine<MessageBody = ProtocolMessage>,
+        P2: StateMachine<MessageBody = ProtocolMessage>,
+        P1::Err: std::fmt::Debug,
+        P2::Err: std::fmt::Debug,
+    {
+        loop {
+            if party1.wants_to_proceed() {
+                party1.proceed().unwrap();
+            }
+            if party2.wants_to_proceed() {
+                party2.proceed().unwrap();
+            }
+
+            let to_party2: Vec<Msg<ProtocolMessage>> = party1.message_queue().drain(..).collect();
+            let to_party1: Vec<Msg<ProtocolMessage>> = party2.message_queue().drain(..).collect();
+            let idle = to_party1.is_empty() && to_party2.is_empty();
+            for msg in to_party2 {
+                party2.handle_incoming(msg).unwrap();
+            }
+            for msg in to_party1 {
+                party1.handle_incoming(msg).unwrap();
+            }
+
+            if party1.is_finished() && party2.is_finished() {
+                break;
+            }
+            assert!(
+                !idle || party1.wants_to_proceed() || party2.wants_to_proceed(),
+                "protocol got stuck"
+            );
+        }
+
+        (
+            party1.pick_output().unwrap().unwrap(),
+            party2.pick_output().unwrap().unwrap(),
+        )
+    }
+}
@@ -0,0 +1,513 @@
+//! Two-party key rotation
+//!
+//! Parties toss a coin to agree on a random factor `r`: party one commits to its seed, party two
+//! replies with its seed, then party one reveals its seed. Party one multiplies its share by `r`,
+//! generates a new Paillier key and proves correctness of the new key and encrypted share, party
+//! two multiplies its share by `r^-1`. Joint public key stays the same, while shares obtained
+//! before rotation can't be used together with rotated ones.
+
+use std::fmt;
+use std::mem::replace;
+use std::time::Duration;
+
+use round_based::containers::*;
+use round_based::{Msg, StateMachine};
+
+use super::keygen::{Party1LocalKey, Party2LocalKey};
+use super::{
+    push_msg, store_status, take_msg, wants_more, Error, ProtocolMessage, Result, M, PARTY_ONE,
+    PARTY_TWO,
+};
+use crate::protocols::two_party_ecdsa::lindell_2017::party_two::PartyTwoError;
+use crate::protocols::two_party_ecdsa::lindell_2017::{party_one, party_two};
+
+/// Key rotation state machine of party one
+pub struct Party1Rotation {
+    round: R1,
+
+    msgs1: Option<Store<P2PMsgs<party_two::RotationFirstMsg>>>,
+
+    msgs_queue: Vec<Msg<ProtocolMessage>>,
+}
+
+impl Party1Rotation {
+    /// Constructs party one of key rotation protocol
+    ///
+    /// Takes `local_key` obtained from [Party1Keygen](super::Party1Keygen) (or previous rotation).
+    pub fn new(local_key: Party1LocalKey) -> Self {
+        let (first_msg, comm_witness) = party_one::RotationFirstMsg::commit();
+        Self {
+            round: R1::Round1 {
+                local_key,
+                comm_witness,
+            },
+
+            msgs1: Some(P2PMsgsStore::new(PARTY_ONE, 2)),
+
+            msgs_queue: vec![Msg {
+                sender: PARTY_ONE,
+                receiver: Some(PARTY_TWO),
+                body: ProtocolMessage(M::Rotation1Party1(first_msg)),
+            }],
+        }
+    }
+
+    /// Proceeds round state if it received enough messages and `may_block == true` (computing
+    /// Paillier key and proofs is expensive)
+    fn proceed_round(&mut self, may_block: bool) -> Result<()> {
+        let store1_wants_more = wants_more(&self.msgs1);
+
+        let next_state = match replace(&mut self.round, R1::Gone) {
+            R1::Round1 {
+                local_key,
+                comm_witness,
+            } if !store1_wants_more && may_block => {
+                let party_two_first = take_msg(&mut self.msgs1)?;
+                let (second_msg, private, factor) =
+                    party_one::RotationSecondMsg::reveal_and_rotate(
+                        comm_witness,
+                        &party_two_first,
+                        &local_key.private,
+                    )
+                    .map_err(|_| Error::ZeroRotationFactor)?;
+                let factor_inv = factor.invert().ok_or(Error::ZeroRotationFactor)?;
+
+                let rotated_key = Party1LocalKey {
+                    private,
+                    paillier_ek: second_msg.ek_new.clone(),
+                    encrypted_share: second_msg.encrypted_share_new.clone(),
+                    public_share: second_msg.pdl_statement.Q.clone(),
+                    counterparty_public_share: &local_key.counterparty_public_share * &factor_inv,
+                    public_key: local_key.public_key,
+                };
+                self.msgs_queue.push(Msg {
+                    sender: PARTY_ONE,
+                    receiver: Some(PARTY_TWO),
+                    body: ProtocolMessage(M::Rotation2Party1(Box::new(second_msg))),
+                });
+                R1::Final(rotated_key)
+            }
+            s => s,
+        };
+
+        self.round = next_state;
+        Ok(())
+    }
+}
+
+impl StateMachine for Party1Rotation {
+    type MessageBody = ProtocolMessage;
+    type Err = Error;
+    type Output = Party1LocalKey;
+
+    fn handle_incoming(&mut self, msg: Msg<Self::MessageBody>) -> Result<()> {
+        let current_round = self.current_round();
+        let Msg {
+            sender,
+            receiver,
+            body: ProtocolMessage(body),
+        } = msg;
+
+        match body {
+            M::Rotation1Party2(m) => push_msg(
+                &mut self.msgs1,
+                Msg {
+                    sender,
+                    receiver,
+                    body: m,
+                },
+                current_round,
+                1,
+            )?,
+            _ => return Err(Error::UnexpectedMessage),
+        }
+        self.proceed_round(false)
+    }
+
+    fn message_queue(&mut self) -> &mut Vec<Msg<Self::MessageBody>> {
+        &mut self.msgs_queue
+    }
+
+    fn wants_to_proceed(&self) -> bool {
+        match &self.round {
+            R1::Round1 { .. } => !wants_more(&self.msgs1),
+            R1::Final(_) | R1::Gone => false,
+        }
+    }
+
+    fn proceed(&mut self) -> Result<()> {
+        self.proceed_round(true)
+    }
+
+    fn round_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    fn round_timeout_reached(&mut self) -> Self::Err {
+        panic!("no timeout was set")
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(self.round, R1::Final(_))
+    }
+
+    fn pick_output(&mut self) -> Option<Result<Self::Output>> {
+        match self.round {
+            R1::Final(_) => (),
+            R1::Gone => return Some(Err(Error::DoublePickOutput)),
+            _ => return None,
+        }
+
+        match replace(&mut self.round, R1::Gone) {
+            R1::Final(result) => Some(Ok(result)),
+            _ => unreachable!("guaranteed by match expression above"),
+        }
+    }
+
+    fn current_round(&self) -> u16 {
+        match &self.round {
+            R1::Round1 { .. } => 1,
+            R1::Final(_) | R1::Gone => 2,
+        }
+    }
+
+    fn total_rounds(&self) -> Option<u16> {
+        Some(2)
+    }
+
+    fn party_ind(&self) -> u16 {
+        PARTY_ONE
+    }
+
+    fn parties(&self) -> u16 {
+        2
+    }
+}
+
+impl fmt::Debug for Party1Rotation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let current_round = match &self.round {
+            R1::Round1 { .. } => "1",
+            R1::Final(_) => "[Final]",
+            R1::Gone => "[Gone]",
+        };
+        write!(
+            f,
+            "{{Party1Rotation at round={} msgs1={} queue=[len={}]}}",
+            current_round,
+            store_status(&self.msgs1),
+            self.msgs_queue.len()
+        )
+    }
+}
+
+/// Key rotation state machine of party two
+pub struct Party2Rotation {
+    round: R2,
+
+    msgs1: Option<Store<P2PMsgs<party_one::RotationFirstMsg>>>,
+    msgs2: Option<Store<P2PMsgs<party_one::RotationSecondMsg>>>,
+
+    msgs_queue: Vec<Msg<ProtocolMessage>>,
+}
+
+impl Party2Rotation {
+    /// Constructs party two of key rotation protocol
+    ///
+    /// Takes `local_key` obtained from [Party2Keygen](super::Party2Keygen) (or previous rotation).
+    /// Party two's seed is sent only once party one's commitment is received, so party one can't
+    /// choose its seed depending on it.
+    pub fn new(local_key: Party2LocalKey) -> Self {
+        Self {
+            round: R2::Round1 {
+                local_key,
+                first_msg: party_two::RotationFirstMsg::share(),
+            },
+
+            msgs1: Some(P2PMsgsStore::new(PARTY_TWO, 2)),
+            msgs2: Some(P2PMsgsStore::new(PARTY_TWO, 2)),
+
+            msgs_queue: vec![],
+        }
+    }
+
+    /// Proceeds round state if it received enough messages and if it's cheap to compute or
+    /// `may_block == true`
+    fn proceed_round(&mut self, may_block: bool) -> Result<()> {
+        let store1_wants_more = wants_more(&self.msgs1);
+        let store2_wants_more = wants_more(&self.msgs2);
+
+        let next_state: R2;
+        let try_again: bool = match replace(&mut self.round, R2::Gone) {
+            R2::Round1 {
+                local_key,
+                first_msg,
+            } if !store1_wants_more => {
+                let party_one_first = take_msg(&mut self.msgs1)?;
+                self.msgs_queue.push(Msg {
+                    sender: PARTY_TWO,
+                    receiver: Some(PARTY_ONE),
+                    body: ProtocolMessage(M::Rotation1Party2(first_msg.clone())),
+                });
+                next_state = R2::Round2 {
+                    local_key,
+                    first_msg,
+                    party_one_first,
+                };
+                true
+            }
+            R2::Round2 {
+                local_key,
+                first_msg,
+                party_one_first,
+            } if !store2_wants_more && may_block => {
+                let party_one_second = take_msg(&mut self.msgs2)?;
+                let factor = &party_one_second.comm_witness.seed + &first_msg.seed;
+                let (private, paillier_public, counterparty_public_share) = first_msg
+                    .verify_and_rotate(
+                        &party_one_first,
+                        party_one_second,
+                        &local_key.private,
+                        &local_key.counterparty_public_share,
+                    )
+                    .map_err(rotation_error)?;
+                let factor_inv = factor.invert().ok_or(Error::ZeroRotationFactor)?;
+
+                next_state = R2::Final(Party2LocalKey {
+                    private,
+                    paillier_public,
+                    public_share: &local_key.public_share * &factor_inv,
+                    counterparty_public_share,
+                    public_key: local_key.public_key,
+                });
+                true
+            }
+            s => {
+                next_state = s;
+                false
+            }
+        };
+
+        self.round = next_state;
+        if try_again {
+            self.proceed_round(may_block)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl StateMachine for Party2Rotation {
+    type MessageBody = ProtocolMessage;
+    type Err = Error;
+    type Output = Party2LocalKey;
+
+    fn handle_incoming(&mut self, msg: Msg<Self::MessageBody>) -> Result<()> {
+        let current_round = self.current_round();
+        let Msg {
+            sender,
+            receiver,
+            body: ProtocolMessage(body),
+        } = msg;
+
+        match body {
+            M::Rotation1Party1(m) => push_msg(
+                &mut self.msgs1,
+                Msg {
+                    sender,
+                    receiver,
+                    body: m,
+                },
+                current_round,
+                1,
+            )?,
+            M::Rotation2Party1(m) => push_msg(
+                &mut self.msgs2,
+                Msg {
+                    sender,
+                    receiver,
+                    body: *m,
+                },
+                current_round,
+                2,
+            )?,
+            _ => return Err(Error::UnexpectedMessage),
+        }
+        self.proceed_round(false)
+    }
+
+    fn message_queue(&mut self) -> &mut Vec<Msg<Self::MessageBody>> {
+        &mut self.msgs_queue
+    }
+
+    fn wants_to_proceed(&self) -> bool {
+        match &self.round {
+            R2::Round1 { .. } => !wants_more(&self.msgs1),
+            R2::Round2 { .. } => !wants_more(&self.msgs2),
+            R2::Final(_) | R2::Gone => false,
+        }
+    }
+
+    fn proceed(&mut self) -> Result<()> {
+        self.proceed_round(true)
+    }
+
+    fn round_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    fn round_timeout_reached(&mut self) -> Self::Err {
+        panic!("no timeout was set")
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(self.round, R2::Final(_))
+    }
+
+    fn pick_output(&mut self) -> Option<Result<Self::Output>> {
+        match self.round {
+            R2::Final(_) => (),
+            R2::Gone => return Some(Err(Error::DoublePickOutput)),
+            _ => return None,
+        }
+
+        match replace(&mut self.round, R2::Gone) {
+            R2::Final(result) => Some(Ok(result)),
+            _ => unreachable!("guaranteed by match expression above"),
+        }
+    }
+
+    fn current_round(&self) -> u16 {
+        match &self.round {
+            R2::Round1 { .. } => 1,
+            R2::Round2 { .. } => 2,
+            R2::Final(_) | R2::Gone => 3,
+        }
+    }
+
+    fn total_rounds(&self) -> Option<u16> {
+        Some(2)
+    }
+
+    fn party_ind(&self) -> u16 {
+        PARTY_TWO
+    }
+
+    fn parties(&self) -> u16 {
+        2
+    }
+}
+
+impl fmt::Debug for Party2Rotation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let current_round = match &self.round {
+            R2::Round1 { .. } => "1",
+            R2::Round2 { .. } => "2",
+            R2::Final(_) => "[Final]",
+            R2::Gone => "[Gone]",
+        };
+        write!(
+            f,
+            "{{Party2Rotation at round={} msgs1={} msgs2={} queue=[len={}]}}",
+            current_round,
+            store_status(&self.msgs1),
+            store_status(&self.msgs2),
+            self.msgs_queue.len()
+        )
+    }
+}
+
+fn rotation_error(err: PartyTwoError) -> Error {
+    match err {
+        PartyTwoError::RotationDecommit => Error::InvalidDecommitment,
+        PartyTwoError::CorrectKeyVerify => Error::InvalidPaillierKey,
+        PartyTwoError::PdlVerify => Error::InvalidPdlProof,
+        PartyTwoError::ZeroRotationFactor => Error::ZeroRotationFactor,
+    }
+}
+
+// Rounds
+
+#[allow(clippy::large_enum_variant)]
+enum R1 {
+    Round1 {
+        local_key: Party1LocalKey,
+        comm_witness: party_one::RotationCommWitness,
+    },
+    Final(Party1LocalKey),
+    Gone,
+}
+
+#[allow(clippy::large_enum_variant)]
+enum R2 {
+    Round1 {
+        local_key: Party2LocalKey,
+        first_msg: party_two::RotationFirstMsg,
+    },
+    Round2 {
+        local_key: Party2LocalKey,
+        first_msg: party_two::RotationFirstMsg,
+        party_one_first: party_one::RotationFirstMsg,
+    },
+    Final(Party2LocalKey),
+    Gone,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::protocols::two_party_ecdsa::lindell_2017::state_machine::keygen::test::simulate_keygen;
+    use crate::protocols::two_party_ecdsa::lindell_2017::state_machine::test::run_two_party;
+    use crate::protocols::two_party_ecdsa::lindell_2017::state_machine::{Party1Sign, Party2Sign};
+    use crate::utilities::digest::MessageDigest;
+    use crate::utilities::signature::SignatureEncoding;
+
+    #[test]
+    fn rotated_keys_keep_public_key_and_sign() {
+        let (party1_key, party2_key) = simulate_keygen();
+        let (rotated1, rotated2) = run_two_party(
+            Party1Rotation::new(party1_key.clone()),
+            Party2Rotation::new(party2_key),
+        );
+
+        assert_eq!(rotated1.public_key, party1_key.public_key);
+        assert_eq!(rotated2.public_key, party1_key.public_key);
+        assert_ne!(rotated1.public_share, party1_key.public_share);
+        assert_ne!(rotated1.paillier_ek.n, party1_key.paillier_ek.n);
+        assert_eq!(rotated1.public_share, rotated2.counterparty_public_share);
+        assert_eq!(rotated2.public_share, rotated1.counterparty_public_share);
+        assert_eq!(
+            rotated1.encrypted_share,
+            rotated2.paillier_public.encrypted_secret_share
+        );
+
+        let digest = MessageDigest::sha256(b"rotated");
+        let (signature, _) = run_two_party(
+            Party1Sign::new(rotated1, digest),
+            Party2Sign::new(rotated2, digest),
+        );
+        assert_eq!(
+            signature.recover_public_key(&digest).unwrap(),
+            party1_key.public_key
+        );
+    }
+
+    #[test]
+    fn party_two_sends_seed_only_after_party_one_commits() {
+        let (party1_key, party2_key) = simulate_keygen();
+        let mut party1 = Party1Rotation::new(party1_key);
+        let mut party2 = Party2Rotation::new(party2_key);
+        assert!(party2.message_queue().is_empty());
+        assert!(!party2.wants_to_proceed());
+
+        let commitment = party1.message_queue().remove(0);
+        party2.handle_incoming(commitment).unwrap();
+        assert!(matches!(
+            party2.message_queue().as_slice(),
+            [Msg {
+                body: ProtocolMessage(M::Rotation1Party2(_)),
+                ..
+            }]
+        ));
+    }
+}
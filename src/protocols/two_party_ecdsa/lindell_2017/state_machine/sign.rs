@@ -0,0 +1,556 @@
+//! Two-party signing
+//!
+//! Parties generate ephemeral key `k = k1 * k2` (party two commits to its share first), then party
+//! two computes encrypted partial signature using Paillier encryption of `x1` obtained at keygen.
+//! Party one decrypts it, completes the signature and sends it to party two, so both parties
+//! output the same [SignatureRecid](party_one::SignatureRecid).
+
+use std::fmt;
+use std::mem::replace;
+use std::time::Duration;
+
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Point};
+use round_based::containers::*;
+use round_based::{Msg, StateMachine};
+
+use super::keygen::{Party1LocalKey, Party2LocalKey};
+use super::{
+    push_msg, store_status, take_msg, wants_more, Error, ProtocolMessage, Result, SignDecommitment,
+    M, PARTY_ONE, PARTY_TWO,
+};
+use crate::protocols::two_party_ecdsa::lindell_2017::{party_one, party_two};
+use crate::utilities::digest::MessageDigest;
+
+/// Signing state machine of party one
+pub struct Party1Sign {
+    round: R1,
+
+    msgs1: Option<Store<P2PMsgs<party_two::EphKeyGenFirstMsg>>>,
+    msgs2: Option<Store<P2PMsgs<SignDecommitment>>>,
+
+    msgs_queue: Vec<Msg<ProtocolMessage>>,
+}
+
+impl Party1Sign {
+    /// Constructs party one of signing protocol
+    ///
+    /// Takes `local_key` obtained from [Party1Keygen](super::Party1Keygen) and `message` to be
+    /// signed. Party one's ephemeral public share is sent only once party two's commitment is
+    /// received.
+    pub fn new(local_key: Party1LocalKey, message: MessageDigest) -> Self {
+        let (first_msg, eph_ec_key_pair) = party_one::EphKeyGenFirstMsg::create();
+        Self {
+            round: R1::Round1(
+                Party1State {
+                    local_key,
+                    message,
+                    eph_ec_key_pair,
+                },
+                first_msg,
+            ),
+
+            msgs1: Some(P2PMsgsStore::new(PARTY_ONE, 2)),
+            msgs2: Some(P2PMsgsStore::new(PARTY_ONE, 2)),
+
+            msgs_queue: vec![],
+        }
+    }
+
+    /// Proceeds round state if it received enough messages and if it's cheap to compute or
+    /// `may_block == true`
+    fn proceed_round(&mut self, may_block: bool) -> Result<()> {
+        let store1_wants_more = wants_more(&self.msgs1);
+        let store2_wants_more = wants_more(&self.msgs2);
+
+        let next_state: R1;
+        let try_again: bool = match replace(&mut self.round, R1::Gone) {
+            R1::Round1(state, first_msg) if !store1_wants_more => {
+                let party_two_first = take_msg(&mut self.msgs1)?;
+                self.msgs_queue.push(Msg {
+                    sender: PARTY_ONE,
+                    receiver: Some(PARTY_TWO),
+                    body: ProtocolMessage(M::Sign1Party1(first_msg)),
+                });
+                next_state = R1::Round2(state, party_two_first);
+                true
+            }
+            R1::Round2(state, party_two_first) if !store2_wants_more && may_block => {
+                let decommitment = take_msg(&mut self.msgs2)?;
+                party_one::EphKeyGenSecondMsg::verify_commitments_and_dlog_proof(
+                    &party_two_first,
+                    &decommitment.second_msg,
+                )
+                .map_err(|_| Error::InvalidDecommitment)?;
+
+                let signature = party_one::Signature::compute_with_recid(
+                    &state.local_key.private,
+                    &decommitment.partial_sig.c3,
+                    &state.eph_ec_key_pair,
+                    &decommitment.second_msg.comm_witness.public_share,
+                );
+                verify(&signature, &state.local_key.public_key, &state.message)?;
+
+                self.msgs_queue.push(Msg {
+                    sender: PARTY_ONE,
+                    receiver: Some(PARTY_TWO),
+                    body: ProtocolMessage(M::Sign3Party1(signature.clone())),
+                });
+                next_state = R1::Final(signature);
+                true
+            }
+            s => {
+                next_state = s;
+                false
+            }
+        };
+
+        self.round = next_state;
+        if try_again {
+            self.proceed_round(may_block)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl StateMachine for Party1Sign {
+    type MessageBody = ProtocolMessage;
+    type Err = Error;
+    type Output = party_one::SignatureRecid;
+
+    fn handle_incoming(&mut self, msg: Msg<Self::MessageBody>) -> Result<()> {
+        let current_round = self.current_round();
+        let Msg {
+            sender,
+            receiver,
+            body: ProtocolMessage(body),
+        } = msg;
+
+        match body {
+            M::Sign1Party2(m) => push_msg(
+                &mut self.msgs1,
+                Msg {
+                    sender,
+                    receiver,
+                    body: m,
+                },
+                current_round,
+                1,
+            )?,
+            M::Sign2Party2(m) => push_msg(
+                &mut self.msgs2,
+                Msg {
+                    sender,
+                    receiver,
+                    body: *m,
+                },
+                current_round,
+                2,
+            )?,
+            _ => return Err(Error::UnexpectedMessage),
+        }
+        self.proceed_round(false)
+    }
+
+    fn message_queue(&mut self) -> &mut Vec<Msg<Self::MessageBody>> {
+        &mut self.msgs_queue
+    }
+
+    fn wants_to_proceed(&self) -> bool {
+        match &self.round {
+            R1::Round1(..) => !wants_more(&self.msgs1),
+            R1::Round2(..) => !wants_more(&self.msgs2),
+            R1::Final(_) | R1::Gone => false,
+        }
+    }
+
+    fn proceed(&mut self) -> Result<()> {
+        self.proceed_round(true)
+    }
+
+    fn round_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    fn round_timeout_reached(&mut self) -> Self::Err {
+        panic!("no timeout was set")
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(self.round, R1::Final(_))
+    }
+
+    fn pick_output(&mut self) -> Option<Result<Self::Output>> {
+        match self.round {
+            R1::Final(_) => (),
+            R1::Gone => return Some(Err(Error::DoublePickOutput)),
+            _ => return None,
+        }
+
+        match replace(&mut self.round, R1::Gone) {
+            R1::Final(result) => Some(Ok(result)),
+            _ => unreachable!("guaranteed by match expression above"),
+        }
+    }
+
+    fn current_round(&self) -> u16 {
+        match &self.round {
+            R1::Round1(..) => 1,
+            R1::Round2(..) => 2,
+            R1::Final(_) | R1::Gone => 3,
+        }
+    }
+
+    fn total_rounds(&self) -> Option<u16> {
+        Some(3)
+    }
+
+    fn party_ind(&self) -> u16 {
+        PARTY_ONE
+    }
+
+    fn parties(&self) -> u16 {
+        2
+    }
+}
+
+impl fmt::Debug for Party1Sign {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let current_round = match &self.round {
+            R1::Round1(..) => "1",
+            R1::Round2(..) => "2",
+            R1::Final(_) => "[Final]",
+            R1::Gone => "[Gone]",
+        };
+        write!(
+            f,
+            "{{Party1Sign at round={} msgs1={} msgs2={} queue=[len={}]}}",
+            current_round,
+            store_status(&self.msgs1),
+            store_status(&self.msgs2),
+            self.msgs_queue.len()
+        )
+    }
+}
+
+/// Signing state machine of party two
+pub struct Party2Sign {
+    round: R2,
+
+    msgs1: Option<Store<P2PMsgs<party_one::EphKeyGenFirstMsg>>>,
+    msgs3: Option<Store<P2PMsgs<party_one::SignatureRecid>>>,
+
+    msgs_queue: Vec<Msg<ProtocolMessage>>,
+}
+
+impl Party2Sign {
+    /// Constructs party two of signing protocol
+    ///
+    /// Takes `local_key` obtained from [Party2Keygen](super::Party2Keygen) and `message` to be
+    /// signed.
+    pub fn new(local_key: Party2LocalKey, message: MessageDigest) -> Self {
+        let (first_msg, eph_comm_witness, eph_ec_key_pair) =
+            party_two::EphKeyGenFirstMsg::create_commitments();
+        Self {
+            round: R2::Round1(
+                Party2State {
+                    local_key,
+                    message,
+                    eph_ec_key_pair,
+                },
+                eph_comm_witness,
+            ),
+
+            msgs1: Some(P2PMsgsStore::new(PARTY_TWO, 2)),
+            msgs3: Some(P2PMsgsStore::new(PARTY_TWO, 2)),
+
+            msgs_queue: vec![Msg {
+                sender: PARTY_TWO,
+                receiver: Some(PARTY_ONE),
+                body: ProtocolMessage(M::Sign1Party2(first_msg)),
+            }],
+        }
+    }
+
+    /// Proceeds round state if it received enough messages and if it's cheap to compute or
+    /// `may_block == true`
+    fn proceed_round(&mut self, may_block: bool) -> Result<()> {
+        let store1_wants_more = wants_more(&self.msgs1);
+        let store3_wants_more = wants_more(&self.msgs3);
+
+        let next_state: R2;
+        let try_again: bool = match replace(&mut self.round, R2::Gone) {
+            R2::Round1(state, eph_comm_witness) if !store1_wants_more && may_block => {
+                let party_one_first = take_msg(&mut self.msgs1)?;
+                let second_msg = party_two::EphKeyGenSecondMsg::verify_and_decommit(
+                    eph_comm_witness,
+                    &party_one_first,
+                )
+                .map_err(|_| Error::InvalidDLogProof)?;
+                let partial_sig = party_two::PartialSig::compute(
+                    &state.local_key.paillier_public.ek,
+                    &state.local_key.paillier_public.encrypted_secret_share,
+                    &state.local_key.private,
+                    &state.eph_ec_key_pair,
+                    &party_one_first.public_share,
+                    &state.message,
+                );
+
+                self.msgs_queue.push(Msg {
+                    sender: PARTY_TWO,
+                    receiver: Some(PARTY_ONE),
+                    body: ProtocolMessage(M::Sign2Party2(Box::new(SignDecommitment {
+                        second_msg,
+                        partial_sig,
+                    }))),
+                });
+                next_state = R2::Round3(state);
+                true
+            }
+            R2::Round3(state) if !store3_wants_more => {
+                let signature = take_msg(&mut self.msgs3)?;
+                verify(&signature, &state.local_key.public_key, &state.message)?;
+                next_state = R2::Final(signature);
+                true
+            }
+            s => {
+                next_state = s;
+                false
+            }
+        };
+
+        self.round = next_state;
+        if try_again {
+            self.proceed_round(may_block)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl StateMachine for Party2Sign {
+    type MessageBody = ProtocolMessage;
+    type Err = Error;
+    type Output = party_one::SignatureRecid;
+
+    fn handle_incoming(&mut self, msg: Msg<Self::MessageBody>) -> Result<()> {
+        let current_round = self.current_round();
+        let Msg {
+            sender,
+            receiver,
+            body: ProtocolMessage(body),
+        } = msg;
+
+        match body {
+            M::Sign1Party1(m) => push_msg(
+                &mut self.msgs1,
+                Msg {
+                    sender,
+                    receiver,
+                    body: m,
+                },
+                current_round,
+                1,
+            )?,
+            M::Sign3Party1(m) => push_msg(
+                &mut self.msgs3,
+                Msg {
+                    sender,
+                    receiver,
+                    body: m,
+                },
+                current_round,
+                3,
+            )?,
+            _ => return Err(Error::UnexpectedMessage),
+        }
+        self.proceed_round(false)
+    }
+
+    fn message_queue(&mut self) -> &mut Vec<Msg<Self::MessageBody>> {
+        &mut self.msgs_queue
+    }
+
+    fn wants_to_proceed(&self) -> bool {
+        match &self.round {
+            R2::Round1(..) => !wants_more(&self.msgs1),
+            R2::Round3(_) => !wants_more(&self.msgs3),
+            R2::Final(_) | R2::Gone => false,
+        }
+    }
+
+    fn proceed(&mut self) -> Result<()> {
+        self.proceed_round(true)
+    }
+
+    fn round_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    fn round_timeout_reached(&mut self) -> Self::Err {
+        panic!("no timeout was set")
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(self.round, R2::Final(_))
+    }
+
+    fn pick_output(&mut self) -> Option<Result<Self::Output>> {
+        match self.round {
+            R2::Final(_) => (),
+            R2::Gone => return Some(Err(Error::DoublePickOutput)),
+            _ => return None,
+        }
+
+        match replace(&mut self.round, R2::Gone) {
+            R2::Final(result) => Some(Ok(result)),
+            _ => unreachable!("guaranteed by match expression above"),
+        }
+    }
+
+    fn current_round(&self) -> u16 {
+        match &self.round {
+            R2::Round1(..) => 1,
+            R2::Round3(_) => 3,
+            R2::Final(_) | R2::Gone => 4,
+        }
+    }
+
+    fn total_rounds(&self) -> Option<u16> {
+        Some(3)
+    }
+
+    fn party_ind(&self) -> u16 {
+        PARTY_TWO
+    }
+
+    fn parties(&self) -> u16 {
+        2
+    }
+}
+
+impl fmt::Debug for Party2Sign {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let current_round = match &self.round {
+            R2::Round1(..) => "1",
+            R2::Round3(_) => "3",
+            R2::Final(_) => "[Final]",
+            R2::Gone => "[Gone]",
+        };
+        write!(
+            f,
+            "{{Party2Sign at round={} msgs1={} msgs3={} queue=[len={}]}}",
+            current_round,
+            store_status(&self.msgs1),
+            store_status(&self.msgs3),
+            self.msgs_queue.len()
+        )
+    }
+}
+
+/// Checks that signature produced by the parties is valid for the joint public key
+fn verify(
+    signature: &party_one::SignatureRecid,
+    public_key: &Point<Secp256k1>,
+    message: &MessageDigest,
+) -> Result<()> {
+    let signature = party_one::Signature {
+        r: signature.r.clone(),
+        s: signature.s.clone(),
+    };
+    party_one::verify(&signature, public_key, &message.to_bigint())
+        .map_err(|_| Error::InvalidSignature)
+}
+
+// Rounds
+
+struct Party1State {
+    local_key: Party1LocalKey,
+    message: MessageDigest,
+    eph_ec_key_pair: party_one::EphEcKeyPair,
+}
+
+struct Party2State {
+    local_key: Party2LocalKey,
+    message: MessageDigest,
+    eph_ec_key_pair: party_two::EphEcKeyPair,
+}
+
+#[allow(clippy::large_enum_variant)]
+enum R1 {
+    Round1(Party1State, party_one::EphKeyGenFirstMsg),
+    Round2(Party1State, party_two::EphKeyGenFirstMsg),
+    Final(party_one::SignatureRecid),
+    Gone,
+}
+
+#[allow(clippy::large_enum_variant)]
+enum R2 {
+    Round1(Party2State, party_two::EphCommWitness),
+    Round3(Party2State),
+    Final(party_one::SignatureRecid),
+    Gone,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::protocols::two_party_ecdsa::lindell_2017::state_machine::keygen::test::simulate_keygen;
+    use crate::protocols::two_party_ecdsa::lindell_2017::state_machine::test::run_two_party;
+    use crate::utilities::signature::SignatureEncoding;
+
+    #[test]
+    fn simulate_keygen_and_signing() {
+        let (party1_key, party2_key) = simulate_keygen();
+        let public_key = party1_key.public_key.clone();
+
+        for message in [&b"ZenGo"[..], b"Lindell 2017"] {
+            let digest = MessageDigest::sha256(message);
+            let (party1_sig, party2_sig) = run_two_party(
+                Party1Sign::new(party1_key.clone(), digest),
+                Party2Sign::new(party2_key.clone(), digest),
+            );
+
+            assert_eq!(party1_sig.r, party2_sig.r);
+            assert_eq!(party1_sig.s, party2_sig.s);
+            assert!(party1_sig.is_low_s());
+            assert_eq!(party1_sig.recover_public_key(&digest).unwrap(), public_key);
+        }
+    }
+
+    #[test]
+    fn signature_for_another_message_is_rejected() {
+        let (party1_key, party2_key) = simulate_keygen();
+        let digest = MessageDigest::sha256(b"ZenGo");
+        let (signature, _) = run_two_party(
+            Party1Sign::new(party1_key.clone(), digest),
+            Party2Sign::new(party2_key.clone(), digest),
+        );
+
+        let other_digest = MessageDigest::sha256(b"another message");
+        assert!(matches!(
+            verify(&signature, &party1_key.public_key, &other_digest),
+            Err(Error::InvalidSignature)
+        ));
+        verify(&signature, &party2_key.public_key, &digest).unwrap();
+    }
+
+    #[test]
+    fn party_one_replies_only_after_party_two_commits() {
+        let (party1_key, party2_key) = simulate_keygen();
+        let digest = MessageDigest::sha256(b"ZenGo");
+        let mut party1 = Party1Sign::new(party1_key, digest);
+        let mut party2 = Party2Sign::new(party2_key, digest);
+        assert!(party1.message_queue().is_empty());
+
+        let commitment = party2.message_queue().remove(0);
+        party1.handle_incoming(commitment).unwrap();
+        assert!(matches!(
+            party1.message_queue().as_slice(),
+            [Msg {
+                body: ProtocolMessage(M::Sign1Party1(_)),
+                ..
+            }]
+        ));
+    }
+}
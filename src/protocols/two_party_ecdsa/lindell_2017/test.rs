@@ -2,9 +2,12 @@
 
 use crate::protocols::two_party_ecdsa::lindell_2017::{party_one, party_two};
 use curv::arithmetic::traits::Samplable;
-use curv::elliptic::curves::{secp256_k1::Secp256k1, Scalar};
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
 use curv::BigInt;
 
+use crate::utilities::backup;
+use crate::utilities::digest::MessageDigest;
+
 #[test]
 fn test_d_log_proof_party_two_party_one() {
     let (party_one_first_message, comm_witness, _ec_key_pair_party1) =
@@ -112,7 +115,7 @@ fn test_two_party_sign() {
         )
         .expect("failed to verify commitments and DLog proof");
     let party2_private = party_two::Party2Private::set_private_key(&ec_key_pair_party2);
-    let message = BigInt::from(1234);
+    let message = MessageDigest::from_bigint(&BigInt::from(1234)).unwrap();
     let partial_sig = party_two::PartialSig::compute(
         &keypair.ek,
         &keypair.encrypted_share,
@@ -133,5 +136,211 @@ fn test_two_party_sign() {
 
     let pubkey =
         party_one::compute_pubkey(&party1_private, &party_two_private_share_gen.public_share);
-    party_one::verify(&signature, &pubkey, &message).expect("Invalid signature")
+    party_one::verify(&signature, &pubkey, &message.to_bigint()).expect("Invalid signature")
+}
+
+#[test]
+fn test_key_rotation() {
+    let (_party_one_first_message, _comm_witness, ec_key_pair_party1) =
+        party_one::KeyGenFirstMsg::create_commitments();
+    let (_party_two_first_message, ec_key_pair_party2) = party_two::KeyGenFirstMsg::create();
+    let keypair =
+        party_one::PaillierKeyPair::generate_keypair_and_encrypted_share(&ec_key_pair_party1);
+    let party1_private = party_one::Party1Private::set_private_key(&ec_key_pair_party1, &keypair);
+    let party2_private = party_two::Party2Private::set_private_key(&ec_key_pair_party2);
+
+    // coin toss for the rotation factor
+    let (party_one_first_message, comm_witness) = party_one::RotationFirstMsg::commit();
+    let party_two_first_message = party_two::RotationFirstMsg::share();
+    // seeds adding up to zero are rejected before the new Paillier key is generated
+    let cancelling_seed = party_two::RotationFirstMsg {
+        seed: -&comm_witness.seed,
+    };
+    assert_eq!(
+        party_one::RotationSecondMsg::reveal_and_rotate(
+            comm_witness.clone(),
+            &cancelling_seed,
+            &party1_private,
+        )
+        .err(),
+        Some(crate::Error::InvalidKey)
+    );
+    let (party_one_second_message, party1_private_new, factor) =
+        party_one::RotationSecondMsg::reveal_and_rotate(
+            comm_witness,
+            &party_two_first_message,
+            &party1_private,
+        )
+        .expect("rotation factor is zero");
+
+    let mut tampered = party_one_second_message.clone();
+    tampered.comm_witness.seed = Scalar::random();
+    assert!(matches!(
+        party_two_first_message.verify_and_rotate(
+            &party_one_first_message,
+            tampered,
+            &party2_private,
+            &ec_key_pair_party1.public_share,
+        ),
+        Err(party_two::PartyTwoError::RotationDecommit)
+    ));
+
+    let (party2_private_new, paillier_public_new, party_one_public_share_new) =
+        party_two_first_message
+            .verify_and_rotate(
+                &party_one_first_message,
+                party_one_second_message,
+                &party2_private,
+                &ec_key_pair_party1.public_share,
+            )
+            .expect("rotation failed");
+    assert_eq!(
+        party_one_public_share_new,
+        &ec_key_pair_party1.public_share * &factor
+    );
+    assert_ne!(paillier_public_new.ek.n, keypair.ek.n);
+
+    // rotated shares sign under the same public key
+    let pubkey = party_one::compute_pubkey(&party1_private, &ec_key_pair_party2.public_share);
+    let (_eph_party_two_first_message, eph_comm_witness, eph_ec_key_pair_party2) =
+        party_two::EphKeyGenFirstMsg::create_commitments();
+    let (eph_party_one_first_message, eph_ec_key_pair_party1) =
+        party_one::EphKeyGenFirstMsg::create();
+    let eph_party_two_second_message = party_two::EphKeyGenSecondMsg::verify_and_decommit(
+        eph_comm_witness,
+        &eph_party_one_first_message,
+    )
+    .expect("party1 DLog proof failed");
+    let message = MessageDigest::from_bigint(&BigInt::from(1234)).unwrap();
+    let partial_sig = party_two::PartialSig::compute(
+        &paillier_public_new.ek,
+        &paillier_public_new.encrypted_secret_share,
+        &party2_private_new,
+        &eph_ec_key_pair_party2,
+        &eph_party_one_first_message.public_share,
+        &message,
+    );
+    let signature = party_one::Signature::compute(
+        &party1_private_new,
+        &partial_sig.c3,
+        &eph_ec_key_pair_party1,
+        &eph_party_two_second_message.comm_witness.public_share,
+    );
+    party_one::verify(&signature, &pubkey, &message.to_bigint()).expect("Invalid signature")
+}
+
+#[test]
+fn test_verifiable_backup_and_recovery() {
+    let (_party_one_first_message, _comm_witness, ec_key_pair_party1) =
+        party_one::KeyGenFirstMsg::create_commitments();
+    let (_party_two_first_message, ec_key_pair_party2) = party_two::KeyGenFirstMsg::create();
+    let keypair =
+        party_one::PaillierKeyPair::generate_keypair_and_encrypted_share(&ec_key_pair_party1);
+    let party1_private = party_one::Party1Private::set_private_key(&ec_key_pair_party1, &keypair);
+    let party2_private = party_two::Party2Private::set_private_key(&ec_key_pair_party2);
+    let pubkey = party_one::compute_pubkey(&party1_private, &ec_key_pair_party2.public_share);
+
+    let recovery_secret = Scalar::<Secp256k1>::random();
+    let recovery_key = Point::generator() * &recovery_secret;
+
+    // each party checks backup of the counterparty
+    let party1_backup = party1_private.to_verifiable_backup(&recovery_key);
+    let party2_backup = party2_private.to_verifiable_backup(&recovery_key);
+    party1_backup
+        .verify(&ec_key_pair_party1.public_share, &recovery_key)
+        .expect("party1 backup is invalid");
+    party2_backup
+        .verify(&ec_key_pair_party2.public_share, &recovery_key)
+        .expect("party2 backup is invalid");
+    assert!(party1_backup
+        .verify(&ec_key_pair_party2.public_share, &recovery_key)
+        .is_err());
+
+    // recovery key holder rebuilds the full private key
+    let x1 = party1_backup
+        .decrypt(&recovery_secret, &ec_key_pair_party1.public_share)
+        .expect("failed to decrypt party1 backup");
+    let x2 = party2_backup
+        .decrypt(&recovery_secret, &ec_key_pair_party2.public_share)
+        .expect("failed to decrypt party2 backup");
+    let x = backup::recover_two_party_key(&x1, &x2, &pubkey).expect("failed to recover key");
+    assert_eq!(Point::generator() * x, pubkey);
+}
+
+#[test]
+fn test_adaptor_signature() {
+    let (_party_one_first_message, _comm_witness, ec_key_pair_party1) =
+        party_one::KeyGenFirstMsg::create_commitments();
+    let (_party_two_first_message, ec_key_pair_party2) = party_two::KeyGenFirstMsg::create();
+    let keypair =
+        party_one::PaillierKeyPair::generate_keypair_and_encrypted_share(&ec_key_pair_party1);
+    let party1_private = party_one::Party1Private::set_private_key(&ec_key_pair_party1, &keypair);
+    let party2_private = party_two::Party2Private::set_private_key(&ec_key_pair_party2);
+    let pubkey = party_one::compute_pubkey(&party1_private, &ec_key_pair_party2.public_share);
+
+    let adaptor_secret = Scalar::<Secp256k1>::random();
+    let adaptor_point = Point::generator() * &adaptor_secret;
+
+    let (_eph_party_two_first_message, eph_comm_witness, eph_ec_key_pair_party2) =
+        party_two::EphKeyGenFirstMsg::create_commitments();
+    let (eph_party_one_first_message, eph_ec_key_pair_party1) =
+        party_one::EphKeyGenFirstMsg::create();
+    let eph_party_two_second_message = party_two::EphKeyGenSecondMsg::verify_and_decommit(
+        eph_comm_witness,
+        &eph_party_one_first_message,
+    )
+    .expect("party1 DLog proof failed");
+
+    let message = MessageDigest::from_bigint(&BigInt::from(1234)).unwrap();
+    let adaptor_first_message =
+        party_one::AdaptorFirstMsg::create(&eph_ec_key_pair_party1, &adaptor_point);
+    let partial_sig = party_two::AdaptorPartialSig::compute(
+        &keypair.ek,
+        &keypair.encrypted_share,
+        &party2_private,
+        &eph_ec_key_pair_party2,
+        &eph_party_one_first_message.public_share,
+        &adaptor_first_message,
+        &adaptor_point,
+        &message,
+    )
+    .expect("party1 adaptor share proof failed");
+    let pre_signature = party_one::PreSignature::compute(
+        &party1_private,
+        &partial_sig,
+        &eph_ec_key_pair_party1,
+        &eph_party_two_second_message.comm_witness.public_share,
+        &adaptor_point,
+    )
+    .expect("party2 adaptor share proof failed");
+
+    pre_signature
+        .verify(&pubkey, &adaptor_point, &message)
+        .expect("invalid pre-signature");
+    let other_point = Point::generator() * Scalar::<Secp256k1>::random();
+    assert!(pre_signature
+        .verify(&pubkey, &other_point, &message)
+        .is_err());
+
+    // pre-signature alone isn't a valid signature
+    let pre_signature_as_signature = party_one::Signature {
+        s: pre_signature.s_hat.to_bigint(),
+        r: pre_signature.r_hat.x_coord().unwrap(),
+    };
+    assert!(party_one::verify(&pre_signature_as_signature, &pubkey, &message.to_bigint()).is_err());
+
+    assert_eq!(
+        pre_signature.adapt(&Scalar::zero()).err(),
+        Some(crate::Error::InvalidKey)
+    );
+    let signature = pre_signature
+        .adapt(&adaptor_secret)
+        .expect("adaptor secret is zero");
+    party_one::verify(&signature, &pubkey, &message.to_bigint()).expect("Invalid signature");
+    assert_eq!(
+        pre_signature
+            .extract(&signature, &adaptor_point)
+            .expect("failed to extract adaptor secret"),
+        adaptor_secret
+    );
 }
@@ -0,0 +1,158 @@
+//! Verifiable backup of secret shares
+//!
+//! A party encrypts its secret share `x` under a recovery key `Y = y * G` using the centipede
+//! segmented ElGamal scheme: `x` is split into [NUM_OF_SEGMENTS] segments of [SEGMENT_SIZE] bits
+//! each, and every segment is encrypted separately. The backup comes with a proof that
+//! the segments are in range and that they add up to the discrete log of the party's public share
+//! `X = x * G`, so a counterparty can check the backup without learning anything about `x`.
+//!
+//! Holder of the recovery secret `y` decrypts backups and rebuilds the full private key out of
+//! the shares with [recover_two_party_key] or [recover_threshold_key].
+
+use centipede::juggling::proof_system::{Helgamalsegmented, Proof, Witness};
+use centipede::juggling::segmentation::Msegmentation;
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::{
+    ShamirSecretSharing, VerifiableSS,
+};
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Size of a single encrypted segment in bits
+pub const SEGMENT_SIZE: usize = 8;
+/// Number of segments a secret is split into, `SEGMENT_SIZE * NUM_OF_SEGMENTS` covers the curve
+/// order
+pub const NUM_OF_SEGMENTS: usize = 32;
+
+#[derive(Error, Debug)]
+pub enum BackupError {
+    #[error("backup proof verification failed")]
+    Verify,
+    #[error("couldn't decrypt backup")]
+    Decrypt,
+    #[error("decrypted share doesn't match public share")]
+    ShareMismatch,
+    #[error("recovered private key doesn't match public key")]
+    KeyMismatch,
+    #[error("not enough shares to recover the key: got {got}, need {need}")]
+    NotEnoughShares { got: usize, need: usize },
+    #[error("party index {index} is out of range or repeated")]
+    InvalidPartyIndex { index: u16 },
+}
+
+/// Secret share encrypted under the recovery key, along with the proof of correct encryption
+#[derive(Serialize, Deserialize)]
+pub struct VerifiableBackup {
+    pub encryptions: Helgamalsegmented,
+    pub proof: Proof,
+}
+
+impl VerifiableBackup {
+    /// Encrypts `secret` under `recovery_key` and proves correctness of encryption
+    pub fn new(secret: &Scalar<Secp256k1>, recovery_key: &Point<Secp256k1>) -> Self {
+        let g = Point::generator().to_point();
+        let (witness, encryptions) = Msegmentation::to_encrypted_segments(
+            secret,
+            &SEGMENT_SIZE,
+            NUM_OF_SEGMENTS,
+            recovery_key,
+            &g,
+        );
+        Self::from_segments(&witness, encryptions, recovery_key)
+    }
+
+    /// Builds a backup out of segments produced by `to_encrypted_segment` of a party's private key
+    pub fn from_segments(
+        witness: &Witness,
+        encryptions: Helgamalsegmented,
+        recovery_key: &Point<Secp256k1>,
+    ) -> Self {
+        let g = Point::generator().to_point();
+        let proof = Proof::prove(witness, &encryptions, &g, recovery_key, &SEGMENT_SIZE);
+        Self { encryptions, proof }
+    }
+
+    /// Checks that backup encrypts discrete log of `public_share` under `recovery_key`
+    pub fn verify(
+        &self,
+        public_share: &Point<Secp256k1>,
+        recovery_key: &Point<Secp256k1>,
+    ) -> Result<(), BackupError> {
+        let g = Point::generator().to_point();
+        self.proof
+            .verify(
+                &self.encryptions,
+                &g,
+                recovery_key,
+                public_share,
+                &SEGMENT_SIZE,
+            )
+            .map_err(|_| BackupError::Verify)
+    }
+
+    /// Decrypts the backup with `recovery_secret`, making sure the result matches `public_share`
+    pub fn decrypt(
+        &self,
+        recovery_secret: &Scalar<Secp256k1>,
+        public_share: &Point<Secp256k1>,
+    ) -> Result<Scalar<Secp256k1>, BackupError> {
+        let g = Point::generator().to_point();
+        let secret = Msegmentation::decrypt(&self.encryptions, &g, recovery_secret, &SEGMENT_SIZE)
+            .map_err(|_| BackupError::Decrypt)?;
+        if &(Point::generator() * &secret) != public_share {
+            return Err(BackupError::ShareMismatch);
+        }
+        Ok(secret)
+    }
+}
+
+/// Rebuilds a two-party (Lindell 2017) private key `x = x1 * x2` out of recovered shares
+pub fn recover_two_party_key(
+    x1: &Scalar<Secp256k1>,
+    x2: &Scalar<Secp256k1>,
+    public_key: &Point<Secp256k1>,
+) -> Result<Scalar<Secp256k1>, BackupError> {
+    let x = x1 * x2;
+    if &(Point::generator() * &x) != public_key {
+        return Err(BackupError::KeyMismatch);
+    }
+    Ok(x)
+}
+
+/// Rebuilds a threshold (GG20) private key out of at least `t + 1` recovered shares `x_i`
+///
+/// `parties` are distinct indexes of parties (in range `[1; n]`) the shares belong to.
+pub fn recover_threshold_key(
+    parameters: &ShamirSecretSharing,
+    parties: &[u16],
+    shares: &[Scalar<Secp256k1>],
+    public_key: &Point<Secp256k1>,
+) -> Result<Scalar<Secp256k1>, BackupError> {
+    let need = usize::from(parameters.threshold) + 1;
+    if parties.len() < need || shares.len() != parties.len() {
+        return Err(BackupError::NotEnoughShares {
+            got: parties.len().min(shares.len()),
+            need,
+        });
+    }
+    let vss = VerifiableSS::<Secp256k1> {
+        parameters: parameters.clone(),
+        commitments: vec![],
+    };
+    let n = parameters.share_count;
+    let mut indices = Vec::with_capacity(parties.len());
+    for &i in parties {
+        if !(1..=n).contains(&i) || indices.contains(&(i - 1)) {
+            return Err(BackupError::InvalidPartyIndex { index: i });
+        }
+        indices.push(i - 1);
+    }
+    let x = vss.reconstruct(&indices, shares);
+    if &(Point::generator() * &x) != public_key {
+        return Err(BackupError::KeyMismatch);
+    }
+    Ok(x)
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,72 @@
+use crate::utilities::backup::*;
+use crate::utilities::rng;
+use curv::elliptic::curves::{Point, Scalar};
+use rand::rngs::OsRng;
+
+#[test]
+fn test_backup_verify_and_decrypt() {
+    let recovery_secret = Scalar::random();
+    let recovery_key = Point::generator() * &recovery_secret;
+    let secret = Scalar::random();
+    let public_share = Point::generator() * &secret;
+
+    let backup = VerifiableBackup::new(&secret, &recovery_key);
+    assert!(backup.verify(&public_share, &recovery_key).is_ok());
+    assert_eq!(
+        backup.decrypt(&recovery_secret, &public_share).unwrap(),
+        secret
+    );
+
+    // backup is bound to the public share and the recovery key
+    let other_share = Point::generator() * Scalar::random();
+    assert!(backup.verify(&other_share, &recovery_key).is_err());
+    let other_key = Point::generator() * Scalar::random();
+    assert!(backup.verify(&public_share, &other_key).is_err());
+    assert!(backup.decrypt(&Scalar::random(), &public_share).is_err());
+}
+
+#[test]
+fn test_recover_threshold_key() {
+    let secret = rng::random_scalar(&mut OsRng);
+    let public_key = Point::generator() * &secret;
+    let (vss, shares) = rng::share(2, 5, &secret, &mut OsRng);
+
+    let recovered = recover_threshold_key(
+        &vss.parameters,
+        &[1, 3, 5],
+        &[shares[0].clone(), shares[2].clone(), shares[4].clone()],
+        &public_key,
+    )
+    .unwrap();
+    assert_eq!(recovered, secret);
+
+    assert!(matches!(
+        recover_threshold_key(
+            &vss.parameters,
+            &[1, 3],
+            &[shares[0].clone(), shares[2].clone()],
+            &public_key,
+        ),
+        Err(BackupError::NotEnoughShares { got: 2, need: 3 })
+    ));
+    assert!(matches!(
+        recover_threshold_key(
+            &vss.parameters,
+            &[1, 2, 5],
+            &[shares[0].clone(), shares[2].clone(), shares[4].clone()],
+            &public_key,
+        ),
+        Err(BackupError::KeyMismatch)
+    ));
+    for (parties, bad_index) in [([0, 3, 5], 0), ([1, 3, 6], 6), ([1, 3, 3], 3)] {
+        assert!(matches!(
+            recover_threshold_key(
+                &vss.parameters,
+                &parties,
+                &[shares[0].clone(), shares[2].clone(), shares[4].clone()],
+                &public_key,
+            ),
+            Err(BackupError::InvalidPartyIndex { index }) if index == bad_index
+        ));
+    }
+}
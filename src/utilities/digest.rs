@@ -0,0 +1,202 @@
+//! Digest of a message being signed
+//!
+//! ECDSA signs a 256 bits hash of the message rather than the message itself. Signing functions
+//! take [MessageDigest] which can only be constructed out of 32 bytes value, so data which is too
+//! long to be signed or hasn't been hashed can't be passed to them by accident. Constructors
+//! taking digest bytes reject values which aren't less than secp256k1 curve order, hash helpers
+//! reduce the hash modulo the order as ECDSA prescribes (see [MessageDigest::new_reduced]).
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use curv::arithmetic::traits::*;
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Scalar};
+use curv::BigInt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sha3::Keccak256;
+use thiserror::Error;
+
+/// Size of digest in bytes
+pub const DIGEST_SIZE: usize = 32;
+
+/// 32 bytes digest of a message, interpreted as big-endian integer less than curve order
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "[u8; DIGEST_SIZE]")]
+pub struct MessageDigest([u8; DIGEST_SIZE]);
+
+impl MessageDigest {
+    /// Wraps a digest computed by the caller
+    ///
+    /// Digest, interpreted as big-endian integer, must be less than curve order.
+    pub fn new(digest: [u8; DIGEST_SIZE]) -> Result<Self, InvalidDigest> {
+        Self::from_bigint(&BigInt::from_bytes(&digest))
+    }
+
+    /// Wraps a hash computed by the caller, reducing it modulo curve order
+    ///
+    /// That's how ECDSA treats a hash which isn't less than the order. Use it only for output of
+    /// a hash function, otherwise prefer [MessageDigest::new].
+    pub fn new_reduced(digest: [u8; DIGEST_SIZE]) -> Self {
+        let q = Scalar::<Secp256k1>::group_order();
+        let digest = BigInt::from_bytes(&digest);
+        if &digest < q {
+            return Self::from_reduced(&digest);
+        }
+        // 256 bits integer is less than `2q`, single subtraction is enough
+        Self::from_reduced(&(digest - q))
+    }
+
+    /// Same as [MessageDigest::new], but takes a slice which must be exactly 32 bytes long
+    pub fn from_slice(digest: &[u8]) -> Result<Self, InvalidDigest> {
+        let digest = <[u8; DIGEST_SIZE]>::try_from(digest)
+            .map_err(|_| InvalidDigest::InvalidLength { len: digest.len() })?;
+        Self::new(digest)
+    }
+
+    /// Converts an integer into digest
+    ///
+    /// Integer must be in range `[0; q)` where `q` is curve order.
+    pub fn from_bigint(digest: &BigInt) -> Result<Self, InvalidDigest> {
+        if digest < &BigInt::zero() || digest >= Scalar::<Secp256k1>::group_order() {
+            return Err(InvalidDigest::NotReduced);
+        }
+        Ok(Self::from_reduced(digest))
+    }
+
+    /// SHA-256 digest of `data`
+    pub fn sha256(data: &[u8]) -> Self {
+        Self::new_reduced(Sha256::digest(data).into())
+    }
+
+    /// Double SHA-256 digest of `data` (as used by Bitcoin)
+    pub fn double_sha256(data: &[u8]) -> Self {
+        Self::new_reduced(Sha256::digest(&Sha256::digest(data)).into())
+    }
+
+    /// Keccak-256 digest of `data` (as used by Ethereum)
+    pub fn keccak256(data: &[u8]) -> Self {
+        Self::new_reduced(Keccak256::digest(data).into())
+    }
+
+    /// Encodes integer in range `[0; q)` as 32 bytes big-endian
+    fn from_reduced(digest: &BigInt) -> Self {
+        let bytes = digest.to_bytes();
+        let mut padded = [0u8; DIGEST_SIZE];
+        padded[DIGEST_SIZE - bytes.len()..].copy_from_slice(&bytes);
+        Self(padded)
+    }
+
+    /// Digest bytes
+    pub fn as_bytes(&self) -> &[u8; DIGEST_SIZE] {
+        &self.0
+    }
+
+    /// Digest interpreted as big-endian integer
+    pub fn to_bigint(&self) -> BigInt {
+        BigInt::from_bytes(&self.0)
+    }
+
+    /// Digest as a scalar
+    pub fn to_scalar(&self) -> Scalar<Secp256k1> {
+        Scalar::from(&self.to_bigint())
+    }
+}
+
+impl TryFrom<[u8; DIGEST_SIZE]> for MessageDigest {
+    type Error = InvalidDigest;
+
+    fn try_from(digest: [u8; DIGEST_SIZE]) -> Result<Self, Self::Error> {
+        Self::new(digest)
+    }
+}
+
+impl fmt::Debug for MessageDigest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MessageDigest(")?;
+        for byte in &self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Explains why value can't be used as [MessageDigest]
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InvalidDigest {
+    /// Digest is not 32 bytes long
+    #[error("digest must be {DIGEST_SIZE} bytes long, got {len} bytes")]
+    InvalidLength { len: usize },
+    /// Digest is not less than curve order
+    #[error("digest is not less than curve order")]
+    NotReduced,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn digest_is_validated() {
+        let q = Scalar::<Secp256k1>::group_order();
+        let q_minus_one = q - &BigInt::one();
+        let digest = MessageDigest::from_bigint(&q_minus_one).unwrap();
+        assert_eq!(digest.to_bigint(), q_minus_one);
+        assert_eq!(
+            MessageDigest::from_bigint(q),
+            Err(InvalidDigest::NotReduced)
+        );
+        assert_eq!(
+            MessageDigest::new([0xff; DIGEST_SIZE]),
+            Err(InvalidDigest::NotReduced)
+        );
+        assert_eq!(
+            MessageDigest::from_slice(&[0xff; DIGEST_SIZE]),
+            Err(InvalidDigest::NotReduced)
+        );
+        assert_eq!(
+            MessageDigest::new_reduced([0xff; DIGEST_SIZE]).to_bigint(),
+            BigInt::from_bytes(&[0xff; DIGEST_SIZE]) - q
+        );
+        assert_eq!(
+            MessageDigest::from_slice(&[1u8; 33]),
+            Err(InvalidDigest::InvalidLength { len: 33 })
+        );
+
+        let small = MessageDigest::from_bigint(&BigInt::from(42)).unwrap();
+        assert_eq!(small.as_bytes()[DIGEST_SIZE - 1], 42);
+        assert_eq!(small.to_bigint(), BigInt::from(42));
+        assert_eq!(MessageDigest::new(*small.as_bytes()), Ok(small));
+    }
+
+    #[test]
+    fn deserialization_rejects_unreduced_digest() {
+        let small = MessageDigest::from_bigint(&BigInt::from(42)).unwrap();
+        let serialized = serde_json::to_string(&small).unwrap();
+        assert_eq!(
+            serde_json::from_str::<MessageDigest>(&serialized).unwrap(),
+            small
+        );
+
+        let unreduced = serde_json::to_string(&[0xffu8; DIGEST_SIZE]).unwrap();
+        assert!(serde_json::from_str::<MessageDigest>(&unreduced).is_err());
+    }
+
+    #[test]
+    fn hash_helpers_match_known_digests() {
+        let encode = |d: MessageDigest| hex::encode(d.as_bytes());
+        assert_eq!(
+            encode(MessageDigest::sha256(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            encode(MessageDigest::keccak256(b"")),
+            "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+        );
+        assert_eq!(
+            encode(MessageDigest::double_sha256(b"")),
+            "5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456"
+        );
+    }
+}
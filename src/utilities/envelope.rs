@@ -0,0 +1,111 @@
+//! Authenticated encryption of secrets at rest
+//!
+//! Shared by envelopes of [LocalKey](crate::protocols::multi_party_ecdsa::gg_2020::state_machine::keygen::LocalKey)
+//! and presignatures. Secret value is serialized and encrypted with AES-256-GCM, while envelope
+//! header (which must include the nonce) is authenticated as associated data. Layout of the header
+//! is defined by the caller.
+
+use aes_gcm::aead::{Aead, NewAead, Payload};
+use aes_gcm::Aes256Gcm;
+use hmac::Hmac;
+use rand::{rngs::OsRng, RngCore};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::Sha256;
+use thiserror::Error;
+use zeroize::Zeroize;
+
+/// Size of encryption key in bytes
+pub const KEY_LEN: usize = 32;
+/// Size of AES-GCM nonce in bytes
+pub const NONCE_LEN: usize = 12;
+/// Size of PBKDF2 salt in bytes
+pub const SALT_LEN: usize = 16;
+/// Envelopes specifying fewer PBKDF2 iterations are rejected
+pub const MIN_PBKDF2_ITERATIONS: u32 = 10_000;
+/// Envelopes specifying more PBKDF2 iterations are rejected, so that a tampered header can't make
+/// key derivation arbitrarily expensive
+pub const MAX_PBKDF2_ITERATIONS: u32 = 10_000_000;
+
+/// Samples a fresh nonce, it must never be reused with the same key
+pub fn random_nonce() -> [u8; NONCE_LEN] {
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+    nonce
+}
+
+/// Samples a fresh PBKDF2 salt
+pub fn random_salt() -> [u8; SALT_LEN] {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    salt
+}
+
+/// Derives encryption key from `passphrase` using PBKDF2-HMAC-SHA256
+///
+/// Returns `None` if `iterations` is not in range `[MIN_PBKDF2_ITERATIONS; MAX_PBKDF2_ITERATIONS]`.
+pub fn derive_key(passphrase: &[u8], salt: &[u8], iterations: u32) -> Option<[u8; KEY_LEN]> {
+    if !(MIN_PBKDF2_ITERATIONS..=MAX_PBKDF2_ITERATIONS).contains(&iterations) {
+        return None;
+    }
+    let mut key = [0u8; KEY_LEN];
+    pbkdf2::pbkdf2::<Hmac<Sha256>>(passphrase, salt, iterations, &mut key);
+    Some(key)
+}
+
+/// Serializes and encrypts `value`, authenticating `header`
+///
+/// Returns ciphertext which is meant to be appended to the header.
+pub fn seal<T: Serialize>(
+    key: &[u8; KEY_LEN],
+    nonce: &[u8; NONCE_LEN],
+    header: &[u8],
+    value: &T,
+) -> Result<Vec<u8>, EnvelopeError> {
+    let mut plaintext = serde_json::to_vec(value).map_err(EnvelopeError::Serialization)?;
+    let ciphertext = Aes256Gcm::new(key.into()).encrypt(
+        nonce.into(),
+        Payload {
+            msg: &plaintext,
+            aad: header,
+        },
+    );
+    plaintext.zeroize();
+    ciphertext.map_err(|_| EnvelopeError::Encryption)
+}
+
+/// Decrypts and deserializes value encrypted by [seal]
+pub fn open<T: DeserializeOwned>(
+    key: &[u8; KEY_LEN],
+    nonce: &[u8; NONCE_LEN],
+    header: &[u8],
+    ciphertext: &[u8],
+) -> Result<T, EnvelopeError> {
+    let mut plaintext = Aes256Gcm::new(key.into())
+        .decrypt(
+            nonce.into(),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| EnvelopeError::Decryption)?;
+    let value = serde_json::from_slice(&plaintext).map_err(EnvelopeError::Serialization);
+    plaintext.zeroize();
+    value
+}
+
+/// Error of sealing or opening an envelope
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum EnvelopeError {
+    /// Serializing or deserializing value failed
+    #[error("serialization: {0}")]
+    Serialization(#[source] serde_json::Error),
+    /// AES-GCM encryption failed
+    #[error("encryption failed")]
+    Encryption,
+    /// Decryption failed: key is wrong or envelope was tampered with
+    #[error("decryption failed: wrong key or envelope was tampered with")]
+    Decryption,
+}
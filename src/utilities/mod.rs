@@ -1,3 +1,11 @@
+pub mod backup;
+pub mod digest;
+pub mod envelope;
 pub mod mta;
+pub mod parallel;
+pub mod rng;
+pub mod signature;
+pub mod zk_no_small_factor;
+pub mod zk_paillier_blum_modulus;
 pub mod zk_pdl;
 pub mod zk_pdl_with_slack;
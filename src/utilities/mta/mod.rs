@@ -15,13 +15,14 @@
 */
 
 /// MtA is described in https://eprint.iacr.org/2019/114.pdf section 3
-use curv::arithmetic::traits::Samplable;
 use curv::cryptographic_primitives::proofs::sigma_dlog::DLogProof;
 use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
 use curv::BigInt;
 use paillier::traits::EncryptWithChosenRandomness;
 use paillier::{Add, Decrypt, Mul};
 use paillier::{DecryptionKey, EncryptionKey, Paillier, Randomness, RawCiphertext, RawPlaintext};
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore};
 use zk_paillier::zkproofs::DLogStatement;
 
 use serde::{Deserialize, Serialize};
@@ -29,6 +30,7 @@ use sha2::Sha256;
 
 use crate::protocols::multi_party_ecdsa::gg_2018::party_i::PartyPrivate;
 use crate::utilities::mta::range_proofs::AliceProof;
+use crate::utilities::rng;
 use crate::Error::{self, InvalidKey};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -54,8 +56,18 @@ impl MessageA {
         alice_ek: &EncryptionKey,
         dlog_statements: &[DLogStatement],
     ) -> (Self, BigInt) {
-        let randomness = BigInt::sample_below(&alice_ek.n);
-        let m_a = MessageA::a_with_predefined_randomness(a, alice_ek, &randomness, dlog_statements);
+        Self::a_with_rng(a, alice_ek, dlog_statements, &mut OsRng)
+    }
+
+    /// Same as [MessageA::a], but takes encryption randomness and range proofs randomness from `rng`
+    pub fn a_with_rng<R: RngCore + CryptoRng + ?Sized>(
+        a: &Scalar<Secp256k1>,
+        alice_ek: &EncryptionKey,
+        dlog_statements: &[DLogStatement],
+        rng: &mut R,
+    ) -> (Self, BigInt) {
+        let randomness = rng::sample_below(&alice_ek.n, rng);
+        let m_a = Self::build(a, alice_ek, &randomness, dlog_statements, rng);
         (m_a, randomness)
     }
 
@@ -64,6 +76,16 @@ impl MessageA {
         alice_ek: &EncryptionKey,
         randomness: &BigInt,
         dlog_statements: &[DLogStatement],
+    ) -> Self {
+        Self::build(a, alice_ek, randomness, dlog_statements, &mut OsRng)
+    }
+
+    fn build<R: RngCore + CryptoRng + ?Sized>(
+        a: &Scalar<Secp256k1>,
+        alice_ek: &EncryptionKey,
+        randomness: &BigInt,
+        dlog_statements: &[DLogStatement],
+        rng: &mut R,
     ) -> Self {
         let c_a = Paillier::encrypt_with_chosen_randomness(
             alice_ek,
@@ -76,7 +98,14 @@ impl MessageA {
         let alice_range_proofs = dlog_statements
             .iter()
             .map(|dlog_statement| {
-                AliceProof::generate(&a.to_bigint(), &c_a, alice_ek, dlog_statement, randomness)
+                AliceProof::generate_with_rng(
+                    &a.to_bigint(),
+                    &c_a,
+                    alice_ek,
+                    dlog_statement,
+                    randomness,
+                    rng,
+                )
             })
             .collect::<Vec<AliceProof>>();
 
@@ -94,8 +123,21 @@ impl MessageB {
         m_a: MessageA,
         dlog_statements: &[DLogStatement],
     ) -> Result<(Self, Scalar<Secp256k1>, BigInt, BigInt), Error> {
-        let beta_tag = BigInt::sample_below(&alice_ek.n);
-        let randomness = BigInt::sample_below(&alice_ek.n);
+        Self::b_with_rng(b, alice_ek, m_a, dlog_statements, &mut OsRng)
+    }
+
+    /// Same as [MessageB::b], but samples `beta_tag` and encryption randomness from `rng`
+    ///
+    /// Nonces of DLog proofs are still sampled by `curv` from operating system.
+    pub fn b_with_rng<R: RngCore + CryptoRng + ?Sized>(
+        b: &Scalar<Secp256k1>,
+        alice_ek: &EncryptionKey,
+        m_a: MessageA,
+        dlog_statements: &[DLogStatement],
+        rng: &mut R,
+    ) -> Result<(Self, Scalar<Secp256k1>, BigInt, BigInt), Error> {
+        let beta_tag = rng::sample_below(&alice_ek.n, rng);
+        let randomness = rng::sample_below(&alice_ek.n, rng);
         let (m_b, beta) = MessageB::b_with_predefined_randomness(
             b,
             alice_ek,
@@ -16,12 +16,16 @@ use curv::BigInt;
 use sha2::Sha256;
 
 use paillier::{EncryptionKey, Randomness};
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore};
 use zk_paillier::zkproofs::DLogStatement;
 
 use serde::{Deserialize, Serialize};
 use std::borrow::Borrow;
 use zeroize::Zeroize;
 
+use crate::utilities::rng;
+
 /// Represents the first round of the interactive version of the proof
 #[derive(Zeroize)]
 #[zeroize(drop)]
@@ -36,19 +40,20 @@ struct AliceZkpRound1 {
 }
 
 impl AliceZkpRound1 {
-    fn from(
+    fn from<R: RngCore + CryptoRng + ?Sized>(
         alice_ek: &EncryptionKey,
         dlog_statement: &DLogStatement,
         a: &BigInt,
         q: &BigInt,
+        rng: &mut R,
     ) -> Self {
         let h1 = &dlog_statement.g;
         let h2 = &dlog_statement.ni;
         let N_tilde = &dlog_statement.N;
-        let alpha = BigInt::sample_below(&q.pow(3));
-        let beta = BigInt::from_paillier_key(alice_ek);
-        let gamma = BigInt::sample_below(&(q.pow(3) * N_tilde));
-        let ro = BigInt::sample_below(&(q * N_tilde));
+        let alpha = rng::sample_below(&q.pow(3), rng);
+        let beta = rng::sample_from_multiplicative_group(&alice_ek.n, rng);
+        let gamma = rng::sample_below(&(q.pow(3) * N_tilde), rng);
+        let ro = rng::sample_below(&(q * N_tilde), rng);
         let z = (BigInt::mod_pow(h1, a, N_tilde) * BigInt::mod_pow(h2, &ro, N_tilde)) % N_tilde;
         let u = ((alpha.borrow() * &alice_ek.n + 1)
             * BigInt::mod_pow(&beta, &alice_ek.n, &alice_ek.nn))
@@ -163,12 +168,25 @@ impl AliceProof {
         alice_ek: &EncryptionKey,
         dlog_statement: &DLogStatement,
         r: &BigInt,
+    ) -> Self {
+        Self::generate_with_rng(a, cipher, alice_ek, dlog_statement, r, &mut OsRng)
+    }
+
+    /// Same as [AliceProof::generate], but takes randomness from `rng`
+    pub fn generate_with_rng<R: RngCore + CryptoRng + ?Sized>(
+        a: &BigInt,
+        cipher: &BigInt,
+        alice_ek: &EncryptionKey,
+        dlog_statement: &DLogStatement,
+        r: &BigInt,
+        rng: &mut R,
     ) -> Self {
         let round1 = AliceZkpRound1::from(
             alice_ek,
             dlog_statement,
             a,
             Scalar::<Secp256k1>::group_order(),
+            rng,
         );
 
         let Gen = alice_ek.n.borrow() + 1;
@@ -215,26 +233,27 @@ impl BobZkpRound1 {
     /// `b` - Bob's secret
     /// `beta_prim`  - randomly chosen in `MtA` by Bob
     /// `a_encrypted` - Alice's secret encrypted by Alice
-    fn from(
+    fn from<R: RngCore + CryptoRng + ?Sized>(
         alice_ek: &EncryptionKey,
         dlog_statement: &DLogStatement,
         b: &Scalar<Secp256k1>,
         beta_prim: &BigInt,
         a_encrypted: &BigInt,
         q: &BigInt,
+        rng: &mut R,
     ) -> Self {
         let h1 = &dlog_statement.g;
         let h2 = &dlog_statement.ni;
         let N_tilde = &dlog_statement.N;
         let b_bn = b.to_bigint();
 
-        let alpha = BigInt::sample_below(&q.pow(3));
-        let beta = BigInt::from_paillier_key(alice_ek);
-        let gamma = BigInt::sample_below(&(q.pow(2) * &alice_ek.n));
-        let ro = BigInt::sample_below(&(q * N_tilde));
-        let ro_prim = BigInt::sample_below(&(q.pow(3) * N_tilde));
-        let sigma = BigInt::sample_below(&(q * N_tilde));
-        let tau = BigInt::sample_below(&(q.pow(3) * N_tilde));
+        let alpha = rng::sample_below(&q.pow(3), rng);
+        let beta = rng::sample_from_multiplicative_group(&alice_ek.n, rng);
+        let gamma = rng::sample_below(&(q.pow(2) * &alice_ek.n), rng);
+        let ro = rng::sample_below(&(q * N_tilde), rng);
+        let ro_prim = rng::sample_below(&(q.pow(3) * N_tilde), rng);
+        let sigma = rng::sample_below(&(q * N_tilde), rng);
+        let tau = rng::sample_below(&(q.pow(3) * N_tilde), rng);
         let z = (BigInt::mod_pow(h1, &b_bn, N_tilde) * BigInt::mod_pow(h2, &ro, N_tilde)) % N_tilde;
         let z_prim = (BigInt::mod_pow(h1, &alpha, N_tilde)
             * BigInt::mod_pow(h2, &ro_prim, N_tilde))
@@ -420,6 +439,31 @@ impl BobProof {
         dlog_statement: &DLogStatement,
         r: &Randomness,
         check: bool,
+    ) -> (BobProof, Option<Point<Secp256k1>>) {
+        Self::generate_with_rng(
+            a_encrypted,
+            mta_encrypted,
+            b,
+            beta_prim,
+            alice_ek,
+            dlog_statement,
+            r,
+            check,
+            &mut OsRng,
+        )
+    }
+
+    /// Same as [BobProof::generate], but takes randomness from `rng`
+    pub fn generate_with_rng<R: RngCore + CryptoRng + ?Sized>(
+        a_encrypted: &BigInt,
+        mta_encrypted: &BigInt,
+        b: &Scalar<Secp256k1>,
+        beta_prim: &BigInt,
+        alice_ek: &EncryptionKey,
+        dlog_statement: &DLogStatement,
+        r: &Randomness,
+        check: bool,
+        rng: &mut R,
     ) -> (BobProof, Option<Point<Secp256k1>>) {
         let round1 = BobZkpRound1::from(
             alice_ek,
@@ -428,6 +472,7 @@ impl BobProof {
             beta_prim,
             a_encrypted,
             Scalar::<Secp256k1>::group_order(),
+            rng,
         );
 
         let Gen = alice_ek.n.borrow() + 1;
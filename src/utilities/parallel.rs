@@ -0,0 +1,28 @@
+//! Per-peer computations which can run in parallel
+//!
+//! Signing requires the same expensive proof (MtA, PDL) to be computed or verified once per every
+//! other party. With `rayon` feature enabled, [map] spreads this work across a thread pool,
+//! otherwise it's carried out sequentially.
+
+/// Applies `f` to every item, preserving order of items
+#[cfg(feature = "rayon")]
+pub fn map<T, U, F>(items: Vec<T>, f: F) -> Vec<U>
+where
+    T: Send,
+    U: Send,
+    F: Fn(T) -> U + Sync + Send,
+{
+    use rayon::prelude::*;
+    items.into_par_iter().map(f).collect()
+}
+
+/// Applies `f` to every item, preserving order of items
+#[cfg(not(feature = "rayon"))]
+pub fn map<T, U, F>(items: Vec<T>, f: F) -> Vec<U>
+where
+    T: Send,
+    U: Send,
+    F: Fn(T) -> U + Sync + Send,
+{
+    items.into_iter().map(f).collect()
+}
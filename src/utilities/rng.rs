@@ -0,0 +1,187 @@
+//! Sampling of secret values from caller-provided random number generator
+//!
+//! Functions here mirror [curv](curv::arithmetic::traits::Samplable) and [paillier] sampling
+//! routines, but take randomness from `rng` instead of operating system. They back `_with_rng`
+//! variants of protocol functions, so that secret values (shares, nonces, MtA and range proof
+//! randomness, `h1, h2` trapdoors) can be taken from a caller-chosen (e.g. hardware-backed)
+//! generator. Functions without `_with_rng` suffix keep using `curv` and `paillier` sampling.
+//!
+//! Primes are not sampled here: neither `curv` nor `paillier` can generate them from
+//! caller-provided randomness, so factors of Paillier modulus and `N_tilde` are always generated by
+//! `paillier` from operating system, `_with_rng` variants included.
+//!
+//! Note that nonces of DLog, Pedersen, composite DLog and homomorphic ElGamal proofs are sampled
+//! by `curv` and `zk-paillier` from operating system, so protocol messages aren't reproducible
+//! even with a seeded `rng`.
+
+use curv::arithmetic::traits::*;
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::{
+    ShamirSecretSharing, VerifiableSS,
+};
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
+use curv::BigInt;
+use rand::rngs::StdRng;
+use rand::{CryptoRng, RngCore, SeedableRng};
+use zeroize::Zeroize;
+
+/// Random number generator suitable for cryptographic use
+///
+/// Object-safe combination of [RngCore] and [CryptoRng], implemented for every such generator.
+pub trait CryptoRngCore: RngCore + CryptoRng {}
+
+impl<R: RngCore + CryptoRng + ?Sized> CryptoRngCore for R {}
+
+/// Samples a number of at most `bits` bits uniformly at random
+pub fn sample_bits<R: RngCore + CryptoRng + ?Sized>(bits: usize, rng: &mut R) -> BigInt {
+    let len = bits.div_ceil(8);
+    let mut bytes = vec![0u8; len];
+    rng.fill_bytes(&mut bytes);
+    let excess = len * 8 - bits;
+    if excess > 0 {
+        bytes[0] &= 0xff >> excess;
+    }
+    let n = BigInt::from_bytes(&bytes);
+    bytes.zeroize();
+    n
+}
+
+/// Samples a number from `[0; upper)` uniformly at random
+pub fn sample_below<R: RngCore + CryptoRng + ?Sized>(upper: &BigInt, rng: &mut R) -> BigInt {
+    assert!(*upper > BigInt::zero(), "upper bound must be positive");
+    let bits = upper.bit_length();
+    loop {
+        let n = sample_bits(bits, rng);
+        if n < *upper {
+            return n;
+        }
+    }
+}
+
+/// Samples a number from `[lower; upper)` uniformly at random
+pub fn sample_range<R: RngCore + CryptoRng + ?Sized>(
+    lower: &BigInt,
+    upper: &BigInt,
+    rng: &mut R,
+) -> BigInt {
+    lower + sample_below(&(upper - lower), rng)
+}
+
+/// Samples an element of multiplicative group `Z*_N` uniformly at random
+pub fn sample_from_multiplicative_group<R: RngCore + CryptoRng + ?Sized>(
+    n: &BigInt,
+    rng: &mut R,
+) -> BigInt {
+    loop {
+        let r = sample_below(n, rng);
+        if r.gcd(n) == BigInt::one() {
+            return r;
+        }
+    }
+}
+
+/// Samples non-zero scalar uniformly at random
+pub fn random_scalar<R: RngCore + CryptoRng + ?Sized>(rng: &mut R) -> Scalar<Secp256k1> {
+    loop {
+        let n = sample_below(Scalar::<Secp256k1>::group_order(), rng);
+        if n != BigInt::zero() {
+            return Scalar::from(&n);
+        }
+    }
+}
+
+/// Derives `n` independent generators seeded from `rng`
+///
+/// Lets work which needs randomness be split between threads, while output still depends only on
+/// `rng` (e.g. stays reproducible for a seeded generator).
+pub fn fork<R: RngCore + CryptoRng + ?Sized>(rng: &mut R, n: usize) -> Vec<StdRng> {
+    (0..n)
+        .map(|_| {
+            let mut seed = <StdRng as SeedableRng>::Seed::default();
+            rng.fill_bytes(&mut seed);
+            let forked = StdRng::from_seed(seed);
+            seed.zeroize();
+            forked
+        })
+        .collect()
+}
+
+/// Feldman verifiable secret sharing of `secret` with threshold `t` among `n` parties
+///
+/// Works the same way as [VerifiableSS::share], party `i` receives share at index `i` (starting
+/// from `1`).
+pub fn share<R: RngCore + CryptoRng + ?Sized>(
+    t: u16,
+    n: u16,
+    secret: &Scalar<Secp256k1>,
+    rng: &mut R,
+) -> (VerifiableSS<Secp256k1>, Vec<Scalar<Secp256k1>>) {
+    let coefficients = std::iter::once(secret.clone())
+        .chain((0..t).map(|_| random_scalar(rng)))
+        .collect::<Vec<_>>();
+    let commitments = coefficients
+        .iter()
+        .map(|c| Point::generator() * c)
+        .collect();
+    let shares = (1..=n)
+        .map(|i| {
+            let x = Scalar::from(&BigInt::from(u64::from(i)));
+            coefficients
+                .iter()
+                .rev()
+                .fold(Scalar::zero(), |acc, c| acc * &x + c)
+        })
+        .collect();
+
+    let vss = VerifiableSS {
+        parameters: ShamirSecretSharing {
+            threshold: t,
+            share_count: n,
+        },
+        commitments,
+    };
+    (vss, shares)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn same_seed_gives_same_values() {
+        let mut rng1 = StdRng::seed_from_u64(42);
+        let mut rng2 = StdRng::seed_from_u64(42);
+        assert_eq!(random_scalar(&mut rng1), random_scalar(&mut rng2));
+        let upper = BigInt::from(1000);
+        let n = sample_below(&upper, &mut rng1);
+        assert_eq!(n, sample_below(&upper, &mut rng2));
+        assert!(n < upper);
+    }
+
+    #[test]
+    fn forked_generators_are_reproducible_and_distinct() {
+        let mut forked1 = fork(&mut StdRng::seed_from_u64(7), 3);
+        let mut forked2 = fork(&mut StdRng::seed_from_u64(7), 3);
+        let values1: Vec<_> = forked1.iter_mut().map(|rng| rng.next_u64()).collect();
+        let values2: Vec<_> = forked2.iter_mut().map(|rng| rng.next_u64()).collect();
+        assert_eq!(values1, values2);
+        assert_ne!(values1[0], values1[1]);
+        assert_ne!(values1[1], values1[2]);
+    }
+
+    #[test]
+    fn shares_are_valid() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let secret = random_scalar(&mut rng);
+        let (vss, shares) = share(2, 5, &secret, &mut rng);
+        for (i, s) in (1..).zip(&shares) {
+            assert!(vss.validate_share(s, i).is_ok());
+        }
+        assert_eq!(
+            vss.reconstruct(
+                &[0, 2, 4],
+                &[shares[0].clone(), shares[2].clone(), shares[4].clone()]
+            ),
+            secret
+        );
+    }
+}
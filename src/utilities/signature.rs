@@ -0,0 +1,409 @@
+//! Standard encodings of ECDSA signature
+//!
+//! Every protocol in this library outputs its own `SignatureRecid` made of `r`, `s` and recovery
+//! id. [SignatureEncoding] is implemented for all of them and converts signature into formats
+//! expected by Bitcoin and Ethereum tooling:
+//!
+//! * DER encoding (Bitcoin transactions, X.509)
+//! * 64 bytes compact encoding `r || s`
+//! * 65 bytes recoverable encoding `r || s || v`, where `v` is recovery id (Ethereum expects
+//!   `v + 27`, add it yourself if needed)
+//! * `secp256k1::ecdsa::RecoverableSignature` (requires `secp256k1` feature)
+//!
+//! Recovery id also allows [recovering](SignatureEncoding::recover_public_key) public key out of
+//! signature and message digest, e.g. to check signature against Ethereum address.
+//!
+//! Decoding functions reject `r` or `s` which are not in range `[1; q)`, where `q` is curve order.
+
+use curv::arithmetic::traits::*;
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
+use curv::BigInt;
+use thiserror::Error;
+
+use crate::utilities::digest::MessageDigest;
+
+/// Size of `r` and `s` in compact encodings
+const SCALAR_SIZE: usize = 32;
+/// Size of [compact encoding](SignatureEncoding::to_compact)
+pub const COMPACT_SIZE: usize = 2 * SCALAR_SIZE;
+/// Size of [recoverable encoding](SignatureEncoding::to_recoverable_bytes)
+pub const RECOVERABLE_SIZE: usize = COMPACT_SIZE + 1;
+
+const DER_SEQUENCE: u8 = 0x30;
+const DER_INTEGER: u8 = 0x02;
+
+/// ECDSA signature with recovery id which can be converted to and from standard encodings
+pub trait SignatureEncoding: Sized {
+    /// `r` component of signature
+    fn r(&self) -> BigInt;
+    /// `s` component of signature
+    fn s(&self) -> BigInt;
+    /// Recovery id, in range `[0; 3]`
+    fn recid(&self) -> u8;
+    /// Constructs signature out of its components
+    ///
+    /// Components are assumed to be valid, i.e. `r`, `s` in range `[1; q)` and `recid <= 3`.
+    fn from_parts(r: BigInt, s: BigInt, recid: u8) -> Self;
+
+    /// Checks that `s` is not greater than `q/2`
+    ///
+    /// Bitcoin and Ethereum reject signatures with high `s` as malleable.
+    fn is_low_s(&self) -> bool {
+        let s = self.s();
+        s <= Scalar::<Secp256k1>::group_order() - &s
+    }
+
+    /// Replaces `s` with `q - s` if `s` is greater than `q/2`, flipping parity bit of recovery id
+    ///
+    /// Signature remains valid for the same message and public key.
+    fn normalize_s(self) -> Self {
+        if self.is_low_s() {
+            return self;
+        }
+        let s = Scalar::<Secp256k1>::group_order() - &self.s();
+        Self::from_parts(self.r(), s, self.recid() ^ 1)
+    }
+
+    /// Encodes signature in DER format
+    ///
+    /// Recovery id is not a part of DER encoding.
+    fn to_der(&self) -> Vec<u8> {
+        let r = der_integer(&self.r());
+        let s = der_integer(&self.s());
+        let mut der = Vec::with_capacity(2 + r.len() + s.len());
+        der.push(DER_SEQUENCE);
+        // r and s are at most 35 bytes each, so length fits into a single byte
+        der.push((r.len() + s.len()) as u8);
+        der.extend(r);
+        der.extend(s);
+        der
+    }
+
+    /// Decodes DER encoded signature
+    ///
+    /// DER encoding doesn't carry recovery id, so it needs to be provided separately.
+    fn from_der(bytes: &[u8], recid: u8) -> Result<Self, SignatureEncodingError> {
+        let body = match bytes {
+            [DER_SEQUENCE, len, body @ ..] if usize::from(*len) == body.len() => body,
+            _ => return Err(SignatureEncodingError::InvalidDer),
+        };
+        let (r, rest) = parse_der_integer(body)?;
+        let (s, rest) = parse_der_integer(rest)?;
+        if !rest.is_empty() {
+            return Err(SignatureEncodingError::InvalidDer);
+        }
+        from_validated_parts(r, s, recid)
+    }
+
+    /// Encodes signature as 64 bytes `r || s`, both are 32 bytes big-endian integers
+    fn to_compact(&self) -> [u8; COMPACT_SIZE] {
+        let mut compact = [0u8; COMPACT_SIZE];
+        write_scalar(&mut compact[..SCALAR_SIZE], &self.r());
+        write_scalar(&mut compact[SCALAR_SIZE..], &self.s());
+        compact
+    }
+
+    /// Decodes signature encoded by [to_compact](Self::to_compact)
+    fn from_compact(bytes: &[u8], recid: u8) -> Result<Self, SignatureEncodingError> {
+        if bytes.len() != COMPACT_SIZE {
+            return Err(SignatureEncodingError::InvalidLength {
+                expected: COMPACT_SIZE,
+                len: bytes.len(),
+            });
+        }
+        let r = BigInt::from_bytes(&bytes[..SCALAR_SIZE]);
+        let s = BigInt::from_bytes(&bytes[SCALAR_SIZE..]);
+        from_validated_parts(r, s, recid)
+    }
+
+    /// Encodes signature as 65 bytes `r || s || v`, where `v` is recovery id
+    fn to_recoverable_bytes(&self) -> [u8; RECOVERABLE_SIZE] {
+        let mut bytes = [0u8; RECOVERABLE_SIZE];
+        bytes[..COMPACT_SIZE].copy_from_slice(&self.to_compact());
+        bytes[COMPACT_SIZE] = self.recid();
+        bytes
+    }
+
+    /// Decodes signature encoded by [to_recoverable_bytes](Self::to_recoverable_bytes)
+    ///
+    /// Accepts `v` both as raw recovery id (`0..=3`) and Ethereum-style `v + 27` (`27..=30`).
+    fn from_recoverable_bytes(bytes: &[u8]) -> Result<Self, SignatureEncodingError> {
+        if bytes.len() != RECOVERABLE_SIZE {
+            return Err(SignatureEncodingError::InvalidLength {
+                expected: RECOVERABLE_SIZE,
+                len: bytes.len(),
+            });
+        }
+        let recid = match bytes[COMPACT_SIZE] {
+            v @ 0..=3 => v,
+            v @ 27..=30 => v - 27,
+            v => return Err(SignatureEncodingError::InvalidRecoveryId(v)),
+        };
+        Self::from_compact(&bytes[..COMPACT_SIZE], recid)
+    }
+
+    /// Recovers public key which signature `self` of message `digest` is valid for
+    ///
+    /// Recovery id tells which of up to four candidate points `R` was used for signing: bit 0 is
+    /// parity of `R.y`, bit 1 is set if `R.x` is `r + q`. Public key is then `r^-1 (s R - m G)`.
+    fn recover_public_key(
+        &self,
+        digest: &MessageDigest,
+    ) -> Result<Point<Secp256k1>, SignatureEncodingError> {
+        let q = Scalar::<Secp256k1>::group_order();
+        let (r, s, recid) = (self.r(), self.s(), self.recid());
+        if r <= BigInt::zero() || &r >= q || s <= BigInt::zero() || &s >= q {
+            return Err(SignatureEncodingError::OutOfRange);
+        }
+        if recid > 3 {
+            return Err(SignatureEncodingError::InvalidRecoveryId(recid));
+        }
+
+        let x = if recid & 2 == 0 { r.clone() } else { &r + q };
+        let x = x.to_bytes();
+        if x.len() > SCALAR_SIZE {
+            return Err(SignatureEncodingError::RecoveryFailed);
+        }
+        let mut compressed = [0u8; SCALAR_SIZE + 1];
+        compressed[0] = 0x02 | (recid & 1);
+        compressed[1 + SCALAR_SIZE - x.len()..].copy_from_slice(&x);
+        let big_r = Point::<Secp256k1>::from_bytes(&compressed)
+            .map_err(|_| SignatureEncodingError::RecoveryFailed)?;
+
+        let r_inv = Scalar::<Secp256k1>::from(&r)
+            .invert()
+            .ok_or(SignatureEncodingError::OutOfRange)?;
+        let s = Scalar::<Secp256k1>::from(&s);
+        let public_key = (big_r * &s - Point::generator() * digest.to_scalar()) * &r_inv;
+        if public_key.is_zero() {
+            return Err(SignatureEncodingError::RecoveryFailed);
+        }
+        Ok(public_key)
+    }
+
+    /// Converts signature into [secp256k1::ecdsa::RecoverableSignature]
+    #[cfg(feature = "secp256k1")]
+    fn to_secp256k1(
+        &self,
+    ) -> Result<secp256k1::ecdsa::RecoverableSignature, SignatureEncodingError> {
+        let recid = secp256k1::ecdsa::RecoveryId::from_i32(i32::from(self.recid()))
+            .map_err(SignatureEncodingError::Secp256k1)?;
+        secp256k1::ecdsa::RecoverableSignature::from_compact(&self.to_compact(), recid)
+            .map_err(SignatureEncodingError::Secp256k1)
+    }
+
+    /// Converts [secp256k1::ecdsa::RecoverableSignature] into signature
+    #[cfg(feature = "secp256k1")]
+    fn from_secp256k1(
+        sig: &secp256k1::ecdsa::RecoverableSignature,
+    ) -> Result<Self, SignatureEncodingError> {
+        let (recid, compact) = sig.serialize_compact();
+        // recovery id is guaranteed to be in range [0; 3]
+        Self::from_compact(&compact, recid.to_i32() as u8)
+    }
+}
+
+/// Explains why signature couldn't be decoded or public key couldn't be recovered
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum SignatureEncodingError {
+    /// Signature is not a valid DER encoding of `r` and `s`
+    #[error("invalid DER encoding")]
+    InvalidDer,
+    /// Encoded signature has unexpected length
+    #[error("expected {expected} bytes, got {len} bytes")]
+    InvalidLength { expected: usize, len: usize },
+    /// `r` or `s` is not in range `[1; q)`
+    #[error("r or s is out of range")]
+    OutOfRange,
+    /// Recovery id is out of range
+    #[error("invalid recovery id: {0}")]
+    InvalidRecoveryId(u8),
+    /// Signature doesn't correspond to any public key
+    #[error("public key can't be recovered from signature")]
+    RecoveryFailed,
+    /// secp256k1 library rejected the signature
+    #[cfg(feature = "secp256k1")]
+    #[error("secp256k1: {0}")]
+    Secp256k1(#[source] secp256k1::Error),
+}
+
+fn from_validated_parts<S: SignatureEncoding>(
+    r: BigInt,
+    s: BigInt,
+    recid: u8,
+) -> Result<S, SignatureEncodingError> {
+    let q = Scalar::<Secp256k1>::group_order();
+    let in_range = |x: &BigInt| x > &BigInt::zero() && x < q;
+    if !in_range(&r) || !in_range(&s) {
+        return Err(SignatureEncodingError::OutOfRange);
+    }
+    if recid > 3 {
+        return Err(SignatureEncodingError::InvalidRecoveryId(recid));
+    }
+    Ok(S::from_parts(r, s, recid))
+}
+
+fn write_scalar(out: &mut [u8], x: &BigInt) {
+    let bytes = x.to_bytes();
+    out[SCALAR_SIZE - bytes.len()..].copy_from_slice(&bytes);
+}
+
+/// Encodes non-negative integer as DER INTEGER
+fn der_integer(x: &BigInt) -> Vec<u8> {
+    let mut bytes = x.to_bytes();
+    if bytes.is_empty() || bytes[0] & 0x80 != 0 {
+        bytes.insert(0, 0);
+    }
+    let mut der = Vec::with_capacity(2 + bytes.len());
+    der.push(DER_INTEGER);
+    der.push(bytes.len() as u8);
+    der.extend(bytes);
+    der
+}
+
+/// Parses DER INTEGER, rejects negative and non-minimally encoded integers
+fn parse_der_integer(bytes: &[u8]) -> Result<(BigInt, &[u8]), SignatureEncodingError> {
+    let (len, rest) = match bytes {
+        [DER_INTEGER, len, rest @ ..] if usize::from(*len) <= rest.len() => {
+            (usize::from(*len), rest)
+        }
+        _ => return Err(SignatureEncodingError::InvalidDer),
+    };
+    let (int, rest) = rest.split_at(len);
+    match int {
+        [] => return Err(SignatureEncodingError::InvalidDer),
+        [first, ..] if first & 0x80 != 0 => return Err(SignatureEncodingError::InvalidDer),
+        [0, second, ..] if second & 0x80 == 0 => return Err(SignatureEncodingError::InvalidDer),
+        _ => (),
+    }
+    if len > SCALAR_SIZE + 1 {
+        return Err(SignatureEncodingError::InvalidDer);
+    }
+    Ok((BigInt::from_bytes(int), rest))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::protocols::multi_party_ecdsa::gg_2020::party_i::SignatureRecid;
+
+    fn signature(r: u64, s: &BigInt, recid: u8) -> SignatureRecid {
+        SignatureRecid::from_parts(BigInt::from(r), s.clone(), recid)
+    }
+
+    #[test]
+    fn high_s_is_normalized() {
+        let q = Scalar::<Secp256k1>::group_order();
+        let high_s = q - &BigInt::from(5);
+        let sig = signature(7, &high_s, 0);
+        assert!(!sig.is_low_s());
+
+        let sig = sig.normalize_s();
+        assert!(sig.is_low_s());
+        assert_eq!(SignatureEncoding::s(&sig), BigInt::from(5));
+        assert_eq!(sig.recid, 1);
+
+        let same = sig.clone().normalize_s();
+        assert_eq!((same.r, same.s, same.recid), (sig.r, sig.s, sig.recid));
+    }
+
+    #[test]
+    fn encodings_round_trip() {
+        let q = Scalar::<Secp256k1>::group_order();
+        // r has high bit set, so DER has to prepend zero byte
+        for sig in [
+            signature(1, &BigInt::from(2), 0),
+            SignatureRecid::from_parts(q - &BigInt::from(1), BigInt::from(0x80), 3),
+        ] {
+            let der = sig.to_der();
+            let decoded = SignatureRecid::from_der(&der, sig.recid).unwrap();
+            assert_eq!((&decoded.r, &decoded.s), (&sig.r, &sig.s));
+
+            let decoded = SignatureRecid::from_compact(&sig.to_compact(), sig.recid).unwrap();
+            assert_eq!((&decoded.r, &decoded.s), (&sig.r, &sig.s));
+
+            let mut bytes = sig.to_recoverable_bytes();
+            let decoded = SignatureRecid::from_recoverable_bytes(&bytes).unwrap();
+            assert_eq!(decoded.recid, sig.recid);
+            bytes[COMPACT_SIZE] += 27;
+            let decoded = SignatureRecid::from_recoverable_bytes(&bytes).unwrap();
+            assert_eq!(decoded.recid, sig.recid);
+        }
+
+        let der = signature(1, &BigInt::from(2), 0).to_der();
+        assert_eq!(der, [0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02]);
+    }
+
+    #[test]
+    fn malformed_encodings_are_rejected() {
+        let non_minimal = [0x30, 0x07, 0x02, 0x02, 0x00, 0x01, 0x02, 0x01, 0x02];
+        assert!(matches!(
+            SignatureRecid::from_der(&non_minimal, 0),
+            Err(SignatureEncodingError::InvalidDer)
+        ));
+        let zero_r = [0x30, 0x06, 0x02, 0x01, 0x00, 0x02, 0x01, 0x02];
+        assert!(matches!(
+            SignatureRecid::from_der(&zero_r, 0),
+            Err(SignatureEncodingError::OutOfRange)
+        ));
+        assert!(matches!(
+            SignatureRecid::from_compact(&[0xff; COMPACT_SIZE], 0),
+            Err(SignatureEncodingError::OutOfRange)
+        ));
+        assert!(matches!(
+            SignatureRecid::from_compact(&[1; 63], 0),
+            Err(SignatureEncodingError::InvalidLength {
+                expected: 64,
+                len: 63
+            })
+        ));
+        let mut bytes = [1u8; RECOVERABLE_SIZE];
+        bytes[COMPACT_SIZE] = 4;
+        assert!(matches!(
+            SignatureRecid::from_recoverable_bytes(&bytes),
+            Err(SignatureEncodingError::InvalidRecoveryId(4))
+        ));
+    }
+
+    #[test]
+    fn public_key_is_recovered() {
+        let digest = MessageDigest::sha256(b"ZenGo");
+        let secret_key = Scalar::<Secp256k1>::random();
+        let public_key = Point::generator() * &secret_key;
+
+        for _ in 0..4 {
+            let k = Scalar::<Secp256k1>::random();
+            let big_r = Point::generator() * &k;
+            let r = Scalar::<Secp256k1>::from(&big_r.x_coord().unwrap());
+            let s = k.invert().unwrap() * (digest.to_scalar() + &r * &secret_key);
+            let recid = if big_r.y_coord().unwrap().test_bit(0) {
+                1
+            } else {
+                0
+            };
+            let sig = SignatureRecid { r, s, recid }.normalize_s();
+
+            assert_eq!(sig.recover_public_key(&digest).unwrap(), public_key);
+            let wrong =
+                SignatureRecid::from_parts(sig.r.to_bigint(), sig.s.to_bigint(), sig.recid ^ 1);
+            assert_ne!(wrong.recover_public_key(&digest).unwrap(), public_key);
+        }
+    }
+
+    #[cfg(feature = "secp256k1")]
+    #[test]
+    fn converts_to_and_from_secp256k1() {
+        let sig = signature(11, &BigInt::from(13), 1);
+        let secp_sig = sig.to_secp256k1().unwrap();
+        let (recid, compact) = secp_sig.serialize_compact();
+        assert_eq!(recid.to_i32(), 1);
+        assert_eq!(compact, sig.to_compact());
+
+        let decoded = SignatureRecid::from_secp256k1(&secp_sig).unwrap();
+        assert_eq!(
+            (decoded.r, decoded.s, decoded.recid),
+            (sig.r, sig.s, sig.recid)
+        );
+    }
+}
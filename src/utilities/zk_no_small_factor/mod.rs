@@ -0,0 +1,227 @@
+#![allow(non_snake_case)]
+
+//! No small factor proof
+//!
+//! We use the proof Π^fac given in Figure 28 of https://eprint.iacr.org/2021/060.pdf, made
+//! non-interactive with Fiat-Shamir.
+//!
+//! Setup: verifier's ring-Pedersen parameters `(N_tilde, h1, h2)`
+//! Statement: `N0`
+//! witness `(p, q)` such that `N0 = pq` and `p`, `q` are bigger than `2^L`
+//!
+//! The proof is only sound if prover doesn't know discrete log between `h1` and `h2`, so it must
+//! be generated separately for every verifier using verifier's own setup.
+
+use curv::arithmetic::traits::*;
+use curv::cryptographic_primitives::hashing::{Digest, DigestExt};
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Scalar};
+use curv::BigInt;
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::Sha256;
+use thiserror::Error;
+use zk_paillier::zkproofs::DLogStatement;
+
+use crate::utilities::rng;
+
+/// Factors are proven to be bigger than `2^L`
+pub const L: usize = 256;
+/// Slackness parameter
+pub const EPSILON: usize = 512;
+
+#[derive(Error, Debug)]
+pub enum ZkNoSmallFactorError {
+    #[error("no small factor proof verification failed")]
+    Verify,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoSmallFactorProof {
+    P: BigInt,
+    Q: BigInt,
+    A: BigInt,
+    B: BigInt,
+    T: BigInt,
+    #[serde(with = "signed_bigint")]
+    sigma: BigInt,
+    #[serde(with = "signed_bigint")]
+    z1: BigInt,
+    #[serde(with = "signed_bigint")]
+    z2: BigInt,
+    #[serde(with = "signed_bigint")]
+    w1: BigInt,
+    #[serde(with = "signed_bigint")]
+    w2: BigInt,
+    #[serde(with = "signed_bigint")]
+    v: BigInt,
+}
+
+impl NoSmallFactorProof {
+    /// Proves that `N0 = pq` has no factors smaller than `2^L`, with respect to verifier's
+    /// `setup`
+    pub fn prove(N0: &BigInt, p: &BigInt, q: &BigInt, setup: &DLogStatement) -> Self {
+        Self::prove_with_rng(N0, p, q, setup, &mut OsRng)
+    }
+
+    /// Same as [NoSmallFactorProof::prove], but takes randomness from `rng`
+    pub fn prove_with_rng<R: RngCore + CryptoRng + ?Sized>(
+        N0: &BigInt,
+        p: &BigInt,
+        q: &BigInt,
+        setup: &DLogStatement,
+        rng: &mut R,
+    ) -> Self {
+        let (N_tilde, s, t) = (&setup.N, &setup.g, &setup.ni);
+        let sqrt_N0 = sqrt_bound(N0);
+        let two_l = BigInt::one() << L;
+        let two_l_e = BigInt::one() << (L + EPSILON);
+
+        let alpha = sample_pm(&(&two_l_e * &sqrt_N0), rng);
+        let beta = sample_pm(&(&two_l_e * &sqrt_N0), rng);
+        let mu = sample_pm(&(&two_l * N_tilde), rng);
+        let nu = sample_pm(&(&two_l * N_tilde), rng);
+        let sigma = sample_pm(&(&two_l * N0 * N_tilde), rng);
+        let r = sample_pm(&(&two_l_e * N0 * N_tilde), rng);
+        let x = sample_pm(&(&two_l_e * N_tilde), rng);
+        let y = sample_pm(&(&two_l_e * N_tilde), rng);
+
+        let commit = |a: &BigInt, b: &BigInt| {
+            BigInt::mod_mul(
+                &pow_signed(s, a, N_tilde).unwrap_or_else(BigInt::zero),
+                &pow_signed(t, b, N_tilde).unwrap_or_else(BigInt::zero),
+                N_tilde,
+            )
+        };
+        let P = commit(p, &mu);
+        let Q = commit(q, &nu);
+        let A = commit(&alpha, &x);
+        let B = commit(&beta, &y);
+        let T = BigInt::mod_mul(
+            &pow_signed(&Q, &alpha, N_tilde).unwrap_or_else(BigInt::zero),
+            &pow_signed(t, &r, N_tilde).unwrap_or_else(BigInt::zero),
+            N_tilde,
+        );
+
+        let e = challenge(N0, setup, &[&P, &Q, &A, &B, &T, &sigma]);
+
+        let sigma_hat = &sigma - &nu * p;
+        Self {
+            z1: &alpha + &e * p,
+            z2: &beta + &e * q,
+            w1: &x + &e * &mu,
+            w2: &y + &e * &nu,
+            v: &r + &e * &sigma_hat,
+            P,
+            Q,
+            A,
+            B,
+            T,
+            sigma,
+        }
+    }
+
+    /// Verifies the proof with respect to verifier's own `setup`
+    pub fn verify(&self, N0: &BigInt, setup: &DLogStatement) -> Result<(), ZkNoSmallFactorError> {
+        let (N_tilde, s, t) = (&setup.N, &setup.g, &setup.ni);
+        let bound = sqrt_bound(N0) << (L + EPSILON);
+        if BigInt::abs(&self.z1) > bound || BigInt::abs(&self.z2) > bound {
+            return Err(ZkNoSmallFactorError::Verify);
+        }
+        let in_group =
+            |x: &BigInt| *x > BigInt::zero() && x < N_tilde && x.gcd(N_tilde) == BigInt::one();
+        if ![&self.P, &self.Q, &self.A, &self.B, &self.T]
+            .iter()
+            .all(|x| in_group(x))
+        {
+            return Err(ZkNoSmallFactorError::Verify);
+        }
+
+        let e = challenge(
+            N0,
+            setup,
+            &[&self.P, &self.Q, &self.A, &self.B, &self.T, &self.sigma],
+        );
+        let pow = |base: &BigInt, exp: &BigInt| {
+            pow_signed(base, exp, N_tilde).ok_or(ZkNoSmallFactorError::Verify)
+        };
+        let R = BigInt::mod_mul(&pow(s, N0)?, &pow(t, &self.sigma)?, N_tilde);
+
+        let lhs = BigInt::mod_mul(&pow(s, &self.z1)?, &pow(t, &self.w1)?, N_tilde);
+        let rhs = BigInt::mod_mul(&self.A, &pow(&self.P, &e)?, N_tilde);
+        if lhs != rhs {
+            return Err(ZkNoSmallFactorError::Verify);
+        }
+
+        let lhs = BigInt::mod_mul(&pow(s, &self.z2)?, &pow(t, &self.w2)?, N_tilde);
+        let rhs = BigInt::mod_mul(&self.B, &pow(&self.Q, &e)?, N_tilde);
+        if lhs != rhs {
+            return Err(ZkNoSmallFactorError::Verify);
+        }
+
+        let lhs = BigInt::mod_mul(&pow(&self.Q, &self.z1)?, &pow(t, &self.v)?, N_tilde);
+        let rhs = BigInt::mod_mul(&self.T, &pow(&R, &e)?, N_tilde);
+        if lhs != rhs {
+            return Err(ZkNoSmallFactorError::Verify);
+        }
+
+        Ok(())
+    }
+}
+
+fn challenge(N0: &BigInt, setup: &DLogStatement, values: &[&BigInt]) -> BigInt {
+    let e = values
+        .iter()
+        .fold(
+            Sha256::new()
+                .chain_bigint(N0)
+                .chain_bigint(&setup.N)
+                .chain_bigint(&setup.g)
+                .chain_bigint(&setup.ni),
+            |acc, x| acc.chain_bigint(x),
+        )
+        .result_bigint();
+    BigInt::modulus(&e, Scalar::<Secp256k1>::group_order())
+}
+
+/// Upper bound on `sqrt(N0)`
+fn sqrt_bound(N0: &BigInt) -> BigInt {
+    BigInt::one() << N0.bit_length().div_ceil(2)
+}
+
+/// Samples integer from `[-bound; bound]`
+fn sample_pm<R: RngCore + CryptoRng + ?Sized>(bound: &BigInt, rng: &mut R) -> BigInt {
+    rng::sample_below(&(bound * BigInt::from(2) + BigInt::one()), rng) - bound
+}
+
+/// Computes `base^exp mod modulus`, exponent might be negative
+fn pow_signed(base: &BigInt, exp: &BigInt, modulus: &BigInt) -> Option<BigInt> {
+    if *exp < BigInt::zero() {
+        let base_inv = BigInt::mod_inv(base, modulus)?;
+        Some(BigInt::mod_pow(&base_inv, &(BigInt::zero() - exp), modulus))
+    } else {
+        Some(BigInt::mod_pow(base, exp, modulus))
+    }
+}
+
+#[cfg(test)]
+mod test;
+
+/// Serializes integer which might be negative as a sign flag followed by its magnitude: curv's
+/// `BigInt` serializes magnitude only
+mod signed_bigint {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(x: &BigInt, serializer: S) -> Result<S::Ok, S::Error> {
+        (*x < BigInt::zero(), BigInt::abs(x)).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BigInt, D::Error> {
+        let (negative, magnitude) = <(bool, BigInt)>::deserialize(deserializer)?;
+        if negative {
+            Ok(BigInt::zero() - magnitude)
+        } else {
+            Ok(magnitude)
+        }
+    }
+}
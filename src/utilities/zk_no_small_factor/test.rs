@@ -0,0 +1,52 @@
+#![allow(non_snake_case)]
+use crate::protocols::multi_party_ecdsa::gg_2020::party_i::{
+    generate_h1_h2_N_tilde, paillier_blum_keypair,
+};
+use crate::utilities::zk_no_small_factor::*;
+use curv::BigInt;
+use paillier::PrimeSampable;
+use zk_paillier::zkproofs::DLogStatement;
+
+fn verifier_setup() -> DLogStatement {
+    let (N_tilde, h1, h2, _, _) = generate_h1_h2_N_tilde();
+    DLogStatement {
+        N: N_tilde,
+        g: h1,
+        ni: h2,
+    }
+}
+
+#[test]
+fn test_no_small_factor_proof() {
+    let setup = verifier_setup();
+    let (ek, dk) = paillier_blum_keypair();
+    let proof = NoSmallFactorProof::prove(&ek.n, &dk.p, &dk.q, &setup);
+    assert!(proof.verify(&ek.n, &setup).is_ok());
+
+    // proof is bound to the statement
+    assert!(proof.verify(&(&ek.n + BigInt::from(2)), &setup).is_err());
+}
+
+#[test]
+fn test_no_small_factor_proof_survives_serialization() {
+    let setup = verifier_setup();
+    let (ek, dk) = paillier_blum_keypair();
+    // each response is negative with probability 1/2, so losing the sign breaks almost any proof
+    for _ in 0..4 {
+        let proof = NoSmallFactorProof::prove(&ek.n, &dk.p, &dk.q, &setup);
+        let proof: NoSmallFactorProof =
+            serde_json::from_str(&serde_json::to_string(&proof).unwrap()).unwrap();
+        assert!(proof.verify(&ek.n, &setup).is_ok());
+    }
+}
+
+#[test]
+fn test_small_factor_is_detected() {
+    let setup = verifier_setup();
+    // N0 = p * q where p is 64 bits long
+    let p = BigInt::sample_prime(64);
+    let q = BigInt::sample_prime(1984);
+    let N0 = &p * &q;
+    let proof = NoSmallFactorProof::prove(&N0, &p, &q, &setup);
+    assert!(proof.verify(&N0, &setup).is_err());
+}
@@ -0,0 +1,220 @@
+#![allow(non_snake_case)]
+
+//! Paillier-Blum modulus proof
+//!
+//! We use the proof Π^mod given in Figure 16 of https://eprint.iacr.org/2021/060.pdf, made
+//! non-interactive with Fiat-Shamir.
+//!
+//! Statement: `N`
+//! witness `(p, q)` such that `N = pq`, `p`, `q` are primes and `p = q = 3 mod 4`
+//!
+//! Together with [NiCorrectKeyProof](zk_paillier::zkproofs::NiCorrectKeyProof) (which shows that
+//! `gcd(N, phi(N)) = 1`) it proves that `N` is a product of exactly two distinct primes.
+
+use curv::arithmetic::traits::*;
+use curv::cryptographic_primitives::hashing::{Digest, DigestExt};
+use curv::BigInt;
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use thiserror::Error;
+
+use crate::utilities::rng;
+
+/// Number of challenges, soundness error is `2^-M`
+pub const M: usize = 80;
+
+#[derive(Error, Debug)]
+pub enum ZkPaillierBlumModulusError {
+    #[error("paillier-blum modulus proof verification failed")]
+    Verify,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaillierBlumModulusProof {
+    w: BigInt,
+    x: Vec<BigInt>,
+    a: Vec<bool>,
+    b: Vec<bool>,
+    z: Vec<BigInt>,
+}
+
+impl PaillierBlumModulusProof {
+    /// Proves that `N = pq` is a Paillier-Blum modulus
+    ///
+    /// If `p` or `q` is not a Blum prime, resulting proof won't verify.
+    pub fn prove(N: &BigInt, p: &BigInt, q: &BigInt) -> Self {
+        Self::prove_with_rng(N, p, q, &mut OsRng)
+    }
+
+    /// Same as [PaillierBlumModulusProof::prove], but takes randomness from `rng`
+    pub fn prove_with_rng<R: RngCore + CryptoRng + ?Sized>(
+        N: &BigInt,
+        p: &BigInt,
+        q: &BigInt,
+        rng: &mut R,
+    ) -> Self {
+        let one = BigInt::one();
+        let phi = (p - &one) * (q - &one);
+
+        // w is a quadratic non-residue modulo p and a residue modulo q, so its Jacobi symbol is -1
+        let w = loop {
+            let w = rng::sample_from_multiplicative_group(N, rng);
+            if !is_quadratic_residue(&w, p) && is_quadratic_residue(&w, q) {
+                break w;
+            }
+        };
+
+        let N_inv = BigInt::mod_inv(N, &phi).unwrap_or_else(BigInt::zero);
+        // fourth root of a quadratic residue modulo Blum prime p is y^(((p+1)/4)^2)
+        let fourth_root_exp = |prime: &BigInt| {
+            let e = (prime + &one) >> 2;
+            BigInt::mod_mul(&e, &e, &(prime - &one))
+        };
+        let (exp_p, exp_q) = (fourth_root_exp(p), fourth_root_exp(q));
+        let minus_one = N - &one;
+
+        let mut proof = Self {
+            w: w.clone(),
+            x: Vec::with_capacity(M),
+            a: Vec::with_capacity(M),
+            b: Vec::with_capacity(M),
+            z: Vec::with_capacity(M),
+        };
+        for y in challenges(N, &w) {
+            let (mut a, mut b, mut y_) = (false, false, y.clone());
+            'search: for &a_ in &[false, true] {
+                for &b_ in &[false, true] {
+                    let mut candidate = y.clone();
+                    if a_ {
+                        candidate = BigInt::mod_mul(&candidate, &minus_one, N);
+                    }
+                    if b_ {
+                        candidate = BigInt::mod_mul(&candidate, &w, N);
+                    }
+                    if is_quadratic_residue(&candidate, p) && is_quadratic_residue(&candidate, q) {
+                        a = a_;
+                        b = b_;
+                        y_ = candidate;
+                        break 'search;
+                    }
+                }
+            }
+            let x_p = BigInt::mod_pow(&y_, &exp_p, p);
+            let x_q = BigInt::mod_pow(&y_, &exp_q, q);
+            proof.x.push(crt(&x_p, &x_q, p, q));
+            proof.a.push(a);
+            proof.b.push(b);
+            proof.z.push(BigInt::mod_pow(&y, &N_inv, N));
+        }
+        proof
+    }
+
+    pub fn verify(&self, N: &BigInt) -> Result<(), ZkPaillierBlumModulusError> {
+        let one = BigInt::one();
+        if *N <= one || !N.test_bit(0) || N.is_probable_prime(20) {
+            return Err(ZkPaillierBlumModulusError::Verify);
+        }
+        if self.x.len() != M || self.a.len() != M || self.b.len() != M || self.z.len() != M {
+            return Err(ZkPaillierBlumModulusError::Verify);
+        }
+        if !in_range(&self.w, N) || jacobi(&self.w, N) != -1 {
+            return Err(ZkPaillierBlumModulusError::Verify);
+        }
+
+        let minus_one = N - &one;
+        let four = BigInt::from(4);
+        for (i, y) in challenges(N, &self.w).into_iter().enumerate() {
+            let (x, z) = (&self.x[i], &self.z[i]);
+            if !in_range(x, N) || !in_range(z, N) {
+                return Err(ZkPaillierBlumModulusError::Verify);
+            }
+            if BigInt::mod_pow(z, N, N) != y {
+                return Err(ZkPaillierBlumModulusError::Verify);
+            }
+            let mut y_ = y;
+            if self.a[i] {
+                y_ = BigInt::mod_mul(&y_, &minus_one, N);
+            }
+            if self.b[i] {
+                y_ = BigInt::mod_mul(&y_, &self.w, N);
+            }
+            if BigInt::mod_pow(x, &four, N) != y_ {
+                return Err(ZkPaillierBlumModulusError::Verify);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Derives `M` challenges from `Z_N` out of statement and first message
+fn challenges(N: &BigInt, w: &BigInt) -> Vec<BigInt> {
+    // sample 128 more bits than N has to make bias negligible
+    let blocks = (N.bit_length() + 128).div_ceil(256);
+    (0..M)
+        .map(|i| {
+            let y = (0..blocks).fold(BigInt::zero(), |acc, j| {
+                let h = Sha256::new()
+                    .chain_bigint(N)
+                    .chain_bigint(w)
+                    .chain_bigint(&BigInt::from(i as u64))
+                    .chain_bigint(&BigInt::from(j as u64))
+                    .result_bigint();
+                (acc << 256) + h
+            });
+            BigInt::modulus(&y, N)
+        })
+        .collect()
+}
+
+fn in_range(x: &BigInt, N: &BigInt) -> bool {
+    *x > BigInt::zero() && x < N
+}
+
+fn is_quadratic_residue(x: &BigInt, p: &BigInt) -> bool {
+    let one = BigInt::one();
+    BigInt::mod_pow(x, &((p - &one) >> 1), p) == one
+}
+
+/// Finds `x mod pq` such that `x = x_p mod p` and `x = x_q mod q`
+fn crt(x_p: &BigInt, x_q: &BigInt, p: &BigInt, q: &BigInt) -> BigInt {
+    let p_inv = BigInt::mod_inv(p, q).unwrap_or_else(BigInt::zero);
+    let h = BigInt::mod_mul(&BigInt::mod_sub(x_q, x_p, q), &p_inv, q);
+    x_p + p * h
+}
+
+/// Computes Jacobi symbol `(a/n)` for odd `n`
+fn jacobi(a: &BigInt, n: &BigInt) -> i8 {
+    let (three, five, eight, four) = (
+        BigInt::from(3),
+        BigInt::from(5),
+        BigInt::from(8),
+        BigInt::from(4),
+    );
+    let mut a = BigInt::modulus(a, n);
+    let mut n = n.clone();
+    let mut t = 1;
+    while a != BigInt::zero() {
+        while !a.test_bit(0) {
+            a >>= 1;
+            let r = BigInt::modulus(&n, &eight);
+            if r == three || r == five {
+                t = -t;
+            }
+        }
+        std::mem::swap(&mut a, &mut n);
+        if BigInt::modulus(&a, &four) == three && BigInt::modulus(&n, &four) == three {
+            t = -t;
+        }
+        a = BigInt::modulus(&a, &n);
+    }
+    if n == BigInt::one() {
+        t
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod test;
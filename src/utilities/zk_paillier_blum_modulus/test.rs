@@ -0,0 +1,32 @@
+#![allow(non_snake_case)]
+use crate::protocols::multi_party_ecdsa::gg_2020::party_i::paillier_blum_keypair;
+use crate::utilities::zk_paillier_blum_modulus::*;
+use curv::BigInt;
+use paillier::PrimeSampable;
+
+#[test]
+fn test_paillier_blum_modulus_proof() {
+    let (ek, dk) = paillier_blum_keypair();
+    let proof = PaillierBlumModulusProof::prove(&ek.n, &dk.p, &dk.q);
+    assert!(proof.verify(&ek.n).is_ok());
+}
+
+#[test]
+fn test_bad_paillier_blum_modulus_proof() {
+    // p = 1 mod 4 is not a Blum prime
+    let p = loop {
+        let p = BigInt::sample_prime(1024);
+        if !p.test_bit(1) {
+            break p;
+        }
+    };
+    let q = paillier_blum_keypair().1.p;
+    let N = &p * &q;
+    let proof = PaillierBlumModulusProof::prove(&N, &p, &q);
+    assert!(proof.verify(&N).is_err());
+
+    // proof for one modulus doesn't verify for another
+    let (ek, dk) = paillier_blum_keypair();
+    let proof = PaillierBlumModulusProof::prove(&ek.n, &dk.p, &dk.q);
+    assert!(proof.verify(&(&ek.n + BigInt::from(2))).is_err());
+}
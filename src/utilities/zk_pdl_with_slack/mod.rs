@@ -27,10 +27,14 @@ use curv::cryptographic_primitives::hashing::{Digest, DigestExt};
 use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
 use curv::BigInt;
 use paillier::EncryptionKey;
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
 use thiserror::Error;
 
+use crate::utilities::rng;
+
 #[derive(Error, Debug)]
 pub enum ZkPdlWithSlackError {
     #[error("zk pdl with slack verification failed")]
@@ -66,15 +70,24 @@ pub struct PDLwSlackProof {
 
 impl PDLwSlackProof {
     pub fn prove(witness: &PDLwSlackWitness, statement: &PDLwSlackStatement) -> Self {
+        Self::prove_with_rng(witness, statement, &mut OsRng)
+    }
+
+    /// Same as [PDLwSlackProof::prove], but takes randomness from `rng`
+    pub fn prove_with_rng<R: RngCore + CryptoRng + ?Sized>(
+        witness: &PDLwSlackWitness,
+        statement: &PDLwSlackStatement,
+        rng: &mut R,
+    ) -> Self {
         let q3 = Scalar::<Secp256k1>::group_order().pow(3);
         let q_N_tilde = Scalar::<Secp256k1>::group_order() * &statement.N_tilde;
         let q3_N_tilde = &q3 * &statement.N_tilde;
 
-        let alpha = BigInt::sample_below(&q3);
+        let alpha = rng::sample_below(&q3, rng);
         let one = BigInt::one();
-        let beta = BigInt::sample_range(&one, &(&statement.ek.n - &one));
-        let rho = BigInt::sample_below(&q_N_tilde);
-        let gamma = BigInt::sample_below(&q3_N_tilde);
+        let beta = rng::sample_range(&one, &(&statement.ek.n - &one), rng);
+        let rho = rng::sample_below(&q_N_tilde, rng);
+        let gamma = rng::sample_below(&q3_N_tilde, rng);
 
         let z = commitment_unknown_order(
             &statement.h1,